@@ -1,4 +1,9 @@
+pub use self::multi_chunk_writer::SnapshotCompression;
 pub use self::rocksdb::*;
+pub use self::snapshot_control::SnapshotCancellationToken;
 
+mod multi_chunk_reader;
 mod multi_chunk_writer;
 mod rocksdb;
+mod snapshot_control;
+mod snapshot_manifest;
@@ -0,0 +1,206 @@
+use flate2::read::MultiGzDecoder;
+use slog::{info, o};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Result};
+use std::path::PathBuf;
+
+use crate::logger::LOGGER;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+enum ChunkDecoder {
+    Gzip(MultiGzDecoder<BufReader<File>>),
+    Zstd(zstd::Decoder<'static, BufReader<File>>),
+}
+
+impl ChunkDecoder {
+    /** Pick the decoder from the magic bytes at the start of the chunk */
+    fn new(file: File) -> Result<Self> {
+        let mut reader = BufReader::new(file);
+        let header = reader.fill_buf()?;
+
+        if header.starts_with(&ZSTD_MAGIC) {
+            Ok(ChunkDecoder::Zstd(zstd::Decoder::with_buffer(reader)?))
+        } else if header.starts_with(&GZIP_MAGIC) {
+            Ok(ChunkDecoder::Gzip(MultiGzDecoder::new(reader)))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unknown snapshot chunk compression",
+            ))
+        }
+    }
+}
+
+impl Read for ChunkDecoder {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            ChunkDecoder::Gzip(decoder) => decoder.read(buf),
+            ChunkDecoder::Zstd(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+/**
+* A reader that reads back the chunks written by `MultiChunkWriter`. The chunk_XXXX.bin files in
+* `base_path` are decompressed and read in order, as if they were a single stream. Each chunk can
+* be compressed with gzip or zstd.
+*/
+pub(crate) struct MultiChunkReader {
+    chunk_paths: Vec<PathBuf>,
+    next_chunk: usize,
+    decoder: Option<ChunkDecoder>,
+    logger: slog::Logger,
+}
+
+impl MultiChunkReader {
+    pub fn new(base_path: PathBuf) -> Result<Self> {
+        let mut chunk_paths = fs::read_dir(&base_path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy())
+                    .map(|name| name.starts_with("chunk_") && name.ends_with(".bin"))
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+
+        if chunk_paths.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No chunks found in {:?}", base_path),
+            ));
+        }
+
+        // The part numbers are zero padded, so sorting by name sorts them by part number
+        chunk_paths.sort();
+
+        Ok(Self {
+            chunk_paths,
+            next_chunk: 0,
+            decoder: None,
+            logger: LOGGER.new(o! ("module" => "snapshot_reader")),
+        })
+    }
+
+    pub fn num_chunks(&self) -> usize {
+        self.chunk_paths.len()
+    }
+
+    fn next_part(&mut self) -> Result<bool> {
+        if self.next_chunk >= self.chunk_paths.len() {
+            return Ok(false);
+        }
+
+        let file = File::open(&self.chunk_paths[self.next_chunk])?;
+        self.decoder = Some(ChunkDecoder::new(file)?);
+
+        info!(self.logger, "Reading chunk"; "chunk" => self.chunk_paths[self.next_chunk].to_string_lossy().to_string());
+        self.next_chunk += 1;
+
+        Ok(true)
+    }
+}
+
+impl Read for MultiChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.decoder.is_none() && !self.next_part()? {
+                return Ok(0); // All chunks have been read
+            }
+
+            let size = self.decoder.as_mut().unwrap().read(buf)?;
+            if size > 0 {
+                return Ok(size);
+            }
+
+            // The current chunk is done, move on to the next one
+            self.decoder = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::multi_chunk_writer::{MultiChunkWriter, SnapshotCompression};
+    use std::io::Write as _;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_single_chunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut writer = MultiChunkWriter::new(temp_dir.path().to_path_buf(), 100);
+
+        writer.write_all(b"Hello, world!").unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = MultiChunkReader::new(temp_dir.path().to_path_buf()).unwrap();
+        assert_eq!(reader.num_chunks(), 1);
+
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "Hello, world!");
+    }
+
+    #[test]
+    fn test_read_multiple_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut writer = MultiChunkWriter::new(temp_dir.path().to_path_buf(), 5);
+
+        writer.write_all(b"12345").unwrap();
+        writer.write_all(b"67890").unwrap(); // This should trigger a new part
+        writer.write_all(b"abcde").unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = MultiChunkReader::new(temp_dir.path().to_path_buf()).unwrap();
+        assert_eq!(reader.num_chunks(), 3);
+
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "1234567890abcde");
+    }
+
+    #[test]
+    fn test_read_zstd_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut writer = MultiChunkWriter::new(temp_dir.path().to_path_buf(), 5)
+            .with_compression(SnapshotCompression::Zstd { level: 3 }, 1);
+
+        writer.write_all(b"12345").unwrap();
+        writer.write_all(b"67890").unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = MultiChunkReader::new(temp_dir.path().to_path_buf()).unwrap();
+        assert_eq!(reader.num_chunks(), 2);
+
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "1234567890");
+    }
+
+    #[test]
+    fn test_unknown_compression() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("chunk_0001.bin"), b"not compressed").unwrap();
+
+        let mut reader = MultiChunkReader::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut contents = String::new();
+        let result = reader.read_to_string(&mut contents);
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_no_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("other.txt"), b"not a chunk").unwrap();
+
+        let result = MultiChunkReader::new(temp_dir.path().to_path_buf());
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::NotFound);
+    }
+}
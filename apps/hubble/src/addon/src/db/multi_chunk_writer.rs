@@ -1,6 +1,6 @@
 use gzp::deflate::Gzip;
 use gzp::par::compress::{ParCompress, ParCompressBuilder};
-use gzp::ZWriter as _;
+use gzp::{Compression, ZWriter as _};
 use slog::{info, o};
 use std::fs::{self, File};
 use std::io::{self, Result, Write};
@@ -8,10 +8,90 @@ use std::path::{Path, PathBuf};
 
 use crate::logger::LOGGER;
 
+/** How snapshot chunks are compressed. The reader detects the codec, so it doesn't need this */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnapshotCompression {
+    /** Gzip, level 0-9. Hubs download snapshots with gunzip, so this is what gets uploaded */
+    Gzip { level: u32 },
+    /** Zstd, usually level 1-22. Smaller archives than gzip, and much faster to decompress */
+    Zstd { level: i32 },
+}
+
+impl Default for SnapshotCompression {
+    fn default() -> Self {
+        SnapshotCompression::Gzip { level: 3 }
+    }
+}
+
+impl SnapshotCompression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SnapshotCompression::Gzip { .. } => "gzip",
+            SnapshotCompression::Zstd { .. } => "zstd",
+        }
+    }
+
+    /** The extension of the archive directory, eg. "tar.gz" */
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SnapshotCompression::Gzip { .. } => "tar.gz",
+            SnapshotCompression::Zstd { .. } => "tar.zst",
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            SnapshotCompression::Gzip { level } => level <= 9,
+            SnapshotCompression::Zstd { level } => zstd::compression_level_range().contains(&level),
+        }
+    }
+}
+
+enum ChunkEncoder {
+    Gzip(ParCompress<Gzip>),
+    Zstd(zstd::Encoder<'static, File>),
+}
+
+impl ChunkEncoder {
+    fn new(file: File, compression: SnapshotCompression, num_threads: usize) -> Result<Self> {
+        match compression {
+            SnapshotCompression::Gzip { level } => Ok(ChunkEncoder::Gzip(
+                ParCompressBuilder::new()
+                    .num_threads(num_threads)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+                    .compression_level(Compression::new(level))
+                    .from_writer(file),
+            )),
+            SnapshotCompression::Zstd { level } => {
+                let mut encoder = zstd::Encoder::new(file, level)?;
+                encoder.multithread(num_threads as u32)?;
+                encoder.include_checksum(true)?;
+                Ok(ChunkEncoder::Zstd(encoder))
+            }
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            ChunkEncoder::Gzip(encoder) => encoder,
+            ChunkEncoder::Zstd(encoder) => encoder,
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            ChunkEncoder::Gzip(mut encoder) => encoder
+                .finish()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            ChunkEncoder::Zstd(encoder) => encoder.finish().map(|_| ()),
+        }
+    }
+}
+
 /**
 * A writer that will write data to multiple files, creating a new file when the current one
-* reaches the max size. The files are compressed using Gzip and the individual parts are
-* named chunk_XXXX.bin where XXXX is the part number.
+* reaches the max size. The files are compressed using Gzip (or Zstd) and the individual parts
+* are named chunk_XXXX.bin where XXXX is the part number.
 *
 * The writer will create the `base_path` directory if it does not exist.
 */
@@ -20,7 +100,9 @@ pub(crate) struct MultiChunkWriter {
     current_part: usize,
     max_size: usize,
     current_size: usize,
-    encoder: Option<ParCompress<Gzip>>,
+    compression: SnapshotCompression,
+    num_threads: usize,
+    encoder: Option<ChunkEncoder>,
     logger: slog::Logger,
 }
 
@@ -31,11 +113,24 @@ impl MultiChunkWriter {
             current_part: 0,
             max_size,
             current_size: 0,
+            compression: SnapshotCompression::default(),
+            num_threads: 4,
             encoder: None,
             logger: LOGGER.new(o! ("module" => "snapshot_writer")),
         }
     }
 
+    /** Compress the chunks with `compression`, using `num_threads` threads per chunk */
+    pub fn with_compression(
+        mut self,
+        compression: SnapshotCompression,
+        num_threads: usize,
+    ) -> Self {
+        self.compression = compression;
+        self.num_threads = num_threads;
+        self
+    }
+
     fn ensure_directory_exists<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         if !path.as_ref().exists() {
             fs::create_dir_all(path)?;
@@ -55,24 +150,19 @@ impl MultiChunkWriter {
             .join(format!("chunk_{:04}.bin", self.current_part));
         let file = File::create(&file_name)?;
 
-        self.encoder = Some(
-            ParCompressBuilder::new()
-                .num_threads(4)
-                .unwrap()
-                .from_writer(file),
-        );
+        self.encoder = Some(ChunkEncoder::new(file, self.compression, self.num_threads)?);
 
         self.current_size = 0;
         Ok(())
     }
 
     pub fn finish(&mut self) -> Result<()> {
-        if let Some(mut encoder) = self.encoder.take() {
+        if let Some(encoder) = self.encoder.take() {
             info!(self.logger, "Finished writing chunk"; "chunk" => self.current_part);
 
             encoder.finish().map_err(|e| {
                 info!(self.logger, "Error finishing chunk"; "chunk" => self.current_part, "error" => e.to_string());
-                io::Error::new(io::ErrorKind::InvalidData, e)
+                e
             })?
         }
         Ok(())
@@ -86,7 +176,7 @@ impl Write for MultiChunkWriter {
         }
 
         if let Some(encoder) = self.encoder.as_mut() {
-            let size = encoder.write(buf)?;
+            let size = encoder.writer().write(buf)?;
             self.current_size += size;
             Ok(size)
         } else {
@@ -96,7 +186,7 @@ impl Write for MultiChunkWriter {
 
     fn flush(&mut self) -> Result<()> {
         if let Some(encoder) = self.encoder.as_mut() {
-            encoder.flush()
+            encoder.writer().flush()
         } else {
             Ok(())
         }
@@ -181,4 +271,33 @@ mod tests {
 
         assert!(non_existent_subdir.exists()); // Directory should now exist
     }
+
+    #[test]
+    fn test_zstd_compression() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut writer = MultiChunkWriter::new(temp_dir.path().to_path_buf(), 5)
+            .with_compression(SnapshotCompression::Zstd { level: 19 }, 2);
+
+        writer.write_all(b"12345").unwrap();
+        writer.write_all(b"67890").unwrap(); // This should trigger a new part
+        writer.finish().unwrap();
+
+        let mut files: Vec<_> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        files.sort();
+        assert_eq!(files.len(), 2);
+
+        let contents = zstd::decode_all(File::open(&files[1]).unwrap()).unwrap();
+        assert_eq!(contents, b"67890");
+    }
+
+    #[test]
+    fn test_compression_levels() {
+        assert!(SnapshotCompression::Gzip { level: 9 }.is_valid());
+        assert!(!SnapshotCompression::Gzip { level: 10 }.is_valid());
+        assert!(SnapshotCompression::Zstd { level: 22 }.is_valid());
+        assert!(!SnapshotCompression::Zstd { level: 23 }.is_valid());
+    }
 }
@@ -1,12 +1,17 @@
-use crate::db::multi_chunk_writer::MultiChunkWriter;
+use crate::db::multi_chunk_reader::MultiChunkReader;
+use crate::db::multi_chunk_writer::{MultiChunkWriter, SnapshotCompression};
+use crate::db::snapshot_control::{
+    SnapshotCancellationToken, SnapshotProgress, SnapshotProgressReporter,
+};
+use crate::db::snapshot_manifest::{SnapshotFile, SnapshotManifest, MANIFEST_FILE_NAME};
 use crate::logger::LOGGER;
+use crate::panics::{catch_panic, execute, read_unpoisoned, write_unpoisoned};
 use crate::statsd::statsd;
 use crate::store::{
     self, get_db, get_iterator_options, hub_error_to_js_throw, increment_vec_u8, HubError,
-    PageOptions, PAGE_SIZE_MAX,
+    HubErrorCode, PageOptions, PAGE_SIZE_MAX,
 };
-use crate::trie::merkle_trie::TRIE_DBPATH_PREFIX;
-use crate::THREAD_POOL;
+use crate::trie::merkle_trie::{MerkleTrie, TRIE_DBPATH_PREFIX};
 use chrono::NaiveDateTime;
 use neon::context::{Context, FunctionContext};
 use neon::handle::Handle;
@@ -15,29 +20,42 @@ use neon::result::JsResult;
 use neon::types::buffer::TypedArray;
 use neon::types::{
     Finalize, JsArray, JsBoolean, JsBox, JsBuffer, JsFunction, JsNumber, JsObject, JsPromise,
-    JsString,
+    JsString, JsValue,
 };
+use rocksdb::checkpoint::Checkpoint;
 use rocksdb::{Options, TransactionDB, WriteBatch, WriteOptions, DB};
-use slog::{info, o, Logger};
+use slog::{error, info, o, Logger};
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock, RwLockReadGuard};
 use tar::Builder;
 use walkdir::WalkDir;
 
 const DB_DIRECTORY: &str = ".rocks";
 
+// How many times to try reopening the DB after taking a checkpoint
+const CHECKPOINT_REOPEN_ATTEMPTS: u32 = 3;
+
+/** Read a counter written by `RocksDbTransactionBatch::increment`. Malformed values count as 0 */
+pub fn decode_counter(value: &[u8]) -> u64 {
+    value.try_into().map_or(0, u64::from_be_bytes)
+}
+
 /** Hold a transaction. List of key/value pairs that will be committed together */
 pub struct RocksDbTransactionBatch {
     pub batch: HashMap<Vec<u8>, Option<Vec<u8>>>,
+    /** Deltas for counter keys, added to the stored counts when the batch is committed */
+    pub counters: HashMap<Vec<u8>, i64>,
 }
 
 impl RocksDbTransactionBatch {
     pub fn new() -> RocksDbTransactionBatch {
         RocksDbTransactionBatch {
             batch: HashMap::new(),
+            counters: HashMap::new(),
         }
     }
 
@@ -49,14 +67,25 @@ impl RocksDbTransactionBatch {
         self.batch.insert(key, None);
     }
 
+    /**
+     * Add `delta` to the counter at `key` when the batch is committed. Counters are stored as
+     * big-endian u64s, never go below 0, and are deleted when they reach 0.
+     */
+    pub fn increment(&mut self, key: Vec<u8>, delta: i64) {
+        *self.counters.entry(key).or_insert(0) += delta;
+    }
+
     pub fn merge(&mut self, other: RocksDbTransactionBatch) {
         for (key, value) in other.batch {
             self.batch.insert(key, value);
         }
+        for (key, delta) in other.counters {
+            self.increment(key, delta);
+        }
     }
 
     pub fn len(&self) -> usize {
-        self.batch.len()
+        self.batch.len() + self.counters.len()
     }
 }
 
@@ -73,12 +102,88 @@ pub struct JsIteratorOptions {
     pub lt: Vec<u8>,
 }
 
+/** How a snapshot backup copies the DBs before they are archived */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnapshotMode {
+    /**
+     * Hard-link the live SST files using RocksDB checkpoints. Writes are paused while linking, and
+     * the DBs are closed and reopened around it
+     */
+    Checkpoint,
+    /** Copy every key into fresh DBs from a RocksDB snapshot. Slow, but never pauses writes */
+    Copy,
+}
+
+/** Options for a snapshot backup, passed in from JS */
+#[derive(Clone)]
+pub struct SnapshotBackupOptions {
+    pub mode: SnapshotMode,
+    /** The network the hub is on, recorded in the snapshot manifest */
+    pub network: i32,
+    /**
+     * Path to the manifest.json of a previous snapshot. If set, the snapshot is incremental, and
+     * only has the files that are not in that snapshot
+     */
+    pub base_manifest: Option<String>,
+    /** Checked while copying and archiving, so JS can stop a long running backup */
+    pub cancellation_token: Arc<SnapshotCancellationToken>,
+    /** Reports keys and bytes copied, per DB */
+    pub progress: SnapshotProgressReporter,
+    pub compression: SnapshotCompression,
+    /** Max size of each chunk of the archive, before compression */
+    pub chunk_size: usize,
+    /** Threads used to compress each chunk */
+    pub num_threads: usize,
+    /** The archive directory is created inside this directory */
+    pub output_dir: String,
+}
+
+impl Default for SnapshotBackupOptions {
+    fn default() -> Self {
+        SnapshotBackupOptions {
+            mode: SnapshotMode::Copy,
+            network: 0,
+            base_manifest: None,
+            cancellation_token: Arc::new(SnapshotCancellationToken::new()),
+            progress: SnapshotProgressReporter::default(),
+            compression: SnapshotCompression::default(),
+            chunk_size: 4 * 1024 * 1024 * 1024, // 4GB
+            num_threads: 4,
+            output_dir: DB_DIRECTORY.to_string(),
+        }
+    }
+}
+
+impl SnapshotBackupOptions {
+    pub fn validate(&self) -> Result<(), HubError> {
+        if !self.compression.is_valid() {
+            return Err(HubError::invalid_parameter(&format!(
+                "Invalid compression level: {:?}",
+                self.compression
+            )));
+        }
+        if self.chunk_size == 0 {
+            return Err(HubError::invalid_parameter("Chunk size must be positive"));
+        }
+        if self.num_threads == 0 {
+            return Err(HubError::invalid_parameter("Threads must be positive"));
+        }
+
+        Ok(())
+    }
+}
+
 pub struct RocksDB {
     pub db: RwLock<Option<rocksdb::TransactionDB>>,
     pub path: String,
     logger: slog::Logger,
 }
 
+/** The error for using a RocksDB that was closed, or never opened */
+fn not_open() -> HubError {
+    HubError::internal_db_error("Database is not open")
+}
+
 /** Needed to make sure neon can clean up the RocksDB at the end */
 impl Finalize for RocksDB {}
 
@@ -88,6 +193,46 @@ impl std::fmt::Debug for RocksDB {
     }
 }
 
+/**
+ * A consistent view of the DB as of when it was taken, for reads that have to agree with each
+ * other. Commits made after it was taken aren't visible through it.
+ */
+pub struct ReadSnapshot<'a> {
+    snapshot: rocksdb::SnapshotWithThreadMode<'a, TransactionDB>,
+}
+
+impl ReadSnapshot<'_> {
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, HubError> {
+        self.snapshot
+            .get(key)
+            .map_err(|e| HubError::new(HubErrorCode::StorageFailure, e.to_string()))
+    }
+
+    /**
+     * Iterate over all keys with a given prefix, in order.
+     * The callback function should return true to stop the iteration, or false to continue.
+     */
+    pub fn for_each_iterator_by_prefix<F>(&self, prefix: &[u8], mut f: F) -> Result<(), HubError>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool, HubError>,
+    {
+        let iter_opts = RocksDB::get_iterator_options(prefix, &PageOptions::default());
+        let mut iter = self.snapshot.raw_iterator_opt(iter_opts.opts);
+
+        iter.seek_to_first();
+        while iter.valid() {
+            if let Some((key, value)) = iter.item() {
+                if f(key, value)? {
+                    break;
+                }
+            }
+            iter.next();
+        }
+
+        Ok(())
+    }
+}
+
 impl RocksDB {
     pub fn new(path: &str) -> Result<RocksDB, HubError> {
         let logger = LOGGER.new(o!("component" => "RustRocksDB"));
@@ -101,9 +246,7 @@ impl RocksDB {
         })
     }
 
-    pub fn open(&self) -> Result<(), HubError> {
-        let mut db_lock = self.db.write().unwrap();
-
+    fn open_transaction_db(&self) -> Result<TransactionDB, HubError> {
         // Create RocksDB options
         let mut opts = Options::default();
         opts.create_if_missing(true); // Creates a database if it does not exist
@@ -113,6 +256,13 @@ impl RocksDB {
 
         // Open the database with multi-threaded support
         let db = rocksdb::TransactionDB::open(&opts, &tx_db_opts, &self.path)?;
+        Ok(db)
+    }
+
+    pub fn open(&self) -> Result<(), HubError> {
+        let mut db_lock = write_unpoisoned(&self.db);
+
+        let db = self.open_transaction_db()?;
         *db_lock = Some(db);
 
         // We put the db in a RwLock to make the compiler happy, but it is strictly not required.
@@ -134,7 +284,7 @@ impl RocksDB {
     }
 
     pub fn close(&self) -> Result<(), HubError> {
-        let mut db_lock = self.db.write().unwrap();
+        let mut db_lock = write_unpoisoned(&self.db);
         if db_lock.is_some() {
             let db = db_lock.take().unwrap();
             drop(db);
@@ -159,11 +309,8 @@ impl RocksDB {
         self.close()?;
         let path = Path::new(&self.path);
 
-        let result =
-            rocksdb::DB::destroy(&rocksdb::Options::default(), path).map_err(|e| HubError {
-                code: "db.internal_error".to_string(),
-                message: e.to_string(),
-            });
+        let result = rocksdb::DB::destroy(&rocksdb::Options::default(), path)
+            .map_err(|e| HubError::new(HubErrorCode::StorageFailure, e.to_string()));
 
         // Also rm -rf the directory, ignore any errors
         let _ = fs::remove_dir_all(path);
@@ -172,12 +319,12 @@ impl RocksDB {
     }
 
     pub fn db(&self) -> RwLockReadGuard<'_, Option<TransactionDB>> {
-        self.db.read().unwrap()
+        read_unpoisoned(&self.db)
     }
 
     pub fn keys_exist(&self, keys: &Vec<Vec<u8>>) -> Result<Vec<bool>, HubError> {
         let db = self.db();
-        let db = db.as_ref().unwrap();
+        let db = db.as_ref().ok_or_else(not_open)?;
 
         Ok(db
             .multi_get(keys)
@@ -191,14 +338,15 @@ impl RocksDB {
     }
 
     pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, HubError> {
-        self.db().as_ref().unwrap().get(key).map_err(|e| HubError {
-            code: "db.internal_error".to_string(),
-            message: e.to_string(),
-        })
+        self.db()
+            .as_ref()
+            .ok_or_else(not_open)?
+            .get(key)
+            .map_err(|e| HubError::new(HubErrorCode::StorageFailure, e.to_string()))
     }
 
     pub fn get_many(&self, keys: &Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>, HubError> {
-        let results = self.db().as_ref().unwrap().multi_get(keys);
+        let results = self.db().as_ref().ok_or_else(not_open)?.multi_get(keys);
 
         // If any of the results are Errors, return an error
         let results = results.into_iter().collect::<Result<Vec<_>, _>>()?;
@@ -210,26 +358,33 @@ impl RocksDB {
         Ok(results)
     }
 
+    /** Run f against a ReadSnapshot of the DB, taken when this is called */
+    pub fn with_read_snapshot<T, F>(&self, f: F) -> Result<T, HubError>
+    where
+        F: FnOnce(&ReadSnapshot) -> Result<T, HubError>,
+    {
+        let db = self.db();
+        let snapshot = ReadSnapshot {
+            snapshot: db.as_ref().ok_or_else(not_open)?.snapshot(),
+        };
+
+        f(&snapshot)
+    }
+
     pub fn put(&self, key: &[u8], value: &[u8]) -> Result<(), HubError> {
         self.db()
             .as_ref()
-            .unwrap()
+            .ok_or_else(not_open)?
             .put(key, value)
-            .map_err(|e| HubError {
-                code: "db.internal_error".to_string(),
-                message: e.to_string(),
-            })
+            .map_err(|e| HubError::new(HubErrorCode::StorageFailure, e.to_string()))
     }
 
     pub fn del(&self, key: &[u8]) -> Result<(), HubError> {
         self.db()
             .as_ref()
-            .unwrap()
+            .ok_or_else(not_open)?
             .delete(key)
-            .map_err(|e| HubError {
-                code: "db.internal_error".to_string(),
-                message: e.to_string(),
-            })
+            .map_err(|e| HubError::new(HubErrorCode::StorageFailure, e.to_string()))
     }
 
     pub fn txn(&self) -> RocksDbTransactionBatch {
@@ -238,14 +393,29 @@ impl RocksDB {
 
     pub fn commit(&self, batch: RocksDbTransactionBatch) -> Result<(), HubError> {
         let db = self.db();
-        if db.is_none() {
-            return Err(HubError {
-                code: "db.internal_error".to_string(),
-                message: "Database is not open".to_string(),
-            });
+        let txn = db.as_ref().ok_or_else(not_open)?.transaction();
+
+        // Counters are shared between fids, so they're read with get_for_update, which locks them
+        // until this transaction commits. That way concurrent commits can't lose each other's
+        // updates. Locking them in sorted order stops two commits from waiting on each other.
+        let mut counters: Vec<(Vec<u8>, i64)> = batch.counters.into_iter().collect();
+        counters.sort();
+        for (key, delta) in counters {
+            if delta == 0 {
+                continue;
+            }
+
+            let current = txn
+                .get_for_update(&key, true)?
+                .map_or(0, |v| decode_counter(&v));
+            let count = (current as i64).saturating_add(delta).max(0) as u64;
+            if count == 0 {
+                txn.delete(key)?;
+            } else {
+                txn.put(key, count.to_be_bytes())?;
+            }
         }
 
-        let txn = db.as_ref().unwrap().transaction();
         for (key, value) in batch.batch {
             if value.is_none() {
                 txn.delete(key)?;
@@ -255,10 +425,8 @@ impl RocksDB {
         }
 
         statsd().incr("rust.db.commit");
-        txn.commit().map_err(|e| HubError {
-            code: "db.internal_error".to_string(),
-            message: e.to_string(),
-        })
+        txn.commit()
+            .map_err(|e| HubError::new(HubErrorCode::StorageFailure, e.to_string()))
     }
 
     fn get_iterator_options(prefix: &[u8], page_options: &PageOptions) -> IteratorOptions {
@@ -294,8 +462,25 @@ impl RocksDB {
             };
         }
 
-        let mut lower_prefix;
-        let mut upper_prefix;
+        let (lower_prefix, upper_prefix) = Self::get_prefix_bounds(prefix, page_options);
+
+        let mut opts = rocksdb::ReadOptions::default();
+        opts.set_iterate_lower_bound(lower_prefix);
+        opts.set_iterate_upper_bound(upper_prefix);
+
+        IteratorOptions {
+            opts,
+            reverse: page_options.reverse,
+        }
+    }
+
+    /**
+     * The [lower, upper) bounds for iterating over a non-empty prefix, starting after the page
+     * token if there is one.
+     */
+    fn get_prefix_bounds(prefix: &[u8], page_options: &PageOptions) -> (Vec<u8>, Vec<u8>) {
+        let lower_prefix;
+        let upper_prefix;
 
         if page_options.reverse {
             lower_prefix = prefix.to_vec();
@@ -320,14 +505,7 @@ impl RocksDB {
             upper_prefix = prefix_end.to_vec();
         }
 
-        let mut opts = rocksdb::ReadOptions::default();
-        opts.set_iterate_lower_bound(lower_prefix);
-        opts.set_iterate_upper_bound(upper_prefix);
-
-        IteratorOptions {
-            opts,
-            reverse: page_options.reverse,
-        }
+        (lower_prefix, upper_prefix)
     }
 
     /**
@@ -337,7 +515,10 @@ impl RocksDB {
         let iter_opts = RocksDB::get_iterator_options(prefix, &PageOptions::default());
 
         let db = self.db();
-        let mut iter = db.as_ref().unwrap().raw_iterator_opt(iter_opts.opts);
+        let mut iter = db
+            .as_ref()
+            .ok_or_else(not_open)?
+            .raw_iterator_opt(iter_opts.opts);
 
         let mut count = 0;
         iter.seek_to_first();
@@ -366,7 +547,10 @@ impl RocksDB {
         let iter_opts = RocksDB::get_iterator_options(prefix, page_options);
 
         let db = self.db();
-        let mut iter = db.as_ref().unwrap().raw_iterator_opt(iter_opts.opts);
+        let mut iter = db
+            .as_ref()
+            .ok_or_else(not_open)?
+            .raw_iterator_opt(iter_opts.opts);
 
         if iter_opts.reverse {
             iter.seek_to_last();
@@ -424,6 +608,70 @@ impl RocksDB {
         Ok(all_done)
     }
 
+    /**
+     * Same as for_each_iterator_by_prefix, but only iterates over keys in
+     * [prefix + start, prefix + stop). A None bound leaves that end of the prefix open. The bounds
+     * are applied with a seek, so keys outside them are never read.
+     * The callback function should return true to stop the iteration, or false to continue.
+     */
+    pub fn for_each_iterator_by_prefix_in_range<F>(
+        &self,
+        prefix: &[u8],
+        start: Option<&[u8]>,
+        stop: Option<&[u8]>,
+        page_options: &PageOptions,
+        mut f: F,
+    ) -> Result<bool, HubError>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool, HubError>,
+    {
+        if prefix.is_empty() {
+            return Err(HubError::invalid_parameter(
+                "a prefix is required to iterate over a range",
+            ));
+        }
+
+        let (mut lower_bound, mut upper_bound) = Self::get_prefix_bounds(prefix, page_options);
+        if let Some(start) = start {
+            lower_bound = lower_bound.max([prefix, start].concat());
+        }
+        if let Some(stop) = stop {
+            upper_bound = upper_bound.min([prefix, stop].concat());
+        }
+        if lower_bound >= upper_bound {
+            return Ok(true);
+        }
+
+        let mut opts = rocksdb::ReadOptions::default();
+        opts.set_iterate_lower_bound(lower_bound);
+        opts.set_iterate_upper_bound(upper_bound);
+
+        let db = self.db();
+        let mut iter = db.as_ref().ok_or_else(not_open)?.raw_iterator_opt(opts);
+
+        if page_options.reverse {
+            iter.seek_to_last();
+        } else {
+            iter.seek_to_first();
+        }
+
+        while iter.valid() {
+            if let Some((key, value)) = iter.item() {
+                if f(&key, &value)? {
+                    return Ok(false);
+                }
+            }
+
+            if page_options.reverse {
+                iter.prev();
+            } else {
+                iter.next();
+            }
+        }
+
+        Ok(true)
+    }
+
     /**
      * Iterate over all keys using the raw lower/upper bound options.
      * The callback function should return true to stop the iteration, or false to continue.
@@ -435,24 +683,26 @@ impl RocksDB {
     ) -> Result<bool, HubError> {
         // Can't have both gte and gt set
         if js_opts.gte.is_some() && js_opts.gt.is_some() {
-            return Err(HubError {
-                code: "db.invalid_iterator_options".to_string(),
-                message: "gte and gt cannot be set at the same time".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::InvalidParam,
+                "gte and gt cannot be set at the same time",
+            ));
         }
 
         // At least one of gte or gt must be set
         if js_opts.gte.is_none() && js_opts.gt.is_none() {
-            return Err(HubError {
-                code: "db.invalid_iterator_options".to_string(),
-                message: "gte or gt must be set".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::InvalidParam,
+                "gte or gt must be set",
+            ));
         }
 
         let (lower_bound, increment_first) = if let Some(gte) = js_opts.gte {
             (gte, false)
+        } else if let Some(gt) = js_opts.gt {
+            (gt, true)
         } else {
-            (js_opts.gt.unwrap(), true)
+            return Err(HubError::invalid_parameter("gte or gt is required"));
         };
         let upper_bound = js_opts.lt;
         let reverse = js_opts.reverse;
@@ -462,7 +712,7 @@ impl RocksDB {
         opts.set_iterate_upper_bound(upper_bound);
 
         let db = self.db();
-        let mut iter = db.as_ref().unwrap().raw_iterator_opt(opts);
+        let mut iter = db.as_ref().ok_or_else(not_open)?.raw_iterator_opt(opts);
 
         if reverse {
             iter.seek_to_last();
@@ -509,7 +759,11 @@ impl RocksDB {
             let mut txn = self.txn();
             let db = self.db();
 
-            for item in db.as_ref().unwrap().iterator(rocksdb::IteratorMode::Start) {
+            for item in db
+                .as_ref()
+                .ok_or_else(not_open)?
+                .iterator(rocksdb::IteratorMode::Start)
+            {
                 if let Ok((key, _)) = item {
                     txn.delete(key.to_vec());
                     deleted += 1;
@@ -527,6 +781,83 @@ impl RocksDB {
         Ok(deleted)
     }
 
+    /**
+     * Create a RocksDB checkpoint of this DB at `checkpoint_path`. A checkpoint hard-links the live
+     * SST files, so it is cheap as long as `checkpoint_path` is on the same filesystem as the DB.
+     *
+     * The rocksdb crate can't take a checkpoint of a TransactionDB, so the DB is closed and briefly
+     * reopened as a plain DB while the checkpoint is taken. The caller must hold the write lock on
+     * `self.db` and pass it in as `db_lock`, so no one else can use the DB while it is reopened.
+     *
+     * The TransactionDB is reopened on every path, including a failed or panicking checkpoint. If it
+     * can't be reopened, that error is returned and the DB stays closed, so every later read and
+     * write fails until the hub restarts. That's why checkpoints aren't the default snapshot mode.
+     */
+    fn create_checkpoint_locked(
+        &self,
+        db_lock: &mut Option<TransactionDB>,
+        checkpoint_path: &Path,
+    ) -> Result<(), HubError> {
+        if db_lock.is_none() {
+            return Err(not_open());
+        }
+
+        // Close the TransactionDB, so we can open the same files as a regular DB
+        drop(db_lock.take());
+
+        // A panicking checkpoint must not skip the reopen below
+        let checkpoint_result = catch_panic(|| {
+            let db = DB::open_default(&self.path)?;
+            // Flush the memtables, so the checkpoint doesn't need to replay the WAL
+            db.flush()?;
+            Checkpoint::new(&db)?.create_checkpoint(checkpoint_path)?;
+            Ok(())
+        });
+
+        *db_lock = Some(self.reopen_transaction_db(&checkpoint_result)?);
+
+        checkpoint_result
+    }
+
+    /** Reopen the TransactionDB after a checkpoint, retrying in case the plain DB is still closing */
+    fn reopen_transaction_db(
+        &self,
+        checkpoint_result: &Result<(), HubError>,
+    ) -> Result<TransactionDB, HubError> {
+        self.retry_reopen(|| self.open_transaction_db(), checkpoint_result)
+    }
+
+    /** Call `open` up to CHECKPOINT_REOPEN_ATTEMPTS times, until it succeeds */
+    fn retry_reopen<T, F>(
+        &self,
+        mut open: F,
+        checkpoint_result: &Result<(), HubError>,
+    ) -> Result<T, HubError>
+    where
+        F: FnMut() -> Result<T, HubError>,
+    {
+        let mut attempt = 1;
+        loop {
+            match open() {
+                Ok(db) => return Ok(db),
+                Err(e) if attempt < CHECKPOINT_REOPEN_ATTEMPTS => {
+                    error!(self.logger, "Failed to reopen DB after checkpoint, retrying";
+                        "path" => &self.path, "attempt" => attempt, "error" => e.to_string());
+                    std::thread::sleep(std::time::Duration::from_millis(100 * attempt as u64));
+                    attempt += 1;
+                }
+                Err(e) => {
+                    // Don't lose the checkpoint error behind the reopen error
+                    if let Err(checkpoint_error) = checkpoint_result {
+                        error!(self.logger, "Checkpoint failed before the DB could not be reopened";
+                            "path" => &self.path, "error" => checkpoint_error.to_string());
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+
     pub fn approximate_size(&self) -> u64 {
         WalkDir::new(self.location())
             .into_iter()
@@ -638,8 +969,8 @@ impl RocksDB {
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let result = db.keys_exist(&key_vec);
+        execute(move || {
+            let result = catch_panic(|| db.keys_exist(&key_vec));
 
             deferred.settle_with(&channel, move |mut cx| match result {
                 Ok(exists) => {
@@ -667,10 +998,11 @@ impl RocksDB {
             Ok(None) => {
                 return hub_error_to_js_throw(
                     &mut cx,
-                    HubError {
-                        code: "not_found".to_string(),
-                        message: format!("NotFound: key not found: {:?}", key),
-                    },
+                    HubError::new(
+                        HubErrorCode::NotFound,
+                        format!("NotFound: key not found: {:?}", key),
+                    )
+                    .with_key(&key),
                 )
             }
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
@@ -697,8 +1029,8 @@ impl RocksDB {
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let result = db.get_many(&key_vec);
+        execute(move || {
+            let result = catch_panic(|| db.get_many(&key_vec));
 
             deferred.settle_with(&channel, move |mut cx| {
                 let result = match result {
@@ -831,8 +1163,8 @@ impl RocksDB {
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let result = db.count_keys_at_prefix(&prefix);
+        execute(move || {
+            let result = catch_panic(|| db.count_keys_at_prefix(&prefix));
             deferred.settle_with(&channel, move |mut cx| {
                 let result = match result {
                     Ok(r) => r,
@@ -860,11 +1192,11 @@ impl RocksDB {
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
-        THREAD_POOL.lock().unwrap().execute(move || {
+        execute(move || {
             let mut results = Vec::new();
             let mut next_page_token = Vec::new();
 
-            let iter_result =
+            let iter_result = catch_panic(|| {
                 db.for_each_iterator_by_prefix_paged(&prefix, &page_options, |key, value| {
                     results.push((key.to_vec(), value.to_vec()));
                     if results.len() > PAGE_SIZE_MAX {
@@ -872,7 +1204,8 @@ impl RocksDB {
                         return Ok(true);
                     }
                     Ok(false)
-                });
+                })
+            });
 
             deferred.settle_with(&channel, move |mut cx| match iter_result {
                 Err(e) => hub_error_to_js_throw(&mut cx, e),
@@ -973,10 +1306,11 @@ impl RocksDB {
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
+        execute(move || {
             // Delete all keys in the range
-            let result =
-                db.for_each_iterator_by_jsopts(js_opts, |key, _| db.del(key).map(|_| false));
+            let result = catch_panic(|| {
+                db.for_each_iterator_by_jsopts(js_opts, |key, _| db.del(key).map(|_| false))
+            });
 
             deferred.settle_with(&channel, move |mut cx| match result {
                 Ok(r) => Ok(cx.boolean(r)),
@@ -989,21 +1323,31 @@ impl RocksDB {
 }
 
 impl RocksDB {
-    fn create_tar_gzip(
+    /**
+     * Archive the `files` under `input_dir` into chunks under `output_dir`. Files in `excludes` are
+     * left out, which is how incremental snapshots skip the SST files their base already has.
+     * If archiving fails or is cancelled, the partially written chunks are removed.
+     */
+    fn create_tar_archive(
         logger: &Logger,
         input_dir: &str,
+        output_dir: &Path,
         timestamp: NaiveDateTime,
+        files: &[SnapshotFile],
+        excludes: &HashSet<String>,
+        options: &SnapshotBackupOptions,
     ) -> Result<String, HubError> {
         let base_name = Path::new(input_dir)
             .file_name()
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or("rocks.hub._default".to_string());
 
-        let chunked_output_dir = Path::new(DB_DIRECTORY)
+        let chunked_output_dir = output_dir
             .join(format!(
-                "{}-{}.tar.gz",
+                "{}-{}.{}",
                 base_name,
-                timestamp.format("%Y-%m-%d-%s")
+                timestamp.format("%Y-%m-%d-%s"),
+                options.compression.extension()
             ))
             .as_os_str()
             .to_str()
@@ -1011,25 +1355,32 @@ impl RocksDB {
             .to_string();
 
         let start = std::time::SystemTime::now();
-        info!(logger, "Creating chunked tar.gz snapshot for directory: {}",
-            input_dir; o!("output_file_path" => &chunked_output_dir, "base_name" => &base_name));
-
-        let mut multi_chunk_writer = MultiChunkWriter::new(
-            PathBuf::from(chunked_output_dir.clone()),
-            4 * 1024 * 1024 * 1024, // 4GB
+        info!(logger, "Creating chunked tar archive snapshot for directory: {}",
+            input_dir; o!("output_file_path" => &chunked_output_dir, "base_name" => &base_name,
+            "num_files" => files.len(), "num_excluded" => excludes.len(),
+            "compression" => format!("{:?}", options.compression),
+            "chunk_size" => options.chunk_size, "num_threads" => options.num_threads));
+
+        let result = Self::write_tar_archive(
+            input_dir,
+            &base_name,
+            &chunked_output_dir,
+            files,
+            excludes,
+            options,
         );
-
-        let mut tar = Builder::new(&mut multi_chunk_writer);
-        tar.append_dir_all(base_name, input_dir)?;
-        tar.finish()?;
-        drop(tar); // Needed so we can call multi_chunk_writer.finish() next
-        multi_chunk_writer.finish()?;
+        if let Err(e) = result {
+            info!(logger, "Failed to create tar archive, removing partial chunks";
+                "error" => &e.message, "output_file_path" => &chunked_output_dir);
+            let _ = fs::remove_dir_all(&chunked_output_dir);
+            return Err(e);
+        }
 
         let metadata = fs::metadata(&chunked_output_dir)?;
         let time_taken = start.elapsed().expect("Time went backwards");
         info!(
             logger,
-            "Created chunked tar.gz archive for snapshot: path = {}, size = {} bytes, time taken = {:?}",
+            "Created chunked tar archive for snapshot: path = {}, size = {} bytes, time taken = {:?}",
             chunked_output_dir,
             metadata.len(),
             time_taken
@@ -1038,146 +1389,352 @@ impl RocksDB {
         Ok(chunked_output_dir)
     }
 
-    fn snapshot_backup(
-        main_db: Arc<RocksDB>,
-        trie_db: Arc<RocksDB>,
-        timestamp_ms: i64,
-    ) -> Result<String, HubError> {
-        let snapshot_logger = LOGGER.new(o! ("component" => "RocksDBSnapshotBackup"));
-        let main_db_path = main_db.location();
+    fn write_tar_archive(
+        input_dir: &str,
+        base_name: &str,
+        chunked_output_dir: &str,
+        files: &[SnapshotFile],
+        excludes: &HashSet<String>,
+        options: &SnapshotBackupOptions,
+    ) -> Result<(), HubError> {
+        let mut multi_chunk_writer =
+            MultiChunkWriter::new(PathBuf::from(chunked_output_dir), options.chunk_size)
+                .with_compression(options.compression, options.num_threads);
+
+        // Progress is tracked separately for the main DB's files and the trie DB's files
+        let mut main_progress = SnapshotProgress {
+            stage: "archive",
+            db: "main",
+            keys: 0,
+            bytes: 0,
+        };
+        let mut trie_progress = SnapshotProgress {
+            db: "trie",
+            ..main_progress.clone()
+        };
 
-        let timestamp = chrono::NaiveDateTime::from_timestamp_millis(timestamp_ms)
-            .unwrap_or(chrono::Utc::now().naive_utc());
+        let mut tar = Builder::new(&mut multi_chunk_writer);
+        for file in files.iter().filter(|file| !excludes.contains(&file.path)) {
+            options.cancellation_token.check()?;
 
-        let main_backup_path = Path::new(&main_db_path)
-            .join("..") // Create backup as sibling directory of normal path
-            .join("backup")
-            .join(format!("{}.backup", timestamp.format("%Y-%m-%d-%s")))
-            .join("rocks.hub._default");
+            tar.append_path_with_name(
+                Path::new(input_dir).join(&file.path),
+                Path::new(base_name).join(&file.path),
+            )?;
 
-        // rm -rf this path if it exists
-        if main_backup_path.exists() {
-            fs::remove_dir_all(&main_backup_path).map_err(|e| HubError {
-                code: "db.internal_error".to_string(),
-                message: e.to_string(),
-            })?;
+            let progress = if Path::new(&file.path).starts_with(TRIE_DBPATH_PREFIX) {
+                &mut trie_progress
+            } else {
+                &mut main_progress
+            };
+            progress.bytes += file.size;
+            options.progress.report(progress.clone());
         }
+        tar.finish()?;
+        drop(tar); // Needed so we can call multi_chunk_writer.finish() next
+        multi_chunk_writer.finish()?;
 
-        let triedb_backup_path = main_backup_path.join(TRIE_DBPATH_PREFIX);
-
-        let main_backup_path = main_backup_path.into_os_string().into_string().unwrap();
-        let triedb_backup_path = triedb_backup_path.into_os_string().into_string().unwrap();
-
-        let start = std::time::SystemTime::now();
-        info!(snapshot_logger, "Creating snapshot for main DB: {}", main_db_path; 
-        o!("output_file_path_main" => &main_backup_path, "output_file_path_trie" => &triedb_backup_path));
-
-        let backup_main = DB::open_default(&main_backup_path)
-            .map_err(|e| HubError::internal_db_error(&e.to_string()))?;
-        // Prepare write options to disable WAL
-        let mut write_opts = WriteOptions::default();
-        write_opts.disable_wal(true);
-        let mut write_batch = WriteBatch::default();
+        Ok(())
+    }
 
-        let backup_trie = DB::open_default(&triedb_backup_path)
-            .map_err(|e| HubError::internal_db_error(&e.to_string()))?;
-
-        let logger = snapshot_logger.clone();
-        let main_backup_thread = std::thread::spawn(move || {
-            let main_db = main_db.db();
-            let main_db_snapshot = main_db.as_ref().unwrap().snapshot();
-
-            let iterator = main_db_snapshot.iterator(rocksdb::IteratorMode::Start);
-            let mut count = 0;
-            for item in iterator {
-                let (key, value) = item.unwrap();
-                write_batch.put(key, value);
-                if write_batch.len() >= 10_000 {
-                    backup_main.write_opt(write_batch, &write_opts).unwrap();
-                    write_batch = WriteBatch::default();
-                }
+    /**
+     * RocksDB writes a unique id to the IDENTITY file when a DB is created. Snapshots record the ids
+     * of both DBs, so an incremental snapshot can check that its base came from the same DBs.
+     */
+    fn db_identity(main_db: &RocksDB, trie_db: &RocksDB) -> String {
+        let read_identity = |db: &RocksDB| {
+            fs::read_to_string(Path::new(&db.location()).join("IDENTITY"))
+                .map(|identity| identity.trim().to_string())
+                .unwrap_or_default()
+        };
 
-                count += 1;
-                if count % 1_000_000 == 0 {
-                    backup_main.flush().unwrap();
-                    info!(
-                        logger,
-                        "mainDb Snapshot backup progress: {}M keys",
-                        count / 1_000_000
-                    );
-                }
-            }
+        format!("{}:{}", read_identity(main_db), read_identity(trie_db))
+    }
 
-            // write any leftover keys
-            backup_main.write_opt(write_batch, &write_opts).unwrap();
+    /**
+     * Take checkpoints of the main and trie DBs. Both DBs are write-locked (main first, then trie)
+     * for the duration, so no writes can land in one DB and not the other, and the two checkpoints
+     * are consistent with each other.
+     */
+    fn create_checkpoints(
+        main_db: &RocksDB,
+        trie_db: &RocksDB,
+        main_checkpoint_path: &Path,
+        trie_checkpoint_path: &Path,
+    ) -> Result<(), HubError> {
+        let mut main_db_lock = write_unpoisoned(&main_db.db);
+        let mut trie_db_lock = write_unpoisoned(&trie_db.db);
+
+        main_db.create_checkpoint_locked(&mut main_db_lock, main_checkpoint_path)?;
+        trie_db.create_checkpoint_locked(&mut trie_db_lock, trie_checkpoint_path)?;
 
-            info!(logger, "mainDB Snapshot backup completed: {}", count);
-            drop(main_db_snapshot);
-            drop(backup_main);
-        });
+        Ok(())
+    }
 
-        let logger = snapshot_logger.clone();
+    /**
+     * Copy every key in `db` into `backup_db`, reading from a RocksDB snapshot so writes that land
+     * during the copy are not included. Stops early if the backup is cancelled, or if `failed` is
+     * set because the copy of the other DB failed.
+     */
+    fn copy_db(
+        logger: &Logger,
+        db: &RocksDB,
+        db_name: &'static str,
+        backup_db: &DB,
+        options: &SnapshotBackupOptions,
+        failed: &AtomicBool,
+    ) -> Result<(), HubError> {
         // Prepare write options to disable WAL
         let mut write_opts = WriteOptions::default();
         write_opts.disable_wal(true);
         let mut write_batch = WriteBatch::default();
 
-        let trie_backup_thread = std::thread::spawn(move || {
-            let trie_db = trie_db.db();
-            let trie_db_snapshot = trie_db.as_ref().unwrap().snapshot();
-
-            let iterator = trie_db_snapshot.iterator(rocksdb::IteratorMode::Start);
-            let mut count = 0;
-            for item in iterator {
-                let (key, value) = item.unwrap();
-                write_batch.put(key, value);
-                if write_batch.len() >= 10_000 {
-                    backup_trie.write_opt(write_batch, &write_opts).unwrap();
-                    write_batch = WriteBatch::default();
-                }
+        let db = db.db();
+        let db_snapshot = db.as_ref().ok_or_else(not_open)?.snapshot();
 
-                count += 1;
-                if count % 1_000_000 == 0 {
-                    backup_trie.flush().unwrap();
-                    info!(
-                        logger,
-                        "trieDb Snapshot backup progress: {}M keys",
-                        count / 1_000_000
-                    );
-                }
+        let mut progress = SnapshotProgress {
+            stage: "copy",
+            db: db_name,
+            keys: 0,
+            bytes: 0,
+        };
+        for item in db_snapshot.iterator(rocksdb::IteratorMode::Start) {
+            options.cancellation_token.check()?;
+            if failed.load(Ordering::Relaxed) {
+                // The other DB's error is the one that gets returned
+                return Ok(());
             }
 
-            // write any leftover keys
-            backup_trie.write_opt(write_batch, &write_opts).unwrap();
+            let (key, value) = item?;
+            progress.bytes += (key.len() + value.len()) as u64;
+            write_batch.put(key, value);
+            if write_batch.len() >= 10_000 {
+                backup_db.write_opt(std::mem::take(&mut write_batch), &write_opts)?;
+            }
 
-            info!(logger, "trieDB Snapshot backup completed: {}", count);
-            drop(trie_db_snapshot);
-            drop(backup_trie);
-        });
+            progress.keys += 1;
+            if progress.keys % 100_000 == 0 {
+                options.progress.report(progress.clone());
+            }
+            if progress.keys % 1_000_000 == 0 {
+                backup_db.flush()?;
+                info!(
+                    logger,
+                    "{}Db Snapshot backup progress: {}M keys",
+                    db_name,
+                    progress.keys / 1_000_000
+                );
+            }
+        }
 
-        main_backup_thread.join().unwrap();
-        trie_backup_thread.join().unwrap();
+        // write any leftover keys
+        backup_db.write_opt(write_batch, &write_opts)?;
+        options.progress.report(progress.clone());
 
         info!(
-            snapshot_logger,
-            "Full DB Snapshot Backup created: path = {}, time taken = {:?}",
-            main_backup_path,
-            start.elapsed().expect("Time went backwards")
+            logger,
+            "{}DB Snapshot backup completed: {}", db_name, progress.keys
         );
-
-        let tar_gz_path = Self::create_tar_gzip(&snapshot_logger, &main_backup_path, timestamp)?;
+        Ok(())
+    }
+
+    /**
+     * Copy the main and trie DBs into fresh DBs at the backup paths, one thread per DB. If either
+     * copy fails, the other one is stopped and the first error is returned.
+     */
+    fn copy_backup(
+        logger: &Logger,
+        main_db: &RocksDB,
+        trie_db: &RocksDB,
+        main_backup_path: &str,
+        triedb_backup_path: &str,
+        options: &SnapshotBackupOptions,
+    ) -> Result<(), HubError> {
+        // The trie backup lives inside the main backup, so the main backup DB has to be created first
+        let backup_main = DB::open_default(main_backup_path)?;
+        let backup_trie = DB::open_default(triedb_backup_path)?;
+
+        let failed = AtomicBool::new(false);
+        let copy = |db: &RocksDB, db_name: &'static str, backup_db: &DB| {
+            let result = Self::copy_db(logger, db, db_name, backup_db, options, &failed);
+            if result.is_err() {
+                failed.store(true, Ordering::Relaxed);
+            }
+            result
+        };
+
+        let (main_result, trie_result) = std::thread::scope(|scope| {
+            let main_backup_thread = scope.spawn(|| copy(main_db, "main", &backup_main));
+            let trie_backup_thread = scope.spawn(|| copy(trie_db, "trie", &backup_trie));
+
+            let join = |result: std::thread::Result<Result<(), HubError>>, db_name: &str| {
+                result.unwrap_or_else(|_| {
+                    Err(HubError::internal_db_error(&format!(
+                        "{}DB Snapshot backup thread panicked",
+                        db_name
+                    )))
+                })
+            };
+            (
+                join(main_backup_thread.join(), "main"),
+                join(trie_backup_thread.join(), "trie"),
+            )
+        });
+
+        main_result?;
+        trie_result?;
+
+        Ok(())
+    }
+
+    fn snapshot_backup(
+        main_db: Arc<RocksDB>,
+        trie_db: Arc<RocksDB>,
+        timestamp_ms: i64,
+        options: SnapshotBackupOptions,
+    ) -> Result<String, HubError> {
+        let snapshot_logger = LOGGER.new(o! ("component" => "RocksDBSnapshotBackup"));
+        options.validate()?;
+
+        let timestamp = chrono::NaiveDateTime::from_timestamp_millis(timestamp_ms)
+            .unwrap_or(chrono::Utc::now().naive_utc());
+
+        // Create backup as sibling directory of normal path
+        let backup_dir = Path::new(&main_db.location())
+            .join("..")
+            .join("backup")
+            .join(format!("{}.backup", timestamp.format("%Y-%m-%d-%s")));
+
+        let result = Self::create_snapshot(
+            &snapshot_logger,
+            &main_db,
+            &trie_db,
+            &backup_dir,
+            timestamp_ms,
+            timestamp,
+            &options,
+        );
+
+        // Don't leave a half written backup behind if the snapshot failed or was cancelled
+        if let Err(e) = &result {
+            info!(snapshot_logger, "Snapshot backup failed, cleaning up"; "error" => &e.message,
+                "code" => e.code.as_str(), "backup_dir" => backup_dir.to_string_lossy().to_string());
+            let _ = fs::remove_dir_all(&backup_dir);
+        }
+
+        result
+    }
+
+    fn create_snapshot(
+        snapshot_logger: &Logger,
+        main_db: &RocksDB,
+        trie_db: &RocksDB,
+        backup_dir: &Path,
+        timestamp_ms: i64,
+        timestamp: NaiveDateTime,
+        options: &SnapshotBackupOptions,
+    ) -> Result<String, HubError> {
+        let main_db_path = main_db.location();
+
+        // Incremental snapshots share SST files with their base, which only works with checkpoints
+        let base_manifest = match &options.base_manifest {
+            Some(base_manifest_path) => {
+                if options.mode != SnapshotMode::Checkpoint {
+                    return Err(HubError::invalid_parameter(
+                        "Incremental snapshots require checkpoint mode",
+                    ));
+                }
+                Some(SnapshotManifest::read_file(Path::new(base_manifest_path))?)
+            }
+            None => None,
+        };
+        let db_identity = Self::db_identity(main_db, trie_db);
+
+        let main_backup_path = backup_dir.join("rocks.hub._default");
+
+        // rm -rf this path if it exists
+        if main_backup_path.exists() {
+            fs::remove_dir_all(&main_backup_path)
+                .map_err(|e| HubError::new(HubErrorCode::StorageFailure, e.to_string()))?;
+        }
+
+        let triedb_backup_path = main_backup_path.join(TRIE_DBPATH_PREFIX);
+
+        let start = std::time::SystemTime::now();
+        info!(snapshot_logger, "Creating snapshot for main DB: {}", main_db_path;
+        o!("output_file_path_main" => main_backup_path.to_string_lossy().to_string(),
+           "output_file_path_trie" => triedb_backup_path.to_string_lossy().to_string(),
+           "mode" => format!("{:?}", options.mode)));
+
+        match options.mode {
+            SnapshotMode::Checkpoint => {
+                // The checkpoint directories themselves must not exist, but their parent must
+                if let Some(parent) = main_backup_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                options.cancellation_token.check()?;
+                Self::create_checkpoints(main_db, trie_db, &main_backup_path, &triedb_backup_path)?;
+            }
+            SnapshotMode::Copy => {
+                Self::copy_backup(
+                    snapshot_logger,
+                    main_db,
+                    trie_db,
+                    &main_backup_path.to_string_lossy(),
+                    &triedb_backup_path.to_string_lossy(),
+                    options,
+                )?;
+            }
+        }
+        options.cancellation_token.check()?;
+
+        let main_backup_path = main_backup_path.into_os_string().into_string().unwrap();
+
         info!(
             snapshot_logger,
-            "Full DB Snapshot Backup tar.gz created: path = {}", tar_gz_path,
+            "Full DB Snapshot Backup created: path = {}, time taken = {:?}",
+            main_backup_path,
+            start.elapsed().expect("Time went backwards")
+        );
+
+        // Read the manifest metadata from the backup, so it matches the archived data exactly
+        let mut manifest =
+            SnapshotManifest::from_db(&main_backup_path, options.network, timestamp_ms)?;
+        manifest.db_identity = db_identity;
+        manifest.compression = options.compression.name().to_string();
+
+        let excludes = match &base_manifest {
+            Some(base_manifest) => {
+                manifest.base_timestamp = Some(base_manifest.timestamp);
+                manifest.incremental_excludes(base_manifest)?
+            }
+            None => HashSet::new(),
+        };
+
+        let archive_path = Self::create_tar_archive(
+            snapshot_logger,
+            &main_backup_path,
+            Path::new(&options.output_dir),
+            timestamp,
+            &manifest.files,
+            &excludes,
+            options,
+        )?;
+
+        manifest.add_chunks(Path::new(&archive_path))?;
+        manifest.write(Path::new(&archive_path))?;
+        info!(
+            snapshot_logger,
+            "Full DB Snapshot Backup archive created: path = {}", archive_path;
+            "num_chunks" => manifest.num_chunks, "trie_root_hash" => &manifest.trie_root_hash
         );
 
         // rm -rf the backup path
-        fs::remove_dir_all(&main_backup_path).map_err(|e| HubError {
-            code: "db.internal_error".to_string(),
-            message: e.to_string(),
-        })?;
+        fs::remove_dir_all(&main_backup_path)
+            .map_err(|e| HubError::new(HubErrorCode::StorageFailure, e.to_string()))?;
 
-        Ok(tar_gz_path)
+        Ok(archive_path)
     }
 
     pub fn js_snapshot_backup(mut cx: FunctionContext) -> JsResult<JsPromise> {
@@ -1188,12 +1745,103 @@ impl RocksDB {
 
         let timestamp_ms = cx.argument::<JsNumber>(2)?.value(&mut cx) as i64;
 
+        // Optional snapshot options object
+        let mut options = SnapshotBackupOptions::default();
+        if let Some(js_options) = cx.argument_opt(3) {
+            if let Ok(js_options) = js_options.downcast::<JsObject, _>(&mut cx) {
+                if let Some(js_network) =
+                    js_options.get_opt::<JsNumber, _, _>(&mut cx, "network")?
+                {
+                    options.network = js_network.value(&mut cx) as i32;
+                }
+                if let Some(js_base_manifest) =
+                    js_options.get_opt::<JsString, _, _>(&mut cx, "baseManifest")?
+                {
+                    options.base_manifest = Some(js_base_manifest.value(&mut cx));
+                }
+                if let Some(js_token) = js_options
+                    .get_opt::<JsBox<Arc<SnapshotCancellationToken>>, _, _>(
+                        &mut cx,
+                        "cancellationToken",
+                    )?
+                {
+                    options.cancellation_token = (**js_token.borrow()).clone();
+                }
+                if let Some(js_on_progress) =
+                    js_options.get_opt::<JsFunction, _, _>(&mut cx, "onProgress")?
+                {
+                    options.progress = SnapshotProgressReporter::new(&mut cx, js_on_progress);
+                }
+                if let Some(js_compression) =
+                    js_options.get_opt::<JsString, _, _>(&mut cx, "compression")?
+                {
+                    options.compression = match js_compression.value(&mut cx).as_str() {
+                        "gzip" => SnapshotCompression::Gzip { level: 3 },
+                        "zstd" => SnapshotCompression::Zstd { level: 3 },
+                        other => {
+                            return hub_error_to_js_throw(
+                                &mut cx,
+                                HubError::invalid_parameter(&format!(
+                                    "Unknown snapshot compression: {}",
+                                    other
+                                )),
+                            )
+                        }
+                    };
+                }
+                if let Some(js_level) =
+                    js_options.get_opt::<JsNumber, _, _>(&mut cx, "compressionLevel")?
+                {
+                    let level = js_level.value(&mut cx);
+                    options.compression = match options.compression {
+                        SnapshotCompression::Gzip { .. } => SnapshotCompression::Gzip {
+                            level: level.max(0.0) as u32,
+                        },
+                        SnapshotCompression::Zstd { .. } => SnapshotCompression::Zstd {
+                            level: level as i32,
+                        },
+                    };
+                }
+                if let Some(js_chunk_size) =
+                    js_options.get_opt::<JsNumber, _, _>(&mut cx, "chunkSize")?
+                {
+                    options.chunk_size = js_chunk_size.value(&mut cx).max(0.0) as usize;
+                }
+                if let Some(js_threads) =
+                    js_options.get_opt::<JsNumber, _, _>(&mut cx, "threads")?
+                {
+                    options.num_threads = js_threads.value(&mut cx).max(0.0) as usize;
+                }
+                if let Some(js_output_dir) =
+                    js_options.get_opt::<JsString, _, _>(&mut cx, "outputDir")?
+                {
+                    options.output_dir = js_output_dir.value(&mut cx);
+                }
+                if let Some(js_mode) = js_options.get_opt::<JsString, _, _>(&mut cx, "mode")? {
+                    options.mode = match js_mode.value(&mut cx).as_str() {
+                        "checkpoint" => SnapshotMode::Checkpoint,
+                        "copy" => SnapshotMode::Copy,
+                        other => {
+                            return hub_error_to_js_throw(
+                                &mut cx,
+                                HubError::invalid_parameter(&format!(
+                                    "Unknown snapshot mode: {}",
+                                    other
+                                )),
+                            )
+                        }
+                    };
+                }
+            }
+        }
+
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
         // Spawn a new thread to create the tarball
         std::thread::spawn(move || {
-            let result = Self::snapshot_backup(main_db, trie_db, timestamp_ms);
+            let result =
+                catch_panic(|| Self::snapshot_backup(main_db, trie_db, timestamp_ms, options));
 
             deferred.settle_with(&channel, move |mut tcx| match result {
                 Ok(output_path) => Ok(tcx.string(output_path)),
@@ -1203,11 +1851,264 @@ impl RocksDB {
 
         Ok(promise)
     }
+
+    /**
+     * Open a restored main DB (and the trie DB inside it) to make sure it is usable: the main DB
+     * must be readable, and the trie's stored root hash must match the root hash recomputed from
+     * all of its nodes. Returns the trie's root hash.
+     */
+    fn verify_restored_db(logger: &Logger, restored_path: &str) -> Result<Vec<u8>, HubError> {
+        let main_db = RocksDB::new(restored_path)?;
+        main_db.open()?;
+
+        // Read a few keys to make sure the data files are readable
+        let mut count = 0;
+        let read_result =
+            main_db.for_each_iterator_by_prefix(&[], &PageOptions::default(), |_, _| {
+                count += 1;
+                Ok(count >= 1_000)
+            });
+        main_db.close()?;
+        read_result?;
+
+        let trie = MerkleTrie::new(restored_path)?;
+        trie.initialize()?;
+        let verify_result = trie.root_hash().and_then(|stored_root_hash| {
+            let recomputed_root_hash = trie.recompute_root_hash()?;
+            let items = trie.items()?;
+            info!(logger, "Verified restored trie";
+                "stored_root_hash" => hex::encode(&stored_root_hash),
+                "recomputed_root_hash" => hex::encode(&recomputed_root_hash),
+                "items" => items);
+
+            if stored_root_hash != recomputed_root_hash {
+                return Err(HubError::internal_db_error(&format!(
+                    "Restored trie root hash {} does not match recomputed root hash {}",
+                    hex::encode(&stored_root_hash),
+                    hex::encode(&recomputed_root_hash)
+                )));
+            }
+
+            Ok(stored_root_hash)
+        });
+        trie.stop()?;
+
+        verify_result
+    }
+
+    /**
+     * Check that `archive_dirs` form a chain that can be restored: a full snapshot, followed by
+     * incremental snapshots that each build on the one before. Returns the manifests of the archives,
+     * or None for an old archive without a manifest, which can only be restored on its own.
+     */
+    fn verify_snapshot_chain(
+        archive_dirs: &[String],
+    ) -> Result<Vec<Option<SnapshotManifest>>, HubError> {
+        if archive_dirs.is_empty() {
+            return Err(HubError::invalid_parameter(
+                "No snapshot archives to restore",
+            ));
+        }
+
+        // Check the chunks against the manifests before spending time unpacking them
+        let manifests = archive_dirs
+            .iter()
+            .map(|archive_dir| {
+                if Path::new(archive_dir).join(MANIFEST_FILE_NAME).exists() {
+                    SnapshotManifest::verify(Path::new(archive_dir)).map(Some)
+                } else {
+                    Ok(None)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (i, manifest) in manifests.iter().enumerate() {
+            let base_timestamp = manifest.as_ref().and_then(|m| m.base_timestamp);
+            let expected_base_timestamp = match i {
+                0 => None,
+                _ => manifests[i - 1].as_ref().map(|m| m.timestamp),
+            };
+
+            if (i > 0 && manifest.is_none()) || base_timestamp != expected_base_timestamp {
+                return Err(HubError::validation_failure(&format!(
+                    "Snapshot {} does not build on the snapshot before it",
+                    archive_dirs[i]
+                )));
+            }
+
+            // Incremental snapshots leave out SST files by name, which is only safe if every
+            // snapshot in the chain was taken from the same DBs
+            let db_identity = manifest.as_ref().map(|m| m.db_identity.as_str());
+            let base_db_identity = manifests[0].as_ref().map(|m| m.db_identity.as_str());
+            if i > 0 && (db_identity == Some("") || db_identity != base_db_identity) {
+                return Err(HubError::validation_failure(&format!(
+                    "Snapshot {} is from a different DB than the snapshot before it",
+                    archive_dirs[i]
+                )));
+            }
+        }
+
+        Ok(manifests)
+    }
+
+    /**
+     * Restore a snapshot created by `snapshot_backup` to `target_path`. `archive_dirs` are the
+     * chunk directories of a full snapshot, optionally followed by a chain of incremental snapshots
+     * on top of it. They are unpacked in order into a staging directory next to `target_path`, and
+     * the result is verified there. Only if it is usable is it moved into place, replacing whatever
+     * was at `target_path` before. The DB at `target_path` must not be open.
+     */
+    fn restore_snapshot(archive_dirs: &[String], target_path: &str) -> Result<String, HubError> {
+        let restore_logger = LOGGER.new(o! ("component" => "RocksDBSnapshotRestore"));
+        let start = std::time::SystemTime::now();
+
+        // Staging and old directories are siblings of the target, so that the renames below stay
+        // on the same filesystem and are atomic
+        let target = Path::new(target_path);
+        let staging_path = PathBuf::from(format!("{}.restore-staging", target_path));
+        let old_path = PathBuf::from(format!("{}.restore-old", target_path));
+
+        info!(restore_logger, "Restoring snapshot";
+            "archive_dirs" => format!("{:?}", archive_dirs), "target_path" => target_path);
+
+        // Archives from before manifests were added are only checked by the DB verification below
+        let manifests = Self::verify_snapshot_chain(archive_dirs)?;
+
+        if staging_path.exists() {
+            fs::remove_dir_all(&staging_path)?;
+        }
+        fs::create_dir_all(&staging_path)?;
+
+        let mut restored_path = None;
+        for (archive_dir, manifest) in archive_dirs.iter().zip(manifests.iter()) {
+            // Incremental archives are unpacked over the previous ones, overwriting changed files
+            let reader = MultiChunkReader::new(PathBuf::from(archive_dir))?;
+            tar::Archive::new(reader).unpack(&staging_path)?;
+
+            // The archive has a single top level directory, which is the main DB with the trie DB inside
+            let mut entries = fs::read_dir(&staging_path)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .collect::<Vec<_>>();
+            if entries.len() != 1 || !entries[0].is_dir() {
+                return Err(HubError::validation_failure(
+                    "Snapshot archive must contain a single DB directory",
+                ));
+            }
+            let db_path = entries.remove(0);
+
+            // Remove the files that were deleted since the previous snapshot
+            if let Some(manifest) = manifest {
+                manifest.apply_file_list(&db_path)?;
+            }
+
+            info!(restore_logger, "Unpacked snapshot archive"; "archive_dir" => archive_dir);
+            restored_path = Some(db_path);
+        }
+        let restored_path = restored_path.unwrap();
+
+        let root_hash =
+            Self::verify_restored_db(&restore_logger, &restored_path.to_string_lossy())?;
+        if let Some(Some(manifest)) = manifests.last() {
+            if hex::encode(&root_hash) != manifest.trie_root_hash {
+                return Err(HubError::validation_failure(&format!(
+                    "Restored trie root hash {} does not match manifest root hash {}",
+                    hex::encode(&root_hash),
+                    manifest.trie_root_hash
+                )));
+            }
+        }
+
+        // Move the old DB out of the way, and the restored DB into its place
+        if target.exists() {
+            if old_path.exists() {
+                fs::remove_dir_all(&old_path)?;
+            }
+            fs::rename(target, &old_path)?;
+        }
+        fs::rename(&restored_path, target)?;
+
+        // Cleanup, ignoring errors since the restore itself has succeeded
+        let _ = fs::remove_dir_all(&staging_path);
+        let _ = fs::remove_dir_all(&old_path);
+
+        info!(
+            restore_logger,
+            "Snapshot restored: path = {}, time taken = {:?}",
+            target_path,
+            start.elapsed().expect("Time went backwards")
+        );
+
+        Ok(target_path.to_string())
+    }
+
+    pub fn js_verify_snapshot(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let archive_dir = cx.argument::<JsString>(0)?.value(&mut cx);
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        // Hashing all the chunks takes a while, so do it on a new thread
+        std::thread::spawn(move || {
+            let result = catch_panic(|| {
+                let manifest = SnapshotManifest::verify(Path::new(&archive_dir))?;
+                serde_json::to_string(&manifest)
+                    .map_err(|e| HubError::internal_db_error(&e.to_string()))
+            });
+
+            deferred.settle_with(&channel, move |mut tcx| match result {
+                Ok(manifest_json) => Ok(tcx.string(manifest_json)),
+                Err(e) => hub_error_to_js_throw(&mut tcx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+
+    pub fn js_restore_snapshot(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        // Either a single archive dir, or a full snapshot followed by incremental snapshots
+        let js_archive_dirs = cx.argument::<JsValue>(0)?;
+        let archive_dirs = if let Ok(js_array) = js_archive_dirs.downcast::<JsArray, _>(&mut cx) {
+            let mut archive_dirs = vec![];
+            for i in 0..js_array.len(&mut cx) {
+                let archive_dir = js_array.get::<JsString, _, u32>(&mut cx, i)?.value(&mut cx);
+                archive_dirs.push(archive_dir);
+            }
+            archive_dirs
+        } else {
+            vec![js_archive_dirs
+                .downcast_or_throw::<JsString, _>(&mut cx)?
+                .value(&mut cx)]
+        };
+        let target_path = cx.argument::<JsString>(1)?.value(&mut cx);
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        // Spawn a new thread, since unpacking and verifying the snapshot takes a while
+        std::thread::spawn(move || {
+            let result = catch_panic(|| Self::restore_snapshot(&archive_dirs, &target_path));
+
+            deferred.settle_with(&channel, move |mut tcx| match result {
+                Ok(restored_path) => Ok(tcx.string(restored_path)),
+                Err(e) => hub_error_to_js_throw(&mut tcx, e),
+            });
+        });
+
+        Ok(promise)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::db::multi_chunk_writer::MultiChunkWriter;
+    use crate::db::snapshot_manifest::SnapshotManifest;
     use crate::db::RocksDbTransactionBatch;
+    use crate::store::{HubErrorCode, PageOptions, RootPrefix};
+    use crate::trie::merkle_trie::MerkleTrie;
+    use std::collections::HashSet;
+    use std::fs;
+    use std::path::Path;
+    use std::sync::Arc;
 
     #[test]
     fn test_merge_rocksdb_transaction() {
@@ -1361,6 +2262,85 @@ mod tests {
         db.destroy().unwrap();
     }
 
+    #[test]
+    fn test_for_each_iterator_by_prefix_in_range() {
+        let tmp_path = tempfile::tempdir()
+            .unwrap()
+            .path()
+            .as_os_str()
+            .to_string_lossy()
+            .to_string();
+        let db = crate::db::RocksDB::new(&tmp_path).unwrap();
+        db.open().unwrap();
+
+        db.put(b"key100", b"value1").unwrap();
+        db.put(b"key101", b"value2").unwrap();
+        db.put(b"key104", b"value3").unwrap();
+        db.put(b"key200", b"value4").unwrap();
+
+        let collect = |start: Option<&[u8]>, stop: Option<&[u8]>, page_options: PageOptions| {
+            let mut keys = vec![];
+            db.for_each_iterator_by_prefix_in_range(
+                b"key1",
+                start,
+                stop,
+                &page_options,
+                |key, _| {
+                    keys.push(key.to_vec());
+                    Ok(false)
+                },
+            )
+            .unwrap();
+            keys
+        };
+
+        // Both bounds are within the prefix, and stop is exclusive
+        let keys = collect(
+            Some(b"01".as_slice()),
+            Some(b"04".as_slice()),
+            PageOptions::default(),
+        );
+        assert_eq!(keys, vec![b"key101".to_vec()]);
+
+        // Open bounds are limited to the prefix
+        let keys = collect(None, None, PageOptions::default());
+        assert_eq!(keys.len(), 3);
+        let keys = collect(Some(b"01".as_slice()), None, PageOptions::default());
+        assert_eq!(keys, vec![b"key101".to_vec(), b"key104".to_vec()]);
+
+        // The page token is combined with the bounds
+        let keys = collect(
+            None,
+            Some(b"05".as_slice()),
+            PageOptions {
+                page_token: Some(b"00".to_vec()),
+                ..PageOptions::default()
+            },
+        );
+        assert_eq!(keys, vec![b"key101".to_vec(), b"key104".to_vec()]);
+
+        let keys = collect(
+            Some(b"01".as_slice()),
+            None,
+            PageOptions {
+                reverse: true,
+                ..PageOptions::default()
+            },
+        );
+        assert_eq!(keys, vec![b"key104".to_vec(), b"key101".to_vec()]);
+
+        // An empty range doesn't iterate
+        let keys = collect(
+            Some(b"04".as_slice()),
+            Some(b"01".as_slice()),
+            PageOptions::default(),
+        );
+        assert!(keys.is_empty());
+
+        // Cleanup
+        db.destroy().unwrap();
+    }
+
     #[test]
     fn test_keys_exist_in_db() {
         let tmp_path = tempfile::tempdir()
@@ -1406,4 +2386,519 @@ mod tests {
         // Cleanup
         db.destroy().unwrap();
     }
+
+    #[test]
+    fn test_create_checkpoints() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let main_db_path = tmp_dir.path().join("rocks.hub._test");
+        let trie_db_path = main_db_path.join("trieDb");
+
+        let main_db = crate::db::RocksDB::new(&main_db_path.to_string_lossy()).unwrap();
+        main_db.open().unwrap();
+        let trie_db = crate::db::RocksDB::new(&trie_db_path.to_string_lossy()).unwrap();
+        trie_db.open().unwrap();
+
+        main_db.put(b"main1", b"value1").unwrap();
+        main_db.put(b"main2", b"value2").unwrap();
+        trie_db.put(b"trie1", b"value3").unwrap();
+
+        let main_checkpoint_path = tmp_dir.path().join("backup").join("rocks.hub._test");
+        let trie_checkpoint_path = main_checkpoint_path.join("trieDb");
+        fs::create_dir_all(tmp_dir.path().join("backup")).unwrap();
+
+        crate::db::RocksDB::create_checkpoints(
+            &main_db,
+            &trie_db,
+            &main_checkpoint_path,
+            &trie_checkpoint_path,
+        )
+        .unwrap();
+
+        // The source DBs are reopened and still usable
+        main_db.put(b"main3", b"value4").unwrap();
+        assert_eq!(main_db.get(b"main1").unwrap().unwrap(), b"value1".to_vec());
+        assert_eq!(trie_db.get(b"trie1").unwrap().unwrap(), b"value3".to_vec());
+
+        // The checkpoints have the keys from before the checkpoint, but not after
+        let main_checkpoint = rocksdb::DB::open_default(&main_checkpoint_path).unwrap();
+        assert_eq!(
+            main_checkpoint.get(b"main2").unwrap().unwrap(),
+            b"value2".to_vec()
+        );
+        assert!(main_checkpoint.get(b"main3").unwrap().is_none());
+        assert!(main_checkpoint.get(b"trie1").unwrap().is_none());
+
+        let trie_checkpoint = rocksdb::DB::open_default(&trie_checkpoint_path).unwrap();
+        assert_eq!(
+            trie_checkpoint.get(b"trie1").unwrap().unwrap(),
+            b"value3".to_vec()
+        );
+
+        // A checkpoint can't be taken into an existing directory, but the DBs are still reopened
+        assert!(crate::db::RocksDB::create_checkpoints(
+            &main_db,
+            &trie_db,
+            &main_checkpoint_path,
+            &trie_checkpoint_path,
+        )
+        .is_err());
+        main_db.put(b"main4", b"value5").unwrap();
+        assert_eq!(main_db.get(b"main4").unwrap().unwrap(), b"value5".to_vec());
+
+        // If the DB can't be reopened, every attempt is made before the error is returned
+        let mut attempts = 0;
+        let reopened: Result<(), _> = main_db.retry_reopen(
+            || {
+                attempts += 1;
+                Err(crate::store::HubError::internal_db_error("LOCK is held"))
+            },
+            &Err(crate::store::HubError::internal_db_error(
+                "checkpoint failed",
+            )),
+        );
+        assert_eq!(reopened.unwrap_err().message, "LOCK is held");
+        assert_eq!(attempts, super::CHECKPOINT_REOPEN_ATTEMPTS);
+
+        // Cleanup
+        drop(main_checkpoint);
+        drop(trie_checkpoint);
+        trie_db.destroy().unwrap();
+        main_db.destroy().unwrap();
+    }
+
+    #[test]
+    fn test_copy_backup() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let main_db_path = tmp_dir.path().join("rocks.hub._test");
+
+        let main_db = crate::db::RocksDB::new(&main_db_path.to_string_lossy()).unwrap();
+        main_db.open().unwrap();
+        let trie_db =
+            crate::db::RocksDB::new(&main_db_path.join("trieDb").to_string_lossy()).unwrap();
+        trie_db.open().unwrap();
+
+        main_db.put(b"main1", b"value1").unwrap();
+        trie_db.put(b"trie1", b"value2").unwrap();
+
+        let logger = crate::logger::LOGGER.new(slog::o!());
+        let backup_path = tmp_dir.path().join("backup");
+        let trie_backup_path = backup_path.join("trieDb");
+        crate::db::RocksDB::copy_backup(
+            &logger,
+            &main_db,
+            &trie_db,
+            &backup_path.to_string_lossy(),
+            &trie_backup_path.to_string_lossy(),
+            &crate::db::SnapshotBackupOptions::default(),
+        )
+        .unwrap();
+
+        let main_backup = rocksdb::DB::open_default(&backup_path).unwrap();
+        assert_eq!(
+            main_backup.get(b"main1").unwrap().unwrap(),
+            b"value1".to_vec()
+        );
+        let trie_backup = rocksdb::DB::open_default(&trie_backup_path).unwrap();
+        assert_eq!(
+            trie_backup.get(b"trie1").unwrap().unwrap(),
+            b"value2".to_vec()
+        );
+
+        // Cleanup
+        drop(main_backup);
+        drop(trie_backup);
+        trie_db.destroy().unwrap();
+        main_db.destroy().unwrap();
+    }
+
+    #[test]
+    fn test_cancelled_snapshot_backup() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let main_db_path = tmp_dir.path().join("rocks.hub._test");
+
+        let main_db = Arc::new(crate::db::RocksDB::new(&main_db_path.to_string_lossy()).unwrap());
+        main_db.open().unwrap();
+        let trie_db = Arc::new(
+            crate::db::RocksDB::new(&main_db_path.join("trieDb").to_string_lossy()).unwrap(),
+        );
+        trie_db.open().unwrap();
+        main_db.put(b"main1", b"value1").unwrap();
+
+        for mode in [
+            crate::db::SnapshotMode::Copy,
+            crate::db::SnapshotMode::Checkpoint,
+        ] {
+            let options = crate::db::SnapshotBackupOptions {
+                mode,
+                ..Default::default()
+            };
+            options.cancellation_token.cancel();
+
+            let result = crate::db::RocksDB::snapshot_backup(
+                main_db.clone(),
+                trie_db.clone(),
+                1_700_000_000_000,
+                options,
+            );
+            assert_eq!(result.unwrap_err().code, HubErrorCode::Unavailable);
+
+            // Nothing is left behind, and the DBs are still usable
+            let backup_dir = tmp_dir.path().join("backup");
+            assert!(!backup_dir.exists() || fs::read_dir(&backup_dir).unwrap().next().is_none());
+            assert_eq!(main_db.get(b"main1").unwrap().unwrap(), b"value1".to_vec());
+        }
+
+        // Cleanup
+        trie_db.destroy().unwrap();
+        main_db.destroy().unwrap();
+    }
+
+    fn write_snapshot_archive(db_path: &std::path::Path, archive_dir: &std::path::Path) {
+        let mut writer = MultiChunkWriter::new(archive_dir.to_path_buf(), 1024 * 1024);
+        let mut tar = tar::Builder::new(&mut writer);
+        tar.append_dir_all("rocks.hub._test", db_path).unwrap();
+        tar.finish().unwrap();
+        drop(tar);
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_restore_snapshot() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let main_db_path = tmp_dir.path().join("rocks.hub._test");
+        let main_db_path_str = main_db_path.to_string_lossy().to_string();
+
+        let main_db = crate::db::RocksDB::new(&main_db_path_str).unwrap();
+        main_db.open().unwrap();
+        main_db.put(b"key1", b"value1").unwrap();
+        main_db.close().unwrap();
+
+        let key1: Vec<_> = "0000482712".bytes().collect();
+        let key2: Vec<_> = "0000482713".bytes().collect();
+        let trie = MerkleTrie::new(&main_db_path_str).unwrap();
+        trie.initialize().unwrap();
+        trie.insert(vec![key1.clone(), key2.clone()]).unwrap();
+        let root_hash = trie.root_hash().unwrap();
+        trie.stop().unwrap();
+
+        let mut manifest = SnapshotManifest::from_db(&main_db_path_str, 2, 1_000).unwrap();
+        assert_eq!(manifest.num_messages, 2);
+        assert_eq!(manifest.trie_root_hash, hex::encode(&root_hash));
+
+        let archive_dir = tmp_dir.path().join("archive");
+        write_snapshot_archive(&main_db_path, &archive_dir);
+        manifest.add_chunks(&archive_dir).unwrap();
+        manifest.write(&archive_dir).unwrap();
+
+        // Restore over an existing directory
+        let target_path = tmp_dir.path().join("restored");
+        fs::create_dir_all(&target_path).unwrap();
+        fs::write(target_path.join("old_file"), b"old").unwrap();
+        let target_path_str = target_path.to_string_lossy().to_string();
+
+        crate::db::RocksDB::restore_snapshot(
+            &[archive_dir.to_string_lossy().to_string()],
+            &target_path_str,
+        )
+        .unwrap();
+
+        assert!(!target_path.join("old_file").exists());
+        assert!(!tmp_dir.path().join("restored.restore-staging").exists());
+        assert!(!tmp_dir.path().join("restored.restore-old").exists());
+
+        let restored_db = crate::db::RocksDB::new(&target_path_str).unwrap();
+        restored_db.open().unwrap();
+        assert_eq!(
+            restored_db.get(b"key1").unwrap().unwrap(),
+            b"value1".to_vec()
+        );
+        restored_db.close().unwrap();
+
+        let restored_trie = MerkleTrie::new(&target_path_str).unwrap();
+        restored_trie.initialize().unwrap();
+        assert_eq!(restored_trie.root_hash().unwrap(), root_hash);
+        assert!(restored_trie.exists(&key2).unwrap());
+        restored_trie.stop().unwrap();
+    }
+
+    #[test]
+    fn test_restore_snapshot_with_corrupted_trie() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let main_db_path = tmp_dir.path().join("rocks.hub._test");
+        let main_db_path_str = main_db_path.to_string_lossy().to_string();
+
+        let key1: Vec<_> = "0000482712".bytes().collect();
+        let key2: Vec<_> = "0000482713".bytes().collect();
+        let trie = MerkleTrie::new(&main_db_path_str).unwrap();
+        trie.initialize().unwrap();
+        trie.insert(vec![key1.clone(), key2.clone()]).unwrap();
+        trie.stop().unwrap();
+
+        // Remove one of the leaf nodes, so the trie can no longer be verified
+        let trie_db =
+            crate::db::RocksDB::new(&main_db_path.join("trieDb").to_string_lossy()).unwrap();
+        trie_db.open().unwrap();
+        let mut leaf_node_key = vec![RootPrefix::SyncMerkleTrieNode as u8];
+        leaf_node_key.extend_from_slice(&key1);
+        trie_db.del(&leaf_node_key).unwrap();
+        trie_db.close().unwrap();
+
+        let archive_dir = tmp_dir.path().join("archive");
+        write_snapshot_archive(&main_db_path, &archive_dir);
+
+        // The existing target is left untouched
+        let target_path = tmp_dir.path().join("restored");
+        fs::create_dir_all(&target_path).unwrap();
+        fs::write(target_path.join("old_file"), b"old").unwrap();
+
+        let result = crate::db::RocksDB::restore_snapshot(
+            &[archive_dir.to_string_lossy().to_string()],
+            &target_path.to_string_lossy(),
+        );
+        assert!(result.is_err());
+        assert!(target_path.join("old_file").exists());
+    }
+
+    fn checkpoint_and_archive(
+        main_db: &crate::db::RocksDB,
+        trie_db: &crate::db::RocksDB,
+        tmp_dir: &std::path::Path,
+        timestamp_ms: i64,
+        base_manifest: Option<&SnapshotManifest>,
+    ) -> (String, SnapshotManifest) {
+        let logger = crate::logger::LOGGER.new(slog::o!());
+        let checkpoint_path = tmp_dir
+            .join(format!("checkpoint-{}", timestamp_ms))
+            .join("rocks.hub._test");
+        fs::create_dir_all(checkpoint_path.parent().unwrap()).unwrap();
+
+        crate::db::RocksDB::create_checkpoints(
+            main_db,
+            trie_db,
+            &checkpoint_path,
+            &checkpoint_path.join("trieDb"),
+        )
+        .unwrap();
+
+        let checkpoint_path = checkpoint_path.to_string_lossy().to_string();
+        let mut manifest = SnapshotManifest::from_db(&checkpoint_path, 2, timestamp_ms).unwrap();
+        manifest.db_identity = crate::db::RocksDB::db_identity(main_db, trie_db);
+
+        let excludes = match base_manifest {
+            Some(base_manifest) => {
+                manifest.base_timestamp = Some(base_manifest.timestamp);
+                manifest.incremental_excludes(base_manifest).unwrap()
+            }
+            None => HashSet::new(),
+        };
+
+        let archive_dir = crate::db::RocksDB::create_tar_archive(
+            &logger,
+            &checkpoint_path,
+            &tmp_dir.join("archives"),
+            chrono::NaiveDateTime::from_timestamp_millis(timestamp_ms).unwrap(),
+            &manifest.files,
+            &excludes,
+            &crate::db::SnapshotBackupOptions::default(),
+        )
+        .unwrap();
+        manifest.add_chunks(Path::new(&archive_dir)).unwrap();
+        manifest.write(Path::new(&archive_dir)).unwrap();
+
+        (archive_dir, manifest)
+    }
+
+    #[test]
+    fn test_restore_incremental_snapshots() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let main_db_path = tmp_dir.path().join("rocks.hub._test");
+
+        let main_db = crate::db::RocksDB::new(&main_db_path.to_string_lossy()).unwrap();
+        main_db.open().unwrap();
+        let trie_db = Arc::new(
+            crate::db::RocksDB::new(&main_db_path.join("trieDb").to_string_lossy()).unwrap(),
+        );
+        trie_db.open().unwrap();
+        let trie = MerkleTrie::new_with_db(trie_db.clone()).unwrap();
+
+        let key1: Vec<_> = "0000482712".bytes().collect();
+        let key2: Vec<_> = "0000482713".bytes().collect();
+
+        // Full snapshot
+        main_db.put(b"key1", b"value1").unwrap();
+        trie.initialize().unwrap();
+        trie.insert(vec![key1.clone()]).unwrap();
+        trie.stop().unwrap();
+        let (full_archive, full_manifest) =
+            checkpoint_and_archive(&main_db, &trie_db, tmp_dir.path(), 1_000, None);
+
+        // Incremental snapshot on top of it
+        main_db.put(b"key2", b"value2").unwrap();
+        main_db.del(b"key1").unwrap();
+        trie.initialize().unwrap();
+        trie.insert(vec![key2.clone()]).unwrap();
+        let root_hash = trie.root_hash().unwrap();
+        trie.stop().unwrap();
+        let (incremental_archive, incremental_manifest) = checkpoint_and_archive(
+            &main_db,
+            &trie_db,
+            tmp_dir.path(),
+            2_000,
+            Some(&full_manifest),
+        );
+        assert_eq!(incremental_manifest.base_timestamp, Some(1_000));
+
+        // The SSTs from the full snapshot are not archived again
+        let full_ssts = full_manifest
+            .files
+            .iter()
+            .filter(|file| file.path.ends_with(".sst"))
+            .count();
+        assert!(full_ssts > 0);
+        assert_eq!(
+            incremental_manifest
+                .incremental_excludes(&full_manifest)
+                .unwrap()
+                .len(),
+            full_ssts
+        );
+
+        // An incremental snapshot can't be restored on its own, or on top of the wrong base
+        let target_path = tmp_dir.path().join("restored");
+        let target_path_str = target_path.to_string_lossy().to_string();
+        assert!(crate::db::RocksDB::restore_snapshot(
+            &[incremental_archive.clone()],
+            &target_path_str
+        )
+        .is_err());
+        assert!(crate::db::RocksDB::restore_snapshot(
+            &[incremental_archive.clone(), full_archive.clone()],
+            &target_path_str
+        )
+        .is_err());
+        assert!(!target_path.exists());
+
+        crate::db::RocksDB::restore_snapshot(
+            &[full_archive.clone(), incremental_archive.clone()],
+            &target_path_str,
+        )
+        .unwrap();
+
+        let restored_db = crate::db::RocksDB::new(&target_path_str).unwrap();
+        restored_db.open().unwrap();
+        assert!(restored_db.get(b"key1").unwrap().is_none());
+        assert_eq!(
+            restored_db.get(b"key2").unwrap().unwrap(),
+            b"value2".to_vec()
+        );
+        restored_db.close().unwrap();
+
+        let restored_trie = MerkleTrie::new(&target_path_str).unwrap();
+        restored_trie.initialize().unwrap();
+        assert_eq!(restored_trie.root_hash().unwrap(), root_hash);
+        assert!(restored_trie.exists(&key1).unwrap());
+        assert!(restored_trie.exists(&key2).unwrap());
+        restored_trie.stop().unwrap();
+
+        // A chain with an incremental snapshot from another DB is rejected
+        let mut foreign_manifest = incremental_manifest.clone();
+        foreign_manifest.db_identity = "other:trie".to_string();
+        foreign_manifest
+            .write(Path::new(&incremental_archive))
+            .unwrap();
+        assert!(crate::db::RocksDB::verify_snapshot_chain(&[
+            full_archive.clone(),
+            incremental_archive.clone()
+        ])
+        .is_err());
+
+        trie_db.close().unwrap();
+        main_db.close().unwrap();
+    }
+
+    #[test]
+    fn test_zstd_snapshot_backup_and_restore() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let main_db_path = tmp_dir.path().join("rocks.hub._test");
+
+        let main_db = Arc::new(crate::db::RocksDB::new(&main_db_path.to_string_lossy()).unwrap());
+        main_db.open().unwrap();
+        let trie_db = Arc::new(
+            crate::db::RocksDB::new(&main_db_path.join("trieDb").to_string_lossy()).unwrap(),
+        );
+        trie_db.open().unwrap();
+        let trie = MerkleTrie::new_with_db(trie_db.clone()).unwrap();
+
+        main_db.put(b"key1", b"value1").unwrap();
+        let key1: Vec<_> = "0000482712".bytes().collect();
+        trie.initialize().unwrap();
+        trie.insert(vec![key1.clone()]).unwrap();
+        let root_hash = trie.root_hash().unwrap();
+        trie.stop().unwrap();
+
+        let output_dir = tmp_dir.path().join("archives");
+        let options = crate::db::SnapshotBackupOptions {
+            compression: crate::db::SnapshotCompression::Zstd { level: 19 },
+            chunk_size: 16 * 1024,
+            num_threads: 2,
+            output_dir: output_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let archive_dir = crate::db::RocksDB::snapshot_backup(
+            main_db.clone(),
+            trie_db.clone(),
+            1_700_000_000_000,
+            options,
+        )
+        .unwrap();
+        assert!(archive_dir.ends_with(".tar.zst"));
+        assert!(Path::new(&archive_dir).starts_with(&output_dir));
+
+        let manifest = crate::db::RocksDB::verify_snapshot_chain(&[archive_dir.clone()])
+            .unwrap()
+            .pop()
+            .unwrap()
+            .unwrap();
+        assert_eq!(manifest.compression, "zstd");
+        assert!(manifest.num_chunks > 1);
+
+        // The reader detects zstd on its own
+        let target_path = tmp_dir.path().join("restored");
+        let target_path_str = target_path.to_string_lossy().to_string();
+        crate::db::RocksDB::restore_snapshot(&[archive_dir], &target_path_str).unwrap();
+
+        let restored_db = crate::db::RocksDB::new(&target_path_str).unwrap();
+        restored_db.open().unwrap();
+        assert_eq!(
+            restored_db.get(b"key1").unwrap().unwrap(),
+            b"value1".to_vec()
+        );
+        restored_db.close().unwrap();
+
+        let restored_trie = MerkleTrie::new(&target_path_str).unwrap();
+        restored_trie.initialize().unwrap();
+        assert_eq!(restored_trie.root_hash().unwrap(), root_hash);
+        restored_trie.stop().unwrap();
+
+        trie_db.close().unwrap();
+        main_db.close().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_snapshot_options() {
+        let options = crate::db::SnapshotBackupOptions {
+            compression: crate::db::SnapshotCompression::Gzip { level: 12 },
+            ..Default::default()
+        };
+        assert_eq!(
+            options.validate().unwrap_err().code,
+            HubErrorCode::InvalidParam
+        );
+
+        let options = crate::db::SnapshotBackupOptions {
+            num_threads: 0,
+            ..Default::default()
+        };
+        assert!(options.validate().is_err());
+    }
 }
@@ -0,0 +1,124 @@
+use crate::store::{HubError, HubErrorCode};
+use neon::context::{Context, FunctionContext};
+use neon::event::Channel;
+use neon::handle::{Handle, Root};
+use neon::object::Object;
+use neon::result::JsResult;
+use neon::types::{Finalize, JsBox, JsFunction, JsObject, JsUndefined};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/**
+ * Lets JS cancel a running snapshot backup. The token is checked between keys and files, so a
+ * cancelled backup stops quickly and fails with an "unavailable" error.
+ */
+#[derive(Debug, Default)]
+pub struct SnapshotCancellationToken {
+    cancelled: AtomicBool,
+}
+
+impl Finalize for SnapshotCancellationToken {}
+
+impl SnapshotCancellationToken {
+    pub fn new() -> Self {
+        SnapshotCancellationToken {
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /** Returns an error if the token was cancelled, so callers can just use `?` */
+    pub fn check(&self) -> Result<(), HubError> {
+        if self.is_cancelled() {
+            return Err(HubError::new(
+                HubErrorCode::Unavailable,
+                "Snapshot backup was cancelled",
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn js_create(mut cx: FunctionContext) -> JsResult<JsBox<Arc<SnapshotCancellationToken>>> {
+        Ok(cx.boxed(Arc::new(SnapshotCancellationToken::new())))
+    }
+
+    pub fn js_cancel(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let token = cx.argument::<JsBox<Arc<SnapshotCancellationToken>>>(0)?;
+        token.cancel();
+
+        Ok(cx.undefined())
+    }
+}
+
+/** How far along a snapshot backup is, for one of the DBs */
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotProgress {
+    /** "copy" while keys are copied (copy mode only), "archive" while files are archived */
+    pub stage: &'static str,
+    /** "main" or "trie" */
+    pub db: &'static str,
+    pub keys: u64,
+    pub bytes: u64,
+}
+
+/** Sends snapshot progress to an optional JS callback, from any thread */
+#[derive(Clone, Default)]
+pub struct SnapshotProgressReporter {
+    callback: Option<(Channel, Arc<Root<JsFunction>>)>,
+}
+
+impl SnapshotProgressReporter {
+    pub fn new<'a, C: Context<'a>>(cx: &mut C, callback: Handle<JsFunction>) -> Self {
+        SnapshotProgressReporter {
+            callback: Some((cx.channel(), Arc::new(callback.root(cx)))),
+        }
+    }
+
+    pub fn report(&self, progress: SnapshotProgress) {
+        if let Some((channel, callback)) = &self.callback {
+            let callback = callback.clone();
+
+            channel.send(move |mut cx| {
+                let js_progress = JsObject::new(&mut cx);
+                let stage = cx.string(progress.stage);
+                js_progress.set(&mut cx, "stage", stage)?;
+                let db = cx.string(progress.db);
+                js_progress.set(&mut cx, "db", db)?;
+                let keys = cx.number(progress.keys as f64);
+                js_progress.set(&mut cx, "keys", keys)?;
+                let bytes = cx.number(progress.bytes as f64);
+                js_progress.set(&mut cx, "bytes", bytes)?;
+
+                // A throwing progress callback shouldn't take down the process, so ignore errors
+                let callback = callback.to_inner(&mut cx);
+                let _ = cx.try_catch(|cx| callback.call_with(cx).arg(js_progress).exec(cx));
+
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = SnapshotCancellationToken::new();
+        assert!(!token.is_cancelled());
+        assert!(token.check().is_ok());
+
+        token.cancel();
+        assert!(token.is_cancelled());
+        assert_eq!(token.check().unwrap_err().code, HubErrorCode::Unavailable);
+    }
+}
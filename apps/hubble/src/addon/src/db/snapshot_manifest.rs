@@ -0,0 +1,484 @@
+use crate::protos::DbTrieNode;
+use crate::store::{HubError, RootPrefix};
+use crate::trie::merkle_trie::TRIE_DBPATH_PREFIX;
+use prost::Message as _;
+use rocksdb::{Direction, IteratorMode, Options, DB};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use walkdir::WalkDir;
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+const MANIFEST_VERSION: u32 = 1;
+
+/** A file in the snapshotted DB directory, relative to the main DB directory */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotFile {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotChunk {
+    pub name: String,
+    pub size: u64,
+    pub blake3: String,
+}
+
+/**
+ * Describes a chunked snapshot archive, so that a consumer can check that it downloaded all the
+ * chunks intact, and knows what is in the snapshot before restoring it.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotManifest {
+    pub manifest_version: u32,
+    pub db_schema_version: u32,
+    pub network: i32,
+    pub trie_root_hash: String,
+    pub num_messages: usize,
+    pub latest_hub_event_id: u64,
+    pub timestamp: i64,
+    pub num_chunks: usize,
+    pub chunks: Vec<SnapshotChunk>,
+    /** Identifies the source DBs. SST file names are only unique within the same DBs */
+    #[serde(default)]
+    pub db_identity: String,
+    /** All the files of the snapshotted DBs, including ones left out of an incremental archive */
+    #[serde(default)]
+    pub files: Vec<SnapshotFile>,
+    /** For incremental snapshots, the timestamp of the snapshot this one must be applied on top of */
+    #[serde(default)]
+    pub base_timestamp: Option<i64>,
+    /** How the chunks are compressed, "gzip" or "zstd" */
+    #[serde(default = "default_compression")]
+    pub compression: String,
+}
+
+fn default_compression() -> String {
+    "gzip".to_string()
+}
+
+impl SnapshotManifest {
+    /**
+     * Read the snapshot metadata from the (closed) main DB at `db_path`, and the trie DB inside it.
+     * The DBs are opened read-only, so the files listed in the manifest are exactly the files that
+     * get archived. The returned manifest has no chunks yet, add them with `add_chunks` once the
+     * archive is written.
+     */
+    pub fn from_db(db_path: &str, network: i32, timestamp_ms: i64) -> Result<Self, HubError> {
+        let (db_schema_version, latest_hub_event_id) = Self::read_main_db_info(db_path)?;
+        let (trie_root_hash, num_messages) = Self::read_trie_db_info(db_path)?;
+
+        Ok(SnapshotManifest {
+            manifest_version: MANIFEST_VERSION,
+            db_schema_version,
+            network,
+            trie_root_hash: hex::encode(trie_root_hash),
+            num_messages,
+            latest_hub_event_id,
+            timestamp: timestamp_ms,
+            num_chunks: 0,
+            chunks: vec![],
+            db_identity: String::new(),
+            files: Self::list_files(Path::new(db_path))?,
+            base_timestamp: None,
+            compression: default_compression(),
+        })
+    }
+
+    fn read_main_db_info(db_path: &str) -> Result<(u32, u64), HubError> {
+        let main_db = DB::open_for_read_only(&Options::default(), db_path, false)?;
+
+        let db_schema_version = main_db
+            .get([RootPrefix::DBSchemaVersion as u8])?
+            .and_then(|bytes| bytes.try_into().ok().map(u32::from_be_bytes))
+            .unwrap_or(0);
+
+        // HubEvent keys are the prefix followed by the 8 byte event id, so the last key has the latest id
+        let mut latest_hub_event_id = 0;
+        let next_prefix = [RootPrefix::HubEvents as u8 + 1];
+        for item in main_db.iterator(IteratorMode::From(&next_prefix[..], Direction::Reverse)) {
+            let (key, _) = item?;
+            match key.first() {
+                // Seeking backwards can land on the next prefix itself
+                Some(prefix) if *prefix > RootPrefix::HubEvents as u8 => continue,
+                Some(prefix) if *prefix == RootPrefix::HubEvents as u8 && key.len() == 9 => {
+                    latest_hub_event_id = u64::from_be_bytes(key[1..].try_into().unwrap());
+                }
+                _ => {}
+            }
+            break;
+        }
+
+        Ok((db_schema_version, latest_hub_event_id))
+    }
+
+    fn read_trie_db_info(db_path: &str) -> Result<(Vec<u8>, usize), HubError> {
+        let trie_db_path = Path::new(db_path).join(TRIE_DBPATH_PREFIX);
+        let trie_db = DB::open_for_read_only(&Options::default(), trie_db_path, false)?;
+
+        // The root node is stored at the empty prefix
+        match trie_db.get([RootPrefix::SyncMerkleTrieNode as u8])? {
+            Some(root_bytes) => {
+                let root = DbTrieNode::decode(root_bytes.as_slice()).map_err(|e| {
+                    HubError::internal_db_error(&format!("Failed to decode trie root: {}", e))
+                })?;
+                Ok((root.hash, root.items as usize))
+            }
+            None => Ok((vec![], 0)),
+        }
+    }
+
+    /** List all the files under the DB directory, sorted by path */
+    pub fn list_files(db_dir: &Path) -> Result<Vec<SnapshotFile>, HubError> {
+        let mut files = vec![];
+        for entry in WalkDir::new(db_dir) {
+            let entry = entry.map_err(|e| HubError::internal_db_error(&e.to_string()))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative_path = entry
+                .path()
+                .strip_prefix(db_dir)
+                .map_err(|e| HubError::internal_db_error(&e.to_string()))?;
+            files.push(SnapshotFile {
+                path: relative_path.to_string_lossy().replace('\\', "/"),
+                size: entry
+                    .metadata()
+                    .map_err(|e| HubError::internal_db_error(&e.to_string()))?
+                    .len(),
+            });
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(files)
+    }
+
+    /**
+     * The files that an incremental snapshot on top of `base` can leave out of its archive. SST files
+     * are immutable and never reuse a name within the same DB, so an SST with the same name and size
+     * in the base is the same file.
+     */
+    pub fn incremental_excludes(
+        &self,
+        base: &SnapshotManifest,
+    ) -> Result<HashSet<String>, HubError> {
+        if self.db_identity.is_empty() || self.db_identity != base.db_identity {
+            return Err(HubError::validation_failure(
+                "Incremental snapshot base is from a different DB",
+            ));
+        }
+
+        let base_files = base
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), file.size))
+            .collect::<HashSet<_>>();
+
+        Ok(self
+            .files
+            .iter()
+            .filter(|file| file.path.ends_with(".sst"))
+            .filter(|file| base_files.contains(&(file.path.as_str(), file.size)))
+            .map(|file| file.path.clone())
+            .collect())
+    }
+
+    /**
+     * Make the DB directory at `db_dir` match this manifest's file list, after an archive has been
+     * unpacked over it. Files that are not in the manifest are deleted, and every file in the
+     * manifest must be present with the right size. Manifests without a file list are skipped.
+     */
+    pub fn apply_file_list(&self, db_dir: &Path) -> Result<(), HubError> {
+        if self.files.is_empty() {
+            return Ok(());
+        }
+
+        let expected = self
+            .files
+            .iter()
+            .map(|file| (file.path.clone(), file.size))
+            .collect::<HashSet<_>>();
+        let expected_paths = self
+            .files
+            .iter()
+            .map(|file| file.path.clone())
+            .collect::<HashSet<_>>();
+
+        let actual = Self::list_files(db_dir)?;
+        for file in actual.iter() {
+            if !expected_paths.contains(&file.path) {
+                fs::remove_file(db_dir.join(&file.path))?;
+            }
+        }
+
+        let actual = Self::list_files(db_dir)?
+            .into_iter()
+            .map(|file| (file.path, file.size))
+            .collect::<HashSet<_>>();
+        if let Some((path, size)) = expected.difference(&actual).next() {
+            return Err(HubError::validation_failure(&format!(
+                "Snapshot file {} ({} bytes) is missing after applying snapshot",
+                path, size
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn chunk_names(chunks_dir: &Path) -> Result<Vec<String>, HubError> {
+        let mut names = fs::read_dir(chunks_dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("chunk_") && name.ends_with(".bin"))
+            .collect::<Vec<_>>();
+        names.sort();
+
+        Ok(names)
+    }
+
+    fn hash_chunk(chunk_path: &Path) -> Result<SnapshotChunk, HubError> {
+        let mut hasher = blake3::Hasher::new();
+        let size = io::copy(&mut File::open(chunk_path)?, &mut hasher)?;
+
+        Ok(SnapshotChunk {
+            name: chunk_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            size,
+            blake3: hasher.finalize().to_hex().to_string(),
+        })
+    }
+
+    /** Add all the chunk files in `chunks_dir` to the manifest, with their sizes and checksums */
+    pub fn add_chunks(&mut self, chunks_dir: &Path) -> Result<(), HubError> {
+        self.chunks = Self::chunk_names(chunks_dir)?
+            .iter()
+            .map(|name| Self::hash_chunk(&chunks_dir.join(name)))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.num_chunks = self.chunks.len();
+
+        Ok(())
+    }
+
+    pub fn write(&self, chunks_dir: &Path) -> Result<(), HubError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| HubError::internal_db_error(&e.to_string()))?;
+        fs::write(chunks_dir.join(MANIFEST_FILE_NAME), json)?;
+
+        Ok(())
+    }
+
+    pub fn read(chunks_dir: &Path) -> Result<Self, HubError> {
+        Self::read_file(&chunks_dir.join(MANIFEST_FILE_NAME))
+    }
+
+    pub fn read_file(manifest_path: &Path) -> Result<Self, HubError> {
+        let json = fs::read_to_string(manifest_path)?;
+        serde_json::from_str(&json)
+            .map_err(|e| HubError::validation_failure(&format!("Invalid snapshot manifest: {}", e)))
+    }
+
+    /**
+     * Check the chunks in `chunks_dir` against its manifest. Every chunk in the manifest must be
+     * present with the right size and checksum, and there must be no other chunks.
+     */
+    pub fn verify(chunks_dir: &Path) -> Result<Self, HubError> {
+        let manifest = Self::read(chunks_dir)?;
+
+        if manifest.num_chunks != manifest.chunks.len() {
+            return Err(HubError::validation_failure(&format!(
+                "Snapshot manifest lists {} chunks, expected {}",
+                manifest.chunks.len(),
+                manifest.num_chunks
+            )));
+        }
+
+        let chunk_names = Self::chunk_names(chunks_dir)?;
+        let expected_names = manifest
+            .chunks
+            .iter()
+            .map(|chunk| chunk.name.clone())
+            .collect::<Vec<_>>();
+        if chunk_names != expected_names {
+            return Err(HubError::validation_failure(&format!(
+                "Snapshot chunks {:?} don't match manifest chunks {:?}",
+                chunk_names, expected_names
+            )));
+        }
+
+        for expected in manifest.chunks.iter() {
+            let chunk_path = chunks_dir.join(&expected.name);
+
+            // Check the size first, since it is much cheaper than hashing the chunk
+            if fs::metadata(&chunk_path)?.len() != expected.size {
+                return Err(HubError::validation_failure(&format!(
+                    "Snapshot chunk {} has the wrong size, expected {} bytes",
+                    expected.name, expected.size
+                )));
+            }
+
+            if Self::hash_chunk(&chunk_path)?.blake3 != expected.blake3 {
+                return Err(HubError::validation_failure(&format!(
+                    "Snapshot chunk {} has the wrong checksum",
+                    expected.name
+                )));
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_chunks(dir: &Path, chunks: &[&[u8]]) {
+        for (i, data) in chunks.iter().enumerate() {
+            fs::write(dir.join(format!("chunk_{:04}.bin", i + 1)), data).unwrap();
+        }
+    }
+
+    fn test_manifest() -> SnapshotManifest {
+        SnapshotManifest {
+            manifest_version: MANIFEST_VERSION,
+            db_schema_version: 24,
+            network: 1,
+            trie_root_hash: "abcd".to_string(),
+            num_messages: 10,
+            latest_hub_event_id: 100,
+            timestamp: 1_700_000_000_000,
+            num_chunks: 0,
+            chunks: vec![],
+            db_identity: "main:trie".to_string(),
+            files: vec![],
+            base_timestamp: None,
+            compression: default_compression(),
+        }
+    }
+
+    fn test_file(path: &str, size: u64) -> SnapshotFile {
+        SnapshotFile {
+            path: path.to_string(),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_write_and_verify_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        write_chunks(temp_dir.path(), &[b"chunk one", b"chunk two"]);
+
+        let mut manifest = test_manifest();
+        manifest.add_chunks(temp_dir.path()).unwrap();
+        manifest.write(temp_dir.path()).unwrap();
+
+        assert_eq!(manifest.num_chunks, 2);
+        assert_eq!(manifest.chunks[0].name, "chunk_0001.bin");
+        assert_eq!(manifest.chunks[0].size, 9);
+        assert_eq!(
+            manifest.chunks[1].blake3,
+            blake3::hash(b"chunk two").to_hex().to_string()
+        );
+
+        let verified = SnapshotManifest::verify(temp_dir.path()).unwrap();
+        assert_eq!(verified, manifest);
+    }
+
+    #[test]
+    fn test_verify_detects_bad_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        write_chunks(temp_dir.path(), &[b"chunk one", b"chunk two"]);
+
+        let mut manifest = test_manifest();
+        manifest.add_chunks(temp_dir.path()).unwrap();
+        manifest.write(temp_dir.path()).unwrap();
+
+        // Same size, different contents
+        write_chunks(temp_dir.path(), &[b"chunk one", b"chunk 2!!"]);
+        assert!(SnapshotManifest::verify(temp_dir.path()).is_err());
+
+        // Truncated chunk
+        write_chunks(temp_dir.path(), &[b"chunk one", b"chunk"]);
+        assert!(SnapshotManifest::verify(temp_dir.path()).is_err());
+
+        // Missing chunk
+        write_chunks(temp_dir.path(), &[b"chunk one", b"chunk two"]);
+        fs::remove_file(temp_dir.path().join("chunk_0002.bin")).unwrap();
+        assert!(SnapshotManifest::verify(temp_dir.path()).is_err());
+
+        // Extra chunk
+        write_chunks(
+            temp_dir.path(),
+            &[b"chunk one", b"chunk two", b"chunk three"],
+        );
+        assert!(SnapshotManifest::verify(temp_dir.path()).is_err());
+
+        // No manifest
+        fs::remove_file(temp_dir.path().join(MANIFEST_FILE_NAME)).unwrap();
+        assert!(SnapshotManifest::verify(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_incremental_excludes() {
+        let mut base = test_manifest();
+        base.files = vec![
+            test_file("000010.sst", 100),
+            test_file("000011.sst", 200),
+            test_file("CURRENT", 16),
+            test_file("trieDb/000005.sst", 50),
+        ];
+
+        let mut incremental = test_manifest();
+        incremental.files = vec![
+            test_file("000011.sst", 200),
+            test_file("000012.sst", 300),
+            test_file("CURRENT", 16),
+            test_file("trieDb/000005.sst", 51),
+        ];
+
+        // Only unchanged SSTs are left out. Other files are always included
+        let excludes = incremental.incremental_excludes(&base).unwrap();
+        assert_eq!(excludes, HashSet::from(["000011.sst".to_string()]));
+
+        // A base from a different DB can't be used
+        base.db_identity = "other:trie".to_string();
+        assert!(incremental.incremental_excludes(&base).is_err());
+    }
+
+    #[test]
+    fn test_apply_file_list() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("trieDb")).unwrap();
+        fs::write(temp_dir.path().join("000010.sst"), b"old").unwrap();
+        fs::write(temp_dir.path().join("000011.sst"), b"kept").unwrap();
+        fs::write(temp_dir.path().join("trieDb/000005.sst"), b"trie").unwrap();
+
+        let mut manifest = test_manifest();
+        manifest.files = vec![
+            test_file("000011.sst", 4),
+            test_file("trieDb/000005.sst", 4),
+        ];
+
+        // Files that are not in the manifest are removed
+        manifest.apply_file_list(temp_dir.path()).unwrap();
+        assert!(!temp_dir.path().join("000010.sst").exists());
+        assert_eq!(
+            SnapshotManifest::list_files(temp_dir.path()).unwrap(),
+            manifest.files
+        );
+
+        // A missing file is an error
+        manifest.files.push(test_file("000012.sst", 10));
+        assert!(manifest.apply_file_list(temp_dir.path()).is_err());
+    }
+}
@@ -0,0 +1,449 @@
+use super::json;
+use super::reader::ReadOnlyDB;
+use crate::protos::{cast_add_body, reaction_body, CastId, DbTrieNode, HubEvent};
+use crate::store::{
+    make_fid_key, make_message_primary_key, message_decode, read_fid_key, unpack_ts_hash,
+    CastStoreDef, HubError, ReactionStoreDef, RootPrefix, UserPostfix, VerificationStoreDef,
+    TS_HASH_LENGTH,
+};
+use crate::trie::merkle_trie::TRIE_DBPATH_PREFIX;
+use prost::Message as _;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/** Names of the RootPrefix values, including deprecated ones that may still be in old DBs */
+pub fn root_prefix_name(prefix: u8) -> String {
+    let name = match prefix {
+        1 => "User",
+        2 => "CastsByParent",
+        3 => "CastsByMention",
+        4 => "LinksByTarget",
+        5 => "ReactionsByTarget",
+        6 => "IdRegistryEvent (deprecated)",
+        7 => "NameRegistryEvent (deprecated)",
+        8 => "IdRegistryEventByCustodyAddress (deprecated)",
+        9 => "HubState",
+        10 => "JobRevokeMessageBySigner",
+        11 => "SyncMerkleTrieNode",
+        12 => "JobUpdateNameExpiry (deprecated)",
+        13 => "NameRegistryEventsByExpiry (deprecated)",
+        14 => "HubCleanShutdown",
+        15 => "HubEvents",
+        16 => "Network",
+        17 => "FNameUserNameProof",
+        18 => "GossipMetrics (deprecated)",
+        19 => "UserNameProofByName",
+        20 => "RentRegistryEvent (deprecated)",
+        21 => "RentRegistryEventsByExpiry (deprecated)",
+        22 => "StorageAdminRegistryEvent (deprecated)",
+        23 => "OnChainEvent",
+        24 => "DBSchemaVersion",
+        25 => "VerificationByAddress",
+        26 => "ConnectedPeers",
+        27 => "FNameUserNameProofByFid",
+        28 => "AggregateCounts",
+        29 => "CastSearchTerms",
+        30 => "OptionalIndexComplete",
+        31 => "CastsByEmbed",
+        32 => "CastsByTime",
+        33 => "FNameDeletion",
+        34 => "FNameHistory",
+        35 => "FNameHistoryByFid",
+        _ => return format!("Unknown({})", prefix),
+    };
+    name.to_string()
+}
+
+/** Names of the UserPostfix values, i.e. the byte after the fid in RootPrefix::User keys */
+pub fn user_postfix_name(postfix: u8) -> String {
+    let name = match postfix {
+        1 => "CastMessage",
+        2 => "LinkMessage",
+        3 => "ReactionMessage",
+        4 => "VerificationMessage",
+        5 => "SignerMessage (deprecated)",
+        6 => "UserDataMessage",
+        7 => "UsernameProofMessage",
+        86 => "BySigner (deprecated)",
+        87 => "CastAdds",
+        88 => "CastRemoves",
+        89 => "LinkAdds",
+        90 => "LinkRemoves",
+        91 => "ReactionAdds",
+        92 => "ReactionRemoves",
+        93 => "VerificationAdds",
+        94 => "VerificationRemoves",
+        95 => "SignerAdds (deprecated)",
+        96 => "SignerRemoves (deprecated)",
+        97 => "UserDataAdds",
+        99 => "UserNameProofAdds",
+        100 => "LinkCompactStateMessage",
+        101 => "ReactionCompactStateMessage",
+        _ => return format!("Unknown({})", postfix),
+    };
+    name.to_string()
+}
+
+/** The message stores, by the name used on the command line */
+pub fn store_postfix(store: &str) -> Result<UserPostfix, HubError> {
+    match store {
+        "casts" => Ok(UserPostfix::CastMessage),
+        "links" => Ok(UserPostfix::LinkMessage),
+        "reactions" => Ok(UserPostfix::ReactionMessage),
+        "verifications" => Ok(UserPostfix::VerificationMessage),
+        "user-data" => Ok(UserPostfix::UserDataMessage),
+        "username-proofs" => Ok(UserPostfix::UsernameProofMessage),
+        _ => Err(HubError::invalid_parameter(&format!(
+            "unknown store {}, expected one of casts, links, reactions, verifications, user-data, username-proofs",
+            store
+        ))),
+    }
+}
+
+#[derive(Default)]
+struct KeyStats {
+    count: u64,
+    key_bytes: u64,
+    value_bytes: u64,
+}
+
+impl KeyStats {
+    fn add(&mut self, key: &[u8], value: &[u8]) {
+        self.count += 1;
+        self.key_bytes += key.len() as u64;
+        self.value_bytes += value.len() as u64;
+    }
+
+    fn to_json(&self, name: String) -> Value {
+        json!({
+            "name": name,
+            "count": self.count,
+            "keyBytes": self.key_bytes,
+            "valueBytes": self.value_bytes,
+        })
+    }
+}
+
+/**
+ * Count the keys and their sizes under every RootPrefix, and under every UserPostfix for the
+ * RootPrefix::User keys. This scans the whole DB.
+ */
+pub fn stats(db: &ReadOnlyDB) -> Result<Value, HubError> {
+    let mut prefixes: BTreeMap<u8, KeyStats> = BTreeMap::new();
+    let mut postfixes: BTreeMap<u8, KeyStats> = BTreeMap::new();
+    let mut total = KeyStats::default();
+
+    db.for_each_in_range(&[], &[], |key, value| {
+        total.add(key, value);
+        prefixes.entry(key[0]).or_default().add(key, value);
+
+        // User keys are <RootPrefix::User>:<fid>:<UserPostfix>:...
+        if key[0] == RootPrefix::User as u8 && key.len() > 5 {
+            postfixes.entry(key[5]).or_default().add(key, value);
+        }
+
+        Ok(false)
+    })?;
+
+    Ok(json!({
+        "path": db.path,
+        "total": total.to_json("Total".to_string()),
+        "rootPrefixes": prefixes
+            .iter()
+            .map(|(prefix, stats)| stats.to_json(root_prefix_name(*prefix)))
+            .collect::<Vec<_>>(),
+        "userPostfixes": postfixes
+            .iter()
+            .map(|(postfix, stats)| stats.to_json(user_postfix_name(*postfix)))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+/** Decode all the messages for a fid, optionally only from one store */
+pub fn messages(
+    db: &ReadOnlyDB,
+    fid: u32,
+    store: Option<&str>,
+    limit: usize,
+) -> Result<Vec<Value>, HubError> {
+    let postfixes = match store {
+        Some(store) => vec![store_postfix(store)?],
+        None => vec![
+            UserPostfix::CastMessage,
+            UserPostfix::LinkMessage,
+            UserPostfix::ReactionMessage,
+            UserPostfix::VerificationMessage,
+            UserPostfix::UserDataMessage,
+            UserPostfix::UsernameProofMessage,
+        ],
+    };
+
+    let mut results = vec![];
+    for postfix in postfixes {
+        let prefix = make_message_primary_key(fid, postfix.as_u8(), None);
+        db.for_each_by_prefix(&prefix, |key, value| {
+            results.push(decode_message_value(key, value));
+            Ok(results.len() >= limit)
+        })?;
+
+        if results.len() >= limit {
+            break;
+        }
+    }
+
+    Ok(results)
+}
+
+/**
+ * Messages that fail to decode are still printed, with the raw bytes, since finding them is
+ * usually why the inspector is being run
+ */
+fn decode_message_value(key: &[u8], value: &[u8]) -> Value {
+    match message_decode(value) {
+        Ok(message) => json!({ "key": json::hex(key), "message": json::message(&message) }),
+        Err(e) => json!({ "key": json::hex(key), "error": e.message, "value": json::hex(value) }),
+    }
+}
+
+/** Decode HubEvents with ids in [from, to) */
+pub fn events(
+    db: &ReadOnlyDB,
+    from: u64,
+    to: Option<u64>,
+    limit: usize,
+) -> Result<Vec<Value>, HubError> {
+    let make_event_key = |id: u64| {
+        let mut key = vec![RootPrefix::HubEvents as u8];
+        key.extend_from_slice(&id.to_be_bytes());
+        key
+    };
+
+    let start = make_event_key(from);
+    let stop = match to {
+        Some(to) => make_event_key(to),
+        None => vec![RootPrefix::HubEvents as u8 + 1],
+    };
+
+    let mut results = vec![];
+    db.for_each_in_range(&start, &stop, |key, value| {
+        let result = match HubEvent::decode(value) {
+            Ok(event) => json::hub_event(&event),
+            Err(e) => {
+                json!({ "key": json::hex(key), "error": e.to_string(), "value": json::hex(value) })
+            }
+        };
+        results.push(result);
+
+        Ok(results.len() >= limit)
+    })?;
+
+    Ok(results)
+}
+
+/** The trie is stored in its own DB, in a subdirectory of the Hub's DB */
+pub fn trie_db_path(db_path: &str) -> String {
+    Path::new(db_path)
+        .join(TRIE_DBPATH_PREFIX)
+        .to_string_lossy()
+        .to_string()
+}
+
+/** Print the trie node at the prefix, and its children down to `depth` levels below it */
+pub fn trie_node(trie_db: &ReadOnlyDB, prefix: &[u8], depth: u32) -> Result<Value, HubError> {
+    let mut key = vec![RootPrefix::SyncMerkleTrieNode as u8];
+    key.extend_from_slice(prefix);
+
+    let bytes = trie_db.get(&key)?.ok_or_else(|| {
+        HubError::not_found(&format!("no trie node at prefix {}", hex::encode(prefix)))
+    })?;
+    let node = DbTrieNode::decode(bytes.as_slice())
+        .map_err(|e| HubError::internal_db_error(&format!("could not decode trie node: {}", e)))?;
+
+    let mut child_chars = node.child_chars.clone();
+    child_chars.sort();
+
+    let children = if depth > 0 {
+        let mut children = vec![];
+        for char in child_chars.iter() {
+            let mut child_prefix = prefix.to_vec();
+            child_prefix.push(*char as u8);
+            children.push(trie_node(trie_db, &child_prefix, depth - 1)?);
+        }
+        Value::Array(children)
+    } else {
+        Value::Null
+    };
+
+    Ok(json!({
+        "prefix": json::hex(prefix),
+        "items": node.items,
+        "hash": json::hex(&node.hash),
+        // Only leaf nodes have a key, which is the sync id of the message
+        "key": if node.key.is_empty() { Value::Null } else { json::hex(&node.key) },
+        "childChars": child_chars,
+        "children": children,
+    }))
+}
+
+/** The secondary indices that can be looked up */
+pub enum IndexQuery {
+    CastsByParentCastId(CastId),
+    CastsByParentUrl(String),
+    CastsByMention(u32),
+    ReactionsByTargetCastId(CastId),
+    ReactionsByTargetUrl(String),
+    LinksByTarget(u32),
+    VerificationByAddress(Vec<u8>),
+}
+
+/**
+ * Look up a secondary index. The by-parent, by-mention and by-target indices are keys of the form
+ * <RootPrefix>:<target>:<tsHash>:<fid> that point to the message's primary key. With `resolve`,
+ * the messages they point to are fetched and decoded too.
+ */
+pub fn index(
+    db: &ReadOnlyDB,
+    query: &IndexQuery,
+    resolve: bool,
+    limit: usize,
+) -> Result<Vec<Value>, HubError> {
+    let (prefix, postfix) = match query {
+        IndexQuery::CastsByParentCastId(cast_id) => (
+            CastStoreDef::make_cast_by_parent_key(
+                &cast_add_body::Parent::ParentCastId(cast_id.clone()),
+                0,
+                None,
+            ),
+            UserPostfix::CastMessage,
+        ),
+        IndexQuery::CastsByParentUrl(url) => (
+            CastStoreDef::make_cast_by_parent_key(
+                &cast_add_body::Parent::ParentUrl(url.clone()),
+                0,
+                None,
+            ),
+            UserPostfix::CastMessage,
+        ),
+        IndexQuery::CastsByMention(fid) => (
+            CastStoreDef::make_cast_by_mention_key(*fid, 0, None),
+            UserPostfix::CastMessage,
+        ),
+        IndexQuery::ReactionsByTargetCastId(cast_id) => (
+            ReactionStoreDef::make_reactions_by_target_key(
+                &reaction_body::Target::TargetCastId(cast_id.clone()),
+                0,
+                None,
+            ),
+            UserPostfix::ReactionMessage,
+        ),
+        IndexQuery::ReactionsByTargetUrl(url) => (
+            ReactionStoreDef::make_reactions_by_target_key(
+                &reaction_body::Target::TargetUrl(url.clone()),
+                0,
+                None,
+            ),
+            UserPostfix::ReactionMessage,
+        ),
+        IndexQuery::LinksByTarget(fid) => {
+            let mut prefix = vec![RootPrefix::LinksByTarget as u8];
+            prefix.extend_from_slice(&make_fid_key(*fid));
+            (prefix, UserPostfix::LinkMessage)
+        }
+        IndexQuery::VerificationByAddress(address) => {
+            // This index is a single key, whose value is the fid that verified the address
+            let key = VerificationStoreDef::make_verification_by_address_key(address);
+            return Ok(match db.get(&key)? {
+                Some(value) if value.len() == 4 => {
+                    let fid = read_fid_key(&value);
+                    let mut result = json!({ "key": json::hex(&key), "fid": fid });
+                    if resolve {
+                        let adds_key =
+                            VerificationStoreDef::make_verification_adds_key(fid, address);
+                        result["verificationAddsKey"] = json::hex(&adds_key);
+                        result["verificationAddsValue"] =
+                            db.get(&adds_key)?.map_or(Value::Null, |v| json::hex(&v));
+                    }
+                    vec![result]
+                }
+                Some(value) => vec![json!({ "key": json::hex(&key), "value": json::hex(&value) })],
+                None => vec![],
+            });
+        }
+    };
+    let postfix = postfix.as_u8();
+
+    let mut results = vec![];
+    db.for_each_by_prefix(&prefix, |key, value| {
+        let suffix = &key[prefix.len()..];
+        if suffix.len() != TS_HASH_LENGTH + 4 {
+            results.push(json!({
+                "key": json::hex(key),
+                "error": "unexpected index key length",
+            }));
+            return Ok(results.len() >= limit);
+        }
+
+        let ts_hash: [u8; TS_HASH_LENGTH] = suffix[..TS_HASH_LENGTH].try_into().unwrap();
+        let fid = read_fid_key(&suffix[TS_HASH_LENGTH..]);
+        let (timestamp, hash) = unpack_ts_hash(&ts_hash);
+        let primary_key = make_message_primary_key(fid, postfix, Some(&ts_hash));
+
+        let mut result = json!({
+            "key": json::hex(key),
+            "value": json::hex(value),
+            "fid": fid,
+            "timestamp": timestamp,
+            "hash": json::hex(&hash),
+            "primaryKey": json::hex(&primary_key),
+        });
+        if resolve {
+            result["message"] = match db.get(&primary_key)? {
+                Some(bytes) => match message_decode(&bytes) {
+                    Ok(message) => json::message(&message),
+                    Err(e) => json!({ "error": e.message, "value": json::hex(&bytes) }),
+                },
+                None => json!({ "error": "the index points to a message that doesn't exist" }),
+            };
+        }
+        results.push(result);
+
+        Ok(results.len() >= limit)
+    })?;
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_match_enums() {
+        assert_eq!(root_prefix_name(RootPrefix::HubEvents as u8), "HubEvents");
+        assert_eq!(
+            root_prefix_name(RootPrefix::FNameUserNameProofByFid as u8),
+            "FNameUserNameProofByFid"
+        );
+        assert_eq!(root_prefix_name(200), "Unknown(200)");
+
+        assert_eq!(user_postfix_name(UserPostfix::CastAdds.as_u8()), "CastAdds");
+        assert_eq!(
+            user_postfix_name(UserPostfix::LinkCompactStateMessage.as_u8()),
+            "LinkCompactStateMessage"
+        );
+    }
+
+    #[test]
+    fn test_store_postfix() {
+        assert_eq!(
+            store_postfix("casts").unwrap().as_u8(),
+            UserPostfix::CastMessage.as_u8()
+        );
+        assert_eq!(
+            store_postfix("user-data").unwrap().as_u8(),
+            UserPostfix::UserDataMessage.as_u8()
+        );
+        assert!(store_postfix("signers").is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use crate::protos::{
+    self, cast_add_body, embed, hub_event, link_body, message_data, on_chain_event, reaction_body,
+    CastId, FarcasterNetwork, HashScheme, HubEvent, HubEventType, IdRegisterEventType, Message,
+    MessageType, OnChainEvent, OnChainEventType, Protocol, ReactionType, SignatureScheme,
+    SignerEventType, UserDataType, UserNameProof, UserNameType,
+};
+use serde_json::{json, Value};
+
+/** Byte fields are printed as 0x-prefixed hex, the same way the Hub's HTTP API prints them */
+pub fn hex(bytes: &[u8]) -> Value {
+    json!(format!("0x{}", hex::encode(bytes)))
+}
+
+/** Enums are printed by name, falling back to the raw value for values this build doesn't know */
+fn enum_name<E: TryFrom<i32>>(value: i32, name: fn(&E) -> &'static str) -> Value {
+    match E::try_from(value) {
+        Ok(e) => json!(name(&e)),
+        Err(_) => json!(value),
+    }
+}
+
+fn cast_id(cast_id: &CastId) -> Value {
+    json!({ "fid": cast_id.fid, "hash": hex(&cast_id.hash) })
+}
+
+fn optional<T>(value: &Option<T>, f: fn(&T) -> Value) -> Value {
+    value.as_ref().map_or(Value::Null, f)
+}
+
+pub fn message(message: &Message) -> Value {
+    let data = message.data.as_ref().map_or(Value::Null, |data| {
+        json!({
+            "type": enum_name(data.r#type, MessageType::as_str_name),
+            "fid": data.fid,
+            "timestamp": data.timestamp,
+            "network": enum_name(data.network, FarcasterNetwork::as_str_name),
+            "body": optional(&data.body, message_body),
+        })
+    });
+
+    json!({
+        "data": data,
+        "hash": hex(&message.hash),
+        "hashScheme": enum_name(message.hash_scheme, HashScheme::as_str_name),
+        "signature": hex(&message.signature),
+        "signatureScheme": enum_name(message.signature_scheme, SignatureScheme::as_str_name),
+        "signer": hex(&message.signer),
+    })
+}
+
+fn message_body(body: &message_data::Body) -> Value {
+    match body {
+        message_data::Body::CastAddBody(body) => {
+            let embeds: Vec<Value> = body
+                .embeds
+                .iter()
+                .map(|e| match &e.embed {
+                    Some(embed::Embed::Url(url)) => json!({ "url": url }),
+                    Some(embed::Embed::CastId(id)) => json!({ "castId": cast_id(id) }),
+                    None => Value::Null,
+                })
+                .collect();
+            let (parent_cast_id, parent_url) = match &body.parent {
+                Some(cast_add_body::Parent::ParentCastId(id)) => (cast_id(id), Value::Null),
+                Some(cast_add_body::Parent::ParentUrl(url)) => (Value::Null, json!(url)),
+                None => (Value::Null, Value::Null),
+            };
+
+            json!({
+                "text": body.text,
+                "type": enum_name(body.r#type, protos::CastType::as_str_name),
+                "mentions": body.mentions,
+                "mentionsPositions": body.mentions_positions,
+                "embeds": embeds,
+                "parentCastId": parent_cast_id,
+                "parentUrl": parent_url,
+            })
+        }
+        message_data::Body::CastRemoveBody(body) => {
+            json!({ "targetHash": hex(&body.target_hash) })
+        }
+        message_data::Body::ReactionBody(body) => {
+            let (target_cast_id, target_url) = match &body.target {
+                Some(reaction_body::Target::TargetCastId(id)) => (cast_id(id), Value::Null),
+                Some(reaction_body::Target::TargetUrl(url)) => (Value::Null, json!(url)),
+                None => (Value::Null, Value::Null),
+            };
+
+            json!({
+                "type": enum_name(body.r#type, ReactionType::as_str_name),
+                "targetCastId": target_cast_id,
+                "targetUrl": target_url,
+            })
+        }
+        message_data::Body::VerificationAddAddressBody(body) => json!({
+            "address": hex(&body.address),
+            "claimSignature": hex(&body.claim_signature),
+            "blockHash": hex(&body.block_hash),
+            "verificationType": body.verification_type,
+            "chainId": body.chain_id,
+            "protocol": enum_name(body.protocol, Protocol::as_str_name),
+        }),
+        message_data::Body::VerificationRemoveBody(body) => json!({
+            "address": hex(&body.address),
+            "protocol": enum_name(body.protocol, Protocol::as_str_name),
+        }),
+        message_data::Body::UserDataBody(body) => json!({
+            "type": enum_name(body.r#type, UserDataType::as_str_name),
+            "value": body.value,
+        }),
+        message_data::Body::LinkBody(body) => {
+            let target_fid = match body.target {
+                Some(link_body::Target::TargetFid(fid)) => json!(fid),
+                None => Value::Null,
+            };
+
+            json!({
+                "type": body.r#type,
+                "displayTimestamp": body.display_timestamp,
+                "targetFid": target_fid,
+            })
+        }
+        message_data::Body::UsernameProofBody(proof) => username_proof(proof),
+        message_data::Body::FrameActionBody(body) => json!({
+            "url": String::from_utf8_lossy(&body.url),
+            "buttonIndex": body.button_index,
+            "castId": optional(&body.cast_id, cast_id),
+            "inputText": String::from_utf8_lossy(&body.input_text),
+            "state": hex(&body.state),
+            "transactionId": hex(&body.transaction_id),
+            "address": hex(&body.address),
+        }),
+        message_data::Body::LinkCompactStateBody(body) => json!({
+            "type": body.r#type,
+            "targetFids": body.target_fids,
+        }),
+        message_data::Body::ReactionCompactStateBody(body) => json!({
+            "type": enum_name(body.r#type, ReactionType::as_str_name),
+            "targetCastIds": body.target_cast_ids.iter().map(cast_id).collect::<Vec<Value>>(),
+            "targetUrls": body.target_urls,
+        }),
+    }
+}
+
+pub fn username_proof(proof: &UserNameProof) -> Value {
+    json!({
+        "timestamp": proof.timestamp,
+        "name": String::from_utf8_lossy(&proof.name),
+        "owner": hex(&proof.owner),
+        "signature": hex(&proof.signature),
+        "fid": proof.fid,
+        "type": enum_name(proof.r#type, UserNameType::as_str_name),
+    })
+}
+
+pub fn on_chain_event(event: &OnChainEvent) -> Value {
+    let body = event.body.as_ref().map_or(Value::Null, |body| match body {
+        on_chain_event::Body::SignerEventBody(body) => json!({
+            "key": hex(&body.key),
+            "keyType": body.key_type,
+            "eventType": enum_name(body.event_type, SignerEventType::as_str_name),
+            "metadata": hex(&body.metadata),
+            "metadataType": body.metadata_type,
+        }),
+        on_chain_event::Body::SignerMigratedEventBody(body) => json!({
+            "migratedAt": body.migrated_at,
+        }),
+        on_chain_event::Body::IdRegisterEventBody(body) => json!({
+            "to": hex(&body.to),
+            "eventType": enum_name(body.event_type, IdRegisterEventType::as_str_name),
+            "from": hex(&body.from),
+            "recoveryAddress": hex(&body.recovery_address),
+        }),
+        on_chain_event::Body::StorageRentEventBody(body) => json!({
+            "payer": hex(&body.payer),
+            "units": body.units,
+            "expiry": body.expiry,
+        }),
+    });
+
+    json!({
+        "type": enum_name(event.r#type, OnChainEventType::as_str_name),
+        "chainId": event.chain_id,
+        "blockNumber": event.block_number,
+        "blockHash": hex(&event.block_hash),
+        "blockTimestamp": event.block_timestamp,
+        "transactionHash": hex(&event.transaction_hash),
+        "logIndex": event.log_index,
+        "txIndex": event.tx_index,
+        "fid": event.fid,
+        "version": event.version,
+        "body": body,
+    })
+}
+
+pub fn hub_event(event: &HubEvent) -> Value {
+    let body = event.body.as_ref().map_or(Value::Null, |body| match body {
+        hub_event::Body::MergeMessageBody(body) => json!({
+            "message": optional(&body.message, message),
+            "deletedMessages": body.deleted_messages.iter().map(message).collect::<Vec<_>>(),
+        }),
+        hub_event::Body::PruneMessageBody(body) => json!({
+            "message": optional(&body.message, message),
+        }),
+        hub_event::Body::RevokeMessageBody(body) => json!({
+            "message": optional(&body.message, message),
+        }),
+        hub_event::Body::MergeUsernameProofBody(body) => json!({
+            "usernameProof": optional(&body.username_proof, username_proof),
+            "deletedUsernameProof": optional(&body.deleted_username_proof, username_proof),
+            "usernameProofMessage": optional(&body.username_proof_message, message),
+            "deletedUsernameProofMessage": optional(&body.deleted_username_proof_message, message),
+        }),
+        hub_event::Body::MergeOnChainEventBody(body) => json!({
+            "onChainEvent": optional(&body.on_chain_event, on_chain_event),
+        }),
+    });
+
+    json!({
+        "id": event.id,
+        "type": enum_name(event.r#type, HubEventType::as_str_name),
+        "body": body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::{CastAddBody, MessageData};
+
+    #[test]
+    fn test_message_json() {
+        let message = Message {
+            data: Some(MessageData {
+                r#type: MessageType::CastAdd as i32,
+                fid: 6833,
+                timestamp: 100,
+                network: FarcasterNetwork::Mainnet as i32,
+                body: Some(message_data::Body::CastAddBody(CastAddBody {
+                    text: "hello".to_string(),
+                    parent: Some(cast_add_body::Parent::ParentUrl(
+                        "chain://eip155:1".to_string(),
+                    )),
+                    ..Default::default()
+                })),
+            }),
+            hash: vec![0xab; 20],
+            ..Default::default()
+        };
+
+        let value = super::message(&message);
+        assert_eq!(value["data"]["type"], "MESSAGE_TYPE_CAST_ADD");
+        assert_eq!(value["data"]["fid"], 6833);
+        assert_eq!(value["data"]["body"]["text"], "hello");
+        assert_eq!(value["data"]["body"]["parentUrl"], "chain://eip155:1");
+        assert_eq!(value["data"]["body"]["parentCastId"], Value::Null);
+        assert_eq!(value["hash"], format!("0x{}", "ab".repeat(20)));
+        assert_eq!(value["hashScheme"], "HASH_SCHEME_NONE");
+    }
+
+    #[test]
+    fn test_unknown_enum_value() {
+        assert_eq!(enum_name(1000, MessageType::as_str_name), json!(1000));
+        assert_eq!(
+            enum_name(HubEventType::MergeMessage as i32, HubEventType::as_str_name),
+            json!("HUB_EVENT_TYPE_MERGE_MESSAGE")
+        );
+    }
+}
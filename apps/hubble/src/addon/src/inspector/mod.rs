@@ -0,0 +1,296 @@
+use self::commands::IndexQuery;
+use self::reader::ReadOnlyDB;
+use crate::protos::CastId;
+use crate::store::HubError;
+use clap::{Parser, Subcommand};
+use serde_json::Value;
+
+mod commands;
+mod json;
+mod reader;
+
+#[derive(Parser)]
+#[command(
+    name = "hub-db-inspector",
+    about = "Inspect a Hub's RocksDB without running the Hub"
+)]
+struct Cli {
+    /// Path to the Hub's DB, e.g. .rocks/rocks.hub._default
+    #[arg(long)]
+    db: String,
+
+    /// Pretty print the JSON output
+    #[arg(long)]
+    pretty: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Key counts and sizes for every RootPrefix and UserPostfix. Scans the whole DB
+    Stats,
+
+    /// Decode the messages for a fid
+    Messages {
+        #[arg(long)]
+        fid: u32,
+
+        /// Only print messages from one store: casts, links, reactions, verifications, user-data
+        /// or username-proofs
+        #[arg(long)]
+        store: Option<String>,
+
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+
+    /// Decode HubEvents with ids in [from, to)
+    Events {
+        #[arg(long, default_value_t = 0)]
+        from: u64,
+
+        #[arg(long)]
+        to: Option<u64>,
+
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+
+    /// Print the sync trie node at a prefix. The trie is read from the trieDb inside the DB path
+    Trie {
+        /// Hex encoded prefix. The root node is printed if this is empty
+        #[arg(long, default_value = "")]
+        prefix: String,
+
+        /// Also print this many levels of children
+        #[arg(long, default_value_t = 0)]
+        depth: u32,
+    },
+
+    /// Look up a secondary index
+    Index {
+        #[command(subcommand)]
+        index: IndexCommand,
+
+        /// Fetch and decode the messages the index points to
+        #[arg(long, global = true)]
+        resolve: bool,
+
+        #[arg(long, global = true, default_value_t = 100)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum IndexCommand {
+    /// Casts that reply to a cast (--parent-fid and --parent-hash) or a url (--parent-url)
+    CastsByParent {
+        #[arg(long, requires = "parent_hash", conflicts_with = "parent_url")]
+        parent_fid: Option<u64>,
+
+        #[arg(long)]
+        parent_hash: Option<String>,
+
+        #[arg(long)]
+        parent_url: Option<String>,
+    },
+
+    /// Casts that mention a fid
+    CastsByMention {
+        #[arg(long)]
+        fid: u32,
+    },
+
+    /// Reactions to a cast (--target-fid and --target-hash) or a url (--target-url)
+    ReactionsByTarget {
+        #[arg(long, requires = "target_hash", conflicts_with = "target_url")]
+        target_fid: Option<u64>,
+
+        #[arg(long)]
+        target_hash: Option<String>,
+
+        #[arg(long)]
+        target_url: Option<String>,
+    },
+
+    /// Links that target a fid
+    LinksByTarget {
+        #[arg(long)]
+        fid: u32,
+    },
+
+    /// The fid that verified an address
+    VerificationByAddress {
+        /// Hex encoded address
+        #[arg(long)]
+        address: String,
+    },
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>, HubError> {
+    hex::decode(value.trim_start_matches("0x"))
+        .map_err(|e| HubError::invalid_parameter(&format!("invalid hex {}: {}", value, e)))
+}
+
+fn parse_cast_id(fid: u64, hash: &str) -> Result<CastId, HubError> {
+    Ok(CastId {
+        fid,
+        hash: parse_hex(hash)?,
+    })
+}
+
+impl IndexCommand {
+    fn query(&self) -> Result<IndexQuery, HubError> {
+        let query = match self {
+            IndexCommand::CastsByParent {
+                parent_fid: Some(fid),
+                parent_hash: Some(hash),
+                ..
+            } => IndexQuery::CastsByParentCastId(parse_cast_id(*fid, hash)?),
+            IndexCommand::CastsByParent {
+                parent_url: Some(url),
+                ..
+            } => IndexQuery::CastsByParentUrl(url.clone()),
+            IndexCommand::CastsByParent { .. } => {
+                return Err(HubError::invalid_parameter(
+                    "either --parent-fid and --parent-hash, or --parent-url is required",
+                ))
+            }
+            IndexCommand::CastsByMention { fid } => IndexQuery::CastsByMention(*fid),
+            IndexCommand::ReactionsByTarget {
+                target_fid: Some(fid),
+                target_hash: Some(hash),
+                ..
+            } => IndexQuery::ReactionsByTargetCastId(parse_cast_id(*fid, hash)?),
+            IndexCommand::ReactionsByTarget {
+                target_url: Some(url),
+                ..
+            } => IndexQuery::ReactionsByTargetUrl(url.clone()),
+            IndexCommand::ReactionsByTarget { .. } => {
+                return Err(HubError::invalid_parameter(
+                    "either --target-fid and --target-hash, or --target-url is required",
+                ))
+            }
+            IndexCommand::LinksByTarget { fid } => IndexQuery::LinksByTarget(*fid),
+            IndexCommand::VerificationByAddress { address } => {
+                IndexQuery::VerificationByAddress(parse_hex(address)?)
+            }
+        };
+
+        Ok(query)
+    }
+}
+
+fn execute(cli: &Cli) -> Result<Value, HubError> {
+    let result = match &cli.command {
+        Command::Stats => commands::stats(&ReadOnlyDB::open(&cli.db)?)?,
+        Command::Messages { fid, store, limit } => Value::Array(commands::messages(
+            &ReadOnlyDB::open(&cli.db)?,
+            *fid,
+            store.as_deref(),
+            *limit,
+        )?),
+        Command::Events { from, to, limit } => Value::Array(commands::events(
+            &ReadOnlyDB::open(&cli.db)?,
+            *from,
+            *to,
+            *limit,
+        )?),
+        Command::Trie { prefix, depth } => {
+            let trie_db = ReadOnlyDB::open(&commands::trie_db_path(&cli.db))?;
+            commands::trie_node(&trie_db, &parse_hex(prefix)?, *depth)?
+        }
+        Command::Index {
+            index,
+            resolve,
+            limit,
+        } => Value::Array(commands::index(
+            &ReadOnlyDB::open(&cli.db)?,
+            &index.query()?,
+            *resolve,
+            *limit,
+        )?),
+    };
+
+    Ok(result)
+}
+
+/**
+ * Entry point for the `hub-db-inspector` binary. Prints the result as JSON on stdout, or the
+ * error as JSON on stderr, and returns the process exit code.
+ */
+pub fn run() -> i32 {
+    let cli = Cli::parse();
+
+    let print = |value: &Value| {
+        if cli.pretty {
+            serde_json::to_string_pretty(value).unwrap()
+        } else {
+            value.to_string()
+        }
+    };
+
+    match execute(&cli) {
+        Ok(result) => {
+            println!("{}", print(&result));
+            0
+        }
+        Err(e) => {
+            eprintln!(
+                "{}",
+                print(&serde_json::json!({ "code": e.code.as_str(), "message": e.message }))
+            );
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_index_query() {
+        let cli = Cli::parse_from([
+            "hub-db-inspector",
+            "--db",
+            "/tmp/db",
+            "index",
+            "casts-by-parent",
+            "--parent-fid",
+            "6833",
+            "--parent-hash",
+            "0xabcd",
+        ]);
+        let Command::Index { index, .. } = cli.command else {
+            panic!("expected an index command");
+        };
+        match index.query().unwrap() {
+            IndexQuery::CastsByParentCastId(cast_id) => {
+                assert_eq!(cast_id.fid, 6833);
+                assert_eq!(cast_id.hash, vec![0xab, 0xcd]);
+            }
+            _ => panic!("expected a cast id query"),
+        }
+
+        let cli = Cli::parse_from([
+            "hub-db-inspector",
+            "--db",
+            "/tmp/db",
+            "index",
+            "casts-by-parent",
+        ]);
+        let Command::Index { index, .. } = cli.command else {
+            panic!("expected an index command");
+        };
+        assert!(index.query().is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::store::{increment_vec_u8, HubError};
+use rocksdb::{Direction, IteratorMode, Options, ReadOptions, DB};
+
+/**
+ * A read-only handle to a Hub's RocksDB. The Hub opens its DB as a TransactionDB, which can't be
+ * opened read-only, so the inspector opens the same files as a plain DB instead. This doesn't take
+ * the DB lock, so it is safe to point at the DB of a running Hub (it will see the DB as of when it
+ * was opened).
+ */
+pub struct ReadOnlyDB {
+    db: DB,
+    pub path: String,
+}
+
+impl ReadOnlyDB {
+    pub fn open(path: &str) -> Result<ReadOnlyDB, HubError> {
+        let opts = Options::default();
+        let db = DB::open_for_read_only(&opts, path, false)?;
+
+        Ok(ReadOnlyDB {
+            db,
+            path: path.to_string(),
+        })
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, HubError> {
+        Ok(self.db.get(key)?)
+    }
+
+    /**
+     * Iterate over all keys that start with the prefix. The callback returns true to stop
+     * iterating, like `RocksDB::for_each_iterator_by_prefix`.
+     */
+    pub fn for_each_by_prefix<F>(&self, prefix: &[u8], f: F) -> Result<(), HubError>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool, HubError>,
+    {
+        let stop = increment_vec_u8(&prefix.to_vec());
+        self.for_each_in_range(prefix, &stop, f)
+    }
+
+    /** Iterate over keys in [start, stop). An empty stop key iterates to the end of the DB */
+    pub fn for_each_in_range<F>(&self, start: &[u8], stop: &[u8], mut f: F) -> Result<(), HubError>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool, HubError>,
+    {
+        let mut opts = ReadOptions::default();
+        if !stop.is_empty() {
+            opts.set_iterate_upper_bound(stop.to_vec());
+        }
+
+        let iter = self
+            .db
+            .iterator_opt(IteratorMode::From(start, Direction::Forward), opts);
+        for item in iter {
+            let (key, value) = item?;
+            if f(&key, &value)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     store::{CastStore, StoreEventHandler, UsernameProofStore, VerificationStore},
     trie::merkle_trie::MerkleTrie,
 };
-use db::RocksDB;
+use db::{RocksDB, SnapshotCancellationToken};
 use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey, EXPANDED_SECRET_KEY_LENGTH};
 use neon::{prelude::*, types::buffer::TypedArray};
 use std::{convert::TryInto, sync::Mutex};
@@ -10,7 +10,10 @@ use store::{LinkStore, ReactionStore, Store, UserDataStore};
 use threadpool::ThreadPool;
 
 mod db;
+pub mod inspector;
 mod logger;
+mod panics;
+mod rpc;
 mod statsd;
 mod store;
 mod trie;
@@ -85,6 +88,8 @@ fn js_blake3_20(mut cx: FunctionContext) -> JsResult<JsBuffer> {
 
 #[neon::main]
 fn main(mut cx: ModuleContext) -> NeonResult<()> {
+    panics::install_panic_hook();
+
     cx.export_function("ed25519_signMessageHash", ed25519_sign_message_hash)?;
     cx.export_function("ed25519_verify", ed25519_verify)?;
     cx.export_function("blake3_20", js_blake3_20)?;
@@ -114,6 +119,13 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("dbDel", RocksDB::js_del)?;
     cx.export_function("dbCommit", RocksDB::js_commit_transaction)?;
     cx.export_function("dbSnapshotBackup", RocksDB::js_snapshot_backup)?;
+    cx.export_function("dbRestoreSnapshot", RocksDB::js_restore_snapshot)?;
+    cx.export_function("dbVerifySnapshot", RocksDB::js_verify_snapshot)?;
+    cx.export_function(
+        "createSnapshotCancellationToken",
+        SnapshotCancellationToken::js_create,
+    )?;
+    cx.export_function("cancelSnapshot", SnapshotCancellationToken::js_cancel)?;
     cx.export_function("dbCountKeysAtPrefix", RocksDB::js_count_keys_at_prefix)?;
     cx.export_function(
         "dbDeleteAllKeysInRange",
@@ -141,6 +153,14 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("revoke", Store::js_revoke)?;
     cx.export_function("pruneMessages", Store::js_prune_messages)?;
     cx.export_function("getAllMessagesByFid", Store::js_get_all_messages_by_fid)?;
+    cx.export_function("checkIndices", Store::js_check_indices)?;
+    cx.export_function("prepareOptionalIndices", Store::js_prepare_optional_indices)?;
+    cx.export_function("rebuildCounters", Store::js_rebuild_counters)?;
+    cx.export_function("validateMerge", Store::js_validate_merge)?;
+    cx.export_function(
+        "previewCompactStateMerge",
+        Store::js_preview_compact_state_merge,
+    )?;
 
     // LinkStore methods
     cx.export_function("createLinkStore", LinkStore::create_link_store)?;
@@ -153,6 +173,9 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
         "getLinkCompactStateMessageByFid",
         LinkStore::js_get_link_compact_state_message_by_fid,
     )?;
+    cx.export_function("buildLinkCompactState", LinkStore::js_build_compact_state)?;
+    cx.export_function("getFollowerCount", LinkStore::js_get_follower_count)?;
+    cx.export_function("getFollowingCount", LinkStore::js_get_following_count)?;
 
     // ReactionStore methods
     cx.export_function("createReactionStore", ReactionStore::create_reaction_store)?;
@@ -170,6 +193,11 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
         "getReactionsByTarget",
         ReactionStore::js_get_reactions_by_target,
     )?;
+    cx.export_function(
+        "getReactionCompactStateMessageByFid",
+        ReactionStore::js_get_reaction_compact_state_message_by_fid,
+    )?;
+    cx.export_function("getReactionCount", ReactionStore::js_get_reaction_count)?;
 
     // CastStore methods
     cx.export_function("createCastStore", CastStore::js_create_cast_store)?;
@@ -179,6 +207,16 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("getCastRemovesByFid", CastStore::js_get_cast_removes_by_fid)?;
     cx.export_function("getCastsByParent", CastStore::js_get_casts_by_parent)?;
     cx.export_function("getCastsByMention", CastStore::js_get_casts_by_mention)?;
+    cx.export_function("getCastThread", CastStore::js_get_cast_thread)?;
+    cx.export_function("getReplyCount", CastStore::js_get_reply_count)?;
+    cx.export_function("searchCasts", CastStore::js_search_casts)?;
+    cx.export_function("getCastsByEmbedUrl", CastStore::js_get_casts_by_embed_url)?;
+    cx.export_function("getQuoteCasts", CastStore::js_get_quote_casts)?;
+    cx.export_function("getRecentCasts", CastStore::js_get_recent_casts)?;
+    cx.export_function(
+        "getRecentCastsByParentUrl",
+        CastStore::js_get_recent_casts_by_parent_url,
+    )?;
 
     // UserDataStore methods
     cx.export_function("createUserDataStore", UserDataStore::create_userdata_store)?;
@@ -193,6 +231,19 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
         UserDataStore::js_get_username_proof_by_fid,
     )?;
     cx.export_function("mergeUserNameProof", UserDataStore::js_merge_username_proof)?;
+    cx.export_function("getProfile", UserDataStore::js_get_profile)?;
+    cx.export_function("getProfiles", UserDataStore::js_get_profiles)?;
+    cx.export_function("resolveName", UserDataStore::js_resolve_name)?;
+    cx.export_function("reverseResolve", UserDataStore::js_reverse_resolve)?;
+    cx.export_function("getFnameHistory", UserDataStore::js_get_fname_history)?;
+    cx.export_function(
+        "getFnameHistoryByFid",
+        UserDataStore::js_get_fname_history_by_fid,
+    )?;
+    cx.export_function(
+        "backfillFnameHistory",
+        UserDataStore::js_backfill_fname_history,
+    )?;
 
     // VerificationStore methods
     cx.export_function(
@@ -238,6 +289,11 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
         UsernameProofStore::js_get_username_proof_by_fid_and_name,
     )?;
 
+    // Rust RPC server
+    cx.export_function("startRpcServer", rpc::RpcServer::js_start)?;
+    cx.export_function("rpcServerAddress", rpc::RpcServer::js_address)?;
+    cx.export_function("stopRpcServer", rpc::RpcServer::js_stop)?;
+
     // Register Merkle Trie methods
     MerkleTrie::register_js_methods(&mut cx)?;
 
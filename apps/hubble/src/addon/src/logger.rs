@@ -1,3 +1,4 @@
+use crate::panics::lock_unpoisoned;
 use chrono::Utc;
 use neon::context::{Context, FunctionContext};
 use neon::result::JsResult;
@@ -100,7 +101,7 @@ fn create_logger() -> Logger {
     let async_drain = Arc::new(slog_async::Async::new(json_logger.fuse()).build());
 
     // Store the async drain in a global variable
-    *ASYNC_DRAIN.lock().unwrap() = Some(async_drain.clone());
+    *lock_unpoisoned(&ASYNC_DRAIN) = Some(async_drain.clone());
 
     // Get the environment variable NODE_ENV, and if it is set to "test" or "CI", then set the log level to "critical"
     let log_level = match std::env::var("NODE_ENV") {
@@ -122,7 +123,7 @@ fn create_logger() -> Logger {
     let switch_ctrl = switch.ctrl();
 
     // Store the switch controller in a global variable
-    *LOG_LEVEL_SWITCH.lock().unwrap() = Some(switch_ctrl);
+    *lock_unpoisoned(&LOG_LEVEL_SWITCH) = Some(switch_ctrl);
 
     // Wrap the switch in a LevelFilter if needed or directly use the switch
     let drain = switch.fuse();
@@ -138,12 +139,12 @@ pub fn js_flush_log_buffer(mut cx: FunctionContext) -> JsResult<JsUndefined> {
 }
 
 pub fn js_set_log_level(mut cx: FunctionContext) -> JsResult<JsUndefined> {
-    let switch = LOG_LEVEL_SWITCH.lock().unwrap();
+    let switch = lock_unpoisoned(&LOG_LEVEL_SWITCH);
     if switch.is_none() {
         return cx.throw_error("Log level switch is not initialized");
     }
 
-    let async_drain = ASYNC_DRAIN.lock().unwrap();
+    let async_drain = lock_unpoisoned(&ASYNC_DRAIN);
     if async_drain.is_none() {
         return cx.throw_error("Async drain is not initialized");
     }
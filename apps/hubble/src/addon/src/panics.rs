@@ -0,0 +1,159 @@
+use crate::logger::LOGGER;
+use crate::store::HubError;
+use crate::THREAD_POOL;
+use slog::error;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/**
+ * Log every panic with its backtrace. The JS side only gets the panic message, so this is the
+ * only place the backtrace shows up.
+ */
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map(|location| location.to_string())
+            .unwrap_or_default();
+
+        error!(LOGGER, "Panic in rust addon";
+            "message" => panic_message(info.payload()),
+            "location" => location,
+            "thread" => std::thread::current().name().unwrap_or("unnamed").to_string(),
+            "backtrace" => Backtrace::force_capture().to_string());
+    }));
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/**
+ * Run f, turning a panic into an internal error instead of unwinding into the caller. Pooled tasks
+ * run their work through this, so a panic rejects their promise instead of leaving it unsettled.
+ */
+pub fn catch_panic<T, F>(f: F) -> Result<T, HubError>
+where
+    F: FnOnce() -> Result<T, HubError>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => Err(HubError::internal_error(&format!(
+            "panic: {}",
+            panic_message(payload.as_ref())
+        ))),
+    }
+}
+
+/**
+ * Lock a mutex even if a thread panicked while holding it. Only use this for locks that don't
+ * guard data a panic could leave half updated, like the fid merge locks.
+ */
+pub fn lock_unpoisoned<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub fn read_unpoisoned<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub fn write_unpoisoned<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn poisoned<E>(_: E) -> HubError {
+    HubError::internal_error("lock poisoned by an earlier panic")
+}
+
+/** Lock a mutex, failing if a thread panicked while holding it, since its data may be corrupt */
+pub fn lock_or_error<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, HubError> {
+    mutex.lock().map_err(poisoned)
+}
+
+pub fn read_or_error<T>(lock: &RwLock<T>) -> Result<RwLockReadGuard<'_, T>, HubError> {
+    lock.read().map_err(poisoned)
+}
+
+pub fn write_or_error<T>(lock: &RwLock<T>) -> Result<RwLockWriteGuard<'_, T>, HubError> {
+    lock.write().map_err(poisoned)
+}
+
+/**
+ * Run a job in the threadpool. Jobs should run their work through `catch_panic` and settle their
+ * promise with the result. If a job panics anyway, its Deferred is dropped, which rejects the
+ * promise, and the worker thread is kept instead of respawned.
+ */
+pub fn execute<F>(job: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    lock_unpoisoned(&THREAD_POOL).execute(move || {
+        // The panic hook has already logged the panic
+        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::HubErrorCode;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(catch_panic(|| Ok(1)), Ok(1));
+
+        let e = catch_panic::<(), _>(|| panic!("bad message {}", 7)).unwrap_err();
+        assert_eq!(e.code, HubErrorCode::Unknown);
+        assert_eq!(e.message, "panic: bad message 7");
+
+        let e = catch_panic(|| {
+            let data: Vec<u32> = vec![];
+            Ok(data[0])
+        })
+        .unwrap_err();
+        assert!(e.message.contains("index out of bounds"));
+    }
+
+    #[test]
+    fn test_poisoned_locks() {
+        let mutex = Arc::new(Mutex::new(1));
+        let poisoner = mutex.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(mutex.is_poisoned());
+
+        assert_eq!(*lock_unpoisoned(&mutex), 1);
+        assert_eq!(
+            lock_or_error(&mutex).unwrap_err().code,
+            HubErrorCode::Unknown
+        );
+
+        let rwlock = Arc::new(RwLock::new(2));
+        let poisoner = rwlock.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.write().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+
+        assert_eq!(*read_unpoisoned(&rwlock), 2);
+        assert_eq!(*write_unpoisoned(&rwlock), 2);
+        assert!(read_or_error(&rwlock).is_err());
+        assert!(write_or_error(&rwlock).is_err());
+
+        rwlock.clear_poison();
+        assert_eq!(*read_or_error(&rwlock).unwrap(), 2);
+    }
+}
@@ -0,0 +1,938 @@
+use crate::logger::LOGGER;
+use crate::panics::{catch_panic, lock_unpoisoned};
+use crate::protos::{
+    self, casts_by_parent_request, hub_service_server::HubService,
+    hub_service_server::HubServiceServer, link_request, links_by_target_request, message_data,
+    reaction_request, reactions_by_target_request, CastId, CastsByParentRequest,
+    ContactInfoResponse, Empty, EventRequest, FidRequest, FidTimestampRequest, FidsRequest,
+    FidsResponse, HubEvent, HubInfoRequest, HubInfoResponse, IdRegistryEventByAddressRequest,
+    LinkRequest, LinksByFidRequest, LinksByTargetRequest, Message, MessagesResponse, OnChainEvent,
+    OnChainEventRequest, OnChainEventResponse, ReactionRequest, ReactionsByFidRequest,
+    ReactionsByTargetRequest, SignerRequest, StorageLimitsResponse, StreamFetchRequest,
+    StreamFetchResponse, StreamSyncRequest, StreamSyncResponse, SubmitBulkMessagesRequest,
+    SubmitBulkMessagesResponse, SubscribeRequest, SyncIds, SyncStatusRequest, SyncStatusResponse,
+    TrieNodeMetadataResponse, TrieNodePrefix, TrieNodeSnapshotResponse, UserDataRequest,
+    UserNameProof, UserNameType, UsernameProofRequest, UsernameProofsResponse, ValidationResponse,
+    VerificationRequest,
+};
+use crate::store::{
+    hub_error_to_js_throw, message_decode, CastStore, HubError, HubErrorCode, LinkStore,
+    MessagesPage, PageOptions, ReactionStore, Store, UserDataStore, UsernameProofStore,
+    VerificationStore, PAGE_SIZE_MAX,
+};
+use neon::context::{Context, FunctionContext};
+use neon::object::Object;
+use neon::result::JsResult;
+use neon::types::{Finalize, JsBox, JsObject, JsPromise, JsString};
+use slog::{error, info, o};
+use std::borrow::Borrow;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use tokio::sync::oneshot;
+use tonic::codegen::BoxStream;
+use tonic::metadata::MetadataValue;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Code, Request, Response, Status, Streaming};
+
+/** The stores the RPC server reads from. They are the same stores the JS engine writes to */
+#[derive(Clone)]
+pub struct RpcStores {
+    pub cast_store: Arc<Store>,
+    pub link_store: Arc<Store>,
+    pub reaction_store: Arc<Store>,
+    pub user_data_store: Arc<Store>,
+    pub verification_store: Arc<Store>,
+    pub username_proof_store: Arc<Store>,
+}
+
+/**
+ * Serves the read-only HubService RPCs straight from the Rust stores, so reads don't have to go
+ * through the Node event loop. RPCs that need state only JS has (submitting messages, on-chain
+ * events, sync, events) return `unimplemented`, and are still served by the JS RPC server.
+ */
+pub struct HubRpcService {
+    stores: RpcStores,
+}
+
+/**
+ * Map a HubError to a gRPC status, the same way the JS RPC server does. The HubError code is
+ * passed along in the "errcode" metadata, so clients can tell errors apart.
+ */
+pub fn hub_error_to_status(e: HubError) -> Status {
+    let code = match e.code {
+        HubErrorCode::Unauthenticated => Code::Unauthenticated,
+        HubErrorCode::Unauthorized => Code::PermissionDenied,
+        HubErrorCode::NotFound => Code::NotFound,
+        code if code.is_bad_request() => Code::InvalidArgument,
+        code if code.is_unavailable() => Code::Unavailable,
+        _ => Code::Unknown,
+    };
+
+    let mut status = Status::new(code, e.message);
+    if let Ok(errcode) = MetadataValue::try_from(e.code.as_str()) {
+        status.metadata_mut().insert("errcode", errcode);
+    }
+    status
+}
+
+fn page_options(
+    page_size: Option<u32>,
+    page_token: Option<Vec<u8>>,
+    reverse: Option<bool>,
+) -> PageOptions {
+    PageOptions {
+        page_size: page_size.map(|size| (size as usize).min(PAGE_SIZE_MAX)),
+        page_token: page_token.filter(|token| !token.is_empty()),
+        reverse: reverse.unwrap_or(false),
+    }
+}
+
+fn validate_fid(fid: u64) -> Result<u32, HubError> {
+    if fid == 0 {
+        return Err(HubError::validation_failure("fid is missing"));
+    }
+
+    u32::try_from(fid).map_err(|_| HubError::invalid_parameter("fid is too large"))
+}
+
+fn messages_response(page: MessagesPage) -> Result<MessagesResponse, HubError> {
+    let messages = page
+        .messages_bytes
+        .iter()
+        .map(|bytes| message_decode(bytes))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(MessagesResponse {
+        messages,
+        next_page_token: Some(page.next_page_token.unwrap_or_default()),
+    })
+}
+
+/** Store reads block on RocksDB, so run them on tokio's blocking threads */
+async fn run_blocking<T, F>(f: F) -> Result<Response<T>, Status>
+where
+    F: FnOnce() -> Result<T, HubError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Status::internal(format!("RPC handler failed: {}", e)))?
+        .map(Response::new)
+        .map_err(hub_error_to_status)
+}
+
+fn unimplemented<T>(method: &str) -> Result<Response<T>, Status> {
+    Err(Status::unimplemented(format!(
+        "{} is not served by the Rust RPC server",
+        method
+    )))
+}
+
+impl HubRpcService {
+    pub fn new(stores: RpcStores) -> Self {
+        HubRpcService { stores }
+    }
+
+    fn username_proof(stores: &RpcStores, name: Vec<u8>) -> Result<UserNameProof, HubError> {
+        if name.ends_with(b".eth") {
+            let message = UsernameProofStore::get_username_proof(
+                &stores.username_proof_store,
+                &name,
+                UserNameType::UsernameTypeEnsL1 as u8,
+            )?
+            .ok_or_else(|| HubError::not_found("usernameProof not found"))?;
+
+            match message.data.and_then(|data| data.body) {
+                Some(message_data::Body::UsernameProofBody(proof)) => Ok(proof),
+                _ => Err(HubError::internal_db_error("usernameProof has no body")),
+            }
+        } else {
+            UserDataStore::get_username_proof(&stores.user_data_store, &name)?
+                .ok_or_else(|| HubError::not_found("usernameProof not found"))
+        }
+    }
+
+    fn username_proofs_by_fid(
+        stores: &RpcStores,
+        fid: u32,
+    ) -> Result<UsernameProofsResponse, HubError> {
+        let mut proofs = vec![];
+        if let Some(fname_proof) =
+            UserDataStore::get_username_proof_by_fid(&stores.user_data_store, fid)?
+        {
+            proofs.push(fname_proof);
+        }
+
+        let ens_proofs = UsernameProofStore::get_username_proofs_by_fid(
+            &stores.username_proof_store,
+            fid,
+            &PageOptions::default(),
+            None,
+            None,
+        )?;
+        for message in messages_response(ens_proofs)?.messages {
+            if let Some(message_data::Body::UsernameProofBody(proof)) =
+                message.data.and_then(|data| data.body)
+            {
+                proofs.push(proof);
+            }
+        }
+
+        Ok(UsernameProofsResponse { proofs })
+    }
+
+    fn all_messages_by_fid(
+        store: &Store,
+        request: FidTimestampRequest,
+    ) -> Result<MessagesResponse, HubError> {
+        let fid = validate_fid(request.fid)?;
+        let page_options = page_options(request.page_size, request.page_token, request.reverse);
+
+        messages_response(store.get_all_messages_by_fid(
+            fid,
+            request.start_timestamp.map(|t| t as u32),
+            request.stop_timestamp.map(|t| t as u32),
+            &page_options,
+        )?)
+    }
+}
+
+#[tonic::async_trait]
+impl HubService for HubRpcService {
+    async fn submit_message(&self, _: Request<Message>) -> Result<Response<Message>, Status> {
+        unimplemented("SubmitMessage")
+    }
+
+    async fn validate_message(
+        &self,
+        _: Request<Message>,
+    ) -> Result<Response<ValidationResponse>, Status> {
+        unimplemented("ValidateMessage")
+    }
+
+    type SubscribeStream = BoxStream<HubEvent>;
+
+    async fn subscribe(
+        &self,
+        _: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        unimplemented("Subscribe")
+    }
+
+    async fn get_event(&self, _: Request<EventRequest>) -> Result<Response<HubEvent>, Status> {
+        unimplemented("GetEvent")
+    }
+
+    async fn get_cast(&self, request: Request<CastId>) -> Result<Response<Message>, Status> {
+        let store = self.stores.cast_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            CastStore::get_cast_add(&store, fid, request.hash)?
+                .ok_or_else(|| HubError::not_found("castAddMessage not found"))
+        })
+        .await
+    }
+
+    async fn get_casts_by_fid(
+        &self,
+        request: Request<FidRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.cast_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(CastStore::get_cast_adds_by_fid(
+                &store,
+                fid,
+                &page_options,
+                None,
+                None,
+            )?)
+        })
+        .await
+    }
+
+    async fn get_casts_by_parent(
+        &self,
+        request: Request<CastsByParentRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.cast_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let parent = match request.parent {
+                Some(casts_by_parent_request::Parent::ParentCastId(cast_id)) => {
+                    protos::cast_add_body::Parent::ParentCastId(cast_id)
+                }
+                Some(casts_by_parent_request::Parent::ParentUrl(url)) => {
+                    protos::cast_add_body::Parent::ParentUrl(url)
+                }
+                None => {
+                    return Err(HubError::invalid_parameter(
+                        "parent_cast_id or parent_url is required",
+                    ))
+                }
+            };
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(CastStore::get_casts_by_parent(
+                &store,
+                &parent,
+                &page_options,
+            )?)
+        })
+        .await
+    }
+
+    async fn get_casts_by_mention(
+        &self,
+        request: Request<FidRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.cast_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(CastStore::get_casts_by_mention(&store, fid, &page_options)?)
+        })
+        .await
+    }
+
+    async fn get_reaction(
+        &self,
+        request: Request<ReactionRequest>,
+    ) -> Result<Response<Message>, Status> {
+        let store = self.stores.reaction_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            let target = match request.target {
+                Some(reaction_request::Target::TargetCastId(cast_id)) => {
+                    protos::reaction_body::Target::TargetCastId(cast_id)
+                }
+                Some(reaction_request::Target::TargetUrl(url)) => {
+                    protos::reaction_body::Target::TargetUrl(url)
+                }
+                None => return Err(HubError::invalid_parameter("target is required")),
+            };
+            ReactionStore::get_reaction_add(&store, fid, request.reaction_type, Some(target))?
+                .ok_or_else(|| HubError::not_found("reactionAddMessage not found"))
+        })
+        .await
+    }
+
+    async fn get_reactions_by_fid(
+        &self,
+        request: Request<ReactionsByFidRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.reaction_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(ReactionStore::get_reaction_adds_by_fid(
+                &store,
+                fid,
+                request.reaction_type.unwrap_or(0),
+                &page_options,
+                None,
+                None,
+            )?)
+        })
+        .await
+    }
+
+    async fn get_reactions_by_cast(
+        &self,
+        request: Request<ReactionsByTargetRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        self.get_reactions_by_target(request).await
+    }
+
+    async fn get_reactions_by_target(
+        &self,
+        request: Request<ReactionsByTargetRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.reaction_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let target = match request.target {
+                Some(reactions_by_target_request::Target::TargetCastId(cast_id)) => {
+                    protos::reaction_body::Target::TargetCastId(cast_id)
+                }
+                Some(reactions_by_target_request::Target::TargetUrl(url)) => {
+                    protos::reaction_body::Target::TargetUrl(url)
+                }
+                None => return Err(HubError::invalid_parameter("target is required")),
+            };
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(ReactionStore::get_reactions_by_target(
+                &store,
+                &target,
+                request.reaction_type.unwrap_or(0),
+                &page_options,
+            )?)
+        })
+        .await
+    }
+
+    async fn get_user_data(
+        &self,
+        request: Request<UserDataRequest>,
+    ) -> Result<Response<Message>, Status> {
+        let store = self.stores.user_data_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            UserDataStore::get_user_data_add(&store, fid, request.user_data_type)?
+                .ok_or_else(|| HubError::not_found("userDataAddMessage not found"))
+        })
+        .await
+    }
+
+    async fn get_user_data_by_fid(
+        &self,
+        request: Request<FidRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.user_data_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(UserDataStore::get_user_data_adds_by_fid(
+                &store,
+                fid,
+                &page_options,
+                None,
+                None,
+            )?)
+        })
+        .await
+    }
+
+    async fn get_username_proof(
+        &self,
+        request: Request<UsernameProofRequest>,
+    ) -> Result<Response<UserNameProof>, Status> {
+        let stores = self.stores.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || Self::username_proof(&stores, request.name)).await
+    }
+
+    async fn get_user_name_proofs_by_fid(
+        &self,
+        request: Request<FidRequest>,
+    ) -> Result<Response<UsernameProofsResponse>, Status> {
+        let stores = self.stores.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            Self::username_proofs_by_fid(&stores, fid)
+        })
+        .await
+    }
+
+    async fn get_verification(
+        &self,
+        request: Request<VerificationRequest>,
+    ) -> Result<Response<Message>, Status> {
+        let store = self.stores.verification_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            VerificationStore::get_verification_add(&store, fid, &request.address)?
+                .ok_or_else(|| HubError::not_found("verificationAddMessage not found"))
+        })
+        .await
+    }
+
+    async fn get_verifications_by_fid(
+        &self,
+        request: Request<FidRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.verification_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(VerificationStore::get_verification_adds_by_fid(
+                &store,
+                fid,
+                &page_options,
+                None,
+                None,
+            )?)
+        })
+        .await
+    }
+
+    async fn get_on_chain_signer(
+        &self,
+        _: Request<SignerRequest>,
+    ) -> Result<Response<OnChainEvent>, Status> {
+        unimplemented("GetOnChainSigner")
+    }
+
+    async fn get_on_chain_signers_by_fid(
+        &self,
+        _: Request<FidRequest>,
+    ) -> Result<Response<OnChainEventResponse>, Status> {
+        unimplemented("GetOnChainSignersByFid")
+    }
+
+    async fn get_on_chain_events(
+        &self,
+        _: Request<OnChainEventRequest>,
+    ) -> Result<Response<OnChainEventResponse>, Status> {
+        unimplemented("GetOnChainEvents")
+    }
+
+    async fn get_id_registry_on_chain_event(
+        &self,
+        _: Request<FidRequest>,
+    ) -> Result<Response<OnChainEvent>, Status> {
+        unimplemented("GetIdRegistryOnChainEvent")
+    }
+
+    async fn get_id_registry_on_chain_event_by_address(
+        &self,
+        _: Request<IdRegistryEventByAddressRequest>,
+    ) -> Result<Response<OnChainEvent>, Status> {
+        unimplemented("GetIdRegistryOnChainEventByAddress")
+    }
+
+    async fn get_current_storage_limits_by_fid(
+        &self,
+        _: Request<FidRequest>,
+    ) -> Result<Response<StorageLimitsResponse>, Status> {
+        unimplemented("GetCurrentStorageLimitsByFid")
+    }
+
+    async fn get_fids(&self, _: Request<FidsRequest>) -> Result<Response<FidsResponse>, Status> {
+        unimplemented("GetFids")
+    }
+
+    async fn get_link(&self, request: Request<LinkRequest>) -> Result<Response<Message>, Status> {
+        let store = self.stores.link_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            let target = match request.target {
+                Some(link_request::Target::TargetFid(target_fid)) => {
+                    protos::link_body::Target::TargetFid(target_fid)
+                }
+                None => return Err(HubError::invalid_parameter("target is required")),
+            };
+            LinkStore::get_link_add(&store, fid, request.link_type, Some(target))?
+                .ok_or_else(|| HubError::not_found("linkAddMessage not found"))
+        })
+        .await
+    }
+
+    async fn get_links_by_fid(
+        &self,
+        request: Request<LinksByFidRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.link_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(LinkStore::get_link_adds_by_fid(
+                &store,
+                fid,
+                request.link_type.unwrap_or_default(),
+                &page_options,
+                None,
+                None,
+            )?)
+        })
+        .await
+    }
+
+    async fn get_links_by_target(
+        &self,
+        request: Request<LinksByTargetRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.link_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let target = match request.target {
+                Some(links_by_target_request::Target::TargetFid(target_fid)) => {
+                    protos::link_body::Target::TargetFid(target_fid)
+                }
+                None => return Err(HubError::invalid_parameter("target is required")),
+            };
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(LinkStore::get_links_by_target(
+                &store,
+                &target,
+                request.link_type.unwrap_or_default(),
+                &page_options,
+            )?)
+        })
+        .await
+    }
+
+    async fn get_all_cast_messages_by_fid(
+        &self,
+        request: Request<FidTimestampRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.cast_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || Self::all_messages_by_fid(&store, request)).await
+    }
+
+    async fn get_all_reaction_messages_by_fid(
+        &self,
+        request: Request<FidTimestampRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.reaction_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || Self::all_messages_by_fid(&store, request)).await
+    }
+
+    async fn get_all_verification_messages_by_fid(
+        &self,
+        request: Request<FidTimestampRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.verification_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || Self::all_messages_by_fid(&store, request)).await
+    }
+
+    async fn get_all_user_data_messages_by_fid(
+        &self,
+        request: Request<FidTimestampRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.user_data_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(UserDataStore::get_user_data_adds_by_fid(
+                &store,
+                fid,
+                &page_options,
+                request.start_timestamp.map(|t| t as u32),
+                request.stop_timestamp.map(|t| t as u32),
+            )?)
+        })
+        .await
+    }
+
+    async fn get_all_link_messages_by_fid(
+        &self,
+        request: Request<FidTimestampRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.link_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || Self::all_messages_by_fid(&store, request)).await
+    }
+
+    async fn get_link_compact_state_message_by_fid(
+        &self,
+        request: Request<FidRequest>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        let store = self.stores.link_store.clone();
+        let request = request.into_inner();
+
+        run_blocking(move || {
+            let fid = validate_fid(request.fid)?;
+            let page_options = page_options(request.page_size, request.page_token, request.reverse);
+            messages_response(LinkStore::get_link_compact_state_message_by_fid(
+                &store,
+                fid,
+                &page_options,
+            )?)
+        })
+        .await
+    }
+
+    async fn submit_bulk_messages(
+        &self,
+        _: Request<SubmitBulkMessagesRequest>,
+    ) -> Result<Response<SubmitBulkMessagesResponse>, Status> {
+        unimplemented("SubmitBulkMessages")
+    }
+
+    async fn get_info(
+        &self,
+        _: Request<HubInfoRequest>,
+    ) -> Result<Response<HubInfoResponse>, Status> {
+        unimplemented("GetInfo")
+    }
+
+    async fn get_current_peers(
+        &self,
+        _: Request<Empty>,
+    ) -> Result<Response<ContactInfoResponse>, Status> {
+        unimplemented("GetCurrentPeers")
+    }
+
+    async fn stop_sync(&self, _: Request<Empty>) -> Result<Response<SyncStatusResponse>, Status> {
+        unimplemented("StopSync")
+    }
+
+    async fn force_sync(
+        &self,
+        _: Request<SyncStatusRequest>,
+    ) -> Result<Response<SyncStatusResponse>, Status> {
+        unimplemented("ForceSync")
+    }
+
+    async fn get_sync_status(
+        &self,
+        _: Request<SyncStatusRequest>,
+    ) -> Result<Response<SyncStatusResponse>, Status> {
+        unimplemented("GetSyncStatus")
+    }
+
+    async fn get_all_sync_ids_by_prefix(
+        &self,
+        _: Request<TrieNodePrefix>,
+    ) -> Result<Response<SyncIds>, Status> {
+        unimplemented("GetAllSyncIdsByPrefix")
+    }
+
+    async fn get_all_messages_by_sync_ids(
+        &self,
+        _: Request<SyncIds>,
+    ) -> Result<Response<MessagesResponse>, Status> {
+        unimplemented("GetAllMessagesBySyncIds")
+    }
+
+    async fn get_sync_metadata_by_prefix(
+        &self,
+        _: Request<TrieNodePrefix>,
+    ) -> Result<Response<TrieNodeMetadataResponse>, Status> {
+        unimplemented("GetSyncMetadataByPrefix")
+    }
+
+    async fn get_sync_snapshot_by_prefix(
+        &self,
+        _: Request<TrieNodePrefix>,
+    ) -> Result<Response<TrieNodeSnapshotResponse>, Status> {
+        unimplemented("GetSyncSnapshotByPrefix")
+    }
+
+    type StreamSyncStream = BoxStream<StreamSyncResponse>;
+
+    async fn stream_sync(
+        &self,
+        _: Request<Streaming<StreamSyncRequest>>,
+    ) -> Result<Response<Self::StreamSyncStream>, Status> {
+        unimplemented("StreamSync")
+    }
+
+    type StreamFetchStream = BoxStream<StreamFetchResponse>;
+
+    async fn stream_fetch(
+        &self,
+        _: Request<Streaming<StreamFetchRequest>>,
+    ) -> Result<Response<Self::StreamFetchStream>, Status> {
+        unimplemented("StreamFetch")
+    }
+}
+
+/**
+ * A running RPC server. The server runs on its own tokio runtime in a background thread, and
+ * keeps running until `stop` is called.
+ */
+pub struct RpcServer {
+    address: SocketAddr,
+    shutdown: Mutex<Option<oneshot::Sender<()>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+/** Needed to make sure neon can clean up the RpcServer at the end */
+impl Finalize for RpcServer {}
+
+impl RpcServer {
+    /**
+     * Bind to `address` and start serving. The address is bound before this returns, so a bad or
+     * taken address is reported right away. Use port 0 to pick a free port.
+     */
+    pub fn start(address: &str, stores: RpcStores) -> Result<RpcServer, HubError> {
+        let logger = LOGGER.new(o!("component" => "RpcServer"));
+
+        let listener = std::net::TcpListener::bind(address).map_err(|e| {
+            HubError::invalid_parameter(&format!("Could not bind to {}: {}", address, e))
+        })?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("hub-rpc")
+            .build()?;
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        let thread = std::thread::spawn(move || {
+            let server_logger = logger.clone();
+            let result = runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener)?;
+                let incoming = TcpIncoming::from_listener(listener, true, None)
+                    .map_err(|e| HubError::internal_db_error(&e.to_string()))?;
+
+                info!(server_logger, "Rust RPC server started"; "address" => address.to_string());
+                tonic::transport::Server::builder()
+                    .add_service(HubServiceServer::new(HubRpcService::new(stores)))
+                    .serve_with_incoming_shutdown(incoming, async {
+                        let _ = shutdown_rx.await;
+                    })
+                    .await
+                    .map_err(|e| HubError::internal_db_error(&e.to_string()))
+            });
+
+            match result {
+                Ok(()) => info!(logger, "Rust RPC server stopped"),
+                Err(e) => error!(logger, "Rust RPC server failed"; "error" => e.message),
+            }
+        });
+
+        Ok(RpcServer {
+            address,
+            shutdown: Mutex::new(Some(shutdown_tx)),
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /** Stop accepting requests, and wait for the in-flight requests to finish */
+    pub fn stop(&self) {
+        if let Some(shutdown) = lock_unpoisoned(&self.shutdown).take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = lock_unpoisoned(&self.thread).take() {
+            let _ = thread.join();
+        }
+    }
+
+    pub fn js_start(mut cx: FunctionContext) -> JsResult<JsBox<Arc<RpcServer>>> {
+        let address = cx.argument::<JsString>(0)?.value(&mut cx);
+        let js_stores = cx.argument::<JsObject>(1)?;
+
+        let mut get_store = |name: &str| -> neon::result::NeonResult<Arc<Store>> {
+            let store = js_stores.get::<JsBox<Arc<Store>>, _, _>(&mut cx, name)?;
+            let store = (**store.borrow()).clone();
+            Ok(store)
+        };
+        let stores = RpcStores {
+            cast_store: get_store("castStore")?,
+            link_store: get_store("linkStore")?,
+            reaction_store: get_store("reactionStore")?,
+            user_data_store: get_store("userDataStore")?,
+            verification_store: get_store("verificationStore")?,
+            username_proof_store: get_store("usernameProofStore")?,
+        };
+
+        match Self::start(&address, stores) {
+            Ok(server) => Ok(cx.boxed(Arc::new(server))),
+            Err(e) => hub_error_to_js_throw(&mut cx, e),
+        }
+    }
+
+    pub fn js_address(mut cx: FunctionContext) -> JsResult<JsString> {
+        let server = cx.argument::<JsBox<Arc<RpcServer>>>(0)?;
+        let address = server.address().to_string();
+
+        Ok(cx.string(address))
+    }
+
+    pub fn js_stop(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let server = (**cx.argument::<JsBox<Arc<RpcServer>>>(0)?.borrow()).clone();
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        // Waiting for in-flight requests can take a while, so don't block the event loop on it
+        std::thread::spawn(move || {
+            let result = catch_panic(|| {
+                server.stop();
+                Ok(())
+            });
+
+            deferred.settle_with(&channel, move |mut cx| match result {
+                Ok(()) => Ok(cx.undefined()),
+                Err(e) => hub_error_to_js_throw(&mut cx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hub_error_to_status() {
+        let status = hub_error_to_status(HubError::invalid_parameter("bad fid"));
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "bad fid");
+        assert_eq!(
+            status.metadata().get("errcode").unwrap(),
+            "bad_request.invalid_param"
+        );
+
+        assert_eq!(
+            hub_error_to_status(HubError::not_found("missing")).code(),
+            Code::NotFound
+        );
+        assert_eq!(
+            hub_error_to_status(HubError::internal_db_error("oops")).code(),
+            Code::Unavailable
+        );
+        assert_eq!(
+            hub_error_to_status(HubError::new(HubErrorCode::Unknown, "oops")).code(),
+            Code::Unknown
+        );
+    }
+
+    #[test]
+    fn test_page_options() {
+        let options = page_options(Some(100_000), Some(vec![]), None);
+        assert_eq!(options.page_size, Some(PAGE_SIZE_MAX));
+        assert_eq!(options.page_token, None);
+        assert!(!options.reverse);
+
+        let options = page_options(Some(10), Some(vec![1, 2]), Some(true));
+        assert_eq!(options.page_size, Some(10));
+        assert_eq!(options.page_token, Some(vec![1, 2]));
+        assert!(options.reverse);
+    }
+
+    #[test]
+    fn test_validate_fid() {
+        assert_eq!(validate_fid(1).unwrap(), 1);
+        assert!(validate_fid(0).is_err());
+        assert!(validate_fid(u64::MAX).is_err());
+    }
+}
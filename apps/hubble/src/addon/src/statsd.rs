@@ -1,3 +1,4 @@
+use crate::panics::{read_unpoisoned, write_unpoisoned};
 use cadence::{
     Counted, CountedExt, Gauged, Histogrammed, NopMetricSink, Setted, StatsdClient, Timed,
 };
@@ -65,7 +66,7 @@ fn create_metrics_client(host: &str, port: u16, prefix: &str) {
     let client = StatsdClient::from_sink(prefix, sink);
 
     // Replace the global client with the new one
-    *STATSD_CLIENT.write().unwrap() = StatsdClientWrapper::new(client);
+    *write_unpoisoned(&STATSD_CLIENT) = StatsdClientWrapper::new(client);
 }
 
 pub fn js_create_statsd_client(mut cx: FunctionContext) -> JsResult<JsUndefined> {
@@ -80,7 +81,7 @@ pub fn js_create_statsd_client(mut cx: FunctionContext) -> JsResult<JsUndefined>
 }
 
 pub fn statsd() -> StatsdClientWrapper {
-    STATSD_CLIENT.read().unwrap().clone()
+    read_unpoisoned(&STATSD_CLIENT).clone()
 }
 
 static STATSD_CLIENT: Lazy<RwLock<StatsdClientWrapper>> = Lazy::new(|| {
@@ -0,0 +1,362 @@
+use super::{
+    deferred_settle_messages, make_fid_key, make_message_primary_key, message_decode,
+    store::Store,
+    utils::{get_page_options, get_store},
+    CastStore, HubError, HubErrorCode, MessagesPage, PageOptions, RootPrefix, PAGE_SIZE_MAX,
+    TS_HASH_LENGTH,
+};
+use crate::panics::{catch_panic, execute};
+use crate::protos::{message_data, CastAddBody};
+use neon::context::{Context, FunctionContext};
+use neon::result::JsResult;
+use neon::types::{JsPromise, JsString};
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+
+/** Terms are truncated to this many bytes, at a char boundary, both when indexed and searched */
+pub const MAX_SEARCH_TERM_LENGTH: usize = 64;
+
+/** Most distinct terms a search query can have */
+pub const MAX_SEARCH_QUERY_TERMS: usize = 8;
+
+/**
+ * Most postings of the driving term a single searchCasts call looks at. A query whose terms rarely
+ * appear together returns a short (or empty) page with a nextPageToken instead of scanning the
+ * whole index.
+ */
+pub const SEARCH_SCAN_LIMIT: usize = 10_000;
+
+/**
+ * Han, Hiragana and Katakana aren't separated by spaces, so each of these characters is a term on
+ * its own. Phrase queries put them back together.
+ */
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF // Hiragana and Katakana
+        | 0x3400..=0x4DBF // CJK Unified Ideographs Extension A
+        | 0x4E00..=0x9FFF // CJK Unified Ideographs
+        | 0xF900..=0xFAFF // CJK Compatibility Ideographs
+        | 0x20000..=0x2FA1F // CJK Unified Ideographs Extension B and later
+    )
+}
+
+fn push_term(terms: &mut Vec<String>, term: &mut String) {
+    if term.is_empty() {
+        return;
+    }
+
+    let mut term = std::mem::take(term);
+    if term.len() > MAX_SEARCH_TERM_LENGTH {
+        let mut end = MAX_SEARCH_TERM_LENGTH;
+        while !term.is_char_boundary(end) {
+            end -= 1;
+        }
+        term.truncate(end);
+    }
+
+    terms.push(term);
+}
+
+/**
+ * Split text into lowercased terms, in order. A term is a run of letters, digits and underscores
+ * in any script, except for CJK characters which are a term each. Everything else separates terms.
+ */
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = vec![];
+    let mut term = String::new();
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            push_term(&mut terms, &mut term);
+            term.push(c);
+            push_term(&mut terms, &mut term);
+        } else if c.is_alphanumeric() || c == '_' {
+            term.extend(c.to_lowercase());
+        } else {
+            push_term(&mut terms, &mut term);
+        }
+    }
+    push_term(&mut terms, &mut term);
+
+    terms
+}
+
+/**
+ * The term a mentioned fid is indexed under. Mentions aren't part of the cast text, and the tokenizer
+ * never produces an '@', so these can't collide with words.
+ */
+pub fn mention_term(fid: u64) -> String {
+    format!("@{}", fid)
+}
+
+/** The distinct terms a CastAdd is indexed under: the words in its text and its mentions */
+pub fn cast_search_terms(body: &CastAddBody) -> BTreeSet<String> {
+    let mut terms: BTreeSet<String> = tokenize(&body.text).into_iter().collect();
+    terms.extend(body.mentions.iter().map(|fid| mention_term(*fid)));
+
+    terms
+}
+
+/** Generates keys used to store or fetch postings in the CastSearchTerms index */
+pub fn make_cast_search_key(
+    term: &str,
+    fid: u32,
+    ts_hash: Option<&[u8; TS_HASH_LENGTH]>,
+) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + 1 + term.len() + TS_HASH_LENGTH + 4);
+
+    key.push(RootPrefix::CastSearchTerms as u8);
+    // Terms are variable length, so prefix them with their length so that "cat" doesn't match "cats"
+    key.push(term.len() as u8);
+    key.extend_from_slice(term.as_bytes());
+    if let Some(ts_hash) = ts_hash {
+        key.extend_from_slice(ts_hash);
+    }
+    if fid > 0 {
+        key.extend_from_slice(&make_fid_key(fid));
+    }
+
+    key
+}
+
+/**
+ * A parsed searchCasts query. Every term has to be in a cast for it to match, and the terms of each
+ * phrase also have to appear next to each other, in order, in its text.
+ */
+#[derive(Debug, PartialEq)]
+pub struct SearchQuery {
+    pub terms: Vec<String>,
+    pub phrases: Vec<Vec<String>>,
+}
+
+impl SearchQuery {
+    /**
+     * Parse a query. Words are ANDed together, "double quoted" words are a phrase, and @<fid>
+     * matches casts that mention that fid.
+     */
+    pub fn parse(query: &str) -> Result<SearchQuery, HubError> {
+        let mut terms = vec![];
+        let mut phrases = vec![];
+
+        // Every other segment between quotes is a phrase. An unclosed quote runs to the end
+        for (i, segment) in query.split('"').enumerate() {
+            if i % 2 == 1 {
+                let phrase = tokenize(segment);
+                terms.extend(phrase.iter().cloned());
+                if phrase.len() > 1 {
+                    phrases.push(phrase);
+                }
+                continue;
+            }
+
+            for word in segment.split_whitespace() {
+                match word.strip_prefix('@').map(|fid| fid.parse::<u64>()) {
+                    Some(Ok(fid)) => terms.push(mention_term(fid)),
+                    _ => terms.extend(tokenize(word)),
+                }
+            }
+        }
+
+        let mut seen = BTreeSet::new();
+        terms.retain(|term| seen.insert(term.clone()));
+
+        if terms.is_empty() {
+            return Err(HubError::invalid_parameter("search query has no terms"));
+        }
+        if terms.len() > MAX_SEARCH_QUERY_TERMS {
+            return Err(HubError::invalid_parameter(&format!(
+                "search query has more than {} terms",
+                MAX_SEARCH_QUERY_TERMS
+            )));
+        }
+
+        Ok(SearchQuery { terms, phrases })
+    }
+
+    fn matches_phrases(&self, text: &str) -> bool {
+        if self.phrases.is_empty() {
+            return true;
+        }
+
+        let text_terms = tokenize(text);
+        self.phrases.iter().all(|phrase| {
+            text_terms
+                .windows(phrase.len())
+                .any(|window| window == phrase.as_slice())
+        })
+    }
+}
+
+impl CastStore {
+    /**
+     * Find CastAdds matching a query, newest first (oldest first with reverse). Postings of the
+     * longest term are walked, since longer terms tend to be rarer, and the other terms are looked
+     * up for each of them. Fails if the store was created without the search index, or it hasn't
+     * been backfilled yet.
+     */
+    pub fn search_casts(
+        store: &Store,
+        query: &str,
+        page_options: &PageOptions,
+    ) -> Result<MessagesPage, HubError> {
+        if !store.has_secondary_index(RootPrefix::CastSearchTerms) {
+            return Err(HubError::invalid_parameter(
+                "cast search index is not enabled on this hub",
+            ));
+        }
+        if !store.is_secondary_index_complete(RootPrefix::CastSearchTerms)? {
+            return Err(HubError::new(
+                HubErrorCode::Unavailable,
+                "cast search index is still being built",
+            ));
+        }
+
+        let query = SearchQuery::parse(query)?;
+        let driving_term = query.terms.iter().max_by_key(|term| term.len()).unwrap();
+        let other_terms: Vec<&String> = query
+            .terms
+            .iter()
+            .filter(|term| *term != driving_term)
+            .collect();
+
+        let prefix = make_cast_search_key(driving_term, 0, None);
+        let iterator_options = PageOptions {
+            page_size: None,
+            page_token: page_options.page_token.clone(),
+            reverse: !page_options.reverse,
+        };
+        let page_size = page_options.page_size.unwrap_or(PAGE_SIZE_MAX);
+
+        let db = store.db();
+        let mut messages_bytes = vec![];
+        let mut scanned = 0;
+        let mut last_key = vec![];
+
+        db.for_each_iterator_by_prefix(&prefix, &iterator_options, |key, _| {
+            let posting = &key[prefix.len()..];
+            if posting.len() != TS_HASH_LENGTH + 4 {
+                return Ok(false);
+            }
+            let ts_hash: [u8; TS_HASH_LENGTH] = posting[..TS_HASH_LENGTH].try_into().unwrap();
+            let fid = u32::from_be_bytes(posting[TS_HASH_LENGTH..].try_into().unwrap());
+
+            scanned += 1;
+
+            let mut matched = true;
+            for term in other_terms.iter() {
+                if db
+                    .get(&make_cast_search_key(term, fid, Some(&ts_hash)))?
+                    .is_none()
+                {
+                    matched = false;
+                    break;
+                }
+            }
+
+            if matched {
+                let primary_key = make_message_primary_key(fid, store.postfix(), Some(&ts_hash));
+                if let Some(message_bytes) = db.get(&primary_key)? {
+                    let text_matches = query.phrases.is_empty()
+                        || match message_decode(&message_bytes)?
+                            .data
+                            .and_then(|data| data.body)
+                        {
+                            Some(message_data::Body::CastAddBody(body)) => {
+                                query.matches_phrases(&body.text)
+                            }
+                            _ => false,
+                        };
+                    if text_matches {
+                        messages_bytes.push(message_bytes);
+                    }
+                }
+            }
+
+            if messages_bytes.len() >= page_size || scanned >= SEARCH_SCAN_LIMIT {
+                last_key = posting.to_vec();
+                return Ok(true); // Stop iterating
+            }
+
+            Ok(false) // Continue iterating
+        })?;
+
+        let next_page_token = if !last_key.is_empty() {
+            Some(last_key)
+        } else {
+            None
+        };
+
+        Ok(MessagesPage {
+            messages_bytes,
+            next_page_token,
+        })
+    }
+
+    pub fn js_search_casts(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let query = cx.argument::<JsString>(0)?.value(&mut cx);
+        let page_options = get_page_options(&mut cx, 1)?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let messages = catch_panic(|| Self::search_casts(&store, &query, &page_options));
+
+            deferred_settle_messages(deferred, &channel, messages);
+        });
+
+        Ok(promise)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Hello, World! gm_frens 123"),
+            vec!["hello", "world", "gm_frens", "123"]
+        );
+        assert_eq!(tokenize("Ünïcödé ΣΊΣΥΦΟΣ"), vec!["ünïcödé", "σίσυφοσ"]);
+        assert_eq!(tokenize("gm 你好!"), vec!["gm", "你", "好"]);
+        assert_eq!(tokenize(" ... "), Vec::<String>::new());
+
+        let long = "a".repeat(MAX_SEARCH_TERM_LENGTH - 1) + "é";
+        assert_eq!(
+            tokenize(&long),
+            vec!["a".repeat(MAX_SEARCH_TERM_LENGTH - 1)]
+        );
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = SearchQuery::parse("Farcaster \"good MORNING\" @3 @dwr").unwrap();
+        assert_eq!(
+            query.terms,
+            vec!["farcaster", "good", "morning", "@3", "dwr"]
+        );
+        assert_eq!(query.phrases, vec![vec!["good", "morning"]]);
+
+        assert!(query.matches_phrases("Good morning, farcaster"));
+        assert!(!query.matches_phrases("morning good farcaster"));
+
+        assert!(SearchQuery::parse("  !!! \"\" ").is_err());
+        assert!(SearchQuery::parse("a b c d e f g h i").is_err());
+    }
+
+    #[test]
+    fn test_search_key() {
+        // A term's postings don't include the postings of longer terms it's a prefix of
+        let cat = make_cast_search_key("cat", 0, None);
+        let cats = make_cast_search_key("cats", 1, Some(&[0; TS_HASH_LENGTH]));
+        assert!(!cats.starts_with(&cat));
+        assert_eq!(
+            cat,
+            vec![RootPrefix::CastSearchTerms as u8, 3, b'c', b'a', b't']
+        );
+    }
+}
@@ -1,28 +1,183 @@
 use super::{
-    bytes_compare, deferred_settle_messages, hub_error_to_js_throw, make_cast_id_key, make_fid_key,
-    make_user_key, message,
+    bytes_compare, cast_search_terms, deferred_settle_messages, get_count, hub_error_to_js_throw,
+    make_cast_id_key, make_cast_search_key, make_fid_key, make_replies_count_key,
+    make_time_range_bounds, make_user_key, message,
     store::{Store, StoreDef},
-    utils::{encode_messages_to_js_object, get_page_options, get_store},
-    HubError, MessagesPage, PageOptions, RootPrefix, StoreEventHandler, UserPostfix, HASH_LENGTH,
-    PAGE_SIZE_MAX, TRUE_VALUE, TS_HASH_LENGTH,
+    utils::{encode_messages_to_js_object, get_page_options, get_store, get_time_range},
+    CounterType, HubError, HubErrorCode, MessagesPage, PageOptions, RootPrefix, StoreEventHandler,
+    UserPostfix, HASH_LENGTH, PAGE_SIZE_MAX, TRUE_VALUE, TS_HASH_LENGTH,
 };
 use crate::{
     db::{RocksDB, RocksDbTransactionBatch},
     protos::{self, Message, MessageType},
 };
 use crate::{
+    panics::{catch_panic, execute},
     protos::{message_data, CastRemoveBody},
-    THREAD_POOL,
 };
 use neon::{
     context::{Context, FunctionContext},
+    object::Object,
     result::JsResult,
-    types::{buffer::TypedArray, JsBox, JsBuffer, JsNumber, JsPromise, JsString},
+    types::{
+        buffer::TypedArray, JsArray, JsBoolean, JsBox, JsBuffer, JsNumber, JsObject, JsPromise,
+        JsString,
+    },
 };
 use prost::Message as _;
 use std::{borrow::Borrow, convert::TryInto, sync::Arc};
 
 type Parent = protos::cast_add_body::Parent;
+type Embed = protos::embed::Embed;
+
+/** Optional CastStore indices. They are off unless a hub turns them on */
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CastIndexOptions {
+    /** Full-text search over cast text, used by searchCasts */
+    pub search: bool,
+    /** Every CastAdd in time order, used by getRecentCasts */
+    pub feed: bool,
+}
+
+/** Embedded urls are indexed by this many bytes of the blake3 hash of their normalized form */
+pub const EMBED_URL_HASH_LENGTH: usize = 20;
+
+/** The first byte of an embed key, since urls and cast ids are indexed under the same prefix */
+#[repr(u8)]
+enum EmbedKeyType {
+    Url = 1,
+    CastId = 2,
+}
+
+/**
+ * Normalize an embedded url so that trivially different spellings of it are indexed together: the
+ * scheme and host are lowercased, default ports, trailing slashes and the fragment are dropped.
+ * The query string is kept, since it can change what the url points to.
+ */
+pub fn normalize_embed_url(url: &str) -> String {
+    let url = url.trim();
+    let url = url.split('#').next().unwrap_or_default();
+
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (scheme.to_ascii_lowercase(), rest),
+        None => return url.to_string(),
+    };
+
+    let authority_end = rest.find(|c| c == '/' || c == '?').unwrap_or(rest.len());
+    let mut authority = rest[..authority_end].to_ascii_lowercase();
+    let default_port = match scheme.as_str() {
+        "http" => Some(":80"),
+        "https" => Some(":443"),
+        _ => None,
+    };
+    if let Some(port) = default_port {
+        if authority.ends_with(port) {
+            authority.truncate(authority.len() - port.len());
+        }
+    }
+
+    let (path, query) = match rest[authority_end..].split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (&rest[authority_end..], ""),
+    };
+
+    let mut normalized = format!("{}://{}{}", scheme, authority, path.trim_end_matches('/'));
+    if !query.is_empty() {
+        normalized.push('?');
+        normalized.push_str(query);
+    }
+
+    normalized
+}
+
+/** Threads deeper than this are cut off, and roots further up than this aren't followed */
+pub const CAST_THREAD_MAX_DEPTH: u32 = 1_000;
+
+/**
+ * A thread of CastAdds in tree order: each cast is followed by its replies, oldest first, and
+ * `depths[i]` is how many replies below the root `messages_bytes[i]` is. `truncated` is set if
+ * the thread had more casts than were returned, and `next_page_token` continues it from there.
+ */
+pub struct CastThreadPage {
+    pub messages_bytes: Vec<Vec<u8>>,
+    pub depths: Vec<u32>,
+    pub truncated: bool,
+    pub next_page_token: Option<Vec<u8>>,
+}
+
+/**
+ * One level of the depth first walk of a thread: the replies to `parent` that come after the
+ * casts-by-parent index suffix `after`. Replies at this level are `depth` below the root.
+ */
+struct CastThreadFrame {
+    parent: protos::CastId,
+    after: Option<Vec<u8>>,
+    depth: u32,
+}
+
+/** The casts-by-parent index suffix after the parent: the reply's tsHash and fid */
+const CAST_THREAD_REPLY_SUFFIX_LENGTH: usize = TS_HASH_LENGTH + 4;
+
+/**
+ * A cast thread page token is the walk's stack, bottom first. Each frame is the parent's fid and
+ * hash, the depth, and a flag byte followed by the index suffix of the last visited reply, if any.
+ */
+fn encode_cast_thread_token(stack: &[CastThreadFrame]) -> Vec<u8> {
+    let mut token = vec![];
+    for frame in stack {
+        token.extend_from_slice(&make_cast_id_key(&frame.parent));
+        token.extend_from_slice(&frame.depth.to_be_bytes());
+        match &frame.after {
+            Some(after) => {
+                token.push(1);
+                token.extend_from_slice(after);
+            }
+            None => token.push(0),
+        }
+    }
+    token
+}
+
+fn decode_cast_thread_token(token: &[u8]) -> Result<Vec<CastThreadFrame>, HubError> {
+    let invalid = || HubError::invalid_parameter("invalid cast thread page token");
+
+    let mut stack = vec![];
+    let mut rest = token;
+    while !rest.is_empty() {
+        if rest.len() < 4 + HASH_LENGTH + 4 + 1 {
+            return Err(invalid());
+        }
+        let fid = u32::from_be_bytes(rest[0..4].try_into().unwrap());
+        let hash = rest[4..4 + HASH_LENGTH].to_vec();
+        let depth = u32::from_be_bytes(rest[4 + HASH_LENGTH..8 + HASH_LENGTH].try_into().unwrap());
+        let has_after = rest[8 + HASH_LENGTH];
+        rest = &rest[9 + HASH_LENGTH..];
+
+        let after = match has_after {
+            0 => None,
+            1 if rest.len() >= CAST_THREAD_REPLY_SUFFIX_LENGTH => {
+                let after = rest[..CAST_THREAD_REPLY_SUFFIX_LENGTH].to_vec();
+                rest = &rest[CAST_THREAD_REPLY_SUFFIX_LENGTH..];
+                Some(after)
+            }
+            _ => return Err(invalid()),
+        };
+
+        stack.push(CastThreadFrame {
+            parent: protos::CastId {
+                fid: fid as u64,
+                hash,
+            },
+            after,
+            depth,
+        });
+    }
+
+    if stack.is_empty() {
+        return Err(invalid());
+    }
+    Ok(stack)
+}
 
 /**
  * CastStore persists Cast messages in RocksDB using a two-phase CRDT set to guarantee eventual
@@ -51,9 +206,13 @@ type Parent = protos::cast_add_body::Parent;
  * 3. fid:set:targetTsHash -> fid:tsHash (Remove Set Index)
  * 4. parentFid:parentTsHash:fid:tsHash -> fid:tsHash (Child Set Index)
  * 5. mentionFid:fid:tsHash -> fid:tsHash (Mentions Set Index)
+ * 6. term:tsHash:fid -> fid:tsHash (Search Index, only with CastIndexOptions.search)
+ * 7. embedKey:tsHash:fid -> fid:tsHash (Embeds Set Index)
+ * 8. tsHash:fid -> fid:tsHash (Feed Index, only with CastIndexOptions.feed)
  */
 pub struct CastStoreDef {
     prune_size_limit: u32,
+    index_options: CastIndexOptions,
 }
 
 impl StoreDef for CastStoreDef {
@@ -149,6 +308,19 @@ impl StoreDef for CastStoreDef {
                 txn.put(by_mention_key, vec![TRUE_VALUE]);
             }
         }
+        for by_embed_key in self.by_embed_secondary_index_keys(ts_hash, message) {
+            txn.put(by_embed_key, vec![TRUE_VALUE]);
+        }
+        if self.index_options.search {
+            for search_key in self.search_secondary_index_keys(ts_hash, message) {
+                txn.put(search_key, vec![TRUE_VALUE]);
+            }
+        }
+        if self.index_options.feed {
+            if let Some(by_time_key) = self.by_time_secondary_index_key(ts_hash, message) {
+                txn.put(by_time_key, vec![TRUE_VALUE]);
+            }
+        }
         Ok(())
     }
 
@@ -170,6 +342,19 @@ impl StoreDef for CastStoreDef {
             }
         }
 
+        for by_embed_key in self.by_embed_secondary_index_keys(ts_hash, message) {
+            txn.delete(by_embed_key);
+        }
+
+        // The optional indices are deleted even if they are off, so that turning them off doesn't
+        // leave entries behind for casts that are removed in the meantime
+        for search_key in self.search_secondary_index_keys(ts_hash, message) {
+            txn.delete(search_key);
+        }
+        if let Some(by_time_key) = self.by_time_secondary_index_key(ts_hash, message) {
+            txn.delete(by_time_key);
+        }
+
         Ok(())
     }
 
@@ -181,6 +366,50 @@ impl StoreDef for CastStoreDef {
         Ok(())
     }
 
+    fn secondary_index_prefixes(&self) -> Vec<u8> {
+        let mut prefixes = vec![
+            RootPrefix::CastsByParent as u8,
+            RootPrefix::CastsByMention as u8,
+            RootPrefix::CastsByEmbed as u8,
+        ];
+        if self.index_options.search {
+            prefixes.push(RootPrefix::CastSearchTerms as u8);
+        }
+        if self.index_options.feed {
+            prefixes.push(RootPrefix::CastsByTime as u8);
+        }
+
+        prefixes
+    }
+
+    fn optional_secondary_index_prefixes(&self) -> Vec<u8> {
+        vec![
+            RootPrefix::CastSearchTerms as u8,
+            RootPrefix::CastsByTime as u8,
+        ]
+    }
+
+    fn counter_types(&self) -> Vec<CounterType> {
+        vec![CounterType::RepliesByParent]
+    }
+
+    fn update_counters(
+        &self,
+        txn: &mut RocksDbTransactionBatch,
+        message: &Message,
+        delta: i64,
+    ) -> Result<(), HubError> {
+        if let Some(message_data::Body::CastAddBody(protos::CastAddBody {
+            parent: Some(Parent::ParentCastId(parent_cast_id)),
+            ..
+        })) = &message.data.as_ref().unwrap().body
+        {
+            txn.increment(make_replies_count_key(parent_cast_id), delta);
+        }
+
+        Ok(())
+    }
+
     fn make_add_key(&self, message: &protos::Message) -> Result<Vec<u8>, HubError> {
         let hash = match message.data.as_ref().unwrap().body.as_ref() {
             Some(message_data::Body::CastAddBody(_)) => message.hash.as_ref(),
@@ -188,10 +417,10 @@ impl StoreDef for CastStoreDef {
                 cast_remove_body.target_hash.as_ref()
             }
             _ => {
-                return Err(HubError {
-                    code: "bad_request.validation_failure".to_string(),
-                    message: "Invalid cast body for add key".to_string(),
-                })
+                return Err(HubError::new(
+                    HubErrorCode::ValidationFailure,
+                    "Invalid cast body for add key",
+                ))
             }
         };
         Ok(Self::make_cast_adds_key(
@@ -207,10 +436,10 @@ impl StoreDef for CastStoreDef {
                 cast_remove_body.target_hash.as_ref()
             }
             _ => {
-                return Err(HubError {
-                    code: "bad_request.validation_failure".to_string(),
-                    message: "Invalid cast body for remove key".to_string(),
-                })
+                return Err(HubError::new(
+                    HubErrorCode::ValidationFailure,
+                    "Invalid cast body for remove key",
+                ))
             }
         };
 
@@ -221,17 +450,17 @@ impl StoreDef for CastStoreDef {
     }
 
     fn make_compact_state_add_key(&self, _message: &Message) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "Cast Store doesn't support compact state".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            "Cast Store doesn't support compact state",
+        ))
     }
 
     fn make_compact_state_prefix(&self, _fid: u32) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "Cast Store doesn't support compact state".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            "Cast Store doesn't support compact state",
+        ))
     }
 
     fn get_prune_size_limit(&self) -> u32 {
@@ -249,15 +478,15 @@ impl CastStoreDef {
         let cast_body = match message.data.as_ref().unwrap().body.as_ref().unwrap() {
             message_data::Body::CastAddBody(cast_add_body) => cast_add_body,
             message_data::Body::CastRemoveBody(_) => return Ok(None),
-            _ => Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Invalid cast body".to_string(),
-            })?,
+            _ => Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "Invalid cast body",
+            ))?,
         };
-        let parent = cast_body.parent.as_ref().ok_or(HubError {
-            code: "bad_request.validation_failure".to_string(),
-            message: "Invalid cast body".to_string(),
-        })?;
+        let parent = cast_body.parent.as_ref().ok_or(HubError::new(
+            HubErrorCode::ValidationFailure,
+            "Invalid cast body",
+        ))?;
 
         let by_parent_key = Self::make_cast_by_parent_key(
             parent,
@@ -304,10 +533,10 @@ impl CastStoreDef {
         let cast_body = match message.data.as_ref().unwrap().body.as_ref().unwrap() {
             message_data::Body::CastAddBody(cast_add_body) => cast_add_body,
             message_data::Body::CastRemoveBody(_) => return Ok(None),
-            _ => Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Invalid cast body".to_string(),
-            })?,
+            _ => Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "Invalid cast body",
+            ))?,
         };
         // Create a vector of mention keys
         if cast_body.mentions.is_empty() {
@@ -325,6 +554,115 @@ impl CastStoreDef {
         return Ok(Some(result));
     }
 
+    fn by_embed_secondary_index_keys(
+        &self,
+        ts_hash: &[u8; TS_HASH_LENGTH],
+        message: &protos::Message,
+    ) -> Vec<Vec<u8>> {
+        let data = message.data.as_ref().unwrap();
+        let cast_add_body = match &data.body {
+            Some(message_data::Body::CastAddBody(cast_add_body)) => cast_add_body,
+            _ => return vec![],
+        };
+
+        let mut keys: Vec<Vec<u8>> = cast_add_body
+            .embeds
+            .iter()
+            .filter_map(|embed| embed.embed.as_ref())
+            .map(|embed| Self::make_cast_by_embed_key(embed, data.fid as u32, Some(ts_hash)))
+            .collect();
+        // The same url can be embedded twice, or spelled two ways that normalize the same
+        keys.sort();
+        keys.dedup();
+
+        keys
+    }
+
+    pub fn make_embed_key(embed: &Embed) -> Vec<u8> {
+        match embed {
+            Embed::Url(url) => {
+                let hash = blake3::hash(normalize_embed_url(url).as_bytes());
+
+                let mut key = Vec::with_capacity(1 + EMBED_URL_HASH_LENGTH);
+                key.push(EmbedKeyType::Url as u8);
+                key.extend_from_slice(&hash.as_bytes()[..EMBED_URL_HASH_LENGTH]);
+                key
+            }
+            Embed::CastId(cast_id) => {
+                let mut key = Vec::with_capacity(1 + 4 + HASH_LENGTH);
+                key.push(EmbedKeyType::CastId as u8);
+                key.extend_from_slice(&make_cast_id_key(cast_id));
+                key
+            }
+        }
+    }
+
+    // Generates unique keys used to store or fetch CastAdd messages in the byEmbed index
+    pub fn make_cast_by_embed_key(
+        embed: &Embed,
+        fid: u32,
+        ts_hash: Option<&[u8; TS_HASH_LENGTH]>,
+    ) -> Vec<u8> {
+        let mut key = Vec::with_capacity(1 + 1 + 24 + TS_HASH_LENGTH + 4);
+
+        key.push(RootPrefix::CastsByEmbed as u8); // CastsByEmbed prefix, 1 byte
+        key.extend_from_slice(&Self::make_embed_key(embed));
+        if let Some(ts_hash) = ts_hash {
+            key.extend_from_slice(ts_hash);
+        }
+        if fid > 0 {
+            key.extend_from_slice(&make_fid_key(fid));
+        }
+
+        key
+    }
+
+    fn by_time_secondary_index_key(
+        &self,
+        ts_hash: &[u8; TS_HASH_LENGTH],
+        message: &protos::Message,
+    ) -> Option<Vec<u8>> {
+        let data = message.data.as_ref().unwrap();
+        match &data.body {
+            Some(message_data::Body::CastAddBody(_)) => {
+                Some(Self::make_cast_by_time_key(data.fid as u32, Some(ts_hash)))
+            }
+            _ => None,
+        }
+    }
+
+    // Generates unique keys used to store or fetch CastAdd messages in the byTime (feed) index
+    pub fn make_cast_by_time_key(fid: u32, ts_hash: Option<&[u8; TS_HASH_LENGTH]>) -> Vec<u8> {
+        let mut key = Vec::with_capacity(1 + TS_HASH_LENGTH + 4);
+
+        key.push(RootPrefix::CastsByTime as u8); // CastsByTime prefix, 1 byte
+        if let Some(ts_hash) = ts_hash {
+            key.extend_from_slice(ts_hash);
+        }
+        if fid > 0 {
+            key.extend_from_slice(&make_fid_key(fid));
+        }
+
+        key
+    }
+
+    fn search_secondary_index_keys(
+        &self,
+        ts_hash: &[u8; TS_HASH_LENGTH],
+        message: &protos::Message,
+    ) -> Vec<Vec<u8>> {
+        let data = message.data.as_ref().unwrap();
+        match &data.body {
+            Some(message_data::Body::CastAddBody(cast_add_body)) => {
+                cast_search_terms(cast_add_body)
+                    .iter()
+                    .map(|term| make_cast_search_key(term, data.fid as u32, Some(ts_hash)))
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+
     // Generates unique keys used to store or fetch CastAdd messages in the adds set index
     pub fn make_cast_adds_key(fid: u32, hash: &Vec<u8>) -> Vec<u8> {
         let mut key = Vec::with_capacity(5 + 1 + 20);
@@ -377,11 +715,15 @@ impl CastStore {
         db: Arc<RocksDB>,
         store_event_handler: Arc<StoreEventHandler>,
         prune_size_limit: u32,
+        index_options: CastIndexOptions,
     ) -> Store {
         Store::new_with_store_def(
             db,
             store_event_handler,
-            Box::new(CastStoreDef { prune_size_limit }),
+            Box::new(CastStoreDef {
+                prune_size_limit,
+                index_options,
+            }),
         )
     }
 
@@ -413,16 +755,16 @@ impl CastStore {
 
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let hash_buffer = cx.argument::<JsBuffer>(1)?;
         let hash_bytes = hash_buffer.as_slice(&cx);
 
         let result = match Self::get_cast_add(&store, fid, hash_bytes.to_vec()) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{} for {}",
-                "not_found", "castAddMessage not found", fid
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found(&format!("castAddMessage not found for {}", fid)).with_fid(fid),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -459,16 +801,17 @@ impl CastStore {
     pub fn js_get_cast_remove(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let hash_buffer = cx.argument::<JsBuffer>(1)?;
         let hash_bytes = hash_buffer.as_slice(&cx).to_vec();
 
         let result = match Self::get_cast_remove(&store, fid, hash_bytes) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{} for {}",
-                "not_found", "CastRemoveMessage not found", fid
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found(&format!("CastRemoveMessage not found for {}", fid))
+                    .with_fid(fid),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -487,8 +830,16 @@ impl CastStore {
         store: &Store,
         fid: u32,
         page_options: &PageOptions,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
     ) -> Result<MessagesPage, HubError> {
-        store.get_adds_by_fid::<fn(&protos::Message) -> bool>(fid, page_options, None)
+        store.get_adds_by_fid::<fn(&protos::Message) -> bool>(
+            fid,
+            start_time,
+            stop_time,
+            page_options,
+            None,
+        )
     }
 
     pub fn js_create_cast_store(mut cx: FunctionContext) -> JsResult<JsBox<Arc<Store>>> {
@@ -504,24 +855,43 @@ impl CastStore {
             .argument::<JsNumber>(2)
             .map(|n| n.value(&mut cx) as u32)?;
 
+        // The optional indices are read from an options object, e.g. { searchIndex: true }
+        let mut index_options = CastIndexOptions::default();
+        if let Some(js_options) = cx.argument_opt(3) {
+            if let Ok(js_options) = js_options.downcast::<JsObject, _>(&mut cx) {
+                if let Some(search) =
+                    js_options.get_opt::<JsBoolean, _, _>(&mut cx, "searchIndex")?
+                {
+                    index_options.search = search.value(&mut cx);
+                }
+                if let Some(feed) = js_options.get_opt::<JsBoolean, _, _>(&mut cx, "feedIndex")? {
+                    index_options.feed = feed.value(&mut cx);
+                }
+            }
+        }
+
         Ok(cx.boxed(Arc::new(Self::new(
             db,
             store_event_handler,
             prune_size_limit,
+            index_options,
         ))))
     }
 
     pub fn js_get_cast_adds_by_fid(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let page_options = get_page_options(&mut cx, 1)?;
+        let (start_time, stop_time) = get_time_range(&mut cx, 2);
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let messages = Self::get_cast_adds_by_fid(&store, fid, &page_options);
+        execute(move || {
+            let messages = catch_panic(|| {
+                Self::get_cast_adds_by_fid(&store, fid, &page_options, start_time, stop_time)
+            });
 
             deferred_settle_messages(deferred, &channel, messages);
         });
@@ -533,21 +903,32 @@ impl CastStore {
         store: &Store,
         fid: u32,
         page_options: &PageOptions,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
     ) -> Result<MessagesPage, HubError> {
-        store.get_removes_by_fid::<fn(&protos::Message) -> bool>(fid, page_options, None)
+        store.get_removes_by_fid::<fn(&protos::Message) -> bool>(
+            fid,
+            start_time,
+            stop_time,
+            page_options,
+            None,
+        )
     }
 
     pub fn js_get_cast_removes_by_fid(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let page_options = get_page_options(&mut cx, 1)?;
+        let (start_time, stop_time) = get_time_range(&mut cx, 2);
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let messages = Self::get_cast_removes_by_fid(&store, fid, &page_options);
+        execute(move || {
+            let messages = catch_panic(|| {
+                Self::get_cast_removes_by_fid(&store, fid, &page_options, start_time, stop_time)
+            });
             deferred_settle_messages(deferred, &channel, messages);
         });
 
@@ -608,7 +989,12 @@ impl CastStore {
         let parent_cast_id = if parent_cast_id_bytes.len() > 0 {
             match protos::CastId::decode(parent_cast_id_bytes) {
                 Ok(cast_id) => Some(cast_id),
-                Err(e) => return cx.throw_error(e.to_string()),
+                Err(e) => {
+                    return hub_error_to_js_throw(
+                        &mut cx,
+                        HubError::new(HubErrorCode::ParseFailure, e.to_string()),
+                    )
+                }
             }
         } else {
             None
@@ -618,7 +1004,10 @@ impl CastStore {
 
         // We need at least one of target_cast_id or target_url
         if parent_cast_id.is_none() && parent_url.is_empty() {
-            return cx.throw_error("parent_cast_id or parent_url is required");
+            return hub_error_to_js_throw(
+                &mut cx,
+                HubError::invalid_parameter("parent_cast_id or parent_url is required"),
+            );
         }
 
         let target = if parent_cast_id.is_some() {
@@ -643,19 +1032,60 @@ impl CastStore {
         Ok(promise)
     }
 
-    pub fn get_casts_by_mention(
+    /**
+     * Find the root of the thread a cast is in, by following `parent_cast_id` upward until a cast
+     * has no parent cast, or its parent isn't in this store (e.g. it was removed or pruned).
+     */
+    pub fn get_cast_thread_root(
         store: &Store,
-        mention: u32,
-        page_options: &PageOptions,
-    ) -> Result<MessagesPage, HubError> {
-        let prefix = CastStoreDef::make_cast_by_mention_key(mention, 0, None);
+        cast_id: &protos::CastId,
+    ) -> Result<protos::Message, HubError> {
+        let mut cast = Self::get_cast_add(store, cast_id.fid as u32, cast_id.hash.clone())?
+            .ok_or(HubError::not_found("castAddMessage not found"))?;
+
+        for _ in 0..CAST_THREAD_MAX_DEPTH {
+            let parent_cast_id = match &cast.data.as_ref().unwrap().body {
+                Some(message_data::Body::CastAddBody(protos::CastAddBody {
+                    parent: Some(Parent::ParentCastId(parent_cast_id)),
+                    ..
+                })) => parent_cast_id,
+                _ => break,
+            };
+
+            match Self::get_cast_add(
+                store,
+                parent_cast_id.fid as u32,
+                parent_cast_id.hash.clone(),
+            )? {
+                Some(parent) => cast = parent,
+                None => break,
+            }
+        }
 
-        let mut message_keys = vec![];
-        let mut last_key = vec![];
+        Ok(cast)
+    }
+
+    /**
+     * The next direct reply to a cast after the casts-by-parent index suffix `after`, oldest first.
+     * Returns the reply's index suffix, to continue from, and its primary key.
+     */
+    fn get_next_cast_reply_key(
+        store: &Store,
+        cast_id: &protos::CastId,
+        after: Option<&[u8]>,
+    ) -> Result<Option<(Vec<u8>, Vec<u8>)>, HubError> {
+        let prefix =
+            CastStoreDef::make_cast_by_parent_key(&Parent::ParentCastId(cast_id.clone()), 0, None);
+        let page_options = PageOptions {
+            page_size: None,
+            page_token: after.map(|after| after.to_vec()),
+            reverse: false,
+        };
 
+        let mut reply = None;
         store
             .db()
-            .for_each_iterator_by_prefix(&prefix, page_options, |key, _| {
+            .for_each_iterator_by_prefix(&prefix, &page_options, |key, _| {
                 let ts_hash_offset = prefix.len();
                 let fid_offset = ts_hash_offset + TS_HASH_LENGTH;
 
@@ -663,23 +1093,296 @@ impl CastStore {
                 let ts_hash = key[ts_hash_offset..ts_hash_offset + TS_HASH_LENGTH]
                     .try_into()
                     .unwrap();
-                let message_primary_key = crate::store::message::make_message_primary_key(
-                    fid,
-                    store.postfix(),
-                    Some(&ts_hash),
-                );
+                reply = Some((
+                    key[ts_hash_offset..].to_vec(),
+                    message::make_message_primary_key(fid, store.postfix(), Some(&ts_hash)),
+                ));
 
-                message_keys.push(message_primary_key.to_vec());
-                if message_keys.len() >= page_options.page_size.unwrap_or(PAGE_SIZE_MAX) {
-                    last_key = key.to_vec();
-                    return Ok(true); // Stop iterating
+                Ok(true) // Only the first reply is needed
+            })?;
+
+        Ok(reply)
+    }
+
+    /**
+     * Get the thread a cast is in, starting from its root. The casts-by-parent index is walked
+     * depth first, so the thread is returned in the order it is rendered. Replies deeper than
+     * `max_depth` below the root aren't returned, and at most `max_nodes` casts are returned.
+     *
+     * If the thread has more casts, the page's `next_page_token` holds the walk's stack, and
+     * passing it back in continues the walk where it stopped, without returning the root again.
+     */
+    pub fn get_cast_thread(
+        store: &Store,
+        cast_id: &protos::CastId,
+        max_depth: u32,
+        max_nodes: usize,
+        page_token: Option<&[u8]>,
+    ) -> Result<CastThreadPage, HubError> {
+        let max_depth = max_depth.min(CAST_THREAD_MAX_DEPTH);
+        let max_nodes = max_nodes.min(PAGE_SIZE_MAX);
+
+        let mut page = CastThreadPage {
+            messages_bytes: vec![],
+            depths: vec![],
+            truncated: false,
+            next_page_token: None,
+        };
+
+        let mut stack = match page_token {
+            Some(page_token) => decode_cast_thread_token(page_token)?,
+            None => {
+                let root = Self::get_cast_thread_root(store, cast_id)?;
+                page.messages_bytes.push(root.encode_to_vec());
+                page.depths.push(0);
+
+                let mut stack = vec![];
+                if max_depth > 0 {
+                    stack.push(CastThreadFrame {
+                        parent: protos::CastId {
+                            fid: root.data.as_ref().unwrap().fid,
+                            hash: root.hash,
+                        },
+                        after: None,
+                        depth: 1,
+                    });
                 }
+                stack
+            }
+        };
 
-                Ok(false) // Continue iterating
-            })?;
+        // Each step visits the next reply at the top of the stack, or pops the top once all of its
+        // replies are visited. Replies are only read from the DB as they're visited
+        while let Some(frame) = stack.last_mut() {
+            let (reply_suffix, reply_key) = match Self::get_next_cast_reply_key(
+                store,
+                &frame.parent,
+                frame.after.as_deref(),
+            )? {
+                Some(reply) => reply,
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            };
 
-        let messages_bytes =
-            message::get_many_messages_as_bytes(store.db().borrow(), message_keys)?;
+            // Stop before the reply, so the token continues with it
+            if page.messages_bytes.len() >= max_nodes {
+                page.truncated = true;
+                page.next_page_token = Some(encode_cast_thread_token(&stack));
+                break;
+            }
+
+            frame.after = Some(reply_suffix);
+            let depth = frame.depth;
+
+            let message_bytes = match store.db().get(&reply_key)? {
+                Some(bytes) => bytes,
+                None => continue,
+            };
+            let message = message::message_decode(&message_bytes)?;
+
+            page.messages_bytes.push(message_bytes);
+            page.depths.push(depth);
+
+            if depth < max_depth {
+                stack.push(CastThreadFrame {
+                    parent: protos::CastId {
+                        fid: message.data.as_ref().unwrap().fid,
+                        hash: message.hash,
+                    },
+                    after: None,
+                    depth: depth + 1,
+                });
+            }
+        }
+
+        Ok(page)
+    }
+
+    pub fn js_get_cast_thread(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let cast_id_buffer = cx.argument::<JsBuffer>(0)?;
+        let cast_id = match protos::CastId::decode(cast_id_buffer.as_slice(&cx)) {
+            Ok(cast_id) => cast_id,
+            Err(e) => {
+                return hub_error_to_js_throw(
+                    &mut cx,
+                    HubError::new(HubErrorCode::ParseFailure, e.to_string()),
+                )
+            }
+        };
+        let max_depth = cx.argument::<JsNumber>(1)?.value(&mut cx) as u32;
+        let max_nodes = cx.argument::<JsNumber>(2)?.value(&mut cx) as usize;
+        let page_token = match cx.argument_opt(3) {
+            Some(arg) => match arg.downcast::<JsBuffer, _>(&mut cx) {
+                Ok(buffer) => Some(buffer.as_slice(&cx).to_vec()),
+                Err(_) => None,
+            },
+            None => None,
+        };
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let thread = catch_panic(|| {
+                Self::get_cast_thread(
+                    &store,
+                    &cast_id,
+                    max_depth,
+                    max_nodes,
+                    page_token.as_deref(),
+                )
+            });
+
+            deferred.settle_with(&channel, move |mut cx| {
+                let thread = match thread {
+                    Ok(thread) => thread,
+                    Err(e) => return hub_error_to_js_throw(&mut cx, e),
+                };
+
+                let js_messages = JsArray::new(&mut cx, thread.messages_bytes.len());
+                for (i, message_bytes) in thread.messages_bytes.iter().enumerate() {
+                    let mut js_buffer = cx.buffer(message_bytes.len())?;
+                    js_buffer
+                        .as_mut_slice(&mut cx)
+                        .copy_from_slice(message_bytes);
+                    js_messages.set(&mut cx, i as u32, js_buffer)?;
+                }
+
+                let js_depths = JsArray::new(&mut cx, thread.depths.len());
+                for (i, depth) in thread.depths.iter().enumerate() {
+                    let js_depth = cx.number(*depth);
+                    js_depths.set(&mut cx, i as u32, js_depth)?;
+                }
+
+                let js_object = JsObject::new(&mut cx);
+                js_object.set(&mut cx, "messageBytes", js_messages)?;
+                js_object.set(&mut cx, "depths", js_depths)?;
+                let js_truncated = cx.boolean(thread.truncated);
+                js_object.set(&mut cx, "truncated", js_truncated)?;
+                if let Some(next_page_token) = thread.next_page_token {
+                    let mut js_token = cx.buffer(next_page_token.len())?;
+                    js_token
+                        .as_mut_slice(&mut cx)
+                        .copy_from_slice(&next_page_token);
+                    js_object.set(&mut cx, "nextPageToken", js_token)?;
+                }
+
+                Ok(js_object)
+            });
+        });
+
+        Ok(promise)
+    }
+
+    /** The number of CastAdds that reply directly to a cast */
+    pub fn get_reply_count(store: &Store, cast_id: &protos::CastId) -> Result<u64, HubError> {
+        get_count(&store.db(), &make_replies_count_key(cast_id))
+    }
+
+    pub fn js_get_reply_count(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let cast_id_buffer = cx.argument::<JsBuffer>(0)?;
+        let cast_id = match protos::CastId::decode(cast_id_buffer.as_slice(&cx)) {
+            Ok(cast_id) => cast_id,
+            Err(e) => {
+                return hub_error_to_js_throw(
+                    &mut cx,
+                    HubError::new(HubErrorCode::ParseFailure, e.to_string()),
+                )
+            }
+        };
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let count = catch_panic(|| Self::get_reply_count(&store, &cast_id));
+
+            deferred.settle_with(&channel, move |mut cx| match count {
+                Ok(count) => Ok(cx.number(count as f64)),
+                Err(e) => hub_error_to_js_throw(&mut cx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+
+    /**
+     * Get the CastAdds under a secondary index prefix whose keys end in <tsHash>:<fid>, in
+     * tsHash order. The page token is the rest of the last key after the prefix.
+     */
+    fn get_casts_by_index_prefix(
+        store: &Store,
+        prefix: &[u8],
+        page_options: &PageOptions,
+    ) -> Result<MessagesPage, HubError> {
+        Self::get_casts_by_index_prefix_in_time_range(store, prefix, None, None, page_options)
+    }
+
+    /**
+     * Same as get_casts_by_index_prefix, but only for casts with timestamps in
+     * [start_time, stop_time]. Since the tsHash comes right after the prefix, the time range is
+     * applied as iterator bounds.
+     */
+    fn get_casts_by_index_prefix_in_time_range(
+        store: &Store,
+        prefix: &[u8],
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
+        page_options: &PageOptions,
+    ) -> Result<MessagesPage, HubError> {
+        let mut message_keys = vec![];
+        let mut last_key = vec![];
+
+        let mut visit = |key: &[u8], _: &[u8]| {
+            // Keys under a variable length prefix, like a parent url, can belong to a longer
+            // prefix that starts with this one
+            if key.len() != prefix.len() + TS_HASH_LENGTH + 4 {
+                return Ok(false);
+            }
+
+            let ts_hash_offset = prefix.len();
+            let fid_offset = ts_hash_offset + TS_HASH_LENGTH;
+
+            let fid = u32::from_be_bytes(key[fid_offset..fid_offset + 4].try_into().unwrap());
+            let ts_hash = key[ts_hash_offset..ts_hash_offset + TS_HASH_LENGTH]
+                .try_into()
+                .unwrap();
+            let message_primary_key = crate::store::message::make_message_primary_key(
+                fid,
+                store.postfix(),
+                Some(&ts_hash),
+            );
+
+            message_keys.push(message_primary_key.to_vec());
+            if message_keys.len() >= page_options.page_size.unwrap_or(PAGE_SIZE_MAX) {
+                last_key = key.to_vec();
+                return Ok(true); // Stop iterating
+            }
+
+            Ok(false) // Continue iterating
+        };
+
+        let db = store.db();
+        if start_time.is_none() && stop_time.is_none() {
+            db.for_each_iterator_by_prefix(prefix, page_options, &mut visit)?;
+        } else {
+            let (start, stop) = make_time_range_bounds(start_time, stop_time);
+            db.for_each_iterator_by_prefix_in_range(
+                prefix,
+                start.as_deref(),
+                stop.as_deref(),
+                page_options,
+                &mut visit,
+            )?;
+        }
+
+        let messages_bytes = message::get_many_messages_as_bytes(db.borrow(), message_keys)?;
         let next_page_token = if last_key.len() > 0 {
             Some(last_key[prefix.len()..].to_vec())
         } else {
@@ -692,6 +1395,16 @@ impl CastStore {
         })
     }
 
+    pub fn get_casts_by_mention(
+        store: &Store,
+        mention: u32,
+        page_options: &PageOptions,
+    ) -> Result<MessagesPage, HubError> {
+        let prefix = CastStoreDef::make_cast_by_mention_key(mention, 0, None);
+
+        Self::get_casts_by_index_prefix(store, &prefix, page_options)
+    }
+
     pub fn js_get_casts_by_mention(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
@@ -702,12 +1415,267 @@ impl CastStore {
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let messages = Self::get_casts_by_mention(&store, mention, &page_options);
+        execute(move || {
+            let messages =
+                catch_panic(|| Self::get_casts_by_mention(&store, mention, &page_options));
+
+            deferred_settle_messages(deferred, &channel, messages);
+        });
+
+        Ok(promise)
+    }
+
+    /** Get the CastAdds that embed a url. The url is normalized, so it doesn't have to match exactly */
+    pub fn get_casts_by_embed_url(
+        store: &Store,
+        url: &str,
+        page_options: &PageOptions,
+    ) -> Result<MessagesPage, HubError> {
+        let prefix = CastStoreDef::make_cast_by_embed_key(&Embed::Url(url.to_string()), 0, None);
+
+        Self::get_casts_by_index_prefix(store, &prefix, page_options)
+    }
+
+    pub fn js_get_casts_by_embed_url(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let url = cx.argument::<JsString>(0)?.value(&mut cx);
+        let page_options = get_page_options(&mut cx, 1)?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let messages =
+                catch_panic(|| Self::get_casts_by_embed_url(&store, &url, &page_options));
+
+            deferred_settle_messages(deferred, &channel, messages);
+        });
+
+        Ok(promise)
+    }
+
+    /** Get the CastAdds that quote a cast, i.e. embed its CastId */
+    pub fn get_quote_casts(
+        store: &Store,
+        cast_id: &protos::CastId,
+        page_options: &PageOptions,
+    ) -> Result<MessagesPage, HubError> {
+        let prefix = CastStoreDef::make_cast_by_embed_key(&Embed::CastId(cast_id.clone()), 0, None);
+
+        Self::get_casts_by_index_prefix(store, &prefix, page_options)
+    }
+
+    pub fn js_get_quote_casts(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let cast_id_buffer = cx.argument::<JsBuffer>(0)?;
+        let cast_id = match protos::CastId::decode(cast_id_buffer.as_slice(&cx)) {
+            Ok(cast_id) => cast_id,
+            Err(e) => {
+                return hub_error_to_js_throw(
+                    &mut cx,
+                    HubError::new(HubErrorCode::ParseFailure, e.to_string()),
+                )
+            }
+        };
+        let page_options = get_page_options(&mut cx, 1)?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let messages = catch_panic(|| Self::get_quote_casts(&store, &cast_id, &page_options));
 
             deferred_settle_messages(deferred, &channel, messages);
         });
 
         Ok(promise)
     }
+
+    /**
+     * Get recent CastAdds from every fid, with timestamps in [start_time, stop_time], newest first
+     * (oldest first with reverse). Fails if the store was created without the feed index, or it
+     * hasn't been backfilled yet.
+     */
+    pub fn get_recent_casts(
+        store: &Store,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
+        page_options: &PageOptions,
+    ) -> Result<MessagesPage, HubError> {
+        if !store.has_secondary_index(RootPrefix::CastsByTime) {
+            return Err(HubError::invalid_parameter(
+                "cast feed index is not enabled on this hub",
+            ));
+        }
+        if !store.is_secondary_index_complete(RootPrefix::CastsByTime)? {
+            return Err(HubError::new(
+                HubErrorCode::Unavailable,
+                "cast feed index is still being built",
+            ));
+        }
+
+        let prefix = CastStoreDef::make_cast_by_time_key(0, None);
+        let newest_first = PageOptions {
+            page_size: page_options.page_size,
+            page_token: page_options.page_token.clone(),
+            reverse: !page_options.reverse,
+        };
+
+        Self::get_casts_by_index_prefix_in_time_range(
+            store,
+            &prefix,
+            start_time,
+            stop_time,
+            &newest_first,
+        )
+    }
+
+    /**
+     * Same as get_recent_casts, but only for casts in a channel, i.e. replies to a parent url.
+     * These come from the CastsByParent index, so they don't need the feed index.
+     */
+    pub fn get_recent_casts_by_parent_url(
+        store: &Store,
+        parent_url: &str,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
+        page_options: &PageOptions,
+    ) -> Result<MessagesPage, HubError> {
+        let prefix = CastStoreDef::make_cast_by_parent_key(
+            &Parent::ParentUrl(parent_url.to_string()),
+            0,
+            None,
+        );
+        let newest_first = PageOptions {
+            page_size: page_options.page_size,
+            page_token: page_options.page_token.clone(),
+            reverse: !page_options.reverse,
+        };
+
+        Self::get_casts_by_index_prefix_in_time_range(
+            store,
+            &prefix,
+            start_time,
+            stop_time,
+            &newest_first,
+        )
+    }
+
+    /** Reads an optional timestamp argument, which is undefined if it isn't a number */
+    fn get_optional_time_argument(cx: &mut FunctionContext, i: usize) -> Option<u32> {
+        cx.argument_opt(i)
+            .and_then(|arg| arg.downcast::<JsNumber, _>(cx).ok())
+            .map(|n| n.value(cx) as u32)
+    }
+
+    pub fn js_get_recent_casts(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let page_options = get_page_options(&mut cx, 0)?;
+        let start_time = Self::get_optional_time_argument(&mut cx, 1);
+        let stop_time = Self::get_optional_time_argument(&mut cx, 2);
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let messages = catch_panic(|| {
+                Self::get_recent_casts(&store, start_time, stop_time, &page_options)
+            });
+
+            deferred_settle_messages(deferred, &channel, messages);
+        });
+
+        Ok(promise)
+    }
+
+    pub fn js_get_recent_casts_by_parent_url(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let parent_url = cx.argument::<JsString>(0)?.value(&mut cx);
+        let page_options = get_page_options(&mut cx, 1)?;
+        let start_time = Self::get_optional_time_argument(&mut cx, 2);
+        let stop_time = Self::get_optional_time_argument(&mut cx, 3);
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let messages = catch_panic(|| {
+                Self::get_recent_casts_by_parent_url(
+                    &store,
+                    &parent_url,
+                    start_time,
+                    stop_time,
+                    &page_options,
+                )
+            });
+
+            deferred_settle_messages(deferred, &channel, messages);
+        });
+
+        Ok(promise)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_embed_url() {
+        assert_eq!(
+            normalize_embed_url(" HTTPS://Example.COM:443/Path/To/?q=1#section "),
+            "https://example.com/Path/To?q=1"
+        );
+        assert_eq!(
+            normalize_embed_url("https://example.com/"),
+            normalize_embed_url("https://example.com")
+        );
+        assert_eq!(
+            normalize_embed_url("http://example.com:8080/"),
+            "http://example.com:8080"
+        );
+        assert_eq!(
+            normalize_embed_url("chain://eip155:1/erc721:0xABC"),
+            "chain://eip155:1/erc721:0xABC"
+        );
+        assert_ne!(
+            normalize_embed_url("https://example.com/?a=1"),
+            normalize_embed_url("https://example.com/?a=2")
+        );
+    }
+
+    #[test]
+    fn test_embed_keys() {
+        let url_key = CastStoreDef::make_embed_key(&Embed::Url("https://example.com".to_string()));
+        assert_eq!(url_key.len(), 1 + EMBED_URL_HASH_LENGTH);
+        assert_eq!(
+            url_key,
+            CastStoreDef::make_embed_key(&Embed::Url("HTTPS://EXAMPLE.COM/".to_string()))
+        );
+
+        let cast_id = protos::CastId {
+            fid: 1,
+            hash: vec![2; HASH_LENGTH],
+        };
+        let cast_id_key = CastStoreDef::make_embed_key(&Embed::CastId(cast_id.clone()));
+        assert_eq!(cast_id_key[0], EmbedKeyType::CastId as u8);
+        assert_eq!(&cast_id_key[1..], make_cast_id_key(&cast_id).as_slice());
+    }
+
+    #[test]
+    fn test_by_time_key() {
+        // The tsHash comes first so that casts from every fid are in time order
+        let key = CastStoreDef::make_cast_by_time_key(1, Some(&[2; TS_HASH_LENGTH]));
+        assert_eq!(key[0], RootPrefix::CastsByTime as u8);
+        assert_eq!(&key[1..1 + TS_HASH_LENGTH], &[2; TS_HASH_LENGTH]);
+        assert_eq!(&key[1 + TS_HASH_LENGTH..], &[0, 0, 0, 1]);
+        assert_eq!(
+            CastStoreDef::make_cast_by_time_key(0, None),
+            vec![RootPrefix::CastsByTime as u8]
+        );
+    }
 }
@@ -0,0 +1,186 @@
+use super::{
+    hub_error_to_js_throw, index_check::REBUILD_BATCH_SIZE, make_cast_id_key, make_fid_key,
+    store::Store, utils::get_store, HubError, PageOptions, ReactionStoreDef, RootPrefix,
+};
+use crate::db::{decode_counter, RocksDB};
+use crate::panics::{catch_panic, execute};
+use crate::protos::{reaction_body, CastId};
+use neon::context::{Context, FunctionContext};
+use neon::result::JsResult;
+use neon::types::JsPromise;
+use slog::info;
+
+/**
+ * What an AggregateCounts row counts. Counter keys are
+ * <RootPrefix::AggregateCounts>:<CounterType>:<counter specific suffix>, and the value is a
+ * big-endian u64 maintained by `RocksDbTransactionBatch::increment`.
+ */
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CounterType {
+    /** Reactions of one type to a cast or url. <reactionType>:<target> */
+    ReactionsByTarget = 1,
+    /** CastAdds that reply to a cast. <parentCastId> */
+    RepliesByParent = 2,
+    /** Links of one type to a fid, e.g. followers. <targetFid>:<linkType> */
+    LinksByTarget = 3,
+    /** Links of one type from a fid, e.g. following. <fid>:<linkType> */
+    LinksBySource = 4,
+}
+
+fn make_counter_key(counter_type: CounterType, suffix: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(2 + suffix.len());
+
+    key.push(RootPrefix::AggregateCounts as u8);
+    key.push(counter_type as u8);
+    key.extend_from_slice(suffix);
+
+    key
+}
+
+pub fn make_reactions_count_key(target: &reaction_body::Target, reaction_type: i32) -> Vec<u8> {
+    let mut suffix = vec![reaction_type as u8];
+    suffix.extend_from_slice(&ReactionStoreDef::make_target_key(target));
+
+    make_counter_key(CounterType::ReactionsByTarget, &suffix)
+}
+
+pub fn make_replies_count_key(parent_cast_id: &CastId) -> Vec<u8> {
+    make_counter_key(
+        CounterType::RepliesByParent,
+        &make_cast_id_key(parent_cast_id),
+    )
+}
+
+pub fn make_links_by_target_count_key(target_fid: u32, link_type: &str) -> Vec<u8> {
+    let mut suffix = make_fid_key(target_fid);
+    suffix.extend_from_slice(link_type.as_bytes());
+
+    make_counter_key(CounterType::LinksByTarget, &suffix)
+}
+
+pub fn make_links_by_source_count_key(fid: u32, link_type: &str) -> Vec<u8> {
+    let mut suffix = make_fid_key(fid);
+    suffix.extend_from_slice(link_type.as_bytes());
+
+    make_counter_key(CounterType::LinksBySource, &suffix)
+}
+
+/** The current value of a counter. Counters that were never incremented are 0 */
+pub fn get_count(db: &RocksDB, key: &[u8]) -> Result<u64, HubError> {
+    Ok(db.get(key)?.map_or(0, |value| decode_counter(&value)))
+}
+
+impl Store {
+    /**
+     * Delete this store's counters and count them again from its add messages, returning the
+     * number of messages counted. Merges into this store are blocked while it runs.
+     */
+    pub fn rebuild_counters(&self) -> Result<u64, HubError> {
+        let counter_types = self.store_def().counter_types();
+        if counter_types.is_empty() {
+            return Ok(0);
+        }
+
+        let _fid_locks = self.lock_all_fids();
+
+        let db = self.db();
+        let mut txn = db.txn();
+
+        for counter_type in counter_types.iter() {
+            let prefix = [RootPrefix::AggregateCounts as u8, *counter_type as u8];
+            db.for_each_iterator_by_prefix(&prefix, &PageOptions::default(), |key, _| {
+                txn.delete(key.to_vec());
+                if txn.len() >= REBUILD_BATCH_SIZE {
+                    db.commit(std::mem::replace(&mut txn, db.txn()))?;
+                }
+
+                Ok(false)
+            })?;
+        }
+        db.commit(std::mem::replace(&mut txn, db.txn()))?;
+
+        // The counters are now empty, so each batch of increments can be committed on its own
+        let mut counted = 0;
+        self.for_each_store_message(|message| {
+            if message.data.is_none()
+                || !self.store_def().is_add_type(message)
+                || self.store_def().is_compact_state_type(message)
+            {
+                return Ok(());
+            }
+
+            self.store_def().update_counters(&mut txn, message, 1)?;
+            counted += 1;
+
+            if txn.len() >= REBUILD_BATCH_SIZE {
+                db.commit(std::mem::replace(&mut txn, db.txn()))?;
+            }
+
+            Ok(())
+        })?;
+        db.commit(txn)?;
+
+        info!(self.logger(), "Rebuilt counters";
+            "postfix" => self.store_def().postfix(),
+            "messages" => counted
+        );
+
+        Ok(counted)
+    }
+
+    pub fn js_rebuild_counters(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        // This scans every message in the store, so it runs in the threadpool
+        execute(move || {
+            let result = catch_panic(|| store.rebuild_counters());
+
+            deferred.settle_with(&channel, move |mut cx| match result {
+                Ok(counted) => Ok(cx.number(counted as f64)),
+                Err(e) => hub_error_to_js_throw(&mut cx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_keys() {
+        let cast_id = CastId {
+            fid: 1,
+            hash: vec![2; 20],
+        };
+
+        let key = make_replies_count_key(&cast_id);
+        assert_eq!(key[0], RootPrefix::AggregateCounts as u8);
+        assert_eq!(key[1], CounterType::RepliesByParent as u8);
+        assert_eq!(&key[2..], make_cast_id_key(&cast_id).as_slice());
+
+        // The reaction type comes before the variable length target
+        let key = make_reactions_count_key(
+            &reaction_body::Target::TargetUrl("https://farcaster.xyz".to_string()),
+            1,
+        );
+        assert_eq!(&key[..3], &[RootPrefix::AggregateCounts as u8, 1, 1]);
+        assert_eq!(&key[3..], b"https://farcaster.xyz");
+
+        // Followers and following of the same fid are different counters
+        assert_ne!(
+            make_links_by_target_count_key(1, "follow"),
+            make_links_by_source_count_key(1, "follow")
+        );
+        assert_eq!(
+            make_links_by_target_count_key(1, "follow")[2..],
+            [0, 0, 0, 1, b'f', b'o', b'l', b'l', b'o', b'w']
+        );
+    }
+}
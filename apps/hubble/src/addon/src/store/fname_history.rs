@@ -0,0 +1,304 @@
+use super::{
+    hub_error_to_js_throw,
+    index_check::REBUILD_BATCH_SIZE,
+    name_registry_events::{
+        make_fname_history_by_fid_prefix, make_fname_history_prefix, put_fname_history_transaction,
+        FnameHistoryEntry,
+    },
+    store::Store,
+    utils::{get_page_options, get_store},
+    HubError, HubErrorCode, PageOptions, RootPrefix, UserDataStore, PAGE_SIZE_MAX,
+};
+use crate::db::RocksDB;
+use crate::panics::{catch_panic, execute};
+use crate::protos::UserNameProof;
+use neon::context::{Context, FunctionContext, TaskContext};
+use neon::object::Object;
+use neon::result::JsResult;
+use neon::types::buffer::TypedArray;
+use neon::types::{JsArray, JsBuffer, JsNumber, JsObject, JsPromise};
+use prost::Message as _;
+use slog::info;
+
+/** A page of FNameHistory entries */
+#[derive(Debug, Default)]
+pub struct FnameHistoryPage {
+    pub entries: Vec<FnameHistoryEntry>,
+    pub next_page_token: Option<Vec<u8>>,
+}
+
+/**
+ * Page through the entries of an FNameHistory index. `read_entry` turns an index row into its
+ * entry, since the by-fid index points to the by-name one instead of repeating it.
+ */
+fn get_fname_history_page<F>(
+    db: &RocksDB,
+    prefix: &[u8],
+    page_options: &PageOptions,
+    mut read_entry: F,
+) -> Result<FnameHistoryPage, HubError>
+where
+    F: FnMut(&[u8]) -> Result<Option<FnameHistoryEntry>, HubError>,
+{
+    let page_size = page_options.page_size.unwrap_or(PAGE_SIZE_MAX);
+    let mut entries = vec![];
+    let mut last_key = vec![];
+
+    db.for_each_iterator_by_prefix(prefix, page_options, |key, value| {
+        if let Some(entry) = read_entry(value)? {
+            entries.push(entry);
+        }
+
+        if entries.len() >= page_size {
+            last_key = key.to_vec();
+            return Ok(true); // Stop iterating
+        }
+
+        Ok(false) // Continue iterating
+    })?;
+
+    let next_page_token = if !last_key.is_empty() {
+        Some(last_key[prefix.len()..].to_vec())
+    } else {
+        None
+    };
+
+    Ok(FnameHistoryPage {
+        entries,
+        next_page_token,
+    })
+}
+
+fn encode_fname_history_page_to_js_object<'a>(
+    cx: &mut TaskContext<'a>,
+    page: FnameHistoryPage,
+) -> JsResult<'a, JsObject> {
+    let js_entries = JsArray::new(cx, page.entries.len());
+    for (i, entry) in page.entries.iter().enumerate() {
+        let js_entry = JsObject::new(cx);
+
+        let js_kind = cx.number(entry.kind);
+        js_entry.set(cx, "kind", js_kind)?;
+        let js_previous_fid = cx.number(entry.previous_fid);
+        js_entry.set(cx, "previousFid", js_previous_fid)?;
+
+        let proof_bytes = entry.proof.encode_to_vec();
+        let mut js_buffer = cx.buffer(proof_bytes.len())?;
+        js_buffer.as_mut_slice(cx).copy_from_slice(&proof_bytes);
+        js_entry.set(cx, "proofBytes", js_buffer)?;
+
+        js_entries.set(cx, i as u32, js_entry)?;
+    }
+
+    let js_object = JsObject::new(cx);
+    js_object.set(cx, "entries", js_entries)?;
+
+    if let Some(page_token) = page.next_page_token {
+        let mut js_page_token = cx.buffer(page_token.len())?;
+        js_page_token.as_mut_slice(cx).copy_from_slice(&page_token);
+        js_object.set(cx, "nextPageToken", js_page_token)?;
+    } else {
+        let undefined_obj = cx.undefined();
+        js_object.set(cx, "nextPageToken", undefined_obj)?;
+    }
+
+    Ok(js_object)
+}
+
+impl UserDataStore {
+    /** Every proof, transfer and deletion of an fname, oldest first (newest first with reverse) */
+    pub fn get_fname_history(
+        store: &Store,
+        name: &[u8],
+        page_options: &PageOptions,
+    ) -> Result<FnameHistoryPage, HubError> {
+        if name.is_empty() {
+            return Err(HubError::invalid_parameter("name is required"));
+        }
+
+        let prefix = make_fname_history_prefix(name);
+        get_fname_history_page(&store.db(), &prefix, page_options, |value| {
+            Ok(Some(FnameHistoryEntry::decode(value)?))
+        })
+    }
+
+    /**
+     * The FNameHistory entries of every fname a fid has owned, both those where it got the name
+     * and those where it lost it, oldest first (newest first with reverse)
+     */
+    pub fn get_fname_history_by_fid(
+        store: &Store,
+        fid: u32,
+        page_options: &PageOptions,
+    ) -> Result<FnameHistoryPage, HubError> {
+        if fid == 0 {
+            return Err(HubError::invalid_parameter("fid is required"));
+        }
+
+        let db = store.db();
+        let prefix = make_fname_history_by_fid_prefix(fid);
+        get_fname_history_page(&db, &prefix, page_options, |history_key| {
+            match db.get(history_key)? {
+                Some(value) => Ok(Some(FnameHistoryEntry::decode(&value)?)),
+                None => Ok(None),
+            }
+        })
+    }
+
+    /**
+     * Hubs only started keeping FNameHistory after they had merged fname proofs, so record a
+     * Register entry for each current proof of a name that has no history yet. Returns the number
+     * of entries written.
+     */
+    pub fn backfill_fname_history(store: &Store) -> Result<u64, HubError> {
+        let db = store.db();
+
+        let mut proofs = vec![];
+        db.for_each_iterator_by_prefix(
+            &[RootPrefix::FNameUserNameProof as u8],
+            &PageOptions::default(),
+            |_, value| {
+                let proof = UserNameProof::decode(value).map_err(|_| {
+                    HubError::new(HubErrorCode::Unknown, "could not decode username proof")
+                })?;
+                // Deleted proofs used to be kept with fid 0, those names have no owner to record
+                if proof.fid != 0 {
+                    proofs.push(proof);
+                }
+
+                Ok(false) // Continue iterating
+            },
+        )?;
+
+        let mut txn = db.txn();
+        let mut written = 0;
+        for proof in proofs {
+            let mut has_history = false;
+            db.for_each_iterator_by_prefix(
+                &make_fname_history_prefix(&proof.name),
+                &PageOptions::default(),
+                |_, _| {
+                    has_history = true;
+                    Ok(true) // Stop iterating
+                },
+            )?;
+            if has_history {
+                continue;
+            }
+
+            put_fname_history_transaction(&mut txn, &proof, None);
+            written += 1;
+
+            if txn.len() >= REBUILD_BATCH_SIZE {
+                db.commit(std::mem::replace(&mut txn, db.txn()))?;
+            }
+        }
+        db.commit(txn)?;
+
+        info!(store.logger(), "Backfilled fname history"; "entries" => written);
+
+        Ok(written)
+    }
+
+    pub fn js_get_fname_history(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let name = cx.argument::<JsBuffer>(0)?.as_slice(&cx).to_vec();
+        let page_options = get_page_options(&mut cx, 1)?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let page = catch_panic(|| Self::get_fname_history(&store, &name, &page_options));
+
+            deferred.settle_with(&channel, move |mut cx| match page {
+                Ok(page) => encode_fname_history_page_to_js_object(&mut cx, page),
+                Err(e) => hub_error_to_js_throw(&mut cx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+
+    pub fn js_get_fname_history_by_fid(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+        let page_options = get_page_options(&mut cx, 1)?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let page = catch_panic(|| Self::get_fname_history_by_fid(&store, fid, &page_options));
+
+            deferred.settle_with(&channel, move |mut cx| match page {
+                Ok(page) => encode_fname_history_page_to_js_object(&mut cx, page),
+                Err(e) => hub_error_to_js_throw(&mut cx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+
+    pub fn js_backfill_fname_history(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        // This scans every fname proof, so it runs in the threadpool
+        execute(move || {
+            let result = catch_panic(|| Self::backfill_fname_history(&store));
+
+            deferred.settle_with(&channel, move |mut cx| match result {
+                Ok(written) => Ok(cx.number(written as f64)),
+                Err(e) => hub_error_to_js_throw(&mut cx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::name_registry_events::{
+        make_fname_history_by_fid_key, make_fname_history_key,
+    };
+    use super::*;
+
+    #[test]
+    fn test_fname_history_keys() {
+        // A name's history doesn't include the history of longer names it's a prefix of
+        let alice = make_fname_history_prefix(b"alice");
+        let alicia = make_fname_history_key(b"alicia", 1, 2);
+        assert!(!alicia.starts_with(&alice));
+
+        // Entries of a name are ordered by timestamp
+        assert!(make_fname_history_key(b"alice", 1, 9) < make_fname_history_key(b"alice", 2, 1));
+
+        let by_fid = make_fname_history_by_fid_key(3, 1, b"alice");
+        assert_eq!(by_fid[0], RootPrefix::FNameHistoryByFid as u8);
+        assert_eq!(&by_fid[1..5], &[0, 0, 0, 3]);
+        assert_eq!(&by_fid[13..], b"alice");
+    }
+
+    #[test]
+    fn test_fname_history_entry_encoding() {
+        let entry = FnameHistoryEntry {
+            kind: 2,
+            previous_fid: 7,
+            proof: UserNameProof {
+                name: b"alice".to_vec(),
+                fid: 8,
+                timestamp: 1,
+                ..Default::default()
+            },
+        };
+
+        assert_eq!(FnameHistoryEntry::decode(&entry.encode()).unwrap(), entry);
+        assert!(FnameHistoryEntry::decode(&[1, 0]).is_err());
+    }
+}
@@ -0,0 +1,460 @@
+use super::{
+    hub_error_to_js_throw, make_ts_hash, message_decode, store::Store, utils::get_store, HubError,
+    PageOptions, RootPrefix, FID_BYTES, TRUE_VALUE,
+};
+use crate::db::RocksDbTransactionBatch;
+use crate::panics::{catch_panic, execute};
+use crate::protos::Message;
+use neon::context::{Context, FunctionContext};
+use neon::event::Channel;
+use neon::handle::{Handle, Root};
+use neon::object::Object;
+use neon::result::JsResult;
+use neon::types::buffer::TypedArray;
+use neon::types::{JsArray, JsBoolean, JsFunction, JsObject, JsPromise};
+use slog::info;
+use std::sync::Arc;
+
+/** Rebuilds are committed in batches of this many keys, instead of one huge transaction */
+pub(super) const REBUILD_BATCH_SIZE: usize = 10_000;
+
+/** Progress is reported every this many keys */
+const PROGRESS_INTERVAL: u64 = 100_000;
+
+/** Only this many missing and dangling keys are returned. The rest are just counted */
+const MAX_REPORTED_KEYS: usize = 100;
+
+/** The result of checking one store's secondary indices */
+#[derive(Debug, Default, PartialEq)]
+pub struct IndexCheckReport {
+    pub messages_checked: u64,
+    pub index_entries_checked: u64,
+    /** Index entries that a message should have, but that don't exist or have the wrong value */
+    pub missing: u64,
+    /** Index entries for messages that don't exist, or that the message wouldn't create */
+    pub dangling: u64,
+    pub missing_keys: Vec<Vec<u8>>,
+    pub dangling_keys: Vec<Vec<u8>>,
+    /** Index entries written when the indices were rebuilt. 0 if `repair` was false */
+    pub rebuilt: u64,
+}
+
+impl IndexCheckReport {
+    fn add_missing(&mut self, key: &[u8]) {
+        self.missing += 1;
+        if self.missing_keys.len() < MAX_REPORTED_KEYS {
+            self.missing_keys.push(key.to_vec());
+        }
+    }
+
+    fn add_dangling(&mut self, key: &[u8]) {
+        self.dangling += 1;
+        if self.dangling_keys.len() < MAX_REPORTED_KEYS {
+            self.dangling_keys.push(key.to_vec());
+        }
+    }
+}
+
+/** How far along an index check is */
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexCheckProgress {
+    /** "messages" while messages are checked, "indices" while index entries are checked and
+     * "rebuild" while the indices are rebuilt */
+    pub stage: &'static str,
+    pub keys: u64,
+}
+
+/** Sends index check progress to an optional JS callback, from any thread */
+#[derive(Clone, Default)]
+pub struct IndexCheckProgressReporter {
+    callback: Option<(Channel, Arc<Root<JsFunction>>)>,
+}
+
+impl IndexCheckProgressReporter {
+    pub fn new<'a, C: Context<'a>>(cx: &mut C, callback: Handle<JsFunction>) -> Self {
+        IndexCheckProgressReporter {
+            callback: Some((cx.channel(), Arc::new(callback.root(cx)))),
+        }
+    }
+
+    pub fn report(&self, progress: IndexCheckProgress) {
+        if let Some((channel, callback)) = &self.callback {
+            let callback = callback.clone();
+
+            channel.send(move |mut cx| {
+                let js_progress = JsObject::new(&mut cx);
+                let stage = cx.string(progress.stage);
+                js_progress.set(&mut cx, "stage", stage)?;
+                let keys = cx.number(progress.keys as f64);
+                js_progress.set(&mut cx, "keys", keys)?;
+
+                // A throwing progress callback shouldn't take down the process, so ignore errors
+                let callback = callback.to_inner(&mut cx);
+                let _ = cx.try_catch(|cx| callback.call_with(cx).arg(js_progress).exec(cx));
+
+                Ok(())
+            });
+        }
+    }
+}
+
+impl Store {
+    pub(super) fn make_optional_index_complete_key(prefix: u8) -> Vec<u8> {
+        vec![RootPrefix::OptionalIndexComplete as u8, prefix]
+    }
+
+    /**
+     * Get the enabled optional indices that aren't complete yet, and need check_indices with
+     * repair to backfill them. Disabled ones are no longer kept up to date by merges, so they're
+     * marked incomplete, and get backfilled again if they're enabled later.
+     */
+    pub fn prepare_optional_indices(&self) -> Result<Vec<u8>, HubError> {
+        let db = self.db();
+        let enabled = self.store_def().secondary_index_prefixes();
+
+        let mut txn = db.txn();
+        let mut incomplete = vec![];
+        for prefix in self.store_def().optional_secondary_index_prefixes() {
+            let key = Self::make_optional_index_complete_key(prefix);
+            if !enabled.contains(&prefix) {
+                txn.delete(key);
+            } else if db.get(&key)?.is_none() {
+                incomplete.push(prefix);
+            }
+        }
+        db.commit(txn)?;
+
+        Ok(incomplete)
+    }
+
+    /**
+     * The index entries `build_secondary_indices` writes for a message. Only add messages have
+     * indices; None is returned for everything else, including messages with invalid bodies.
+     */
+    fn expected_secondary_indices(&self, message: &Message) -> Option<RocksDbTransactionBatch> {
+        if message.data.is_none()
+            || !self.store_def().is_add_type(message)
+            || self.store_def().is_compact_state_type(message)
+        {
+            return None;
+        }
+
+        let ts_hash = make_ts_hash(message.data.as_ref().unwrap().timestamp, &message.hash).ok()?;
+        let mut txn = RocksDbTransactionBatch::new();
+        self.store_def()
+            .build_secondary_indices(&mut txn, &ts_hash, message)
+            .ok()?;
+
+        Some(txn)
+    }
+
+    /**
+     * Iterate over all of this store's messages. Messages are stored under
+     * <RootPrefix::User>:<fid>:<postfix>:<tsHash>, so this has to scan every fid.
+     */
+    pub(super) fn for_each_store_message<F>(&self, mut f: F) -> Result<(), HubError>
+    where
+        F: FnMut(&Message) -> Result<(), HubError>,
+    {
+        let postfix = self.store_def().postfix();
+
+        self.db().for_each_iterator_by_prefix(
+            &[RootPrefix::User as u8],
+            &PageOptions::default(),
+            |key, value| {
+                if key.len() <= 1 + FID_BYTES || key[1 + FID_BYTES] != postfix {
+                    return Ok(false);
+                }
+
+                // Undecodable messages don't have indices to check
+                if let Ok(message) = message_decode(value) {
+                    f(&message)?;
+                }
+
+                Ok(false)
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /** An index entry is valid if it points to a message that would create exactly this key */
+    fn is_index_entry_valid(&self, key: &[u8], value: &[u8]) -> Result<bool, HubError> {
+        let db = self.db();
+
+        let message_key = match self
+            .store_def()
+            .secondary_index_message_key(&db, key, value)
+        {
+            Ok(Some(message_key)) => message_key,
+            _ => return Ok(false),
+        };
+
+        let message = match db.get(&message_key)? {
+            Some(bytes) => match message_decode(&bytes) {
+                Ok(message) => message,
+                Err(_) => return Ok(false),
+            },
+            None => return Ok(false),
+        };
+
+        Ok(self
+            .expected_secondary_indices(&message)
+            .map_or(false, |expected| expected.batch.contains_key(key)))
+    }
+
+    /**
+     * Check this store's secondary indices in both directions: every add message must have all of
+     * its index entries, and every index entry must point to a message that creates it.
+     *
+     * With `repair`, the indices are then deleted and rebuilt from the messages. Merges into this
+     * store are blocked while that runs, so the rebuilt indices match the messages.
+     */
+    pub fn check_indices(
+        &self,
+        repair: bool,
+        progress: &IndexCheckProgressReporter,
+    ) -> Result<IndexCheckReport, HubError> {
+        let mut report = IndexCheckReport::default();
+
+        let prefixes = self.store_def().secondary_index_prefixes();
+        if prefixes.is_empty() {
+            return Ok(report);
+        }
+
+        let _fid_locks = if repair {
+            Some(self.lock_all_fids())
+        } else {
+            None
+        };
+
+        let db = self.db();
+
+        self.for_each_store_message(|message| {
+            report.messages_checked += 1;
+            if report.messages_checked % PROGRESS_INTERVAL == 0 {
+                progress.report(IndexCheckProgress {
+                    stage: "messages",
+                    keys: report.messages_checked,
+                });
+            }
+
+            if let Some(expected) = self.expected_secondary_indices(message) {
+                for (key, value) in expected.batch.iter() {
+                    if value.is_some() && db.get(key)? != *value {
+                        report.add_missing(key);
+                    }
+                }
+            }
+
+            Ok(())
+        })?;
+        progress.report(IndexCheckProgress {
+            stage: "messages",
+            keys: report.messages_checked,
+        });
+
+        for prefix in prefixes.iter() {
+            db.for_each_iterator_by_prefix(&[*prefix], &PageOptions::default(), |key, value| {
+                report.index_entries_checked += 1;
+                if report.index_entries_checked % PROGRESS_INTERVAL == 0 {
+                    progress.report(IndexCheckProgress {
+                        stage: "indices",
+                        keys: report.index_entries_checked,
+                    });
+                }
+
+                if !self.is_index_entry_valid(key, value)? {
+                    report.add_dangling(key);
+                }
+
+                Ok(false)
+            })?;
+        }
+        progress.report(IndexCheckProgress {
+            stage: "indices",
+            keys: report.index_entries_checked,
+        });
+
+        info!(self.logger(), "Checked secondary indices";
+            "postfix" => self.store_def().postfix(),
+            "messages" => report.messages_checked,
+            "index_entries" => report.index_entries_checked,
+            "missing" => report.missing,
+            "dangling" => report.dangling
+        );
+
+        if repair {
+            report.rebuilt = self.rebuild_secondary_indices(&prefixes, progress)?;
+
+            // Every message is in the rebuilt indices, and merges are blocked until the fid locks
+            // are dropped, so the optional ones are complete from now on
+            let mut txn = db.txn();
+            for prefix in self.store_def().optional_secondary_index_prefixes() {
+                if prefixes.contains(&prefix) {
+                    txn.put(
+                        Self::make_optional_index_complete_key(prefix),
+                        vec![TRUE_VALUE],
+                    );
+                }
+            }
+            db.commit(txn)?;
+        }
+
+        Ok(report)
+    }
+
+    /**
+     * Delete everything under the index prefixes, and write the index entries for every message
+     * again. The caller must hold the fid locks.
+     */
+    fn rebuild_secondary_indices(
+        &self,
+        prefixes: &[u8],
+        progress: &IndexCheckProgressReporter,
+    ) -> Result<u64, HubError> {
+        let db = self.db();
+        let mut txn = db.txn();
+
+        for prefix in prefixes.iter() {
+            db.for_each_iterator_by_prefix(&[*prefix], &PageOptions::default(), |key, _| {
+                txn.delete(key.to_vec());
+                if txn.len() >= REBUILD_BATCH_SIZE {
+                    db.commit(std::mem::replace(&mut txn, db.txn()))?;
+                }
+
+                Ok(false)
+            })?;
+        }
+        db.commit(std::mem::replace(&mut txn, db.txn()))?;
+
+        let mut rebuilt = 0;
+        self.for_each_store_message(|message| {
+            if let Some(expected) = self.expected_secondary_indices(message) {
+                for (key, value) in expected.batch {
+                    if let Some(value) = value {
+                        txn.put(key, value);
+                        rebuilt += 1;
+
+                        if rebuilt % PROGRESS_INTERVAL == 0 {
+                            progress.report(IndexCheckProgress {
+                                stage: "rebuild",
+                                keys: rebuilt,
+                            });
+                        }
+                    }
+                }
+            }
+
+            if txn.len() >= REBUILD_BATCH_SIZE {
+                db.commit(std::mem::replace(&mut txn, db.txn()))?;
+            }
+
+            Ok(())
+        })?;
+        db.commit(txn)?;
+
+        progress.report(IndexCheckProgress {
+            stage: "rebuild",
+            keys: rebuilt,
+        });
+        info!(self.logger(), "Rebuilt secondary indices";
+            "postfix" => self.store_def().postfix(),
+            "index_entries" => rebuilt
+        );
+
+        Ok(rebuilt)
+    }
+
+    pub fn js_check_indices(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let mut repair = false;
+        let mut progress = IndexCheckProgressReporter::default();
+        if let Some(js_options) = cx.argument_opt(0) {
+            if let Ok(js_options) = js_options.downcast::<JsObject, _>(&mut cx) {
+                if let Some(js_repair) = js_options.get_opt::<JsBoolean, _, _>(&mut cx, "repair")? {
+                    repair = js_repair.value(&mut cx);
+                }
+                if let Some(js_on_progress) =
+                    js_options.get_opt::<JsFunction, _, _>(&mut cx, "onProgress")?
+                {
+                    progress = IndexCheckProgressReporter::new(&mut cx, js_on_progress);
+                }
+            }
+        }
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        // This scans every message in the store, so it runs in the threadpool to avoid blocking
+        // the NodeJS main thread
+        execute(move || {
+            let result = catch_panic(|| store.check_indices(repair, &progress));
+
+            deferred.settle_with(&channel, move |mut cx| {
+                let report = match result {
+                    Ok(report) => report,
+                    Err(e) => return hub_error_to_js_throw(&mut cx, e),
+                };
+
+                let js_report = cx.empty_object();
+                let val = cx.number(report.messages_checked as f64);
+                js_report.set(&mut cx, "messagesChecked", val)?;
+                let val = cx.number(report.index_entries_checked as f64);
+                js_report.set(&mut cx, "indexEntriesChecked", val)?;
+                let val = cx.number(report.missing as f64);
+                js_report.set(&mut cx, "missing", val)?;
+                let val = cx.number(report.dangling as f64);
+                js_report.set(&mut cx, "dangling", val)?;
+                let val = cx.number(report.rebuilt as f64);
+                js_report.set(&mut cx, "rebuilt", val)?;
+
+                for (name, keys) in [
+                    ("missingKeys", &report.missing_keys),
+                    ("danglingKeys", &report.dangling_keys),
+                ] {
+                    let js_keys = JsArray::new(&mut cx, keys.len());
+                    for (i, key) in keys.iter().enumerate() {
+                        let mut js_key = cx.buffer(key.len())?;
+                        js_key.as_mut_slice(&mut cx).copy_from_slice(key);
+                        js_keys.set(&mut cx, i as u32, js_key)?;
+                    }
+                    js_report.set(&mut cx, name, js_keys)?;
+                }
+
+                Ok(js_report)
+            });
+        });
+
+        Ok(promise)
+    }
+
+    pub fn js_prepare_optional_indices(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let result = catch_panic(|| store.prepare_optional_indices());
+
+            deferred.settle_with(&channel, move |mut cx| {
+                let incomplete = match result {
+                    Ok(incomplete) => incomplete,
+                    Err(e) => return hub_error_to_js_throw(&mut cx, e),
+                };
+
+                let js_prefixes = JsArray::new(&mut cx, incomplete.len());
+                for (i, prefix) in incomplete.iter().enumerate() {
+                    let val = cx.number(*prefix as f64);
+                    js_prefixes.set(&mut cx, i as u32, val)?;
+                }
+
+                Ok(js_prefixes)
+            });
+        });
+
+        Ok(promise)
+    }
+}
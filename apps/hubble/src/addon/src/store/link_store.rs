@@ -1,28 +1,43 @@
-use std::{borrow::Borrow, convert::TryInto, sync::Arc};
+use std::{borrow::Borrow, collections::BTreeSet, convert::TryInto, sync::Arc};
 
 use crate::db::{RocksDB, RocksDbTransactionBatch};
 use crate::logger::LOGGER;
+use crate::panics::{catch_panic, execute};
+use crate::protos;
 use crate::protos::link_body::Target;
 use crate::protos::message_data::Body;
-use crate::protos::{message_data, LinkBody, Message, MessageData, MessageType};
+use crate::protos::{
+    message_data, LinkBody, LinkCompactStateBody, Message, MessageData, MessageType,
+};
 use crate::store::{
-    get_message, get_page_options, get_store, hub_error_to_js_throw, make_fid_key, make_user_key,
-    message, utils, HubError, IntoI32, IntoU8, MessagesPage, PageOptions, RootPrefix, Store,
-    StoreDef, StoreEventHandler, UserPostfix, PAGE_SIZE_MAX, TS_HASH_LENGTH,
+    get_count, get_message, get_messages_at_keys, get_page_options, get_store, get_time_range,
+    hub_error_to_js_throw, make_fid_key, make_links_by_source_count_key,
+    make_links_by_target_count_key, make_message_primary_key, make_user_key, message,
+    message_decode, utils, CompactState, CounterType, HubError, HubErrorCode, IntoI32, IntoU8,
+    MessagesPage, PageOptions, RootPrefix, Store, StoreDef, StoreEventHandler, UserPostfix,
+    PAGE_SIZE_MAX, TS_HASH_LENGTH,
 };
-use crate::{protos, THREAD_POOL};
+use neon::object::Object;
 use neon::prelude::{JsPromise, JsString};
 use neon::types::buffer::TypedArray;
 use neon::{
     context::{Context, FunctionContext},
     result::JsResult,
-    types::{JsBox, JsNumber},
+    types::{JsArray, JsBox, JsNumber, JsObject},
 };
 use prost::Message as _;
 use slog::{o, warn};
 
 use super::deferred_settle_messages;
 
+/** A LinkCompactStateBody matching a fid's links of one type, see `LinkStore::build_compact_state` */
+#[derive(Debug, Default, PartialEq)]
+pub struct LinkCompactStateCandidate {
+    pub body: LinkCompactStateBody,
+    /** The earliest timestamp a compact state message with this body is safe at */
+    pub timestamp: u32,
+}
+
 /**
  * LinkStore persists Link Messages in RocksDB using a two-phase CRDT set to guarantee
  * eventual consistency.
@@ -142,9 +157,13 @@ impl LinkStore {
         fid: u32,
         r#type: String,
         page_options: &PageOptions,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
     ) -> Result<MessagesPage, HubError> {
         store.get_adds_by_fid(
             fid,
+            start_time,
+            stop_time,
             page_options,
             Some(|message: &Message| {
                 message
@@ -168,6 +187,75 @@ impl LinkStore {
         store.get_compact_state_messages_by_fid(fid, page_options)
     }
 
+    /// Builds the LinkCompactStateBody that keeps exactly the links of a type a fid has now, along
+    /// with the earliest timestamp a compact state message with it is safe at. That timestamp is no
+    /// earlier than any link message of the type, so the compact state accounts for all of them,
+    /// and later than the compact state of the type that's already merged, which it has to replace.
+    ///
+    /// A link compact state keeps the links to its targets whatever their type, so older links of
+    /// other types to fids outside target_fids are still deleted by it. Use
+    /// `Store::preview_compact_state_merge` to check for them before publishing.
+    ///
+    /// # Arguments
+    /// * `store` - the Rust data store used to read the links
+    /// * `fid` - fid of the user the compact state is for
+    /// * `link_type` - type of link the compact state is for
+    pub fn build_compact_state(
+        store: &Store,
+        fid: u32,
+        link_type: &str,
+    ) -> Result<LinkCompactStateCandidate, HubError> {
+        if fid == 0 {
+            return Err(HubError::invalid_parameter("fid is required"));
+        }
+        if link_type.is_empty() || link_type.len() > Self::LINK_TYPE_BYTE_SIZE {
+            return Err(HubError::invalid_parameter("invalid link type"));
+        }
+
+        let db = store.db();
+        let mut target_fids = BTreeSet::new();
+        let mut timestamp = 0;
+
+        // Only the winning message of each link is in the store, so its adds are the current links
+        let prefix = make_message_primary_key(fid, store.postfix(), None);
+        db.for_each_iterator_by_prefix(&prefix, &PageOptions::default(), |_key, value| {
+            let message = message_decode(value)?;
+            let data = match message.data.as_ref() {
+                Some(data) => data,
+                None => return Ok(false),
+            };
+
+            if let Some(Body::LinkBody(link_body)) = &data.body {
+                if link_body.r#type == link_type {
+                    timestamp = timestamp.max(data.timestamp);
+
+                    if let Some(Target::TargetFid(target_fid)) = link_body.target {
+                        if store.store_def().is_add_type(&message) {
+                            target_fids.insert(target_fid);
+                        }
+                    }
+                }
+            }
+
+            Ok(false) // Continue iterating
+        })?;
+
+        let compact_state_key = Self::link_compact_state_add_key(fid, &link_type.to_string())?;
+        if let Some(compact_state_bytes) = db.get(&compact_state_key)? {
+            if let Some(data) = message_decode(&compact_state_bytes)?.data {
+                timestamp = timestamp.max(data.timestamp + 1);
+            }
+        }
+
+        Ok(LinkCompactStateCandidate {
+            body: LinkCompactStateBody {
+                r#type: link_type.to_string(),
+                target_fids: target_fids.into_iter().collect(),
+            },
+            timestamp,
+        })
+    }
+
     pub fn get_links_by_target(
         store: &Store,
         target: &Target,
@@ -502,18 +590,70 @@ impl LinkStore {
             })
     }
 
+    /** The number of links of a type that target a fid, e.g. its followers for "follow" */
+    pub fn get_follower_count(store: &Store, fid: u32, link_type: &str) -> Result<u64, HubError> {
+        get_count(&store.db(), &make_links_by_target_count_key(fid, link_type))
+    }
+
+    /** The number of links of a type that a fid has made, e.g. who it follows for "follow" */
+    pub fn get_following_count(store: &Store, fid: u32, link_type: &str) -> Result<u64, HubError> {
+        get_count(&store.db(), &make_links_by_source_count_key(fid, link_type))
+    }
+
+    fn js_get_link_count(
+        mut cx: FunctionContext,
+        get_link_count: fn(&Store, u32, &str) -> Result<u64, HubError>,
+    ) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+        let link_type = cx.argument::<JsString>(1).map(|s| s.value(&mut cx))?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let count = catch_panic(|| get_link_count(&store, fid, &link_type));
+
+            deferred.settle_with(&channel, move |mut cx| match count {
+                Ok(count) => Ok(cx.number(count as f64)),
+                Err(e) => hub_error_to_js_throw(&mut cx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+
+    pub fn js_get_follower_count(cx: FunctionContext) -> JsResult<JsPromise> {
+        Self::js_get_link_count(cx, Self::get_follower_count)
+    }
+
+    pub fn js_get_following_count(cx: FunctionContext) -> JsResult<JsPromise> {
+        Self::js_get_link_count(cx, Self::get_following_count)
+    }
+
     pub fn js_get_link_adds_by_fid(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
         let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let link_type = cx.argument::<JsString>(1).map(|s| s.value(&mut cx))?;
         let page_options = get_page_options(&mut cx, 2)?;
+        let (start_time, stop_time) = get_time_range(&mut cx, 3);
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let messages = Self::get_link_adds_by_fid(&store, fid, link_type, &page_options);
+        execute(move || {
+            let messages = catch_panic(|| {
+                Self::get_link_adds_by_fid(
+                    &store,
+                    fid,
+                    link_type,
+                    &page_options,
+                    start_time,
+                    stop_time,
+                )
+            });
 
             deferred_settle_messages(deferred, &channel, messages);
         });
@@ -527,12 +667,22 @@ impl LinkStore {
         let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let link_type = cx.argument::<JsString>(1).map(|s| s.value(&mut cx))?;
         let page_options = get_page_options(&mut cx, 2)?;
+        let (start_time, stop_time) = get_time_range(&mut cx, 3);
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let messages = Self::get_link_removes_by_fid(&store, fid, link_type, &page_options);
+        execute(move || {
+            let messages = catch_panic(|| {
+                Self::get_link_removes_by_fid(
+                    &store,
+                    fid,
+                    link_type,
+                    &page_options,
+                    start_time,
+                    stop_time,
+                )
+            });
 
             deferred_settle_messages(deferred, &channel, messages);
         });
@@ -545,9 +695,13 @@ impl LinkStore {
         fid: u32,
         r#type: String,
         page_options: &PageOptions,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
     ) -> Result<MessagesPage, HubError> {
         store.get_removes_by_fid(
             fid,
+            start_time,
+            stop_time,
             page_options,
             Some(|message: &Message| {
                 message
@@ -568,13 +722,16 @@ impl LinkStore {
 
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let link_type = cx.argument::<JsString>(1).map(|s| s.value(&mut cx))?;
-        let target_fid = cx.argument::<JsNumber>(2).unwrap().value(&mut cx) as u32;
+        let target_fid = cx.argument::<JsNumber>(2)?.value(&mut cx) as u32;
 
         // target fid must be specified
         if target_fid == 0 {
-            return cx.throw_error("target fid is required");
+            return hub_error_to_js_throw(
+                &mut cx,
+                HubError::invalid_parameter("target fid is required"),
+            );
         }
 
         let target = Some(crate::protos::link_body::Target::TargetFid(
@@ -583,10 +740,11 @@ impl LinkStore {
 
         let result = match Self::get_link_add(&store, fid, link_type, target) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{} for {}",
-                "not_found", "Link Add Message not found", fid
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found(&format!("Link Add Message not found for {}", fid))
+                    .with_fid(fid),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -605,14 +763,17 @@ impl LinkStore {
 
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let link_type = cx.argument::<JsString>(1).map(|s| s.value(&mut cx))?;
 
-        let target_fid = cx.argument::<JsNumber>(2).unwrap().value(&mut cx) as u32;
+        let target_fid = cx.argument::<JsNumber>(2)?.value(&mut cx) as u32;
 
         // target fid must be specified
         if target_fid == 0 {
-            return cx.throw_error("target_fid is required");
+            return hub_error_to_js_throw(
+                &mut cx,
+                HubError::invalid_parameter("target_fid is required"),
+            );
         }
 
         let target = Some(crate::protos::link_body::Target::TargetFid(
@@ -621,10 +782,11 @@ impl LinkStore {
 
         let result = match Self::get_link_remove(&store, fid, link_type, target) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{} for {}",
-                "not_found", "Link Remove Message not found", fid
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found(&format!("Link Remove Message not found for {}", fid))
+                    .with_fid(fid),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -641,13 +803,16 @@ impl LinkStore {
     pub fn js_get_links_by_target(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let target_fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let target_fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let link_type = cx.argument::<JsString>(1).map(|s| s.value(&mut cx))?;
         let page_options = get_page_options(&mut cx, 2)?;
 
         // target fid must be specified
         if target_fid == 0 {
-            return cx.throw_error("target_fid is required");
+            return hub_error_to_js_throw(
+                &mut cx,
+                HubError::invalid_parameter("target_fid is required"),
+            );
         }
 
         let target = crate::protos::link_body::Target::TargetFid(target_fid as u64);
@@ -655,8 +820,10 @@ impl LinkStore {
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let messages = Self::get_links_by_target(&store, &target, link_type, &page_options);
+        execute(move || {
+            let messages = catch_panic(|| {
+                Self::get_links_by_target(&store, &target, link_type, &page_options)
+            });
 
             deferred_settle_messages(deferred, &channel, messages);
         });
@@ -664,24 +831,66 @@ impl LinkStore {
         Ok(promise)
     }
 
+    pub fn js_build_compact_state(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+        let link_type = cx.argument::<JsString>(1)?.value(&mut cx);
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        // This reads every link message of the fid, so it runs in the threadpool
+        execute(move || {
+            let candidate = catch_panic(|| Self::build_compact_state(&store, fid, &link_type));
+
+            deferred.settle_with(&channel, move |mut cx| {
+                let candidate = match candidate {
+                    Ok(candidate) => candidate,
+                    Err(e) => return hub_error_to_js_throw(&mut cx, e),
+                };
+
+                let js_object = JsObject::new(&mut cx);
+                let js_type = cx.string(&candidate.body.r#type);
+                js_object.set(&mut cx, "type", js_type)?;
+
+                let js_target_fids = JsArray::new(&mut cx, candidate.body.target_fids.len());
+                for (i, target_fid) in candidate.body.target_fids.iter().enumerate() {
+                    let js_target_fid = cx.number(*target_fid as f64);
+                    js_target_fids.set(&mut cx, i as u32, js_target_fid)?;
+                }
+                js_object.set(&mut cx, "targetFids", js_target_fids)?;
+
+                let js_timestamp = cx.number(candidate.timestamp);
+                js_object.set(&mut cx, "timestamp", js_timestamp)?;
+
+                Ok(js_object)
+            });
+        });
+
+        Ok(promise)
+    }
+
     pub fn js_get_link_compact_state_message_by_fid(
         mut cx: FunctionContext,
     ) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let page_options = get_page_options(&mut cx, 1)?;
 
         // fid must be specified
         if fid == 0 {
-            return cx.throw_error("fid is required");
+            return hub_error_to_js_throw(&mut cx, HubError::invalid_parameter("fid is required"));
         }
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let messages = Self::get_link_compact_state_message_by_fid(&store, fid, &page_options);
+        execute(move || {
+            let messages = catch_panic(|| {
+                Self::get_link_compact_state_message_by_fid(&store, fid, &page_options)
+            });
 
             deferred_settle_messages(deferred, &channel, messages);
         });
@@ -731,6 +940,48 @@ impl StoreDef for LinkStore {
             })
     }
 
+    fn read_compact_state(&self, message: &Message) -> Result<CompactState, HubError> {
+        let data = message.data.as_ref().ok_or(HubError::validation_failure(
+            "Invalid compact state message: no data",
+        ))?;
+
+        match &data.body {
+            Some(Body::LinkCompactStateBody(link_compact_body)) => Ok(CompactState {
+                fid: data.fid as u32,
+                timestamp: data.timestamp,
+                scope: link_compact_body.r#type.as_bytes().to_vec(),
+                targets: link_compact_body
+                    .target_fids
+                    .iter()
+                    .map(|target_fid| target_fid.to_be_bytes().to_vec())
+                    .collect(),
+            }),
+            _ => Err(HubError::validation_failure(
+                "Invalid compact state message: No link compact state body",
+            )),
+        }
+    }
+
+    /**
+     * A link compact state keeps the links to its target fids, whatever their type, and no
+     * removes. Links without a target fid aren't affected by it.
+     */
+    fn compact_state_keeps(&self, compact_state: &CompactState, message: &Message) -> bool {
+        if self.is_remove_type(message) {
+            return false;
+        }
+
+        match &message.data.as_ref().unwrap().body {
+            Some(Body::LinkBody(LinkBody {
+                target: Some(Target::TargetFid(target_fid)),
+                ..
+            })) => compact_state
+                .targets
+                .contains(target_fid.to_be_bytes().as_slice()),
+            _ => true,
+        }
+    }
+
     fn build_secondary_indices(
         &self,
         txn: &mut RocksDbTransactionBatch,
@@ -780,6 +1031,40 @@ impl StoreDef for LinkStore {
         Ok(())
     }
 
+    fn secondary_index_prefixes(&self) -> Vec<u8> {
+        vec![RootPrefix::LinksByTarget as u8]
+    }
+
+    fn counter_types(&self) -> Vec<CounterType> {
+        vec![CounterType::LinksByTarget, CounterType::LinksBySource]
+    }
+
+    fn update_counters(
+        &self,
+        txn: &mut RocksDbTransactionBatch,
+        message: &Message,
+        delta: i64,
+    ) -> Result<(), HubError> {
+        let data = message.data.as_ref().unwrap();
+        if let Some(Body::LinkBody(LinkBody {
+            r#type,
+            target: Some(Target::TargetFid(target_fid)),
+            ..
+        })) = &data.body
+        {
+            txn.increment(
+                make_links_by_target_count_key(*target_fid as u32, r#type),
+                delta,
+            );
+            txn.increment(
+                make_links_by_source_count_key(data.fid as u32, r#type),
+                delta,
+            );
+        }
+
+        Ok(())
+    }
+
     // During the initial rust migration, we were not padding the type field to 8 bytes, so we still
     // have some links that don't have the right padding. Override the default merge conflict resolution
     // to check for the presence of incorrectly padded links as well
@@ -804,16 +1089,22 @@ impl StoreDef for LinkStore {
             );
 
             if remove_compare > 0 {
-                return Err(HubError {
-                    code: "bad_request.conflict".to_string(),
-                    message: "message conflicts with a more recent remove".to_string(),
-                });
+                return Err(HubError::new(
+                    HubErrorCode::Conflict,
+                    "message conflicts with a more recent remove",
+                )
+                .with_fid(message.data.as_ref().unwrap().fid as u32)
+                .with_hash(&message.hash)
+                .with_key(&remove_key));
             }
             if remove_compare == 0 {
-                return Err(HubError {
-                    code: "bad_request.duplicate".to_string(),
-                    message: "message has already been merged".to_string(),
-                });
+                return Err(HubError::new(
+                    HubErrorCode::Duplicate,
+                    "message has already been merged",
+                )
+                .with_fid(message.data.as_ref().unwrap().fid as u32)
+                .with_hash(&message.hash)
+                .with_key(&remove_key));
             }
 
             // If the existing remove has a lower order than the new message, retrieve the full
@@ -846,16 +1137,22 @@ impl StoreDef for LinkStore {
             );
 
             if add_compare > 0 {
-                return Err(HubError {
-                    code: "bad_request.conflict".to_string(),
-                    message: "message conflicts with a more recent add".to_string(),
-                });
+                return Err(HubError::new(
+                    HubErrorCode::Conflict,
+                    "message conflicts with a more recent add",
+                )
+                .with_fid(message.data.as_ref().unwrap().fid as u32)
+                .with_hash(&message.hash)
+                .with_key(&add_key));
             }
             if add_compare == 0 {
-                return Err(HubError {
-                    code: "bad_request.duplicate".to_string(),
-                    message: "message has already been merged".to_string(),
-                });
+                return Err(HubError::new(
+                    HubErrorCode::Duplicate,
+                    "message has already been merged",
+                )
+                .with_fid(message.data.as_ref().unwrap().fid as u32)
+                .with_hash(&message.hash)
+                .with_key(&add_key));
             }
 
             // If the existing add has a lower order than the new message, retrieve the full
@@ -878,6 +1175,27 @@ impl StoreDef for LinkStore {
         return Ok(conflicts);
     }
 
+    fn get_competing_messages(
+        &self,
+        db: &RocksDB,
+        message: &Message,
+    ) -> Result<Vec<Message>, HubError> {
+        // Also look for messages at the incorrectly padded keys, like get_merge_conflicts
+        let keys = vec![
+            Self::make_add_key_padded(message, true)?,
+            Self::make_remove_key_padded(message, true)?,
+            Self::make_add_key_padded(message, false)?,
+            Self::make_remove_key_padded(message, false)?,
+        ];
+
+        get_messages_at_keys(
+            db,
+            message.data.as_ref().unwrap().fid as u32,
+            self.postfix(),
+            &keys,
+        )
+    }
+
     fn find_merge_add_conflicts(&self, _db: &RocksDB, _message: &Message) -> Result<(), HubError> {
         // For links, there will be no additional conflict logic
         Ok(())
@@ -5,7 +5,10 @@ use crate::{
     protos::{CastId, Message as MessageProto, MessageData, MessageType},
 };
 
-use super::{store::HubError, PageOptions, PAGE_SIZE_MAX};
+use super::{
+    store::{HubError, HubErrorCode},
+    PageOptions, PAGE_SIZE_MAX,
+};
 
 pub const FID_BYTES: usize = 4;
 
@@ -74,6 +77,30 @@ pub enum RootPrefix {
 
     /* Used to index fname username proofs by fid */
     FNameUserNameProofByFid = 27,
+
+    /* Aggregate counters, e.g. reactions per cast. Followed by a CounterType */
+    AggregateCounts = 28,
+
+    /* Full-text search postings for cast text. <termLength>:<term>:<tsHash>:<fid> */
+    CastSearchTerms = 29,
+
+    /* Set once an optional secondary index has been built from every message. <indexPrefix> */
+    OptionalIndexComplete = 30,
+
+    /* Used to index casts by the urls and casts they embed. <embedKey>:<tsHash>:<fid> */
+    CastsByEmbed = 31,
+
+    /* Every CastAdd in time order. <tsHash>:<fid> */
+    CastsByTime = 32,
+
+    /* The proof that deleted an fname, so older proofs for it can't be merged again. <name> */
+    FNameDeletion = 33,
+
+    /* Every fname proof, transfer and deletion. <nameLength>:<name>:<timestamp>:<fid> */
+    FNameHistory = 34,
+
+    /* FNameHistory entries a fid was the owner or previous owner in. <fid>:<timestamp>:<name> */
+    FNameHistoryByFid = 35,
 }
 
 /** Copied from the JS code */
@@ -124,6 +151,9 @@ pub enum UserPostfix {
 
     /* Link Compact State set */
     LinkCompactStateMessage = 100,
+
+    /* Reaction Compact State set */
+    ReactionCompactStateMessage = 101,
 }
 
 impl UserPostfix {
@@ -158,44 +188,47 @@ impl IntoI32 for MessageType {
 }
 
 /** Convert a specific message type (CastAdd / CastRemove) to a class of message (CastMessage) */
-pub fn type_to_set_postfix(message_type: MessageType) -> UserPostfix {
+pub fn type_to_set_postfix(message_type: MessageType) -> Result<UserPostfix, HubError> {
     if message_type == MessageType::CastAdd || message_type == MessageType::CastRemove {
-        return UserPostfix::CastMessage;
+        return Ok(UserPostfix::CastMessage);
     }
 
     if message_type == MessageType::ReactionAdd || message_type == MessageType::ReactionRemove {
-        return UserPostfix::ReactionMessage;
+        return Ok(UserPostfix::ReactionMessage);
     }
 
     if message_type == MessageType::VerificationAddEthAddress
         || message_type == MessageType::VerificationRemove
     {
-        return UserPostfix::VerificationMessage;
+        return Ok(UserPostfix::VerificationMessage);
     }
 
     if message_type == MessageType::UserDataAdd {
-        return UserPostfix::UserDataMessage;
+        return Ok(UserPostfix::UserDataMessage);
     }
 
     if message_type == MessageType::LinkAdd || message_type == MessageType::LinkRemove {
-        return UserPostfix::LinkMessage;
+        return Ok(UserPostfix::LinkMessage);
     }
 
     if message_type == MessageType::UsernameProof {
-        return UserPostfix::UsernameProofMessage;
+        return Ok(UserPostfix::UsernameProofMessage);
     }
 
-    panic!("invalid type");
+    Err(HubError::validation_failure(&format!(
+        "no message set for type {}",
+        message_type.as_str_name()
+    )))
 }
 
 pub fn make_ts_hash(timestamp: u32, hash: &Vec<u8>) -> Result<[u8; TS_HASH_LENGTH], HubError> {
     // No need to check if timestamp > 2^32 because it's already a u32
 
     if hash.len() != HASH_LENGTH {
-        return Err(HubError {
-            code: "internal_error".to_string(),
-            message: "hash length is not 20".to_string(),
-        });
+        return Err(HubError::new(
+            HubErrorCode::Unknown,
+            "hash length is not 20",
+        ));
     }
 
     let mut ts_hash = [0u8; 24];
@@ -273,10 +306,10 @@ pub fn get_message(
     match db.get(&key)? {
         Some(bytes) => match message_decode(bytes.as_slice()) {
             Ok(message) => Ok(Some(message)),
-            Err(_) => Err(HubError {
-                code: "db.internal_error".to_string(),
-                message: "could not decode message".to_string(),
-            }),
+            Err(_) => Err(HubError::new(
+                HubErrorCode::StorageFailure,
+                "could not decode message",
+            )),
         },
         None => Ok(None),
     }
@@ -311,13 +344,50 @@ pub fn get_messages_page_by_prefix<F>(
     page_options: &PageOptions,
     filter: F,
 ) -> Result<MessagesPage, HubError>
+where
+    F: Fn(&MessageProto) -> bool,
+{
+    get_messages_page_by_prefix_in_time_range(db, prefix, None, None, page_options, filter)
+}
+
+/**
+ * The [start, stop) key suffixes for messages with timestamps in [start_time, stop_time]. Message
+ * keys end in a tsHash, which starts with the big-endian timestamp, so these can be appended to a
+ * message key prefix to seek straight to the time range.
+ */
+pub fn make_time_range_bounds(
+    start_time: Option<u32>,
+    stop_time: Option<u32>,
+) -> (Option<Vec<u8>>, Option<Vec<u8>>) {
+    let start = start_time.map(|t| t.to_be_bytes().to_vec());
+    let stop = match stop_time {
+        Some(t) if t < u32::MAX => Some((t + 1).to_be_bytes().to_vec()),
+        _ => None,
+    };
+
+    (start, stop)
+}
+
+/**
+ * Same as get_messages_page_by_prefix, but only for messages with timestamps in
+ * [start_time, stop_time]. The prefix must be followed by the tsHash in the keys it covers, e.g. a
+ * `make_message_primary_key` prefix.
+ */
+pub fn get_messages_page_by_prefix_in_time_range<F>(
+    db: &RocksDB,
+    prefix: &[u8],
+    start_time: Option<u32>,
+    stop_time: Option<u32>,
+    page_options: &PageOptions,
+    filter: F,
+) -> Result<MessagesPage, HubError>
 where
     F: Fn(&MessageProto) -> bool,
 {
     let mut messages_bytes = Vec::new();
     let mut last_key = vec![];
 
-    db.for_each_iterator_by_prefix(prefix, page_options, |key, value| {
+    let mut visit = |key: &[u8], value: &[u8]| {
         match message_decode(value) {
             Ok(message) => {
                 if filter(&message) {
@@ -331,12 +401,25 @@ where
 
                 Ok(false) // Continue iterating
             }
-            Err(e) => Err(HubError {
-                code: "db.internal_error".to_string(),
-                message: format!("could not decode message: {}", e),
-            }),
+            Err(e) => Err(HubError::new(
+                HubErrorCode::StorageFailure,
+                format!("could not decode message: {}", e),
+            )),
         }
-    })?;
+    };
+
+    if start_time.is_none() && stop_time.is_none() {
+        db.for_each_iterator_by_prefix(prefix, page_options, &mut visit)?;
+    } else {
+        let (start, stop) = make_time_range_bounds(start_time, stop_time);
+        db.for_each_iterator_by_prefix_in_range(
+            prefix,
+            start.as_deref(),
+            stop.as_deref(),
+            page_options,
+            &mut visit,
+        )?;
+    }
 
     let next_page_token = if last_key.len() > 0 {
         Some(last_key[prefix.len()..].to_vec())
@@ -375,25 +458,35 @@ pub fn message_decode(bytes: &[u8]) -> Result<MessageProto, HubError> {
 
         Ok(msg)
     } else {
-        Err(HubError {
-            code: "db.internal_error".to_string(),
-            message: "could not decode message".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::StorageFailure,
+            "could not decode message",
+        ))
     }
 }
 
+/** The primary key of a message, failing on messages without data or with an unknown type */
+fn make_message_primary_key_for(message: &MessageProto) -> Result<Vec<u8>, HubError> {
+    let data = message
+        .data
+        .as_ref()
+        .ok_or_else(|| HubError::validation_failure("message data is missing"))?;
+    let message_type = MessageType::try_from(data.r#type)
+        .map_err(|_| HubError::validation_failure("message type is invalid"))?;
+    let ts_hash = make_ts_hash(data.timestamp, &message.hash)?;
+
+    Ok(make_message_primary_key(
+        data.fid as u32,
+        type_to_set_postfix(message_type)? as u8,
+        Some(&ts_hash),
+    ))
+}
+
 pub fn put_message_transaction(
     txn: &mut RocksDbTransactionBatch,
     message: &MessageProto,
 ) -> Result<(), HubError> {
-    let ts_hash = make_ts_hash(message.data.as_ref().unwrap().timestamp, &message.hash)?;
-
-    let primary_key = make_message_primary_key(
-        message.data.as_ref().unwrap().fid as u32,
-        type_to_set_postfix(MessageType::try_from(message.data.as_ref().unwrap().r#type).unwrap())
-            as u8,
-        Some(&ts_hash),
-    );
+    let primary_key = make_message_primary_key_for(message)?;
     txn.put(primary_key, message_encode(&message));
 
     Ok(())
@@ -403,31 +496,8 @@ pub fn delete_message_transaction(
     txn: &mut RocksDbTransactionBatch,
     message: &MessageProto,
 ) -> Result<(), HubError> {
-    let ts_hash = make_ts_hash(message.data.as_ref().unwrap().timestamp, &message.hash)?;
-
-    let primary_key = make_message_primary_key(
-        message.data.as_ref().unwrap().fid as u32,
-        type_to_set_postfix(MessageType::try_from(message.data.as_ref().unwrap().r#type).unwrap())
-            as u8,
-        Some(&ts_hash),
-    );
+    let primary_key = make_message_primary_key_for(message)?;
     txn.delete(primary_key);
 
     Ok(())
 }
-
-pub fn is_message_in_time_range(
-    start_time: Option<u32>,
-    stop_time: Option<u32>,
-    message: &MessageProto,
-) -> bool {
-    let start_time = start_time.unwrap_or(std::u32::MIN);
-    let stop_time = stop_time.unwrap_or(std::u32::MAX);
-    match &message.data {
-        None => {
-            // We expect all valid messages to have data
-            return false;
-        }
-        Some(data) => return data.timestamp >= start_time && data.timestamp <= stop_time,
-    };
-}
@@ -1,6 +1,12 @@
+pub use self::cast_search::*;
 pub use self::cast_store::*;
+pub use self::counters::*;
+pub use self::fname_history::*;
+pub use self::index_check::*;
 pub use self::link_store::*;
 pub use self::message::*;
+pub use self::name_resolution::*;
+pub use self::profile::*;
 pub use self::reaction_store::*;
 pub use self::store::*;
 pub use self::store_event_handler::*;
@@ -9,10 +15,16 @@ pub use self::username_proof_store::*;
 pub use self::utils::*;
 pub use self::verification_store::*;
 
+mod cast_search;
 mod cast_store;
+mod counters;
+mod fname_history;
+mod index_check;
 mod link_store;
 mod message;
 mod name_registry_events;
+mod name_resolution;
+mod profile;
 mod reaction_store;
 mod store;
 mod store_event_handler;
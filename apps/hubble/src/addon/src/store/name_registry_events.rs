@@ -5,7 +5,58 @@ use crate::{
     protos::UserNameProof,
 };
 
-use super::{make_fid_key, HubError, RootPrefix};
+use super::{make_fid_key, read_fid_key, HubError, HubErrorCode, RootPrefix, FID_BYTES};
+
+/** What happened to an fname in an FNameHistory entry */
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FnameHistoryKind {
+    /** The name got an owner, after not having one */
+    Register = 1,
+    /** The name moved from one fid to another */
+    Transfer = 2,
+    /** The name got a new proof for the fid that already owned it, e.g. for a new owner address */
+    Update = 3,
+    /** The name's proof was deleted */
+    Delete = 4,
+}
+
+/** An FNameHistory entry: what happened, who owned the name before, and the proof that did it */
+#[derive(Debug, Clone, PartialEq)]
+pub struct FnameHistoryEntry {
+    pub kind: u8,
+    pub previous_fid: u32,
+    pub proof: UserNameProof,
+}
+
+impl FnameHistoryEntry {
+    /** Entries are stored as <kind>:<previousFid>:<proof bytes> */
+    pub fn encode(&self) -> Vec<u8> {
+        let mut value = vec![self.kind];
+        value.extend_from_slice(&make_fid_key(self.previous_fid));
+        value.extend_from_slice(&self.proof.encode_to_vec());
+
+        value
+    }
+
+    pub fn decode(value: &[u8]) -> Result<FnameHistoryEntry, HubError> {
+        if value.len() < 1 + FID_BYTES {
+            return Err(HubError::new(
+                HubErrorCode::Unknown,
+                "could not decode fname history entry",
+            ));
+        }
+
+        let proof = UserNameProof::decode(&value[1 + FID_BYTES..])
+            .map_err(|_| HubError::new(HubErrorCode::Unknown, "could not decode username proof"))?;
+
+        Ok(FnameHistoryEntry {
+            kind: value[0],
+            previous_fid: read_fid_key(&value[1..]),
+            proof,
+        })
+    }
+}
 
 pub fn make_fname_username_proof_key(name: &[u8]) -> Vec<u8> {
     let mut key = Vec::with_capacity(1 + 32);
@@ -22,19 +73,117 @@ pub fn make_fname_username_proof_by_fid_key(fid: u32) -> Vec<u8> {
     key
 }
 
+pub fn make_fname_deletion_key(name: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + 32);
+    key.push(RootPrefix::FNameDeletion as u8);
+    key.extend_from_slice(name);
+    key
+}
+
+/**
+ * The prefix of a name's FNameHistory entries. The name is length prefixed so that the history of
+ * "alice" doesn't include "alicia".
+ */
+pub fn make_fname_history_prefix(name: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + 1 + name.len());
+
+    key.push(RootPrefix::FNameHistory as u8);
+    key.push(name.len() as u8);
+    key.extend_from_slice(name);
+    key
+}
+
+pub fn make_fname_history_key(name: &[u8], timestamp: u64, fid: u32) -> Vec<u8> {
+    let mut key = make_fname_history_prefix(name);
+
+    key.extend_from_slice(&timestamp.to_be_bytes());
+    key.extend_from_slice(&make_fid_key(fid));
+    key
+}
+
+pub fn make_fname_history_by_fid_prefix(fid: u32) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + FID_BYTES);
+
+    key.push(RootPrefix::FNameHistoryByFid as u8);
+    key.extend_from_slice(&make_fid_key(fid));
+    key
+}
+
+pub fn make_fname_history_by_fid_key(fid: u32, timestamp: u64, name: &[u8]) -> Vec<u8> {
+    let mut key = make_fname_history_by_fid_prefix(fid);
+
+    key.extend_from_slice(&timestamp.to_be_bytes());
+    key.extend_from_slice(name);
+    key
+}
+
+/**
+ * Append a proof to the name's history. existing_proof is the proof it replaces, if any. Entries
+ * are never deleted, so the history outlives the proofs in it.
+ */
+pub fn put_fname_history_transaction(
+    txn: &mut RocksDbTransactionBatch,
+    username_proof: &UserNameProof,
+    existing_proof: Option<&UserNameProof>,
+) {
+    let fid = username_proof.fid as u32;
+    let previous_fid = existing_proof.map_or(0, |proof| proof.fid as u32);
+
+    let kind = if fid == 0 {
+        FnameHistoryKind::Delete
+    } else if previous_fid == 0 {
+        FnameHistoryKind::Register
+    } else if previous_fid == fid {
+        FnameHistoryKind::Update
+    } else {
+        FnameHistoryKind::Transfer
+    };
+
+    let entry = FnameHistoryEntry {
+        kind: kind as u8,
+        previous_fid,
+        proof: username_proof.clone(),
+    };
+    let key = make_fname_history_key(&username_proof.name, username_proof.timestamp, fid);
+    txn.put(key.clone(), entry.encode());
+
+    // The entry shows up in the history of both the new and the previous owner
+    let mut owners = vec![fid];
+    if previous_fid != fid {
+        owners.push(previous_fid);
+    }
+    for owner in owners.into_iter().filter(|owner| *owner != 0) {
+        txn.put(
+            make_fname_history_by_fid_key(owner, username_proof.timestamp, &username_proof.name),
+            key.clone(),
+        );
+    }
+}
+
 pub fn get_username_proof(db: &RocksDB, name: &[u8]) -> Result<Option<UserNameProof>, HubError> {
-    let key = make_fname_username_proof_key(name);
-    let buf = db.get(&key)?;
+    decode_username_proof_at(db, &make_fname_username_proof_key(name))
+}
+
+/** The fid 0 proof that deleted a name, if its latest proof was a deletion */
+pub fn get_username_proof_deletion(
+    db: &RocksDB,
+    name: &[u8],
+) -> Result<Option<UserNameProof>, HubError> {
+    decode_username_proof_at(db, &make_fname_deletion_key(name))
+}
+
+fn decode_username_proof_at(db: &RocksDB, key: &[u8]) -> Result<Option<UserNameProof>, HubError> {
+    let buf = db.get(key)?;
     if buf.is_none() {
         return Ok(None);
     }
 
     match UserNameProof::decode(buf.unwrap().as_slice()) {
         Ok(proof) => Ok(Some(proof)),
-        Err(_) => Err(HubError {
-            code: "internal_error".to_string(),
-            message: "could not decode username proof".to_string(),
-        }),
+        Err(_) => Err(HubError::new(
+            HubErrorCode::Unknown,
+            "could not decode username proof",
+        )),
     }
 }
 
@@ -52,10 +201,10 @@ pub fn get_fname_proof_by_fid(db: &RocksDB, fid: u32) -> Result<Option<UserNameP
 
     match UserNameProof::decode(buf.unwrap().as_slice()) {
         Ok(proof) => Ok(Some(proof)),
-        Err(_) => Err(HubError {
-            code: "internal_error".to_string(),
-            message: "could not decode username proof".to_string(),
-        }),
+        Err(_) => Err(HubError::new(
+            HubErrorCode::Unknown,
+            "could not decode username proof",
+        )),
     }
 }
 
@@ -67,6 +216,7 @@ pub fn put_username_proof_transaction(
 
     let primary_key = make_fname_username_proof_key(&username_proof.name);
     txn.put(primary_key.clone(), buf);
+    txn.delete(make_fname_deletion_key(&username_proof.name));
 
     let secondary_key = make_fname_username_proof_by_fid_key(username_proof.fid as u32);
     txn.put(secondary_key, primary_key);
@@ -77,10 +227,14 @@ pub fn delete_username_proof_transaction(
     username_proof: &UserNameProof,
     existing_fid: Option<u32>,
 ) {
-    let buf = username_proof.encode_to_vec();
-
     let primary_key = make_fname_username_proof_key(&username_proof.name);
-    txn.put(primary_key.clone(), buf);
+    txn.delete(primary_key);
+
+    // Only the deletion is kept, so a proof older than it is rejected instead of merging again
+    txn.put(
+        make_fname_deletion_key(&username_proof.name),
+        username_proof.encode_to_vec(),
+    );
 
     if existing_fid.is_some() {
         let secondary_key = make_fname_username_proof_by_fid_key(existing_fid.unwrap());
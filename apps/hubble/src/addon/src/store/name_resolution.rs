@@ -0,0 +1,319 @@
+use super::{
+    hub_error_to_js_throw, make_message_primary_key, message_decode,
+    name_registry_events::{make_fname_username_proof_by_fid_key, make_fname_username_proof_key},
+    read_fid_key,
+    utils::{self, get_store},
+    HubError, HubErrorCode, UserDataStore, UserDataStoreDef, UserPostfix, UsernameProofStoreDef,
+    FID_BYTES,
+};
+use crate::db::ReadSnapshot;
+use crate::panics::{catch_panic, execute};
+use crate::protos::{message_data, UserDataType, UserNameProof, UserNameType};
+use neon::context::{Context, FunctionContext, TaskContext};
+use neon::handle::Handle;
+use neon::object::Object;
+use neon::result::{JsResult, NeonResult};
+use neon::types::buffer::TypedArray;
+use neon::types::{JsNumber, JsObject, JsPromise, JsString};
+use prost::Message as _;
+
+/** How a fid resolves to a name, see `UserDataStore::reverse_resolve` */
+#[derive(Debug, Default)]
+pub struct ReverseResolution {
+    pub fid: u32,
+    /** The username the fid set with USER_DATA_TYPE_USERNAME, if any */
+    pub username: Option<String>,
+    /** Whether username is backed by a proof the fid currently holds */
+    pub verified: bool,
+    /** The proof for the name the fid resolves to, if it has one */
+    pub proof: Option<UserNameProof>,
+}
+
+fn decode_username_proof(bytes: &[u8]) -> Result<UserNameProof, HubError> {
+    UserNameProof::decode(bytes)
+        .map_err(|_| HubError::new(HubErrorCode::Unknown, "could not decode username proof"))
+}
+
+/**
+ * The fname proof for a name. Deleted fname proofs used to be written back with fid 0 instead of
+ * being deleted, so those don't count.
+ */
+fn get_fname_proof(
+    snapshot: &ReadSnapshot,
+    name: &[u8],
+) -> Result<Option<UserNameProof>, HubError> {
+    match snapshot.get(&make_fname_username_proof_key(name))? {
+        Some(bytes) => {
+            let proof = decode_username_proof(&bytes)?;
+            Ok(if proof.fid == 0 { None } else { Some(proof) })
+        }
+        None => Ok(None),
+    }
+}
+
+fn get_fname_proof_by_fid(
+    snapshot: &ReadSnapshot,
+    fid: u32,
+) -> Result<Option<UserNameProof>, HubError> {
+    match snapshot.get(&make_fname_username_proof_by_fid_key(fid))? {
+        Some(primary_key) => match snapshot.get(&primary_key)? {
+            Some(bytes) => {
+                let proof = decode_username_proof(&bytes)?;
+                Ok(if proof.fid == fid as u64 {
+                    Some(proof)
+                } else {
+                    None
+                })
+            }
+            None => Ok(None),
+        },
+        None => Ok(None),
+    }
+}
+
+/** The body of a fid's ENS UsernameProof message for a name, if the fid has one */
+fn get_ens_proof_by_fid(
+    snapshot: &ReadSnapshot,
+    fid: u32,
+    name: &[u8],
+) -> Result<Option<UserNameProof>, HubError> {
+    let adds_key = UsernameProofStoreDef::make_username_proof_by_fid_key(fid, &name.to_vec());
+    let ts_hash = match snapshot.get(&adds_key)? {
+        Some(ts_hash) => utils::vec_to_u8_24(&Some(ts_hash))?,
+        None => return Ok(None),
+    };
+
+    let primary_key =
+        make_message_primary_key(fid, UserPostfix::UsernameProofMessage as u8, Some(&ts_hash));
+    let message_bytes = match snapshot.get(&primary_key)? {
+        Some(message_bytes) => message_bytes,
+        None => return Ok(None),
+    };
+
+    match message_decode(&message_bytes)?
+        .data
+        .and_then(|data| data.body)
+    {
+        Some(message_data::Body::UsernameProofBody(proof))
+            if proof.r#type == UserNameType::UsernameTypeEnsL1 as i32
+                && proof.fid == fid as u64 =>
+        {
+            Ok(Some(proof))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn get_ens_proof(snapshot: &ReadSnapshot, name: &[u8]) -> Result<Option<UserNameProof>, HubError> {
+    let by_name_key = UsernameProofStoreDef::make_username_proof_by_name_key(&name.to_vec());
+    match snapshot.get(&by_name_key)? {
+        Some(fid) if fid.len() == FID_BYTES => {
+            get_ens_proof_by_fid(snapshot, read_fid_key(&fid), name)
+        }
+        _ => Ok(None),
+    }
+}
+
+/** The fid's oldest ENS proof, which is the ENS name it resolves to without a username */
+fn get_oldest_ens_proof(
+    snapshot: &ReadSnapshot,
+    fid: u32,
+) -> Result<Option<UserNameProof>, HubError> {
+    let mut names = vec![];
+    let prefix = UsernameProofStoreDef::make_username_proof_by_fid_key(fid, &vec![]);
+    snapshot.for_each_iterator_by_prefix(&prefix, |key, _| {
+        names.push(key[prefix.len()..].to_vec());
+        Ok(false) // Continue iterating
+    })?;
+
+    let mut oldest: Option<UserNameProof> = None;
+    for name in names {
+        if let Some(proof) = get_ens_proof_by_fid(snapshot, fid, &name)? {
+            if oldest
+                .as_ref()
+                .map_or(true, |o| proof.timestamp < o.timestamp)
+            {
+                oldest = Some(proof);
+            }
+        }
+    }
+
+    Ok(oldest)
+}
+
+fn set_proof_bytes<'a>(
+    cx: &mut TaskContext<'a>,
+    js_object: Handle<'a, JsObject>,
+    proof: Option<&UserNameProof>,
+) -> NeonResult<()> {
+    if let Some(proof) = proof {
+        let proof_bytes = proof.encode_to_vec();
+        let mut js_buffer = cx.buffer(proof_bytes.len())?;
+        js_buffer.as_mut_slice(cx).copy_from_slice(&proof_bytes);
+        js_object.set(cx, "proofBytes", js_buffer)?;
+    } else {
+        let undefined_obj = cx.undefined();
+        js_object.set(cx, "proofBytes", undefined_obj)?;
+    }
+
+    Ok(())
+}
+
+impl UserDataStore {
+    /**
+     * Find the proof that a fid owns a name. Fnames take precedence over ENS names, though a
+     * valid fname can't end in .eth so a name is never both. Deleted and revoked proofs don't
+     * resolve.
+     */
+    pub fn resolve_name(
+        snapshot: &ReadSnapshot,
+        name: &[u8],
+    ) -> Result<Option<UserNameProof>, HubError> {
+        if name.is_empty() {
+            return Err(HubError::invalid_parameter("name is required"));
+        }
+
+        match get_fname_proof(snapshot, name)? {
+            Some(proof) => Ok(Some(proof)),
+            None => get_ens_proof(snapshot, name),
+        }
+    }
+
+    /**
+     * Find the name a fid goes by. That's its username if a proof it holds backs it, and
+     * otherwise its fname, or else its oldest ENS name.
+     */
+    pub fn reverse_resolve(
+        snapshot: &ReadSnapshot,
+        fid: u32,
+    ) -> Result<ReverseResolution, HubError> {
+        if fid == 0 {
+            return Err(HubError::invalid_parameter("fid is required"));
+        }
+
+        let username_key =
+            UserDataStoreDef::make_user_data_adds_key(fid, UserDataType::Username as i32);
+        let mut username = None;
+        if let Some(ts_hash) = snapshot.get(&username_key)? {
+            let primary_key = make_message_primary_key(
+                fid,
+                UserPostfix::UserDataMessage as u8,
+                Some(&utils::vec_to_u8_24(&Some(ts_hash))?),
+            );
+            if let Some(message_bytes) = snapshot.get(&primary_key)? {
+                if let Some(message_data::Body::UserDataBody(body)) =
+                    message_decode(&message_bytes)?
+                        .data
+                        .and_then(|data| data.body)
+                {
+                    if !body.value.is_empty() {
+                        username = Some(body.value);
+                    }
+                }
+            }
+        }
+
+        let username_proof = match &username {
+            Some(username) => Self::resolve_name(snapshot, username.as_bytes())?
+                .filter(|proof| proof.fid == fid as u64),
+            None => None,
+        };
+        let verified = username_proof.is_some();
+
+        let proof = match username_proof {
+            Some(proof) => Some(proof),
+            None => match get_fname_proof_by_fid(snapshot, fid)? {
+                Some(proof) => Some(proof),
+                None => get_oldest_ens_proof(snapshot, fid)?,
+            },
+        };
+
+        Ok(ReverseResolution {
+            fid,
+            username,
+            verified,
+            proof,
+        })
+    }
+
+    pub fn js_resolve_name(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let name = cx.argument::<JsString>(0)?.value(&mut cx);
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let proof = catch_panic(|| {
+                store
+                    .db()
+                    .with_read_snapshot(|snapshot| Self::resolve_name(snapshot, name.as_bytes()))
+            });
+
+            deferred.settle_with(&channel, move |mut cx| match proof {
+                Ok(Some(proof)) => {
+                    let js_object = JsObject::new(&mut cx);
+                    let js_fid = cx.number(proof.fid as f64);
+                    js_object.set(&mut cx, "fid", js_fid)?;
+                    set_proof_bytes(&mut cx, js_object, Some(&proof))?;
+
+                    Ok(js_object)
+                }
+                Ok(None) => hub_error_to_js_throw(
+                    &mut cx,
+                    HubError::new(
+                        HubErrorCode::NotFound,
+                        format!("NotFound: no proof found for name {}", name),
+                    ),
+                ),
+                Err(e) => hub_error_to_js_throw(&mut cx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+
+    pub fn js_reverse_resolve(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let resolution = catch_panic(|| {
+                store
+                    .db()
+                    .with_read_snapshot(|snapshot| Self::reverse_resolve(snapshot, fid))
+            });
+
+            deferred.settle_with(&channel, move |mut cx| {
+                let resolution = match resolution {
+                    Ok(resolution) => resolution,
+                    Err(e) => return hub_error_to_js_throw(&mut cx, e),
+                };
+
+                let js_object = JsObject::new(&mut cx);
+                let js_fid = cx.number(resolution.fid);
+                js_object.set(&mut cx, "fid", js_fid)?;
+                if let Some(username) = &resolution.username {
+                    let js_username = cx.string(username);
+                    js_object.set(&mut cx, "username", js_username)?;
+                } else {
+                    let undefined_obj = cx.undefined();
+                    js_object.set(&mut cx, "username", undefined_obj)?;
+                }
+                let js_verified = cx.boolean(resolution.verified);
+                js_object.set(&mut cx, "verified", js_verified)?;
+
+                set_proof_bytes(&mut cx, js_object, resolution.proof.as_ref())?;
+
+                Ok(js_object)
+            });
+        });
+
+        Ok(promise)
+    }
+}
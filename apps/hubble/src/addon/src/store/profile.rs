@@ -0,0 +1,269 @@
+use super::{
+    hub_error_to_js_throw, make_message_primary_key, message_decode,
+    name_registry_events::make_fname_username_proof_by_fid_key,
+    utils::{self, get_store},
+    HubError, UserDataStore, UserDataStoreDef, UserPostfix, UsernameProofStoreDef,
+    VerificationStoreDef,
+};
+use crate::db::ReadSnapshot;
+use crate::panics::{catch_panic, execute};
+use crate::protos::{message_data, UserNameType};
+use neon::context::{Context, FunctionContext, TaskContext};
+use neon::object::Object;
+use neon::result::JsResult;
+use neon::types::buffer::TypedArray;
+use neon::types::{JsArray, JsNumber, JsObject, JsPromise};
+use std::collections::BTreeMap;
+
+/** Most fids a single getProfiles call can ask for */
+pub const MAX_PROFILE_FIDS: usize = 100;
+
+/** A fid's profile, read from one snapshot so that its parts agree with each other */
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub fid: u32,
+    /** The UserDataAdd of each type the fid has set, in UserDataType order */
+    pub user_data_bytes: Vec<Vec<u8>>,
+    /** The fname the fid owns, as a UserNameProof */
+    pub fname_proof_bytes: Option<Vec<u8>>,
+    /** The fid's ENS UsernameProof messages */
+    pub ens_proofs_bytes: Vec<Vec<u8>>,
+    /**
+     * The fid's primary verification for each protocol, which is its oldest verification on that
+     * protocol
+     */
+    pub verifications_bytes: Vec<Vec<u8>>,
+}
+
+/**
+ * Read the messages an adds index under a prefix points to. The index values are the tsHashes of
+ * messages in the `postfix` set of the fid.
+ */
+fn get_messages_by_adds_index(
+    snapshot: &ReadSnapshot,
+    fid: u32,
+    prefix: &[u8],
+    postfix: UserPostfix,
+) -> Result<Vec<Vec<u8>>, HubError> {
+    let mut message_keys = vec![];
+    snapshot.for_each_iterator_by_prefix(prefix, |_, value| {
+        let ts_hash = utils::vec_to_u8_24(&Some(value.to_vec()))?;
+        message_keys.push(make_message_primary_key(fid, postfix as u8, Some(&ts_hash)));
+
+        Ok(false) // Continue iterating
+    })?;
+
+    let mut messages_bytes = vec![];
+    for key in message_keys {
+        if let Some(message_bytes) = snapshot.get(&key)? {
+            messages_bytes.push(message_bytes);
+        }
+    }
+
+    Ok(messages_bytes)
+}
+
+fn encode_buffers_to_js_array<'a>(
+    cx: &mut TaskContext<'a>,
+    buffers: &[Vec<u8>],
+) -> JsResult<'a, JsArray> {
+    let js_array = JsArray::new(cx, buffers.len());
+    for (i, bytes) in buffers.iter().enumerate() {
+        let mut js_buffer = cx.buffer(bytes.len())?;
+        js_buffer.as_mut_slice(cx).copy_from_slice(bytes);
+        js_array.set(cx, i as u32, js_buffer)?;
+    }
+
+    Ok(js_array)
+}
+
+fn encode_profile_to_js_object<'a>(
+    cx: &mut TaskContext<'a>,
+    profile: &Profile,
+) -> JsResult<'a, JsObject> {
+    let js_object = JsObject::new(cx);
+
+    let js_fid = cx.number(profile.fid);
+    js_object.set(cx, "fid", js_fid)?;
+
+    let js_user_data = encode_buffers_to_js_array(cx, &profile.user_data_bytes)?;
+    js_object.set(cx, "userDataBytes", js_user_data)?;
+
+    if let Some(proof_bytes) = &profile.fname_proof_bytes {
+        let mut js_buffer = cx.buffer(proof_bytes.len())?;
+        js_buffer.as_mut_slice(cx).copy_from_slice(proof_bytes);
+        js_object.set(cx, "fnameProofBytes", js_buffer)?;
+    } else {
+        let undefined_obj = cx.undefined();
+        js_object.set(cx, "fnameProofBytes", undefined_obj)?;
+    }
+
+    let js_ens_proofs = encode_buffers_to_js_array(cx, &profile.ens_proofs_bytes)?;
+    js_object.set(cx, "ensProofsBytes", js_ens_proofs)?;
+
+    let js_verifications = encode_buffers_to_js_array(cx, &profile.verifications_bytes)?;
+    js_object.set(cx, "verificationsBytes", js_verifications)?;
+
+    Ok(js_object)
+}
+
+impl UserDataStore {
+    /**
+     * Get a fid's user data, fname and ENS proofs and primary verifications. A fid without any
+     * of them gets an empty profile, not an error.
+     */
+    pub fn get_profile(snapshot: &ReadSnapshot, fid: u32) -> Result<Profile, HubError> {
+        if fid == 0 {
+            return Err(HubError::invalid_parameter("fid is required"));
+        }
+
+        let user_data_bytes = get_messages_by_adds_index(
+            snapshot,
+            fid,
+            &UserDataStoreDef::make_user_data_adds_key(fid, 0),
+            UserPostfix::UserDataMessage,
+        )?;
+
+        let fname_proof_bytes = match snapshot.get(&make_fname_username_proof_by_fid_key(fid))? {
+            Some(proof_key) => snapshot.get(&proof_key)?,
+            None => None,
+        };
+
+        let mut ens_proofs_bytes = vec![];
+        for message_bytes in get_messages_by_adds_index(
+            snapshot,
+            fid,
+            &UsernameProofStoreDef::make_username_proof_by_fid_key(fid, &vec![]),
+            UserPostfix::UsernameProofMessage,
+        )? {
+            let is_ens = match message_decode(&message_bytes)?
+                .data
+                .and_then(|data| data.body)
+            {
+                Some(message_data::Body::UsernameProofBody(proof)) => {
+                    proof.r#type == UserNameType::UsernameTypeEnsL1 as i32
+                }
+                _ => false,
+            };
+            if is_ens {
+                ens_proofs_bytes.push(message_bytes);
+            }
+        }
+
+        // The oldest verification of each protocol, keyed by protocol so they come out in order
+        let mut primary_verifications: BTreeMap<i32, (u32, Vec<u8>)> = BTreeMap::new();
+        for message_bytes in get_messages_by_adds_index(
+            snapshot,
+            fid,
+            &VerificationStoreDef::make_verification_adds_key(fid, &[]),
+            UserPostfix::VerificationMessage,
+        )? {
+            let data = match message_decode(&message_bytes)?.data {
+                Some(data) => data,
+                None => continue,
+            };
+            let protocol = match &data.body {
+                Some(message_data::Body::VerificationAddAddressBody(body)) => body.protocol,
+                _ => continue,
+            };
+
+            match primary_verifications.get(&protocol) {
+                Some((timestamp, _)) if *timestamp <= data.timestamp => {}
+                _ => {
+                    primary_verifications.insert(protocol, (data.timestamp, message_bytes));
+                }
+            }
+        }
+
+        Ok(Profile {
+            fid,
+            user_data_bytes,
+            fname_proof_bytes,
+            ens_proofs_bytes,
+            verifications_bytes: primary_verifications
+                .into_values()
+                .map(|(_, message_bytes)| message_bytes)
+                .collect(),
+        })
+    }
+
+    /** Get the profiles of several fids, in the same order, from one snapshot */
+    pub fn get_profiles(snapshot: &ReadSnapshot, fids: &[u32]) -> Result<Vec<Profile>, HubError> {
+        if fids.len() > MAX_PROFILE_FIDS {
+            return Err(HubError::invalid_parameter(&format!(
+                "at most {} fids can be requested at once",
+                MAX_PROFILE_FIDS
+            )));
+        }
+
+        fids.iter()
+            .map(|fid| Self::get_profile(snapshot, *fid))
+            .collect()
+    }
+
+    pub fn js_get_profile(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let profile = catch_panic(|| {
+                store
+                    .db()
+                    .with_read_snapshot(|snapshot| Self::get_profile(snapshot, fid))
+            });
+
+            deferred.settle_with(&channel, move |mut cx| match profile {
+                Ok(profile) => encode_profile_to_js_object(&mut cx, &profile),
+                Err(e) => hub_error_to_js_throw(&mut cx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+
+    pub fn js_get_profiles(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let js_fids = cx.argument::<JsArray>(0)?.to_vec(&mut cx)?;
+        let mut fids = Vec::with_capacity(js_fids.len());
+        for js_fid in js_fids {
+            fids.push(
+                js_fid
+                    .downcast_or_throw::<JsNumber, _>(&mut cx)?
+                    .value(&mut cx) as u32,
+            );
+        }
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let profiles = catch_panic(|| {
+                store
+                    .db()
+                    .with_read_snapshot(|snapshot| Self::get_profiles(snapshot, &fids))
+            });
+
+            deferred.settle_with(&channel, move |mut cx| {
+                let profiles = match profiles {
+                    Ok(profiles) => profiles,
+                    Err(e) => return hub_error_to_js_throw(&mut cx, e),
+                };
+
+                let js_profiles = JsArray::new(&mut cx, profiles.len());
+                for (i, profile) in profiles.iter().enumerate() {
+                    let js_profile = encode_profile_to_js_object(&mut cx, profile)?;
+                    js_profiles.set(&mut cx, i as u32, js_profile)?;
+                }
+
+                Ok(js_profiles)
+            });
+        });
+
+        Ok(promise)
+    }
+}
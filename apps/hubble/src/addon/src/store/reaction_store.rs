@@ -1,16 +1,17 @@
 use super::{
-    deferred_settle_messages, hub_error_to_js_throw, make_cast_id_key, make_fid_key, make_user_key,
-    message,
+    deferred_settle_messages, get_count, hub_error_to_js_throw, make_cast_id_key, make_fid_key,
+    make_reactions_count_key, make_user_key, message,
     store::{Store, StoreDef},
-    utils::{get_page_options, get_store},
-    HubError, IntoU8, MessagesPage, PageOptions, RootPrefix, StoreEventHandler, UserPostfix,
-    PAGE_SIZE_MAX, TS_HASH_LENGTH,
+    utils::{get_page_options, get_store, get_time_range},
+    CompactState, CounterType, HubError, HubErrorCode, IntoU8, MessagesPage, PageOptions,
+    RootPrefix, StoreEventHandler, UserPostfix, PAGE_SIZE_MAX, TS_HASH_LENGTH,
 };
+use crate::panics::{catch_panic, execute};
+use crate::protos::message_data;
 use crate::{
     db::{RocksDB, RocksDbTransactionBatch},
     protos::{self, reaction_body::Target, Message, MessageType, ReactionBody, ReactionType},
 };
-use crate::{protos::message_data, THREAD_POOL};
 use neon::{
     context::{Context, FunctionContext},
     result::JsResult,
@@ -51,11 +52,67 @@ impl StoreDef for ReactionStoreDef {
     }
 
     fn compact_state_message_type(&self) -> u8 {
-        MessageType::None as u8
+        MessageType::ReactionCompactState as u8
     }
 
-    fn is_compact_state_type(&self, _message: &Message) -> bool {
-        false
+    fn is_compact_state_type(&self, message: &Message) -> bool {
+        message.signature_scheme == protos::SignatureScheme::Ed25519 as i32
+            && message.data.as_ref().is_some_and(|data| {
+                data.r#type == MessageType::ReactionCompactState as i32 && data.body.is_some()
+            })
+    }
+
+    fn read_compact_state(&self, message: &Message) -> Result<CompactState, HubError> {
+        let data = message.data.as_ref().ok_or(HubError::validation_failure(
+            "Invalid compact state message: no data",
+        ))?;
+
+        match &data.body {
+            Some(message_data::Body::ReactionCompactStateBody(body)) => {
+                let cast_targets = body
+                    .target_cast_ids
+                    .iter()
+                    .map(|cast_id| Self::make_target_key(&Target::TargetCastId(cast_id.clone())));
+                let url_targets = body
+                    .target_urls
+                    .iter()
+                    .map(|url| Self::make_target_key(&Target::TargetUrl(url.clone())));
+
+                Ok(CompactState {
+                    fid: data.fid as u32,
+                    timestamp: data.timestamp,
+                    scope: vec![body.r#type as u8],
+                    targets: cast_targets.chain(url_targets).collect(),
+                })
+            }
+            _ => Err(HubError::validation_failure(
+                "Invalid compact state message: No reaction compact state body",
+            )),
+        }
+    }
+
+    /**
+     * A reaction compact state only covers reactions of its type. Of those, it keeps the adds to
+     * its targets and no removes.
+     */
+    fn compact_state_keeps(&self, compact_state: &CompactState, message: &Message) -> bool {
+        let reaction_body = match &message.data.as_ref().unwrap().body {
+            Some(message_data::Body::ReactionBody(reaction_body)) => reaction_body,
+            _ => return true,
+        };
+        if compact_state.scope != [reaction_body.r#type as u8] {
+            return true;
+        }
+        if self.is_remove_type(message) {
+            return false;
+        }
+
+        match &reaction_body.target {
+            Some(target) => compact_state
+                .targets
+                .contains(&Self::make_target_key(target)),
+            None => true,
+        }
     }
 
     fn build_secondary_indices(
@@ -92,6 +149,31 @@ impl StoreDef for ReactionStoreDef {
         Ok(())
     }
 
+    fn secondary_index_prefixes(&self) -> Vec<u8> {
+        vec![RootPrefix::ReactionsByTarget as u8]
+    }
+
+    fn counter_types(&self) -> Vec<CounterType> {
+        vec![CounterType::ReactionsByTarget]
+    }
+
+    fn update_counters(
+        &self,
+        txn: &mut RocksDbTransactionBatch,
+        message: &Message,
+        delta: i64,
+    ) -> Result<(), HubError> {
+        if let Some(message_data::Body::ReactionBody(ReactionBody {
+            r#type,
+            target: Some(target),
+        })) = &message.data.as_ref().unwrap().body
+        {
+            txn.increment(make_reactions_count_key(target, *r#type), delta);
+        }
+
+        Ok(())
+    }
+
     fn find_merge_add_conflicts(
         &self,
         _db: &RocksDB,
@@ -114,10 +196,10 @@ impl StoreDef for ReactionStoreDef {
         let reaction_body = match message.data.as_ref().unwrap().body.as_ref().unwrap() {
             message_data::Body::ReactionBody(reaction_body) => reaction_body,
             _ => {
-                return Err(HubError {
-                    code: "bad_request.validation_failure".to_string(),
-                    message: "Invalid reaction body".to_string(),
-                })
+                return Err(HubError::new(
+                    HubErrorCode::ValidationFailure,
+                    "Invalid reaction body",
+                ))
             }
         };
 
@@ -132,10 +214,10 @@ impl StoreDef for ReactionStoreDef {
         let reaction_body = match message.data.as_ref().unwrap().body.as_ref().unwrap() {
             message_data::Body::ReactionBody(reaction_body) => reaction_body,
             _ => {
-                return Err(HubError {
-                    code: "bad_request.validation_failure".to_string(),
-                    message: "Invalid reaction body".to_string(),
-                })
+                return Err(HubError::new(
+                    HubErrorCode::ValidationFailure,
+                    "Invalid reaction body",
+                ))
             }
         };
 
@@ -146,18 +228,34 @@ impl StoreDef for ReactionStoreDef {
         )
     }
 
-    fn make_compact_state_add_key(&self, _message: &Message) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "Reaction Store doesn't support compact state".to_string(),
-        })
+    fn make_compact_state_add_key(&self, message: &Message) -> Result<Vec<u8>, HubError> {
+        let data = message
+            .data
+            .as_ref()
+            .ok_or(HubError::invalid_parameter("invalid message data"))?;
+
+        // Reactions look up the compact state of their type
+        let reaction_type = match data.body.as_ref() {
+            Some(message_data::Body::ReactionCompactStateBody(body)) => body.r#type,
+            Some(message_data::Body::ReactionBody(body)) => body.r#type,
+            _ => {
+                return Err(HubError::invalid_parameter(
+                    "reaction_compact_state_body not specified",
+                ))
+            }
+        };
+
+        let mut key = self.make_compact_state_prefix(data.fid as u32)?;
+        key.push(reaction_type as u8);
+
+        Ok(key)
     }
 
-    fn make_compact_state_prefix(&self, _fid: u32) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "Reaction Store doesn't support compact state".to_string(),
-        })
+    fn make_compact_state_prefix(&self, fid: u32) -> Result<Vec<u8>, HubError> {
+        let mut prefix = make_user_key(fid);
+        prefix.push(UserPostfix::ReactionCompactStateMessage.as_u8());
+
+        Ok(prefix)
     }
 
     fn get_prune_size_limit(&self) -> u32 {
@@ -174,15 +272,15 @@ impl ReactionStoreDef {
         // Make sure at least one of targetCastId or targetUrl is set
         let reaction_body = match message.data.as_ref().unwrap().body.as_ref().unwrap() {
             message_data::Body::ReactionBody(reaction_body) => reaction_body,
-            _ => Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Invalid reaction body".to_string(),
-            })?,
+            _ => Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "Invalid reaction body",
+            ))?,
         };
-        let target = reaction_body.target.as_ref().ok_or(HubError {
-            code: "bad_request.validation_failure".to_string(),
-            message: "Invalid reaction body".to_string(),
-        })?;
+        let target = reaction_body.target.as_ref().ok_or(HubError::new(
+            HubErrorCode::ValidationFailure,
+            "Invalid reaction body",
+        ))?;
 
         let by_target_key = ReactionStoreDef::make_reactions_by_target_key(
             target,
@@ -225,10 +323,10 @@ impl ReactionStoreDef {
         target: Option<&Target>,
     ) -> Result<Vec<u8>, HubError> {
         if target.is_some() && r#type == 0 {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "targetId provided without type".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "targetId provided without type",
+            ));
         }
         let mut key = Vec::with_capacity(33 + 1 + 1 + 28);
 
@@ -251,10 +349,10 @@ impl ReactionStoreDef {
         target: Option<&Target>,
     ) -> Result<Vec<u8>, HubError> {
         if target.is_some() && r#type == 0 {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "targetId provided without type".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "targetId provided without type",
+            ));
         }
         let mut key = Vec::with_capacity(33 + 1 + 1 + 28);
 
@@ -314,15 +412,20 @@ impl ReactionStore {
 
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
-        let reaction_type = cx.argument::<JsNumber>(1).unwrap().value(&mut cx) as i32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+        let reaction_type = cx.argument::<JsNumber>(1)?.value(&mut cx) as i32;
 
         let target_cast_id_buffer = cx.argument::<JsBuffer>(2)?;
         let target_cast_id_bytes = target_cast_id_buffer.as_slice(&cx);
         let target_cast_id = if target_cast_id_bytes.len() > 0 {
             match protos::CastId::decode(target_cast_id_bytes) {
                 Ok(cast_id) => Some(cast_id),
-                Err(e) => return cx.throw_error(e.to_string()),
+                Err(e) => {
+                    return hub_error_to_js_throw(
+                        &mut cx,
+                        HubError::new(HubErrorCode::ParseFailure, e.to_string()),
+                    )
+                }
             }
         } else {
             None
@@ -332,7 +435,10 @@ impl ReactionStore {
 
         // We need at least one of target_cast_id or target_url
         if target_cast_id.is_none() && target_url.is_empty() {
-            return cx.throw_error("target_cast_id or target_url is required");
+            return hub_error_to_js_throw(
+                &mut cx,
+                HubError::invalid_parameter("target_cast_id or target_url is required"),
+            );
         }
 
         let target = if target_cast_id.is_some() {
@@ -343,10 +449,11 @@ impl ReactionStore {
 
         let result = match Self::get_reaction_add(&store, fid, reaction_type, target) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{} for {}",
-                "not_found", "reactionAddMessage not found", fid
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found(&format!("reactionAddMessage not found for {}", fid))
+                    .with_fid(fid),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -388,15 +495,20 @@ impl ReactionStore {
     pub fn js_get_reaction_remove(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
-        let reaction_type = cx.argument::<JsNumber>(1).unwrap().value(&mut cx) as i32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+        let reaction_type = cx.argument::<JsNumber>(1)?.value(&mut cx) as i32;
 
         let target_cast_id_buffer = cx.argument::<JsBuffer>(2)?;
         let target_cast_id_bytes = target_cast_id_buffer.as_slice(&cx);
         let target_cast_id = if target_cast_id_bytes.len() > 0 {
             match protos::CastId::decode(target_cast_id_bytes) {
                 Ok(cast_id) => Some(cast_id),
-                Err(e) => return cx.throw_error(e.to_string()),
+                Err(e) => {
+                    return hub_error_to_js_throw(
+                        &mut cx,
+                        HubError::new(HubErrorCode::ParseFailure, e.to_string()),
+                    )
+                }
             }
         } else {
             None
@@ -406,7 +518,10 @@ impl ReactionStore {
 
         // We need at least one of target_cast_id or target_url
         if target_cast_id.is_none() && target_url.is_empty() {
-            return cx.throw_error("target_cast_id or target_url is required");
+            return hub_error_to_js_throw(
+                &mut cx,
+                HubError::invalid_parameter("target_cast_id or target_url is required"),
+            );
         }
 
         let target = if target_cast_id.is_some() {
@@ -417,10 +532,11 @@ impl ReactionStore {
 
         let result = match ReactionStore::get_reaction_remove(&store, fid, reaction_type, target) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{} for {}",
-                "not_found", "reactionRemoveMessage not found", fid
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found(&format!("reactionRemoveMessage not found for {}", fid))
+                    .with_fid(fid),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -440,9 +556,13 @@ impl ReactionStore {
         fid: u32,
         reaction_type: i32,
         page_options: &PageOptions,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
     ) -> Result<MessagesPage, HubError> {
         store.get_adds_by_fid(
             fid,
+            start_time,
+            stop_time,
             page_options,
             Some(|message: &Message| {
                 if let Some(reaction_body) = &message.data.as_ref().unwrap().body {
@@ -458,6 +578,36 @@ impl ReactionStore {
         )
     }
 
+    pub fn get_reaction_compact_state_message_by_fid(
+        store: &Store,
+        fid: u32,
+        page_options: &PageOptions,
+    ) -> Result<MessagesPage, HubError> {
+        store.get_compact_state_messages_by_fid(fid, page_options)
+    }
+
+    pub fn js_get_reaction_compact_state_message_by_fid(
+        mut cx: FunctionContext,
+    ) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+        let page_options = get_page_options(&mut cx, 1)?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let messages = catch_panic(|| {
+                ReactionStore::get_reaction_compact_state_message_by_fid(&store, fid, &page_options)
+            });
+
+            deferred_settle_messages(deferred, &channel, messages);
+        });
+
+        Ok(promise)
+    }
+
     pub fn create_reaction_store(mut cx: FunctionContext) -> JsResult<JsBox<Arc<Store>>> {
         let db_js_box = cx.argument::<JsBox<Arc<RocksDB>>>(0)?;
         let db = (**db_js_box.borrow()).clone();
@@ -481,16 +631,25 @@ impl ReactionStore {
     pub fn js_get_reaction_adds_by_fid(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
-        let reaction_type = cx.argument::<JsNumber>(1).unwrap().value(&mut cx) as i32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+        let reaction_type = cx.argument::<JsNumber>(1)?.value(&mut cx) as i32;
 
         let page_options = get_page_options(&mut cx, 2)?;
+        let (start_time, stop_time) = get_time_range(&mut cx, 3);
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let messages =
-                ReactionStore::get_reaction_adds_by_fid(&store, fid, reaction_type, &page_options);
+        execute(move || {
+            let messages = catch_panic(|| {
+                ReactionStore::get_reaction_adds_by_fid(
+                    &store,
+                    fid,
+                    reaction_type,
+                    &page_options,
+                    start_time,
+                    stop_time,
+                )
+            });
 
             deferred_settle_messages(deferred, &channel, messages);
         });
@@ -503,9 +662,13 @@ impl ReactionStore {
         fid: u32,
         reaction_type: i32,
         page_options: &PageOptions,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
     ) -> Result<MessagesPage, HubError> {
         store.get_removes_by_fid(
             fid,
+            start_time,
+            stop_time,
             page_options,
             Some(|message: &Message| {
                 if let Some(reaction_body) = &message.data.as_ref().unwrap().body {
@@ -524,20 +687,25 @@ impl ReactionStore {
     pub fn js_get_reaction_removes_by_fid(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
-        let reaction_type = cx.argument::<JsNumber>(1).unwrap().value(&mut cx) as i32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+        let reaction_type = cx.argument::<JsNumber>(1)?.value(&mut cx) as i32;
 
         let page_options = get_page_options(&mut cx, 2)?;
+        let (start_time, stop_time) = get_time_range(&mut cx, 3);
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let messages = ReactionStore::get_reaction_removes_by_fid(
-                &store,
-                fid,
-                reaction_type,
-                &page_options,
-            );
+        execute(move || {
+            let messages = catch_panic(|| {
+                ReactionStore::get_reaction_removes_by_fid(
+                    &store,
+                    fid,
+                    reaction_type,
+                    &page_options,
+                    start_time,
+                    stop_time,
+                )
+            });
 
             deferred_settle_messages(deferred, &channel, messages);
         });
@@ -600,6 +768,84 @@ impl ReactionStore {
         })
     }
 
+    /** The number of reactions of a type to a target. A reaction_type of 0 counts every type */
+    pub fn get_reaction_count(
+        store: &Store,
+        target: &Target,
+        reaction_type: i32,
+    ) -> Result<u64, HubError> {
+        if reaction_type != ReactionType::None as i32 {
+            return get_count(
+                &store.db(),
+                &make_reactions_count_key(target, reaction_type),
+            );
+        }
+
+        let mut count = 0;
+        for reaction_type in [ReactionType::Like, ReactionType::Recast] {
+            count += get_count(
+                &store.db(),
+                &make_reactions_count_key(target, reaction_type as i32),
+            )?;
+        }
+
+        Ok(count)
+    }
+
+    pub fn js_get_reaction_count(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let target_cast_id_buffer = cx.argument::<JsBuffer>(0)?;
+        let target_cast_id_bytes = target_cast_id_buffer.as_slice(&cx);
+        let target_cast_id = if target_cast_id_bytes.len() > 0 {
+            match protos::CastId::decode(target_cast_id_bytes) {
+                Ok(cast_id) => Some(cast_id),
+                Err(e) => {
+                    return hub_error_to_js_throw(
+                        &mut cx,
+                        HubError::new(HubErrorCode::ParseFailure, e.to_string()),
+                    )
+                }
+            }
+        } else {
+            None
+        };
+
+        let target_url = cx.argument::<JsString>(1).map(|s| s.value(&mut cx))?;
+
+        // We need at least one of target_cast_id or target_url
+        if target_cast_id.is_none() && target_url.is_empty() {
+            return hub_error_to_js_throw(
+                &mut cx,
+                HubError::invalid_parameter("target_cast_id or target_url is required"),
+            );
+        }
+
+        let target = if target_cast_id.is_some() {
+            Target::TargetCastId(target_cast_id.unwrap())
+        } else {
+            Target::TargetUrl(target_url)
+        };
+
+        let reaction_type = cx
+            .argument::<JsNumber>(2)
+            .map(|n| n.value(&mut cx) as i32)?;
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let count = catch_panic(|| Self::get_reaction_count(&store, &target, reaction_type));
+
+            deferred.settle_with(&channel, move |mut cx| match count {
+                Ok(count) => Ok(cx.number(count as f64)),
+                Err(e) => hub_error_to_js_throw(&mut cx, e),
+            });
+        });
+
+        Ok(promise)
+    }
+
     pub fn js_get_reactions_by_target(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
@@ -608,7 +854,12 @@ impl ReactionStore {
         let target_cast_id = if target_cast_id_bytes.len() > 0 {
             match protos::CastId::decode(target_cast_id_bytes) {
                 Ok(cast_id) => Some(cast_id),
-                Err(e) => return cx.throw_error(e.to_string()),
+                Err(e) => {
+                    return hub_error_to_js_throw(
+                        &mut cx,
+                        HubError::new(HubErrorCode::ParseFailure, e.to_string()),
+                    )
+                }
             }
         } else {
             None
@@ -618,7 +869,10 @@ impl ReactionStore {
 
         // We need at least one of target_cast_id or target_url
         if target_cast_id.is_none() && target_url.is_empty() {
-            return cx.throw_error("target_cast_id or target_url is required");
+            return hub_error_to_js_throw(
+                &mut cx,
+                HubError::invalid_parameter("target_cast_id or target_url is required"),
+            );
         }
 
         let target = if target_cast_id.is_some() {
@@ -636,13 +890,15 @@ impl ReactionStore {
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let messages = ReactionStore::get_reactions_by_target(
-                &store,
-                &target,
-                reaction_type,
-                &page_options,
-            );
+        execute(move || {
+            let messages = catch_panic(|| {
+                ReactionStore::get_reactions_by_target(
+                    &store,
+                    &target,
+                    reaction_type,
+                    &page_options,
+                )
+            });
 
             deferred_settle_messages(deferred, &channel, messages);
         });
@@ -1,63 +1,159 @@
 use super::{
-    bytes_compare, delete_message_transaction, get_message, hub_error_to_js_throw,
-    is_message_in_time_range, make_message_primary_key, message, message_decode, message_encode,
-    put_message_transaction,
+    bytes_compare, deferred_settle_messages, delete_message_transaction, get_message,
+    hub_error_to_js_error, hub_error_to_js_throw, make_message_primary_key, message,
+    message_decode, message_encode, put_message_transaction, read_fid_key,
     utils::{self, encode_messages_to_js_object, get_page_options, get_store, vec_to_u8_24},
-    MessagesPage, StoreEventHandler, TS_HASH_LENGTH,
+    CounterType, MessagesPage, RootPrefix, StoreEventHandler, FID_BYTES, TS_HASH_LENGTH,
 };
+use crate::logger::LOGGER;
+use crate::panics::{catch_panic, execute, lock_unpoisoned};
 use crate::{
     db::{RocksDB, RocksDbTransactionBatch},
-    protos::{
-        self, hub_event, link_body::Target, message_data::Body, HubEvent, HubEventType,
-        MergeMessageBody, Message, MessageType,
-    },
+    protos::{self, hub_event, HubEvent, HubEventType, MergeMessageBody, Message, MessageType},
     store::make_ts_hash,
 };
-use crate::{logger::LOGGER, THREAD_POOL};
-use neon::types::{Finalize, JsBuffer, JsNumber, JsString};
+use neon::types::{Finalize, JsBuffer, JsNumber, JsObject};
 use neon::{context::Context, types::JsArray};
 use neon::{context::FunctionContext, result::JsResult, types::JsPromise};
 use neon::{object::Object, types::buffer::TypedArray};
 use prost::Message as _;
 use rocksdb;
 use slog::{o, warn};
+use std::collections::HashSet;
 use std::string::ToString;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::{clone::Clone, fmt::Display};
 
-#[derive(Debug, PartialEq)]
+/**
+ * The kinds of errors the hub raises. These are the same as the HubErrorCode union in
+ * @farcaster/core, so JS gets back codes it already knows how to handle.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HubErrorCode {
+    /** The request did not have valid authentication credentials, retry with credentials */
+    Unauthenticated,
+    /** The authenticated request did not have the authority to perform this action */
+    Unauthorized,
+    /** The request cannot be completed as constructed, do not retry */
+    BadRequest,
+    ParseFailure,
+    InvalidParam,
+    ValidationFailure,
+    UnknownSigner,
+    Duplicate,
+    Conflict,
+    Prunable,
+    NoStorage,
+    UnknownFid,
+    /** The requested resource could not be found */
+    NotFound,
+    /** The request could not be completed because the operation is not executable */
+    NotImplemented,
+    Deprecated,
+    /** The request could not be completed, it may or may not be safe to retry */
+    Unavailable,
+    NetworkFailure,
+    StorageFailure,
+    /** An unknown error was encountered */
+    #[default]
+    Unknown,
+}
+
+impl HubErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HubErrorCode::Unauthenticated => "unauthenticated",
+            HubErrorCode::Unauthorized => "unauthorized",
+            HubErrorCode::BadRequest => "bad_request",
+            HubErrorCode::ParseFailure => "bad_request.parse_failure",
+            HubErrorCode::InvalidParam => "bad_request.invalid_param",
+            HubErrorCode::ValidationFailure => "bad_request.validation_failure",
+            HubErrorCode::UnknownSigner => "bad_request.unknown_signer",
+            HubErrorCode::Duplicate => "bad_request.duplicate",
+            HubErrorCode::Conflict => "bad_request.conflict",
+            HubErrorCode::Prunable => "bad_request.prunable",
+            HubErrorCode::NoStorage => "bad_request.no_storage",
+            HubErrorCode::UnknownFid => "bad_request.unknown_fid",
+            HubErrorCode::NotFound => "not_found",
+            HubErrorCode::NotImplemented => "not_implemented",
+            HubErrorCode::Deprecated => "not_implemented.deprecated",
+            HubErrorCode::Unavailable => "unavailable",
+            HubErrorCode::NetworkFailure => "unavailable.network_failure",
+            HubErrorCode::StorageFailure => "unavailable.storage_failure",
+            HubErrorCode::Unknown => "unknown",
+        }
+    }
+
+    pub fn is_bad_request(&self) -> bool {
+        self.as_str().starts_with("bad_request")
+    }
+
+    pub fn is_unavailable(&self) -> bool {
+        self.as_str().starts_with("unavailable")
+    }
+}
+
+impl Display for HubErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
 pub struct HubError {
-    pub code: String,
+    pub code: HubErrorCode,
     pub message: String,
+    /** The fid the error is about, if any */
+    pub fid: Option<u32>,
+    /** The hash of the message the error is about, if any */
+    pub hash: Option<Vec<u8>>,
+    /** The DB key the error is about, if any */
+    pub key: Option<Vec<u8>>,
 }
 
 impl HubError {
-    pub fn validation_failure(error_message: &str) -> HubError {
+    pub fn new(code: HubErrorCode, message: impl Into<String>) -> HubError {
         HubError {
-            code: "bad_request.validation_failure".to_string(),
-            message: error_message.to_string(),
+            code,
+            message: message.into(),
+            ..Default::default()
         }
     }
 
+    pub fn validation_failure(error_message: &str) -> HubError {
+        HubError::new(HubErrorCode::ValidationFailure, error_message)
+    }
+
     pub fn invalid_parameter(error_message: &str) -> HubError {
-        HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: error_message.to_string(),
-        }
+        HubError::new(HubErrorCode::InvalidParam, error_message)
     }
 
     pub fn internal_db_error(error_message: &str) -> HubError {
-        HubError {
-            code: "db.internal_error".to_string(),
-            message: error_message.to_string(),
-        }
+        HubError::new(HubErrorCode::StorageFailure, error_message)
     }
 
     pub fn not_found(error_message: &str) -> HubError {
-        HubError {
-            code: "not_found".to_string(),
-            message: error_message.to_string(),
-        }
+        HubError::new(HubErrorCode::NotFound, error_message)
+    }
+
+    /** A bug in the hub rather than a bad request, like a panic. The TS taxonomy calls it unknown */
+    pub fn internal_error(error_message: &str) -> HubError {
+        HubError::new(HubErrorCode::Unknown, error_message)
+    }
+
+    pub fn with_fid(mut self, fid: u32) -> HubError {
+        self.fid = Some(fid);
+        self
+    }
+
+    pub fn with_hash(mut self, hash: &[u8]) -> HubError {
+        self.hash = Some(hash.to_vec());
+        self
+    }
+
+    pub fn with_key(mut self, key: &[u8]) -> HubError {
+        self.key = Some(key.to_vec());
+        self
     }
 }
 
@@ -70,30 +166,24 @@ impl Display for HubError {
 /** Convert RocksDB errors  */
 impl From<rocksdb::Error> for HubError {
     fn from(e: rocksdb::Error) -> HubError {
-        HubError {
-            code: "db.internal_error".to_string(),
-            message: e.to_string(),
-        }
+        HubError::new(HubErrorCode::StorageFailure, e.to_string())
     }
 }
 
-/** Convert Neon errors */
+/**
+ * Convert Neon errors. A Throw means a JS exception is pending, which happens when the arguments
+ * JS passed in aren't what the function expects.
+ */
 impl From<neon::result::Throw> for HubError {
     fn from(e: neon::result::Throw) -> HubError {
-        HubError {
-            code: "bad_request.validation_failure".to_string(),
-            message: e.to_string(),
-        }
+        HubError::new(HubErrorCode::InvalidParam, e.to_string())
     }
 }
 
 /** Convert io::Result error type to HubError */
 impl From<std::io::Error> for HubError {
     fn from(e: std::io::Error) -> HubError {
-        HubError {
-            code: "bad_request.io_error".to_string(),
-            message: e.to_string(),
-        }
+        HubError::new(HubErrorCode::StorageFailure, e.to_string())
     }
 }
 
@@ -107,6 +197,61 @@ pub struct PageOptions {
     pub reverse: bool,
 }
 
+/**
+ * What a compact state message keeps of its fid's messages. Adds and removes older than it are
+ * deleted when it's merged, and rejected after, unless the store's `compact_state_keeps` says they
+ * survive.
+ */
+#[derive(Debug, Default, PartialEq)]
+pub struct CompactState {
+    pub fid: u32,
+    pub timestamp: u32,
+    /** Which of the fid's messages the compact state covers, e.g. its reaction type */
+    pub scope: Vec<u8>,
+    /** The targets of the adds it keeps, encoded by the store */
+    pub targets: HashSet<Vec<u8>>,
+}
+
+/** What merging a message would do, see `Store::validate_merge` */
+#[derive(Debug, PartialEq)]
+pub enum MergeOutcome {
+    /** The message would be merged, deleting these messages */
+    Merge { deleted: Vec<Message> },
+    /** The message is already merged */
+    Duplicate,
+    /** The message loses to an existing message, which is kept */
+    Conflict {
+        winner: Option<Message>,
+        reason: String,
+    },
+    /** The message is a remove older than a compact state message, which would delete it */
+    Prunable {
+        winner: Option<Message>,
+        reason: String,
+    },
+}
+
+/** The messages of a fid that the <tsHash> values at keys point to, skipping missing keys */
+pub fn get_messages_at_keys(
+    db: &RocksDB,
+    fid: u32,
+    set: u8,
+    keys: &[Vec<u8>],
+) -> Result<Vec<Message>, HubError> {
+    let mut messages: Vec<Message> = vec![];
+    for key in keys {
+        if let Some(ts_hash) = db.get(key)? {
+            if let Some(message) = get_message(db, fid, set, &vec_to_u8_24(&Some(ts_hash))?)? {
+                if !messages.iter().any(|m| m.hash == message.hash) {
+                    messages.push(message);
+                }
+            }
+        }
+    }
+
+    Ok(messages)
+}
+
 /// The `Send` trait indicates that a type can be safely transferred between threads.
 /// The `Sync` trait indicates that a type can be safely shared between threads.
 /// The `StoreDef` trait is implemented for types that are both `Send` and `Sync`,
@@ -135,6 +280,24 @@ pub trait StoreDef: Send + Sync {
         self.compact_state_message_type() != MessageType::None as u8
     }
 
+    /**
+     * Read the fid, timestamp, scope and targets of a compact state message. Only called for
+     * messages that is_compact_state_type accepts.
+     */
+    fn read_compact_state(&self, _message: &Message) -> Result<CompactState, HubError> {
+        Err(HubError::validation_failure(
+            "compact state type not supported",
+        ))
+    }
+
+    /**
+     * Whether a message that is older than a compact state survives it. Messages that don't are
+     * deleted when the compact state is merged and can't be merged after it.
+     */
+    fn compact_state_keeps(&self, _compact_state: &CompactState, _message: &Message) -> bool {
+        true
+    }
+
     fn build_secondary_indices(
         &self,
         _txn: &mut RocksDbTransactionBatch,
@@ -161,6 +324,69 @@ pub trait StoreDef: Send + Sync {
         Ok(())
     }
 
+    /**
+     * The RootPrefixes of the indices written by build_secondary_indices. Each index prefix must
+     * belong to only one store, since check_indices rebuilds everything under them.
+     */
+    fn secondary_index_prefixes(&self) -> Vec<u8> {
+        vec![]
+    }
+
+    /**
+     * The RootPrefixes of the indices this store can be created without, whether or not they're
+     * enabled. Only messages merged while one is enabled are in it, so it has to be backfilled by
+     * check_indices with repair before it can be queried.
+     */
+    fn optional_secondary_index_prefixes(&self) -> Vec<u8> {
+        vec![]
+    }
+
+    /**
+     * The primary key of the message that a secondary index entry points to. Most indices end
+     * with <tsHash>:<fid>, which is handled here; stores with other index layouts override this.
+     */
+    fn secondary_index_message_key(
+        &self,
+        _db: &RocksDB,
+        key: &[u8],
+        _value: &[u8],
+    ) -> Result<Option<Vec<u8>>, HubError> {
+        if key.len() < 1 + TS_HASH_LENGTH + FID_BYTES {
+            return Ok(None);
+        }
+
+        let fid_offset = key.len() - FID_BYTES;
+        let ts_hash_offset = fid_offset - TS_HASH_LENGTH;
+        let ts_hash = vec_to_u8_24(&Some(key[ts_hash_offset..fid_offset].to_vec()))?;
+
+        Ok(Some(make_message_primary_key(
+            read_fid_key(&key[fid_offset..]),
+            self.postfix(),
+            Some(&ts_hash),
+        )))
+    }
+
+    /**
+     * The aggregate counters this store maintains. Each counter type must belong to only one
+     * store, since rebuild_counters deletes and recounts everything under them.
+     */
+    fn counter_types(&self) -> Vec<CounterType> {
+        vec![]
+    }
+
+    /**
+     * Add `delta` to the counters an add message contributes to. Called with 1 when the message
+     * is merged and -1 when it is deleted, in the same transaction.
+     */
+    fn update_counters(
+        &self,
+        _txn: &mut RocksDbTransactionBatch,
+        _message: &Message,
+        _delta: i64,
+    ) -> Result<(), HubError> {
+        Ok(())
+    }
+
     fn find_merge_add_conflicts(&self, db: &RocksDB, message: &Message) -> Result<(), HubError>;
     fn find_merge_remove_conflicts(&self, db: &RocksDB, message: &Message) -> Result<(), HubError>;
 
@@ -210,16 +436,22 @@ pub trait StoreDef: Send + Sync {
                 );
 
                 if remove_compare > 0 {
-                    return Err(HubError {
-                        code: "bad_request.conflict".to_string(),
-                        message: "message conflicts with a more recent remove".to_string(),
-                    });
+                    return Err(HubError::new(
+                        HubErrorCode::Conflict,
+                        "message conflicts with a more recent remove",
+                    )
+                    .with_fid(message.data.as_ref().unwrap().fid as u32)
+                    .with_hash(&message.hash)
+                    .with_key(&remove_key));
                 }
                 if remove_compare == 0 {
-                    return Err(HubError {
-                        code: "bad_request.duplicate".to_string(),
-                        message: "message has already been merged".to_string(),
-                    });
+                    return Err(HubError::new(
+                        HubErrorCode::Duplicate,
+                        "message has already been merged",
+                    )
+                    .with_fid(message.data.as_ref().unwrap().fid as u32)
+                    .with_hash(&message.hash)
+                    .with_key(&remove_key));
                 }
 
                 // If the existing remove has a lower order than the new message, retrieve the full
@@ -253,16 +485,22 @@ pub trait StoreDef: Send + Sync {
             );
 
             if add_compare > 0 {
-                return Err(HubError {
-                    code: "bad_request.conflict".to_string(),
-                    message: "message conflicts with a more recent add".to_string(),
-                });
+                return Err(HubError::new(
+                    HubErrorCode::Conflict,
+                    "message conflicts with a more recent add",
+                )
+                .with_fid(message.data.as_ref().unwrap().fid as u32)
+                .with_hash(&message.hash)
+                .with_key(&add_key));
             }
             if add_compare == 0 {
-                return Err(HubError {
-                    code: "bad_request.duplicate".to_string(),
-                    message: "message has already been merged".to_string(),
-                });
+                return Err(HubError::new(
+                    HubErrorCode::Duplicate,
+                    "message has already been merged",
+                )
+                .with_fid(message.data.as_ref().unwrap().fid as u32)
+                .with_hash(&message.hash)
+                .with_key(&add_key));
             }
 
             // If the existing add has a lower order than the new message, retrieve the full
@@ -285,6 +523,29 @@ pub trait StoreDef: Send + Sync {
         Ok(conflicts)
     }
 
+    /**
+     * The existing messages a message is compared against in get_merge_conflicts, whether or not
+     * they win. Used to explain conflicts, so stores that override get_merge_conflicts to look at
+     * more messages override this too.
+     */
+    fn get_competing_messages(
+        &self,
+        db: &RocksDB,
+        message: &Message,
+    ) -> Result<Vec<Message>, HubError> {
+        let mut keys = vec![self.make_add_key(message)?];
+        if self.remove_type_supported() {
+            keys.push(self.make_remove_key(message)?);
+        }
+
+        get_messages_at_keys(
+            db,
+            message.data.as_ref().unwrap().fid as u32,
+            self.postfix(),
+            &keys,
+        )
+    }
+
     fn message_compare(
         &self,
         a_type: u8,
@@ -396,6 +657,40 @@ impl Store {
         self.store_def.as_ref()
     }
 
+    /** Whether this store maintains the secondary index under a prefix. Some are optional */
+    pub fn has_secondary_index(&self, prefix: RootPrefix) -> bool {
+        self.store_def
+            .secondary_index_prefixes()
+            .contains(&(prefix as u8))
+    }
+
+    /**
+     * Whether this store maintains the secondary index under a prefix, and it has every message
+     * in it. Optional indices are complete once check_indices with repair has backfilled them.
+     */
+    pub fn is_secondary_index_complete(&self, prefix: RootPrefix) -> Result<bool, HubError> {
+        if !self.has_secondary_index(prefix) {
+            return Ok(false);
+        }
+        if !self
+            .store_def
+            .optional_secondary_index_prefixes()
+            .contains(&(prefix as u8))
+        {
+            return Ok(true);
+        }
+
+        Ok(self
+            .db
+            .get(&Self::make_optional_index_complete_key(prefix as u8))?
+            .is_some())
+    }
+
+    /** Hold every fid lock, which blocks all merges into this store until they are dropped */
+    pub fn lock_all_fids(&self) -> Vec<MutexGuard<'_, ()>> {
+        self.fid_locks.iter().map(lock_unpoisoned).collect()
+    }
+
     pub fn db(&self) -> Arc<RocksDB> {
         self.db.clone()
     }
@@ -414,10 +709,7 @@ impl Store {
     ) -> Result<Option<protos::Message>, HubError> {
         // First check the fid
         if partial_message.data.is_none() || partial_message.data.as_ref().unwrap().fid == 0 {
-            return Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: "fid is required".to_string(),
-            });
+            return Err(HubError::new(HubErrorCode::InvalidParam, "fid is required"));
         }
 
         let adds_key = self.store_def.make_add_key(partial_message)?;
@@ -440,18 +732,15 @@ impl Store {
         partial_message: &protos::Message,
     ) -> Result<Option<protos::Message>, HubError> {
         if !self.store_def.remove_type_supported() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "remove type not supported".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "remove type not supported",
+            ));
         }
 
         // First check the fid
         if partial_message.data.is_none() || partial_message.data.as_ref().unwrap().fid == 0 {
-            return Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: "fid is required".to_string(),
-            });
+            return Err(HubError::new(HubErrorCode::InvalidParam, "fid is required"));
         }
 
         let removes_key = self.store_def.make_remove_key(partial_message)?;
@@ -472,6 +761,8 @@ impl Store {
     pub fn get_adds_by_fid<F>(
         &self,
         fid: u32,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
         page_options: &PageOptions,
         filter: Option<F>,
     ) -> Result<MessagesPage, HubError>
@@ -479,11 +770,17 @@ impl Store {
         F: Fn(&protos::Message) -> bool,
     {
         let prefix = make_message_primary_key(fid, self.store_def.postfix(), None);
-        let messages_page =
-            message::get_messages_page_by_prefix(&self.db, &prefix, &page_options, |message| {
+        let messages_page = message::get_messages_page_by_prefix_in_time_range(
+            &self.db,
+            &prefix,
+            start_time,
+            stop_time,
+            &page_options,
+            |message| {
                 self.store_def.is_add_type(&message)
                     && filter.as_ref().map(|f| f(&message)).unwrap_or(true)
-            })?;
+            },
+        )?;
 
         Ok(messages_page)
     }
@@ -491,6 +788,8 @@ impl Store {
     pub fn get_removes_by_fid<F>(
         &self,
         fid: u32,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
         page_options: &PageOptions,
         filter: Option<F>,
     ) -> Result<MessagesPage, HubError>
@@ -498,18 +797,24 @@ impl Store {
         F: Fn(&protos::Message) -> bool,
     {
         if !self.store_def.remove_type_supported() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "remove type not supported".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "remove type not supported",
+            ));
         }
 
         let prefix = make_message_primary_key(fid, self.store_def.postfix(), None);
-        let messages =
-            message::get_messages_page_by_prefix(&self.db, &prefix, &page_options, |message| {
+        let messages = message::get_messages_page_by_prefix_in_time_range(
+            &self.db,
+            &prefix,
+            start_time,
+            stop_time,
+            &page_options,
+            |message| {
                 self.store_def.is_remove_type(&message)
                     && filter.as_ref().map(|f| f(&message)).unwrap_or(true)
-            })?;
+            },
+        )?;
 
         Ok(messages)
     }
@@ -520,10 +825,10 @@ impl Store {
         message: &Message,
     ) -> Result<(), HubError> {
         if !self.store_def.compact_state_type_supported() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "compact state type not supported".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "compact state type not supported",
+            ));
         }
 
         let compact_state_key = self.store_def.make_compact_state_add_key(message)?;
@@ -546,6 +851,7 @@ impl Store {
 
         self.store_def
             .build_secondary_indices(txn, ts_hash, message)?;
+        self.store_def.update_counters(txn, message, 1)?;
 
         Ok(())
     }
@@ -556,10 +862,10 @@ impl Store {
         message: &Message,
     ) -> Result<(), HubError> {
         if !self.store_def.compact_state_type_supported() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "compact state type not supported".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "compact state type not supported",
+            ));
         }
 
         let compact_state_key = self.store_def.make_compact_state_add_key(message)?;
@@ -576,6 +882,7 @@ impl Store {
     ) -> Result<(), HubError> {
         self.store_def
             .delete_secondary_indices(txn, ts_hash, message)?;
+        self.store_def.update_counters(txn, message, -1)?;
 
         let add_key = self.store_def.make_add_key(message)?;
         txn.delete(add_key);
@@ -590,10 +897,10 @@ impl Store {
         message: &Message,
     ) -> Result<(), HubError> {
         if !self.store_def.remove_type_supported() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "remove type not supported".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "remove type not supported",
+            ));
         }
 
         put_message_transaction(txn, &message)?;
@@ -610,10 +917,10 @@ impl Store {
         message: &Message,
     ) -> Result<(), HubError> {
         if !self.store_def.remove_type_supported() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "remove type not supported".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "remove type not supported",
+            ));
         }
 
         self.store_def
@@ -650,23 +957,24 @@ impl Store {
         // Grab a merge lock. The typescript code does this by individual fid, but we don't have a
         // good way of doing that efficiently here. We'll just use an array of locks, with each fid
         // deterministically mapped to a lock.
-        let _fid_lock = &self.fid_locks
-            [message.data.as_ref().unwrap().fid as usize % FID_LOCKS_COUNT]
-            .lock()
-            .unwrap();
+        let data = message
+            .data
+            .as_ref()
+            .ok_or_else(|| HubError::validation_failure("message data is missing"))?;
+        let _fid_lock = lock_unpoisoned(&self.fid_locks[data.fid as usize % FID_LOCKS_COUNT]);
 
         if !self.store_def.is_add_type(message)
             && !(self.store_def.remove_type_supported() && self.store_def.is_remove_type(message))
             && !(self.store_def.compact_state_type_supported()
                 && self.store_def.is_compact_state_type(message))
         {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "invalid message type".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "invalid message type",
+            ));
         }
 
-        let ts_hash = make_ts_hash(message.data.as_ref().unwrap().timestamp, &message.hash)?;
+        let ts_hash = make_ts_hash(data.timestamp, &message.hash)?;
 
         if self.store_def().is_compact_state_type(message) {
             self.merge_compact_state(message)
@@ -682,7 +990,11 @@ impl Store {
         let mut txn = self.db.txn();
 
         // Get the message ts_hash
-        let ts_hash = make_ts_hash(message.data.as_ref().unwrap().timestamp, &message.hash)?;
+        let data = message
+            .data
+            .as_ref()
+            .ok_or_else(|| HubError::invalid_parameter("message data is missing"))?;
+        let ts_hash = make_ts_hash(data.timestamp, &message.hash)?;
 
         if self.store_def().is_compact_state_type(message) {
             self.delete_compact_state_transaction(&mut txn, message)?;
@@ -691,10 +1003,10 @@ impl Store {
         } else if self.store_def.remove_type_supported() && self.store_def.is_remove_type(message) {
             self.delete_remove_transaction(&mut txn, message)?;
         } else {
-            return Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: "invalid message type".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::InvalidParam,
+                "invalid message type",
+            ));
         }
 
         let mut hub_event = self.store_def.revoke_event_args(message);
@@ -714,33 +1026,12 @@ impl Store {
         Ok(hub_event_bytes)
     }
 
-    fn read_compact_state_details(
-        &self,
-        message: &Message,
-    ) -> Result<(u32, u32, Vec<u64>), HubError> {
-        if let Some(data) = &message.data {
-            if let Some(Body::LinkCompactStateBody(link_compact_body)) = &data.body {
-                Ok((
-                    data.fid as u32,
-                    data.timestamp,
-                    link_compact_body.target_fids.clone(),
-                ))
-            } else {
-                return Err(HubError {
-                    code: "bad_request.validation_failure".to_string(),
-                    message: "Invalid compact state message: No link compact state body"
-                        .to_string(),
-                });
-            }
-        } else {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Invalid compact state message: no data".to_string(),
-            });
-        }
-    }
-
-    pub fn merge_compact_state(&self, message: &Message) -> Result<Vec<u8>, HubError> {
+    /**
+     * Find the messages that merging a compact state message would delete: the older compact state
+     * message it replaces, and the adds and removes older than it that it doesn't keep. Fails if a
+     * newer compact state message is already merged.
+     */
+    fn find_compact_state_conflicts(&self, message: &Message) -> Result<Vec<Message>, HubError> {
         let mut merge_conflicts = vec![];
 
         // First, find if there's an existing compact state message, and if there is,
@@ -762,54 +1053,67 @@ impl Store {
                     merge_conflicts.push(existing_compact_state_message);
                 } else {
                     // Can't merge an older compact state message
-                    return Err(HubError {
-                        code: "bad_request.conflict".to_string(),
-                        message: "A newer Compact State message is already merged".to_string(),
-                    });
+                    return Err(HubError::new(
+                        HubErrorCode::Conflict,
+                        "A newer Compact State message is already merged",
+                    )
+                    .with_fid(message.data.as_ref().unwrap().fid as u32)
+                    .with_hash(&message.hash));
                 }
             }
         }
 
-        let (fid, compact_state_timestamp, target_fids) =
-            self.read_compact_state_details(message)?;
+        let compact_state = self.store_def.read_compact_state(message)?;
 
-        // Go over all the messages for this Fid, that are older than the compact state message and
-        // 1. Delete all remove messages
-        // 2. Delete all add messages that are not in the target_fids list
-        let prefix = &make_message_primary_key(fid, self.store_def.postfix(), None);
+        // Go over all the messages for this Fid that are older than the compact state message, and
+        // delete the adds and removes that don't survive it
+        let prefix = &make_message_primary_key(compact_state.fid, self.store_def.postfix(), None);
         self.db
             .for_each_iterator_by_prefix(prefix, &PageOptions::default(), |_key, value| {
                 let message = message_decode(value)?;
 
                 // Only if message is older than the compact state message
-                if message.data.as_ref().unwrap().timestamp > compact_state_timestamp {
+                if message.data.as_ref().unwrap().timestamp > compact_state.timestamp {
                     // Finish the iteration since all future messages will have greater timestamp
                     return Ok(true);
                 }
 
-                if self.store_def.is_remove_type(&message) {
+                if (self.store_def.is_remove_type(&message) || self.store_def.is_add_type(&message))
+                    && !self.store_def.compact_state_keeps(&compact_state, &message)
+                {
                     merge_conflicts.push(message);
-                } else if self.store_def.is_add_type(&message) {
-                    // Get the link_body fid
-                    if let Some(data) = &message.data {
-                        if let Some(Body::LinkBody(link_body)) = &data.body {
-                            if let Some(Target::TargetFid(target_fid)) = link_body.target {
-                                if !target_fids.contains(&target_fid) {
-                                    merge_conflicts.push(message);
-                                }
-                            }
-                        }
-                    }
                 }
 
                 Ok(false) // Continue the iteration
             })?;
 
+        Ok(merge_conflicts)
+    }
+
+    /**
+     * Dry run of merge_compact_state. Returns the messages merging the compact state message would
+     * delete, without changing anything.
+     */
+    pub fn preview_compact_state_merge(&self, message: &Message) -> Result<Vec<Message>, HubError> {
+        if !(self.store_def.compact_state_type_supported()
+            && self.store_def.is_compact_state_type(message))
+        {
+            return Err(HubError::validation_failure(
+                "not a compact state message for this store",
+            ));
+        }
+
+        self.find_compact_state_conflicts(message)
+    }
+
+    pub fn merge_compact_state(&self, message: &Message) -> Result<Vec<u8>, HubError> {
+        let merge_conflicts = self.find_compact_state_conflicts(message)?;
+
         let mut txn = self.db.txn();
         // Delete all the merge conflicts
         self.delete_many_transaction(&mut txn, &merge_conflicts)?;
 
-        // Add the Link compact state message
+        // Add the compact state message
         self.put_add_compact_state_transaction(&mut txn, message)?;
 
         // Event Handler
@@ -829,38 +1133,136 @@ impl Store {
         Ok(hub_event_bytes)
     }
 
+    /**
+     * The compact state message that an add or remove loses to: one that is newer than it and
+     * doesn't keep it
+     */
+    fn find_compact_state_winner(&self, message: &Message) -> Result<Option<Message>, HubError> {
+        if !self.store_def.compact_state_type_supported() {
+            return Ok(None);
+        }
+
+        let compact_state_key = self.store_def.make_compact_state_add_key(message)?;
+        if let Some(compact_state_message_bytes) = self.db.get(&compact_state_key)? {
+            let compact_state_message = message_decode(compact_state_message_bytes.as_ref())?;
+
+            let compact_state = self.store_def.read_compact_state(&compact_state_message)?;
+
+            // If the message is older than the compact state message, and it doesn't survive it
+            if message.data.as_ref().unwrap().timestamp < compact_state.timestamp
+                && !self.store_def.compact_state_keeps(&compact_state, message)
+            {
+                return Ok(Some(compact_state_message));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /**
+     * Dry run of merge. Runs the same compact state and merge conflict checks without committing,
+     * and explains the result: whether the message would be merged and what that deletes, or
+     * which existing message it loses to. Storage limits aren't checked.
+     */
+    pub fn validate_merge(&self, message: &Message) -> Result<MergeOutcome, HubError> {
+        if !self.store_def.is_add_type(message)
+            && !(self.store_def.remove_type_supported() && self.store_def.is_remove_type(message))
+            && !(self.store_def.compact_state_type_supported()
+                && self.store_def.is_compact_state_type(message))
+        {
+            return Err(HubError::validation_failure("invalid message type"));
+        }
+
+        if self.store_def.is_compact_state_type(message) {
+            return match self.find_compact_state_conflicts(message) {
+                Ok(deleted) => Ok(MergeOutcome::Merge { deleted }),
+                Err(e) if e.code == HubErrorCode::Conflict => {
+                    let compact_state_key = self.store_def.make_compact_state_add_key(message)?;
+                    let winner = match self.db.get(&compact_state_key)? {
+                        Some(bytes) => Some(message_decode(&bytes)?),
+                        None => None,
+                    };
+                    Ok(MergeOutcome::Conflict {
+                        winner,
+                        reason: e.message,
+                    })
+                }
+                Err(e) => Err(e),
+            };
+        }
+
+        if let Some(compact_state_message) = self.find_compact_state_winner(message)? {
+            return Ok(if self.store_def.is_add_type(message) {
+                MergeOutcome::Conflict {
+                    winner: Some(compact_state_message),
+                    reason: "Target not in the compact state targets".to_string(),
+                }
+            } else {
+                MergeOutcome::Prunable {
+                    winner: Some(compact_state_message),
+                    reason: "Remove message earlier than the compact state message will be immediately pruned".to_string(),
+                }
+            });
+        }
+
+        let ts_hash = make_ts_hash(message.data.as_ref().unwrap().timestamp, &message.hash)?;
+        match self
+            .store_def
+            .get_merge_conflicts(&self.db, message, &ts_hash)
+        {
+            Ok(deleted) => Ok(MergeOutcome::Merge { deleted }),
+            Err(e) if e.code == HubErrorCode::Duplicate => Ok(MergeOutcome::Duplicate),
+            Err(e) if e.code == HubErrorCode::Conflict => {
+                // The winner is the highest ordered of the messages it was compared against
+                let mut winner: Option<(Message, [u8; TS_HASH_LENGTH])> = None;
+                for existing in self.store_def.get_competing_messages(&self.db, message)? {
+                    let existing_ts_hash =
+                        make_ts_hash(existing.data.as_ref().unwrap().timestamp, &existing.hash)?;
+                    let (winning_type, winning_ts_hash) = match &winner {
+                        Some((winning, winning_ts_hash)) => (
+                            winning.data.as_ref().unwrap().r#type,
+                            winning_ts_hash.to_vec(),
+                        ),
+                        None => (message.data.as_ref().unwrap().r#type, ts_hash.to_vec()),
+                    };
+
+                    if self.store_def.message_compare(
+                        existing.data.as_ref().unwrap().r#type as u8,
+                        &existing_ts_hash.to_vec(),
+                        winning_type as u8,
+                        &winning_ts_hash,
+                    ) >= 0
+                    {
+                        winner = Some((existing, existing_ts_hash));
+                    }
+                }
+
+                match winner {
+                    // Some stores report merging a message again as a conflict
+                    Some((winner, _)) if winner.hash == message.hash => Ok(MergeOutcome::Duplicate),
+                    winner => Ok(MergeOutcome::Conflict {
+                        winner: winner.map(|(winner, _)| winner),
+                        reason: e.message,
+                    }),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn merge_add(
         &self,
         ts_hash: &[u8; TS_HASH_LENGTH],
         message: &Message,
     ) -> Result<Vec<u8>, HubError> {
         // If the store supports compact state messages, we don't merge messages that don't exist in the compact state
-        if self.store_def.compact_state_type_supported() {
-            // Get the compact state message
-            let compact_state_key = self.store_def.make_compact_state_add_key(message)?;
-            if let Some(compact_state_message_bytes) = self.db.get(&compact_state_key)? {
-                let compact_state_message = message_decode(compact_state_message_bytes.as_ref())?;
-
-                let (_, compact_state_timestamp, target_fids) =
-                    self.read_compact_state_details(&compact_state_message)?;
-
-                if let Some(Body::LinkBody(link_body)) = &message.data.as_ref().unwrap().body {
-                    if let Some(Target::TargetFid(target_fid)) = link_body.target {
-                        // If the message is older than the compact state message, and the target fid is not in the target_fids list
-                        if message.data.as_ref().unwrap().timestamp < compact_state_timestamp
-                            && !target_fids.contains(&target_fid)
-                        {
-                            return Err(HubError {
-                                code: "bad_request.conflict".to_string(),
-                                message: format!(
-                                    "Target fid {} not in the compact state target fids",
-                                    target_fid
-                                ),
-                            });
-                        }
-                    }
-                }
-            }
+        if self.find_compact_state_winner(message)?.is_some() {
+            return Err(HubError::new(
+                HubErrorCode::Conflict,
+                "Target not in the compact state targets",
+            )
+            .with_fid(message.data.as_ref().unwrap().fid as u32)
+            .with_hash(&message.hash));
         }
 
         // Get the merge conflicts first
@@ -899,26 +1301,10 @@ impl Store {
         message: &Message,
     ) -> Result<Vec<u8>, HubError> {
         // If the store supports compact state messages, we don't merge remove messages before its timestamp
-        // If the store supports compact state messages, we don't merge messages that don't exist in the compact state
-        if self.store_def.compact_state_type_supported() {
-            // Get the compact state message
-            let compact_state_key = self.store_def.make_compact_state_add_key(message)?;
-            if let Some(compact_state_message_bytes) = self.db.get(&compact_state_key)? {
-                let compact_state_message = message_decode(compact_state_message_bytes.as_ref())?;
-
-                let (_, compact_state_timestamp, _) =
-                    self.read_compact_state_details(&compact_state_message)?;
-
-                // If the message is older than the compact state message, and the target fid is not in the target_fids list
-                if message.data.as_ref().unwrap().timestamp < compact_state_timestamp {
-                    return Err(HubError {
-                        code: "bad_request.prunable".to_string(),
-                        message: format!(
-                            "Remove message earlier than the compact state message will be immediately pruned",
-                        ),
-                    });
-                }
-            }
+        if self.find_compact_state_winner(message)?.is_some() {
+            return Err(HubError::new(HubErrorCode::Prunable, format!(
+                    "Remove message earlier than the compact state message will be immediately pruned",
+                )).with_fid(message.data.as_ref().unwrap().fid as u32).with_hash(&message.hash));
         }
 
         // Get the merge conflicts first
@@ -1019,14 +1405,20 @@ impl Store {
         stop_time: Option<u32>,
         page_options: &PageOptions,
     ) -> Result<MessagesPage, HubError> {
+        // Message keys are sorted by tsHash, so the time range is applied as iterator bounds
         let prefix = make_message_primary_key(fid, self.store_def.postfix(), None);
-        let messages =
-            message::get_messages_page_by_prefix(&self.db, &prefix, &page_options, |message| {
-                is_message_in_time_range(start_time, stop_time, message)
-                    && (self.store_def.is_add_type(&message)
-                        || (self.store_def.remove_type_supported()
-                            && self.store_def.is_remove_type(&message)))
-            })?;
+        let messages = message::get_messages_page_by_prefix_in_time_range(
+            &self.db,
+            &prefix,
+            start_time,
+            stop_time,
+            &page_options,
+            |message| {
+                self.store_def.is_add_type(&message)
+                    || (self.store_def.remove_type_supported()
+                        && self.store_def.is_remove_type(&message))
+            },
+        )?;
 
         Ok(messages)
     }
@@ -1067,20 +1459,12 @@ impl Store {
     pub fn js_merge(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let message_bytes_result = cx.argument::<JsBuffer>(0);
-        let message_bytes = message_bytes_result.unwrap().as_slice(&cx).to_vec();
-        let message = Message::decode(message_bytes.as_slice());
-
-        let result = if message.is_err() {
-            let e = message.unwrap_err();
-            Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: e.to_string(),
-            })
-        } else {
-            let m = message.unwrap();
-            store.merge(&m)
-        };
+        let message_bytes = cx.argument::<JsBuffer>(0)?.as_slice(&cx).to_vec();
+        let result = catch_panic(|| {
+            Message::decode(message_bytes.as_slice())
+                .map_err(|e| HubError::validation_failure(&e.to_string()))
+                .and_then(|message| store.merge(&message))
+        });
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
@@ -1092,7 +1476,7 @@ impl Store {
                     .copy_from_slice(&hub_event_bytes);
                 Ok(js_buffer)
             }
-            Err(e) => cx.throw_error(format!("{}/{}", e.code, e.message)),
+            Err(e) => hub_error_to_js_throw(&mut cx, e),
         });
 
         Ok(promise)
@@ -1102,20 +1486,16 @@ impl Store {
         let store = get_store(&mut cx)?;
 
         // Get the messages array. Each message is a buffer in this array
-        let messages_array = cx.argument::<JsArray>(0).unwrap();
+        let messages_array = cx.argument::<JsArray>(0)?;
         let messages = messages_array
             .to_vec(&mut cx)?
             .iter()
             .map(|message_bytes| {
-                let message_bytes = message_bytes.downcast::<JsBuffer, _>(&mut cx).unwrap();
-                let message = Message::decode(message_bytes.as_slice(&cx));
-                if message.is_err() {
-                    return Err(HubError {
-                        code: "bad_request.validation_failure".to_string(),
-                        message: message.unwrap_err().to_string(),
-                    });
-                }
-                Ok(message.unwrap())
+                let message_bytes = message_bytes
+                    .downcast::<JsBuffer, _>(&mut cx)
+                    .map_err(|_| HubError::invalid_parameter("message is not a buffer"))?;
+                Message::decode(message_bytes.as_slice(&cx))
+                    .map_err(|e| HubError::validation_failure(&e.to_string()))
             })
             .collect::<Vec<_>>();
 
@@ -1124,18 +1504,19 @@ impl Store {
 
         // We run the merge in a threadpool because it can be very CPU intensive and it will block
         // the NodeJS main thread.
-        THREAD_POOL.lock().unwrap().execute(move || {
+        execute(move || {
+            // Catch panics per message, so one bad message doesn't fail the rest of the batch
             let results = messages
                 .into_iter()
                 .map(|message| match message {
                     Err(e) => return Err(e),
-                    Ok(message) => store.merge(&message),
+                    Ok(message) => catch_panic(|| store.merge(&message)),
                 })
                 .collect::<Vec<_>>();
 
             deferred.settle_with(&channel, move |mut cx| {
                 let js_array = JsArray::new(&mut cx, results.len());
-                results.iter().enumerate().for_each(|(i, r)| match r {
+                results.into_iter().enumerate().for_each(|(i, r)| match r {
                     Ok(hub_event_bytes) => {
                         let mut js_buffer = cx.buffer(hub_event_bytes.len()).unwrap();
                         js_buffer
@@ -1144,9 +1525,8 @@ impl Store {
                         js_array.set(&mut cx, i as u32, js_buffer).unwrap();
                     }
                     Err(e) => {
-                        let js_error_string =
-                            JsString::new(&mut cx, format!("{}/{}", e.code, e.message));
-                        js_array.set(&mut cx, i as u32, js_error_string).unwrap();
+                        let js_error = hub_error_to_js_error(&mut cx, e).unwrap();
+                        js_array.set(&mut cx, i as u32, js_error).unwrap();
                     }
                 });
 
@@ -1160,18 +1540,12 @@ impl Store {
     pub fn js_revoke(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let message_bytes = cx.argument::<JsBuffer>(0);
-        let message = Message::decode(message_bytes.unwrap().as_slice(&cx));
-
-        let result = if message.is_err() {
-            Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: message.unwrap_err().to_string(),
-            })
-        } else {
-            let m = message.unwrap();
-            store.revoke(&m)
-        };
+        let message_bytes = cx.argument::<JsBuffer>(0)?.as_slice(&cx).to_vec();
+        let result = catch_panic(|| {
+            Message::decode(message_bytes.as_slice())
+                .map_err(|e| HubError::validation_failure(&e.to_string()))
+                .and_then(|message| store.revoke(&message))
+        });
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
@@ -1183,7 +1557,7 @@ impl Store {
                     .copy_from_slice(&hub_event_bytes);
                 Ok(js_buffer)
             }
-            Err(e) => cx.throw_error(format!("{}/{}", e.code, e.message)),
+            Err(e) => hub_error_to_js_throw(&mut cx, e),
         });
 
         Ok(promise)
@@ -1192,23 +1566,23 @@ impl Store {
     pub fn js_prune_messages(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
-        let cached_count = cx.argument::<JsNumber>(1).unwrap().value(&mut cx) as u64;
-        let units = cx.argument::<JsNumber>(2).unwrap().value(&mut cx) as u64;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+        let cached_count = cx.argument::<JsNumber>(1)?.value(&mut cx) as u64;
+        let units = cx.argument::<JsNumber>(2)?.value(&mut cx) as u64;
 
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
         // We run the prune in a threadpool because it can be very CPU intensive and it will block
         // the NodeJS main thread.
-        THREAD_POOL.lock().unwrap().execute(move || {
+        execute(move || {
             // Run the prune job in a separate thread
-            let prune_result = store.prune_messages(fid, cached_count, units);
+            let prune_result = catch_panic(|| store.prune_messages(fid, cached_count, units));
 
             deferred.settle_with(&channel, move |mut cx| {
                 let pruned_events = match prune_result {
                     Ok(pruned_events) => pruned_events,
-                    Err(e) => return cx.throw_error(format!("{}/{}", e.code, e.message)),
+                    Err(e) => return hub_error_to_js_throw(&mut cx, e),
                 };
 
                 let js_array = cx.empty_array();
@@ -1231,8 +1605,8 @@ impl Store {
     pub fn js_get_message(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
-        let set = cx.argument::<JsNumber>(1).unwrap().value(&mut cx) as u8;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+        let set = cx.argument::<JsNumber>(1)?.value(&mut cx) as u8;
         let ts_hash = match vec_to_u8_24(&Some(cx.argument::<JsBuffer>(2)?.as_slice(&cx).to_vec()))
         {
             Ok(ts_hash) => ts_hash,
@@ -1246,7 +1620,10 @@ impl Store {
             let message = match get_message(&store.db, fid, set, &ts_hash) {
                 Ok(Some(message)) => message,
                 Ok(None) => {
-                    return cx.throw_error(format!("{}/{}", "not_found", "message not found"))
+                    return hub_error_to_js_throw(
+                        &mut cx,
+                        HubError::not_found("message not found").with_fid(fid),
+                    )
                 }
                 Err(e) => return hub_error_to_js_throw(&mut cx, e),
             };
@@ -1265,7 +1642,7 @@ impl Store {
     pub fn js_get_all_messages_by_fid(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let page_options = get_page_options(&mut cx, 1)?;
         let start_time = match cx.argument_opt(2) {
             Some(arg) => match arg.downcast::<JsNumber, _>(&mut cx) {
@@ -1289,7 +1666,7 @@ impl Store {
             let messages =
                 match store.get_all_messages_by_fid(fid, start_time, stop_time, &page_options) {
                     Ok(messages) => messages,
-                    Err(e) => return tcx.throw_error(format!("{}/{}", e.code, e.message)),
+                    Err(e) => return hub_error_to_js_throw(&mut tcx, e),
                 };
 
             encode_messages_to_js_object(&mut tcx, messages)
@@ -1297,4 +1674,95 @@ impl Store {
 
         Ok(promise)
     }
+
+    pub fn js_validate_merge(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let message_bytes = cx.argument::<JsBuffer>(0)?.as_slice(&cx).to_vec();
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        execute(move || {
+            let outcome = catch_panic(|| {
+                message_decode(&message_bytes).and_then(|message| store.validate_merge(&message))
+            });
+
+            deferred.settle_with(&channel, move |mut cx| {
+                let outcome = match outcome {
+                    Ok(outcome) => outcome,
+                    Err(e) => return hub_error_to_js_throw(&mut cx, e),
+                };
+
+                let (name, winner, reason, deleted) = match outcome {
+                    MergeOutcome::Merge { deleted } => ("merge", None, None, deleted),
+                    MergeOutcome::Duplicate => ("duplicate", None, None, vec![]),
+                    MergeOutcome::Conflict { winner, reason } => {
+                        ("conflict", winner, Some(reason), vec![])
+                    }
+                    MergeOutcome::Prunable { winner, reason } => {
+                        ("prunable", winner, Some(reason), vec![])
+                    }
+                };
+
+                let js_object = JsObject::new(&mut cx);
+                let js_outcome = cx.string(name);
+                js_object.set(&mut cx, "outcome", js_outcome)?;
+
+                if let Some(reason) = reason {
+                    let js_reason = cx.string(reason);
+                    js_object.set(&mut cx, "reason", js_reason)?;
+                }
+
+                if let Some(winner) = winner {
+                    let winner_bytes = winner.encode_to_vec();
+                    let mut js_buffer = cx.buffer(winner_bytes.len())?;
+                    js_buffer
+                        .as_mut_slice(&mut cx)
+                        .copy_from_slice(&winner_bytes);
+                    js_object.set(&mut cx, "winnerBytes", js_buffer)?;
+                }
+
+                let js_deleted = JsArray::new(&mut cx, deleted.len());
+                for (i, message) in deleted.iter().enumerate() {
+                    let message_bytes = message.encode_to_vec();
+                    let mut js_buffer = cx.buffer(message_bytes.len())?;
+                    js_buffer
+                        .as_mut_slice(&mut cx)
+                        .copy_from_slice(&message_bytes);
+                    js_deleted.set(&mut cx, i as u32, js_buffer)?;
+                }
+                js_object.set(&mut cx, "deletedMessagesBytes", js_deleted)?;
+
+                Ok(js_object)
+            });
+        });
+
+        Ok(promise)
+    }
+
+    pub fn js_preview_compact_state_merge(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let store = get_store(&mut cx)?;
+
+        let message_bytes = cx.argument::<JsBuffer>(0)?.as_slice(&cx).to_vec();
+
+        let channel = cx.channel();
+        let (deferred, promise) = cx.promise();
+
+        // This reads every message of the fid in the store, so it runs in the threadpool
+        execute(move || {
+            let messages = catch_panic(|| {
+                message_decode(&message_bytes)
+                    .and_then(|message| store.preview_compact_state_merge(&message))
+                    .map(|messages| MessagesPage {
+                        messages_bytes: messages.iter().map(|m| m.encode_to_vec()).collect(),
+                        next_page_token: None,
+                    })
+            });
+
+            deferred_settle_messages(deferred, &channel, messages);
+        });
+
+        Ok(promise)
+    }
 }
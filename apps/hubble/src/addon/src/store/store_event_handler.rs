@@ -1,4 +1,5 @@
-use super::{hub_error_to_js_throw, HubError, RootPrefix};
+use super::{hub_error_to_js_throw, HubError, HubErrorCode, RootPrefix};
+use crate::panics::lock_unpoisoned;
 use crate::{db::RocksDbTransactionBatch, protos::HubEvent};
 use neon::context::{Context, FunctionContext};
 use neon::result::JsResult;
@@ -48,17 +49,17 @@ impl HubEventIdGenerator {
         }
 
         if self.last_timestamp >= 2u64.pow(TIMESTAMP_BITS) {
-            return Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: format!("timestamp > {} bits", TIMESTAMP_BITS),
-            });
+            return Err(HubError::new(
+                HubErrorCode::InvalidParam,
+                format!("timestamp > {} bits", TIMESTAMP_BITS),
+            ));
         }
 
         if self.last_seq >= 2u64.pow(SEQUENCE_BITS) {
-            return Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: format!("sequence > {} bits", SEQUENCE_BITS),
-            });
+            return Err(HubError::new(
+                HubErrorCode::InvalidParam,
+                format!("sequence > {} bits", SEQUENCE_BITS),
+            ));
         }
 
         Ok(make_event_id(self.last_timestamp, self.last_seq))
@@ -93,7 +94,7 @@ impl StoreEventHandler {
         raw_event: &mut HubEvent,
     ) -> Result<u64, HubError> {
         // Acquire the lock so we don't generate multiple IDs. This also serves as the commit lock
-        let mut generator = self.generator.lock().unwrap();
+        let mut generator = lock_unpoisoned(&self.generator);
 
         // Generate the event ID
         let event_id = generator.generate_id(None)?;
@@ -175,7 +176,7 @@ impl StoreEventHandler {
             None => None,
         };
 
-        let mut generator = this.generator.lock().unwrap();
+        let mut generator = lock_unpoisoned(&this.generator);
         let event_id = match generator.generate_id(timestamp) {
             Ok(id) => id,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
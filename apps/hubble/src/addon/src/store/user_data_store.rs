@@ -1,12 +1,12 @@
 use super::{
-    bytes_compare, encode_messages_to_js_object, get_page_options, get_store,
-    hub_error_to_js_throw, is_message_in_time_range, make_user_key,
+    bytes_compare, encode_messages_to_js_object, get_page_options, get_store, get_time_range,
+    hub_error_to_js_throw, make_user_key,
     name_registry_events::{
         delete_username_proof_transaction, get_fname_proof_by_fid, get_username_proof,
-        put_username_proof_transaction,
+        get_username_proof_deletion, put_fname_history_transaction, put_username_proof_transaction,
     },
     store::{Store, StoreDef},
-    HubError, MessagesPage, PageOptions, StoreEventHandler, UserPostfix,
+    HubError, HubErrorCode, MessagesPage, PageOptions, StoreEventHandler, UserPostfix,
 };
 use crate::protos::{hub_event, message_data, HubEvent, HubEventType, UserDataBody};
 use crate::{
@@ -68,20 +68,20 @@ impl StoreDef for UserDataStoreDef {
         _db: &RocksDB,
         _message: &Message,
     ) -> Result<(), HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "UserDataStoree doesn't support merging removes".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            "UserDataStoree doesn't support merging removes",
+        ))
     }
 
     fn make_add_key(&self, message: &Message) -> Result<Vec<u8>, HubError> {
         let user_data_body = match message.data.as_ref().unwrap().body.as_ref().unwrap() {
             message_data::Body::UserDataBody(body) => body,
             _ => {
-                return Err(HubError {
-                    code: "bad_request.invalid_param".to_string(),
-                    message: "UserDataAdd message missing body".to_string(),
-                })
+                return Err(HubError::new(
+                    HubErrorCode::InvalidParam,
+                    "UserDataAdd message missing body",
+                ))
             }
         };
 
@@ -93,24 +93,24 @@ impl StoreDef for UserDataStoreDef {
     }
 
     fn make_remove_key(&self, _message: &Message) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "removes not supported".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            "removes not supported",
+        ))
     }
 
     fn make_compact_state_add_key(&self, _message: &Message) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "UserDataStore doesn't support compact state".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            "UserDataStore doesn't support compact state",
+        ))
     }
 
     fn make_compact_state_prefix(&self, _fid: u32) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "UserDataStore doesn't support compact state".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            "UserDataStore doesn't support compact state",
+        ))
     }
 
     fn get_prune_size_limit(&self) -> u32 {
@@ -126,7 +126,7 @@ impl UserDataStoreDef {
      * @param dataType type of data being added
      * @returns RocksDB key of the form <root_prefix>:<fid>:<user_postfix>:<dataType?>
      */
-    fn make_user_data_adds_key(fid: u32, data_type: i32) -> Vec<u8> {
+    pub fn make_user_data_adds_key(fid: u32, data_type: i32) -> Vec<u8> {
         let mut key = Vec::with_capacity(33 + 1 + 1);
 
         key.extend_from_slice(&make_user_key(fid));
@@ -205,10 +205,10 @@ impl UserDataStore {
 
         let result = match Self::get_user_data_add(&store, fid, r#type) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{}",
-                "not_found", "NotFound: UserDataAdd message not found"
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found("NotFound: UserDataAdd message not found"),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -229,12 +229,12 @@ impl UserDataStore {
         start_time: Option<u32>,
         stop_time: Option<u32>,
     ) -> Result<MessagesPage, HubError> {
-        store.get_adds_by_fid(
+        store.get_adds_by_fid::<fn(&Message) -> bool>(
             fid,
+            start_time,
+            stop_time,
             page_options,
-            Some(|message: &Message| {
-                return is_message_in_time_range(start_time, stop_time, message);
-            }),
+            None,
         )
     }
 
@@ -243,20 +243,7 @@ impl UserDataStore {
 
         let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let page_options = get_page_options(&mut cx, 1)?;
-        let start_time = match cx.argument_opt(2) {
-            Some(arg) => match arg.downcast::<JsNumber, _>(&mut cx) {
-                Ok(v) => Some(v.value(&mut cx) as u32),
-                _ => None,
-            },
-            None => None,
-        };
-        let stop_time = match cx.argument_opt(3) {
-            Some(arg) => match arg.downcast::<JsNumber, _>(&mut cx) {
-                Ok(v) => Some(v.value(&mut cx) as u32),
-                _ => None,
-            },
-            None => None,
-        };
+        let (start_time, stop_time) = get_time_range(&mut cx, 2);
 
         let messages = match Self::get_user_data_adds_by_fid(
             &store,
@@ -292,16 +279,16 @@ impl UserDataStore {
 
         let result = match Self::get_username_proof(&store, &name) {
             Ok(Some(proof)) => match proof.fid {
-                0 => cx.throw_error(format!(
-                    "{}/{}",
-                    "not_found", "NotFound: UserDataAdd message not found"
-                ))?,
+                0 => hub_error_to_js_throw(
+                    &mut cx,
+                    HubError::not_found("NotFound: UserDataAdd message not found"),
+                )?,
                 _ => proof.encode_to_vec(),
             },
-            Ok(None) => cx.throw_error(format!(
-                "{}/{}",
-                "not_found", "NotFound: UserDataAdd message not found"
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found("NotFound: UserDataAdd message not found"),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -329,10 +316,10 @@ impl UserDataStore {
 
         let result = match Self::get_username_proof_by_fid(&store, fid) {
             Ok(Some(proof)) => proof.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{}",
-                "not_found", "NotFound: UserDataAdd message not found"
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found("NotFound: UserDataAdd message not found"),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -352,32 +339,36 @@ impl UserDataStore {
         username_proof: &protos::UserNameProof,
     ) -> Result<Vec<u8>, HubError> {
         let existing_proof = get_username_proof(&store.db(), &username_proof.name)?;
-        let mut existing_fid: Option<u32> = None;
+        let existing_fid = existing_proof.as_ref().map(|proof| proof.fid as u32);
+
+        // A deleted name has no proof, so the deletion is what newer proofs are compared against
+        let latest_proof = match &existing_proof {
+            Some(proof) => Some(proof.clone()),
+            None => get_username_proof_deletion(&store.db(), &username_proof.name)?,
+        };
 
-        if existing_proof.is_some() {
-            let cmp =
-                Self::username_proof_compare(existing_proof.as_ref().unwrap(), username_proof);
+        if latest_proof.is_some() {
+            let cmp = Self::username_proof_compare(latest_proof.as_ref().unwrap(), username_proof);
 
             if cmp == 0 {
-                return Err(HubError {
-                    code: "bad_request.duplicate".to_string(),
-                    message: "username proof already exists".to_string(),
-                });
+                return Err(HubError::new(
+                    HubErrorCode::Duplicate,
+                    "username proof already exists",
+                ));
             }
             if cmp > 0 {
-                return Err(HubError {
-                    code: "bad_request.conflict".to_string(),
-                    message: "event conflicts with a more recent UserNameProof".to_string(),
-                });
+                return Err(HubError::new(
+                    HubErrorCode::Conflict,
+                    "event conflicts with a more recent UserNameProof",
+                ));
             }
-            existing_fid = Some(existing_proof.as_ref().unwrap().fid as u32);
         }
 
         if existing_proof.is_none() && username_proof.fid == 0 {
-            return Err(HubError {
-                code: "bad_request.conflict".to_string(),
-                message: "proof does not exist".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::Conflict,
+                "proof does not exist",
+            ));
         }
 
         let mut txn = RocksDbTransactionBatch::new();
@@ -386,6 +377,7 @@ impl UserDataStore {
         } else {
             put_username_proof_transaction(&mut txn, username_proof);
         }
+        put_fname_history_transaction(&mut txn, username_proof, existing_proof.as_ref());
 
         let mut hub_event = HubEvent {
             r#type: HubEventType::MergeUsernameProof as i32,
@@ -419,10 +411,10 @@ impl UserDataStore {
 
         let result = if username_proof.is_err() {
             let e = username_proof.unwrap_err();
-            Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: e.to_string(),
-            })
+            Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                e.to_string(),
+            ))
         } else {
             Self::merge_username_proof(store.as_ref(), &username_proof.unwrap())
         };
@@ -437,7 +429,7 @@ impl UserDataStore {
                     .copy_from_slice(&hub_event_bytes);
                 Ok(js_buffer)
             }
-            Err(e) => cx.throw_error(format!("{}/{}", e.code, e.message)),
+            Err(e) => hub_error_to_js_throw(&mut cx, e),
         });
         // });
 
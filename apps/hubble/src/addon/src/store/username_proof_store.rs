@@ -1,9 +1,10 @@
 use super::{
-    get_message, hub_error_to_js_throw, make_fid_key, make_user_key, read_fid_key,
+    get_message, get_messages_at_keys, hub_error_to_js_throw, make_fid_key,
+    make_message_primary_key, make_user_key, read_fid_key,
     store::{Store, StoreDef},
-    utils::{self, encode_messages_to_js_object, get_page_options, get_store},
-    HubError, IntoU8, MessagesPage, PageOptions, RootPrefix, StoreEventHandler, UserPostfix,
-    TS_HASH_LENGTH,
+    utils::{self, encode_messages_to_js_object, get_page_options, get_store, get_time_range},
+    HubError, HubErrorCode, IntoU8, MessagesPage, PageOptions, RootPrefix, StoreEventHandler,
+    UserPostfix, FID_BYTES, TS_HASH_LENGTH,
 };
 use crate::protos::{
     hub_event, message_data::Body, HubEvent, HubEventType, MergeUserNameProofBody, UserNameType,
@@ -35,27 +36,27 @@ impl StoreDef for UsernameProofStoreDef {
 
     fn make_add_key(&self, message: &Message) -> Result<Vec<u8>, HubError> {
         if message.data.is_none() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Message data is missing".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "Message data is missing",
+            ));
         }
 
         let data = message.data.as_ref().unwrap();
         if data.body.is_none() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Message body is missing".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "Message body is missing",
+            ));
         }
 
         let name = match &data.body {
             Some(Body::UsernameProofBody(body)) => &body.name,
             _ => {
-                return Err(HubError {
-                    code: "bad_request.validation_failure".to_string(),
-                    message: "Message body is missing".to_string(),
-                })
+                return Err(HubError::new(
+                    HubErrorCode::ValidationFailure,
+                    "Message body is missing",
+                ))
             }
         };
 
@@ -78,24 +79,24 @@ impl StoreDef for UsernameProofStoreDef {
     }
 
     fn make_remove_key(&self, _message: &Message) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.validation_failure".to_string(),
-            message: "Remove not supported".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::ValidationFailure,
+            "Remove not supported",
+        ))
     }
 
     fn make_compact_state_add_key(&self, _message: &Message) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "Username Proof Store doesn't support compact state".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            "Username Proof Store doesn't support compact state",
+        ))
     }
 
     fn make_compact_state_prefix(&self, _fid: u32) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "Username Proof Store doesn't support compact state".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            "Username Proof Store doesn't support compact state",
+        ))
     }
 
     fn is_add_type(&self, message: &Message) -> bool {
@@ -112,19 +113,16 @@ impl StoreDef for UsernameProofStoreDef {
         message: &Message,
     ) -> Result<(), HubError> {
         if message.data.is_none() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Message data is missing".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "Message data is missing",
+            ));
         }
 
         let data = message.data.as_ref().unwrap();
         if let Some(Body::UsernameProofBody(body)) = &data.body {
             if body.name.len() == 0 {
-                return Err(HubError {
-                    code: "bad_request.invalid_param".to_string(),
-                    message: "name empty".to_string(),
-                });
+                return Err(HubError::new(HubErrorCode::InvalidParam, "name empty"));
             }
 
             let by_name_key = Self::make_username_proof_by_name_key(&body.name);
@@ -134,10 +132,10 @@ impl StoreDef for UsernameProofStoreDef {
             );
             Ok(())
         } else {
-            Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Message body is missing or incorrect".to_string(),
-            })
+            Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "Message body is missing or incorrect",
+            ))
         }
     }
 
@@ -148,29 +146,26 @@ impl StoreDef for UsernameProofStoreDef {
         message: &Message,
     ) -> Result<(), HubError> {
         if message.data.is_none() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Message data is missing".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "Message data is missing",
+            ));
         }
 
         let data = message.data.as_ref().unwrap();
         if let Some(Body::UsernameProofBody(body)) = &data.body {
             if body.name.len() == 0 {
-                return Err(HubError {
-                    code: "bad_request.invalid_param".to_string(),
-                    message: "name empty".to_string(),
-                });
+                return Err(HubError::new(HubErrorCode::InvalidParam, "name empty"));
             }
 
             let by_name_key = Self::make_username_proof_by_name_key(&body.name);
             txn.delete(by_name_key);
             Ok(())
         } else {
-            Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Message data body is missing or incorrect".to_string(),
-            })
+            Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "Message data body is missing or incorrect",
+            ))
         }
     }
 
@@ -182,6 +177,34 @@ impl StoreDef for UsernameProofStoreDef {
         Ok(())
     }
 
+    fn secondary_index_prefixes(&self) -> Vec<u8> {
+        vec![RootPrefix::UserNameProofByName as u8]
+    }
+
+    // The byName index is <RootPrefix>:<name> -> fid, so the message is found through the fid's
+    // adds set
+    fn secondary_index_message_key(
+        &self,
+        db: &RocksDB,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, HubError> {
+        if value.len() != FID_BYTES {
+            return Ok(None);
+        }
+
+        let fid = read_fid_key(value);
+        let adds_key = Self::make_username_proof_by_fid_key(fid, &key[1..].to_vec());
+        match db.get(&adds_key)? {
+            Some(ts_hash) => Ok(Some(make_message_primary_key(
+                fid,
+                self.postfix(),
+                Some(&utils::vec_to_u8_24(&Some(ts_hash))?),
+            ))),
+            None => Ok(None),
+        }
+    }
+
     fn get_merge_conflicts(
         &self,
         db: &RocksDB,
@@ -189,20 +212,20 @@ impl StoreDef for UsernameProofStoreDef {
         ts_hash: &[u8; TS_HASH_LENGTH],
     ) -> Result<Vec<Message>, HubError> {
         if message.data.is_none() {
-            return Err(HubError {
-                code: "bad_request.validation_failure".to_string(),
-                message: "Message data is missing".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::ValidationFailure,
+                "Message data is missing",
+            ));
         }
 
         let data = message.data.as_ref().unwrap();
         let name = match &data.body {
             Some(Body::UsernameProofBody(body)) => &body.name,
             _ => {
-                return Err(HubError {
-                    code: "bad_request.validation_failure".to_string(),
-                    message: "Message data body is missing".to_string(),
-                })
+                return Err(HubError::new(
+                    HubErrorCode::ValidationFailure,
+                    "Message data body is missing",
+                ))
             }
         };
 
@@ -229,16 +252,16 @@ impl StoreDef for UsernameProofStoreDef {
                         );
 
                         if message_compare > 0 {
-                            return Err(HubError {
-                                code: "bad_request.conflict".to_string(),
-                                message: "message conflicts with a more recent add".to_string(),
-                            });
+                            return Err(HubError::new(
+                                HubErrorCode::Conflict,
+                                "message conflicts with a more recent add",
+                            ));
                         }
                         if message_compare == 0 {
-                            return Err(HubError {
-                                code: "bad_request.duplicate".to_string(),
-                                message: "message has already been merged".to_string(),
-                            });
+                            return Err(HubError::new(
+                                HubErrorCode::Duplicate,
+                                "message has already been merged",
+                            ));
                         }
                         conflicts.push(existing_message);
                     }
@@ -249,6 +272,27 @@ impl StoreDef for UsernameProofStoreDef {
         Ok(conflicts)
     }
 
+    fn get_competing_messages(
+        &self,
+        db: &RocksDB,
+        message: &Message,
+    ) -> Result<Vec<Message>, HubError> {
+        // Proofs of a name compete with the proof of whichever fid has it now
+        let name = match message.data.as_ref().and_then(|data| data.body.as_ref()) {
+            Some(Body::UsernameProofBody(body)) => &body.name,
+            _ => return Ok(vec![]),
+        };
+
+        match db.get(&Self::make_username_proof_by_name_key(name))? {
+            Some(fid_bytes) if read_fid_key(&fid_bytes) > 0 => {
+                let fid = read_fid_key(&fid_bytes);
+                let keys = vec![Self::make_username_proof_by_fid_key(fid, name)];
+                get_messages_at_keys(db, fid, self.postfix(), &keys)
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
     fn remove_message_type(&self) -> u8 {
         MessageType::None.into_u8()
     }
@@ -266,10 +310,10 @@ impl StoreDef for UsernameProofStoreDef {
         _db: &RocksDB,
         _message: &Message,
     ) -> Result<(), HubError> {
-        return Err(HubError {
-            code: "bad_request.validation_failure".to_string(),
-            message: "Username Proof store does not support removes".to_string(),
-        });
+        return Err(HubError::new(
+            HubErrorCode::ValidationFailure,
+            "Username Proof store does not support removes",
+        ));
     }
 
     fn get_prune_size_limit(&self) -> u32 {
@@ -347,7 +391,7 @@ impl StoreDef for UsernameProofStoreDef {
 }
 
 impl UsernameProofStoreDef {
-    fn make_username_proof_by_name_key(name: &Vec<u8>) -> Vec<u8> {
+    pub fn make_username_proof_by_name_key(name: &Vec<u8>) -> Vec<u8> {
         let mut key = Vec::with_capacity(1 + name.len());
 
         key.push(RootPrefix::UserNameProofByName as u8);
@@ -356,7 +400,7 @@ impl UsernameProofStoreDef {
         key
     }
 
-    fn make_username_proof_by_fid_key(fid: u32, name: &Vec<u8>) -> Vec<u8> {
+    pub fn make_username_proof_by_fid_key(fid: u32, name: &Vec<u8>) -> Vec<u8> {
         let mut key = Vec::with_capacity(1 + 4 + 1 + name.len());
 
         key.extend_from_slice(&make_user_key(fid));
@@ -388,25 +432,25 @@ impl UsernameProofStore {
         name_type: u8,
     ) -> Result<Option<protos::Message>, HubError> {
         if name_type != UserNameType::UsernameTypeEnsL1 as u8 {
-            return Err(HubError {
-                code: "bad_request".to_string(),
-                message: format!(
+            return Err(HubError::new(
+                HubErrorCode::BadRequest,
+                format!(
                     "Unsupported username type {}. Only ENS L1 is supported",
                     name_type as u8
                 ),
-            });
+            ));
         }
 
         let by_name_key = UsernameProofStoreDef::make_username_proof_by_name_key(name);
         let fid_result = store.db().get(by_name_key.as_slice())?;
         if fid_result.is_none() {
-            return Err(HubError {
-                code: "not_found".to_string(),
-                message: format!(
+            return Err(HubError::new(
+                HubErrorCode::NotFound,
+                format!(
                     "NotFound: Username proof not found for name {}",
                     String::from_utf8_lossy(name)
                 ),
-            });
+            ));
         }
 
         let fid = read_fid_key(&fid_result.unwrap());
@@ -437,12 +481,13 @@ impl UsernameProofStore {
 
         let result = match Self::get_username_proof(&store, &name, name_type) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{} for {}",
-                "not_found",
-                "NotFound: usernameproof not found for {}",
-                String::from_utf8_lossy(&name)
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found(&format!(
+                    "NotFound: usernameproof not found for {}",
+                    String::from_utf8_lossy(&name)
+                )),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -460,8 +505,16 @@ impl UsernameProofStore {
         store: &Store,
         fid: u32,
         page_options: &PageOptions,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
     ) -> Result<MessagesPage, HubError> {
-        store.get_adds_by_fid::<fn(&protos::Message) -> bool>(fid, page_options, None)
+        store.get_adds_by_fid::<fn(&protos::Message) -> bool>(
+            fid,
+            start_time,
+            stop_time,
+            page_options,
+            None,
+        )
     }
 
     pub fn js_get_username_proofs_by_fid(mut cx: FunctionContext) -> JsResult<JsPromise> {
@@ -471,8 +524,15 @@ impl UsernameProofStore {
 
         let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let page_options = get_page_options(&mut cx, 1)?;
-
-        let messages = match Self::get_username_proofs_by_fid(&store, fid, &page_options) {
+        let (start_time, stop_time) = get_time_range(&mut cx, 2);
+
+        let messages = match Self::get_username_proofs_by_fid(
+            &store,
+            fid,
+            &page_options,
+            start_time,
+            stop_time,
+        ) {
             Ok(page) => page,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
@@ -517,12 +577,13 @@ impl UsernameProofStore {
 
         let result = match Self::get_username_proof_by_fid_and_name(&store, &name, fid) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{} for {}",
-                "not_found",
-                "NotFound: username proof not found for {}",
-                String::from_utf8_lossy(&name)
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found(&format!(
+                    "NotFound: username proof not found for {}",
+                    String::from_utf8_lossy(&name)
+                )),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
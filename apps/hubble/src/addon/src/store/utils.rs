@@ -1,4 +1,4 @@
-use super::{HubError, MessagesPage, PageOptions, Store, FARCASTER_EPOCH};
+use super::{HubError, HubErrorCode, MessagesPage, PageOptions, Store, FARCASTER_EPOCH};
 use crate::{
     db::{JsIteratorOptions, RocksDB},
     trie::merkle_trie::{MerkleTrie, NodeMetadata},
@@ -9,7 +9,8 @@ use neon::{
     object::Object,
     result::{JsResult, Throw},
     types::{
-        buffer::TypedArray, Deferred, JsArray, JsBoolean, JsBox, JsBuffer, JsNumber, JsObject,
+        buffer::TypedArray, Deferred, JsArray, JsBoolean, JsBox, JsBuffer, JsError, JsNumber,
+        JsObject,
     },
 };
 use std::{borrow::Borrow, sync::Arc};
@@ -24,16 +25,16 @@ pub fn vec_to_u8_24(vec: &Option<Vec<u8>>) -> Result<[u8; 24], HubError> {
             arr.copy_from_slice(&vec);
             Ok(arr)
         } else {
-            Err(HubError {
-                code: "bad_request.internal_error".to_string(),
-                message: format!("message_ts_hash is not 24 bytes: {:x?}", vec),
-            })
+            Err(HubError::new(
+                HubErrorCode::ParseFailure,
+                format!("message_ts_hash is not 24 bytes: {:x?}", vec),
+            ))
         }
     } else {
-        Err(HubError {
-            code: "bad_request.internal_error".to_string(),
-            message: "message_ts_hash is not 24 bytes: None".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::ParseFailure,
+            "message_ts_hash is not 24 bytes: None",
+        ))
     }
 }
 
@@ -143,6 +144,9 @@ pub fn encode_node_metadata_to_js_object<'a>(
     let js_hash = tcx.string(node_metadata.hash.clone());
     js_object.set(tcx, "hash", js_hash)?;
 
+    let js_truncated = tcx.boolean(node_metadata.truncated);
+    js_object.set(tcx, "truncated", js_truncated)?;
+
     // We can't return a map from rust to JS, so we return two arrays,
     // one with keys and one with values
     let js_keys = JsArray::new(tcx, node_metadata.children.len());
@@ -191,6 +195,24 @@ pub fn get_page_options(cx: &mut FunctionContext, at: usize) -> Result<PageOptio
     })
 }
 
+/**
+ * Extract the optional start and stop times (in farcaster time) of a by-fid listing, at the given
+ * index and the one after it. Anything that isn't a number leaves that end of the range open.
+ */
+pub fn get_time_range(cx: &mut FunctionContext, at: usize) -> (Option<u32>, Option<u32>) {
+    (get_optional_u32(cx, at), get_optional_u32(cx, at + 1))
+}
+
+fn get_optional_u32(cx: &mut FunctionContext, at: usize) -> Option<u32> {
+    match cx.argument_opt(at) {
+        Some(arg) => match arg.downcast::<JsNumber, _>(cx) {
+            Ok(v) => Some(v.value(cx) as u32),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
 /**
  * Extract the iterator opts
  */
@@ -241,8 +263,37 @@ pub fn get_merkle_trie(cx: &mut FunctionContext) -> Result<Arc<MerkleTrie>, Thro
     Ok((**merkle_trie_js_box.borrow()).clone())
 }
 
+/**
+ * Turn a HubError into a JS Error, with the HubError code as `errCode` and any context it has as
+ * `fid`, `hash` and `key`, so JS can make a HubError out of it without parsing the message.
+ */
+pub fn hub_error_to_js_error<'a, U: Context<'a>>(cx: &mut U, e: HubError) -> JsResult<'a, JsError> {
+    let js_error = JsError::error(cx, e.message)?;
+
+    let js_code = cx.string(e.code.as_str());
+    js_error.set(cx, "errCode", js_code)?;
+
+    if let Some(fid) = e.fid {
+        let js_fid = cx.number(fid);
+        js_error.set(cx, "fid", js_fid)?;
+    }
+    if let Some(hash) = e.hash {
+        let mut js_hash = cx.buffer(hash.len())?;
+        js_hash.as_mut_slice(cx).copy_from_slice(&hash);
+        js_error.set(cx, "hash", js_hash)?;
+    }
+    if let Some(key) = e.key {
+        let mut js_key = cx.buffer(key.len())?;
+        js_key.as_mut_slice(cx).copy_from_slice(&key);
+        js_error.set(cx, "key", js_key)?;
+    }
+
+    Ok(js_error)
+}
+
 pub fn hub_error_to_js_throw<'a, T, U: Context<'a>>(cx: &mut U, e: HubError) -> Result<T, Throw> {
-    cx.throw_error::<String, T>(format!("{}/{}", e.code, e.message))
+    let js_error = hub_error_to_js_error(cx, e)?;
+    cx.throw(js_error)
 }
 
 pub fn deferred_settle_messages(
@@ -259,18 +310,18 @@ pub fn deferred_settle_messages(
 #[allow(dead_code)]
 pub fn to_farcaster_time(time_ms: u64) -> Result<u64, HubError> {
     if time_ms < FARCASTER_EPOCH {
-        return Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: format!("time_ms is before the farcaster epoch: {}", time_ms),
-        });
+        return Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            format!("time_ms is before the farcaster epoch: {}", time_ms),
+        ));
     }
 
     let seconds_since_epoch = (time_ms - FARCASTER_EPOCH) / 1000;
     if seconds_since_epoch > u32::MAX as u64 {
-        return Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: format!("time too far in future: {}", time_ms),
-        });
+        return Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            format!("time too far in future: {}", time_ms),
+        ));
     }
 
     Ok(seconds_since_epoch as u64)
@@ -285,10 +336,7 @@ pub fn from_farcaster_time(time: u64) -> u64 {
 pub fn get_farcaster_time() -> Result<u64, HubError> {
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| HubError {
-            code: "internal_error".to_string(),
-            message: format!("failed to get time: {}", e),
-        })?;
+        .map_err(|e| HubError::new(HubErrorCode::Unknown, format!("failed to get time: {}", e)))?;
     Ok(to_farcaster_time(now.as_millis() as u64)?)
 }
 
@@ -1,10 +1,10 @@
 use super::{
-    get_message, hub_error_to_js_throw, make_fid_key, make_ts_hash, make_user_key, message_decode,
-    read_fid_key,
+    get_message, get_messages_at_keys, hub_error_to_js_throw, make_fid_key,
+    make_message_primary_key, make_ts_hash, make_user_key, message_decode, read_fid_key,
     store::{Store, StoreDef},
-    utils::{self, encode_messages_to_js_object, get_page_options, get_store},
-    HubError, MessagesPage, PageOptions, RootPrefix, StoreEventHandler, UserPostfix, FID_BYTES,
-    TS_HASH_LENGTH,
+    utils::{self, encode_messages_to_js_object, get_page_options, get_store, get_time_range},
+    HubError, HubErrorCode, MessagesPage, PageOptions, RootPrefix, StoreEventHandler, UserPostfix,
+    FID_BYTES, TS_HASH_LENGTH,
 };
 use crate::{
     db::{RocksDB, RocksDbTransactionBatch},
@@ -104,19 +104,11 @@ impl StoreDef for VerificationStoreDef {
     ) -> Result<(), HubError> {
         let address = match message.data.as_ref().unwrap().body.as_ref().unwrap() {
             message_data::Body::VerificationAddAddressBody(body) => &body.address,
-            _ => {
-                return Err(HubError {
-                    code: "bad_request.invalid_param".to_string(),
-                    message: "address empty".to_string(),
-                })
-            }
+            _ => return Err(HubError::new(HubErrorCode::InvalidParam, "address empty")),
         };
 
         if address.is_empty() {
-            return Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: "address empty".to_string(),
-            });
+            return Err(HubError::new(HubErrorCode::InvalidParam, "address empty"));
         }
 
         // Puts the fid into the byAddress index
@@ -137,19 +129,11 @@ impl StoreDef for VerificationStoreDef {
     ) -> Result<(), HubError> {
         let address = match message.data.as_ref().unwrap().body.as_ref().unwrap() {
             message_data::Body::VerificationAddAddressBody(body) => &body.address,
-            _ => {
-                return Err(HubError {
-                    code: "bad_request.invalid_param".to_string(),
-                    message: "address empty".to_string(),
-                })
-            }
+            _ => return Err(HubError::new(HubErrorCode::InvalidParam, "address empty")),
         };
 
         if address.is_empty() {
-            return Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: "address empty".to_string(),
-            });
+            return Err(HubError::new(HubErrorCode::InvalidParam, "address empty"));
         }
 
         // Delete the message key from byAddress index
@@ -167,15 +151,43 @@ impl StoreDef for VerificationStoreDef {
         Ok(())
     }
 
+    fn secondary_index_prefixes(&self) -> Vec<u8> {
+        vec![RootPrefix::VerificationByAddress as u8]
+    }
+
+    // The byAddress index is <RootPrefix>:<address> -> fid, so the message is found through the
+    // fid's adds set
+    fn secondary_index_message_key(
+        &self,
+        db: &RocksDB,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, HubError> {
+        if value.len() != FID_BYTES {
+            return Ok(None);
+        }
+
+        let fid = read_fid_key(value);
+        let adds_key = Self::make_verification_adds_key(fid, &key[1..]);
+        match db.get(&adds_key)? {
+            Some(ts_hash) => Ok(Some(make_message_primary_key(
+                fid,
+                self.postfix(),
+                Some(&utils::vec_to_u8_24(&Some(ts_hash))?),
+            ))),
+            None => Ok(None),
+        }
+    }
+
     fn make_add_key(&self, message: &protos::Message) -> Result<Vec<u8>, HubError> {
         let address = match message.data.as_ref().unwrap().body.as_ref().unwrap() {
             message_data::Body::VerificationAddAddressBody(body) => &body.address,
             message_data::Body::VerificationRemoveBody(body) => &body.address,
             _ => {
-                return Err(HubError {
-                    code: "bad_request.validation_failure".to_string(),
-                    message: "Invalid verification body".to_string(),
-                })
+                return Err(HubError::new(
+                    HubErrorCode::ValidationFailure,
+                    "Invalid verification body",
+                ))
             }
         };
 
@@ -190,10 +202,10 @@ impl StoreDef for VerificationStoreDef {
             message_data::Body::VerificationAddAddressBody(body) => &body.address,
             message_data::Body::VerificationRemoveBody(body) => &body.address,
             _ => {
-                return Err(HubError {
-                    code: "bad_request.validation_failure".to_string(),
-                    message: "Invalid verification body".to_string(),
-                })
+                return Err(HubError::new(
+                    HubErrorCode::ValidationFailure,
+                    "Invalid verification body",
+                ))
             }
         };
 
@@ -204,17 +216,17 @@ impl StoreDef for VerificationStoreDef {
     }
 
     fn make_compact_state_add_key(&self, _message: &Message) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "Verification Store doesn't support compact state".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            "Verification Store doesn't support compact state",
+        ))
     }
 
     fn make_compact_state_prefix(&self, _fid: u32) -> Result<Vec<u8>, HubError> {
-        Err(HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: "Verification Store doesn't support compact state".to_string(),
-        })
+        Err(HubError::new(
+            HubErrorCode::InvalidParam,
+            "Verification Store doesn't support compact state",
+        ))
     }
 
     fn get_prune_size_limit(&self) -> u32 {
@@ -239,20 +251,20 @@ impl StoreDef for VerificationStoreDef {
         let address = match message.data.as_ref().unwrap().body.as_ref().unwrap() {
             message_data::Body::VerificationAddAddressBody(body) => &body.address,
             _ => {
-                return Err(HubError {
-                    code: "bad_request.validation_failure".to_string(),
-                    message: "Invalid verification body".to_string(),
-                })
+                return Err(HubError::new(
+                    HubErrorCode::ValidationFailure,
+                    "Invalid verification body",
+                ))
             }
         };
 
         let by_address_key = Self::make_verification_by_address_key(address);
         let fid_result = match db.get(&by_address_key) {
             Ok(Some(fid)) => Ok(fid),
-            _ => Err(HubError {
-                code: "not_found".to_string(),
-                message: "verification not found".to_string(),
-            }),
+            _ => Err(HubError::new(
+                HubErrorCode::NotFound,
+                "verification not found",
+            )),
         };
 
         if fid_result.is_ok() {
@@ -272,17 +284,17 @@ impl StoreDef for VerificationStoreDef {
                     );
 
                     if message_compare > 0 {
-                        return Err(HubError {
-                            code: "bad_request.conflict".to_string(),
-                            message: "message conflicts with a more recent add".to_string(),
-                        });
+                        return Err(HubError::new(
+                            HubErrorCode::Conflict,
+                            "message conflicts with a more recent add",
+                        ));
                     }
 
                     if message_compare == 0 {
-                        return Err(HubError {
-                            code: "bad_request.conflict".to_string(),
-                            message: "message has already been merged".to_string(),
-                        });
+                        return Err(HubError::new(
+                            HubErrorCode::Conflict,
+                            "message has already been merged",
+                        ));
                     }
 
                     let existing_message = get_message(
@@ -300,6 +312,40 @@ impl StoreDef for VerificationStoreDef {
 
         Ok(conflicts)
     }
+
+    fn get_competing_messages(
+        &self,
+        db: &RocksDB,
+        message: &Message,
+    ) -> Result<Vec<Message>, HubError> {
+        let fid = message.data.as_ref().unwrap().fid as u32;
+        let mut keys = vec![self.make_add_key(message)?, self.make_remove_key(message)?];
+        let mut messages = get_messages_at_keys(db, fid, self.postfix(), &keys)?;
+
+        if self.is_remove_type(message) {
+            return Ok(messages);
+        }
+
+        // Adds also compete with the verification of the same address by another fid
+        let address = match message.data.as_ref().unwrap().body.as_ref() {
+            Some(message_data::Body::VerificationAddAddressBody(body)) => &body.address,
+            _ => return Ok(messages),
+        };
+        if let Some(fid_bytes) = db.get(&Self::make_verification_by_address_key(address))? {
+            let existing_fid = read_fid_key(&fid_bytes);
+            if existing_fid > 0 && existing_fid != fid {
+                keys = vec![Self::make_verification_adds_key(existing_fid, address)];
+                messages.extend(get_messages_at_keys(
+                    db,
+                    existing_fid,
+                    self.postfix(),
+                    &keys,
+                )?);
+            }
+        }
+
+        Ok(messages)
+    }
 }
 
 impl VerificationStoreDef {
@@ -371,15 +417,19 @@ impl VerificationStore {
 
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let address = cx.argument::<JsBuffer>(1)?.as_slice(&cx).to_vec();
 
         let result = match Self::get_verification_add(&store, fid, &address) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{} for {}",
-                "not_found", "NotFound: verificationAddMessage not found", fid
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found(&format!(
+                    "NotFound: verificationAddMessage not found for {}",
+                    fid
+                ))
+                .with_fid(fid),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -419,15 +469,16 @@ impl VerificationStore {
     pub fn js_get_verification_remove(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let address = cx.argument::<JsBuffer>(1)?.as_slice(&cx).to_vec();
 
         let result = match Self::get_verification_remove(&store, fid, &address) {
             Ok(Some(message)) => message.encode_to_vec(),
-            Ok(None) => cx.throw_error(format!(
-                "{}/{} for {}",
-                "not_found", "verificationRemoveMessage not found", fid
-            ))?,
+            Ok(None) => hub_error_to_js_throw(
+                &mut cx,
+                HubError::not_found(&format!("verificationRemoveMessage not found for {}", fid))
+                    .with_fid(fid),
+            )?,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
@@ -446,17 +497,32 @@ impl VerificationStore {
         store: &Store,
         fid: u32,
         page_options: &PageOptions,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
     ) -> Result<MessagesPage, HubError> {
-        store.get_adds_by_fid::<fn(&protos::Message) -> bool>(fid, page_options, None)
+        store.get_adds_by_fid::<fn(&protos::Message) -> bool>(
+            fid,
+            start_time,
+            stop_time,
+            page_options,
+            None,
+        )
     }
 
     pub fn js_get_verification_adds_by_fid(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let page_options = get_page_options(&mut cx, 1)?;
-
-        let messages = match Self::get_verification_adds_by_fid(&store, fid, &page_options) {
+        let (start_time, stop_time) = get_time_range(&mut cx, 2);
+
+        let messages = match Self::get_verification_adds_by_fid(
+            &store,
+            fid,
+            &page_options,
+            start_time,
+            stop_time,
+        ) {
             Ok(messages) => messages,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
@@ -474,17 +540,32 @@ impl VerificationStore {
         store: &Store,
         fid: u32,
         page_options: &PageOptions,
+        start_time: Option<u32>,
+        stop_time: Option<u32>,
     ) -> Result<MessagesPage, HubError> {
-        store.get_removes_by_fid::<fn(&protos::Message) -> bool>(fid, page_options, None)
+        store.get_removes_by_fid::<fn(&protos::Message) -> bool>(
+            fid,
+            start_time,
+            stop_time,
+            page_options,
+            None,
+        )
     }
 
     pub fn js_get_verification_removes_by_fid(mut cx: FunctionContext) -> JsResult<JsPromise> {
         let store = get_store(&mut cx)?;
 
-        let fid = cx.argument::<JsNumber>(0).unwrap().value(&mut cx) as u32;
+        let fid = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
         let page_options = get_page_options(&mut cx, 1)?;
-
-        let messages = match Self::get_verification_removes_by_fid(&store, fid, &page_options) {
+        let (start_time, stop_time) = get_time_range(&mut cx, 2);
+
+        let messages = match Self::get_verification_removes_by_fid(
+            &store,
+            fid,
+            &page_options,
+            start_time,
+            stop_time,
+        ) {
             Ok(messages) => messages,
             Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
@@ -535,10 +616,10 @@ impl VerificationStore {
                         VerificationStoreDef::make_verification_by_address_key(address);
                     let existing_fid_res = match store.db().get(&by_address_key) {
                         Ok(Some(existing_fid)) => Ok(existing_fid),
-                        _ => Err(HubError {
-                            code: "not_found".to_string(),
-                            message: "verification not found".to_string(),
-                        }),
+                        _ => Err(HubError::new(
+                            HubErrorCode::NotFound,
+                            "verification not found",
+                        )),
                     };
 
                     if existing_fid_res.is_ok() {
@@ -547,10 +628,10 @@ impl VerificationStore {
                             match Self::get_verification_add(store, existing_fid, address) {
                                 Ok(Some(message)) => message,
                                 _ => {
-                                    return Err(HubError {
-                                        code: "not_found".to_string(),
-                                        message: "verification not found".to_string(),
-                                    })
+                                    return Err(HubError::new(
+                                        HubErrorCode::NotFound,
+                                        "verification not found",
+                                    ))
                                 }
                             };
 
@@ -562,10 +643,10 @@ impl VerificationStore {
                         );
 
                         if ts_hash.is_err() || existing_ts_hash.is_err() {
-                            return Err(HubError {
-                                code: "bad_request".to_string(),
-                                message: "failed to make tsHash".to_string(),
-                            });
+                            return Err(HubError::new(
+                                HubErrorCode::BadRequest,
+                                "failed to make tsHash",
+                            ));
                         }
 
                         let message_compare = store.store_def().message_compare(
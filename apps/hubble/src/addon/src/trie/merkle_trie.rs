@@ -2,15 +2,21 @@ use super::trie_node::{TrieNode, TIMESTAMP_LENGTH};
 use crate::{
     db::{RocksDB, RocksDbTransactionBatch},
     logger::LOGGER,
+    panics::{
+        catch_panic, execute, lock_or_error, lock_unpoisoned, read_or_error, write_or_error,
+        write_unpoisoned,
+    },
     statsd::statsd,
-    store::{encode_node_metadata_to_js_object, get_merkle_trie, hub_error_to_js_throw, HubError},
-    THREAD_POOL,
+    store::{
+        encode_node_metadata_to_js_object, get_merkle_trie, hub_error_to_js_throw, HubError,
+        HubErrorCode,
+    },
 };
 use neon::object::Object as _;
 use neon::{
     context::ModuleContext,
     result::NeonResult,
-    types::{buffer::TypedArray as _, JsArray, JsObject},
+    types::{buffer::TypedArray as _, JsArray, JsNumber, JsObject},
 };
 use neon::{
     context::{Context as _, FunctionContext},
@@ -28,12 +34,30 @@ use std::{
 pub const TRIE_DBPATH_PREFIX: &str = "trieDb";
 const TRIE_UNLOAD_THRESHOLD: usize = 10_000;
 
+// Limits for multi-level node metadata calls, so a single call can't be used to dump the whole trie
+const MAX_NODE_METADATA_NODES: usize = 4096;
+const MAX_NODE_METADATA_DEPTH: usize = 4;
+
 #[derive(Debug)]
 pub struct NodeMetadata {
     pub prefix: Vec<u8>,
     pub num_messages: usize,
     pub hash: String,
     pub children: HashMap<u8, NodeMetadata>,
+    // Set if this node has children that were not expanded because the node cap was reached
+    pub truncated: bool,
+}
+
+impl NodeMetadata {
+    fn from_node(prefix: &[u8], node: &TrieNode) -> Self {
+        NodeMetadata {
+            prefix: prefix.to_vec(),
+            num_messages: node.items(),
+            hash: hex::encode(&node.hash()),
+            children: HashMap::new(),
+            truncated: false,
+        }
+    }
 }
 
 pub struct TrieSnapshot {
@@ -92,8 +116,11 @@ impl MerkleTrie {
         let serialized = TrieNode::serialize(&empty);
 
         // Write the empty root node to the DB
-        self.txn_batch.lock().unwrap().put(root_key, serialized);
-        self.root.write().unwrap().replace(empty);
+        // This replaces whatever an earlier panic left behind, so the trie is usable again
+        lock_unpoisoned(&self.txn_batch).put(root_key, serialized);
+        write_unpoisoned(&self.root).replace(empty);
+        self.txn_batch.clear_poison();
+        self.root.clear_poison();
     }
 
     pub fn initialize(&self) -> Result<(), HubError> {
@@ -111,7 +138,7 @@ impl MerkleTrie {
                 "rootHash" => hex::encode(root_node.hash()), 
                 "items" => root_node.items());
             // Replace the root node
-            self.root.write().unwrap().replace(root_node);
+            write_or_error(&self.root)?.replace(root_node);
         } else {
             info!(self.logger, "Merkle Trie initialized with empty root node");
             self.create_empty_root();
@@ -125,7 +152,7 @@ impl MerkleTrie {
     }
 
     pub fn clear(&self) -> Result<(), HubError> {
-        self.txn_batch.lock().unwrap().batch.clear();
+        lock_unpoisoned(&self.txn_batch).batch.clear();
         self.db.clear()?;
 
         self.create_empty_root();
@@ -135,7 +162,7 @@ impl MerkleTrie {
 
     pub fn stop(&self) -> Result<(), HubError> {
         // Grab the root with a write lock
-        let mut root = self.root.write().unwrap().take();
+        let mut root = write_or_error(&self.root)?.take();
         if let Some(root) = root.as_mut() {
             // And write everything to disk
             self.unload_from_memory(root, true)?;
@@ -155,7 +182,7 @@ impl MerkleTrie {
      *  be supplied by the caller.
      */
     fn unload_from_memory(&self, root: &mut TrieNode, force: bool) -> Result<(), HubError> {
-        let mut txn_batch = self.txn_batch.lock().unwrap();
+        let mut txn_batch = lock_or_error(&self.txn_batch)?;
         if force || txn_batch.batch.len() > TRIE_UNLOAD_THRESHOLD {
             // Take the txn_batch out of the lock and replace it with a new one
             let pending_txn_batch =
@@ -179,26 +206,26 @@ impl MerkleTrie {
 
         for key in keys.iter() {
             if key.len() < TIMESTAMP_LENGTH {
-                return Err(HubError {
-                    code: "bad_request.invalid_param".to_string(),
-                    message: "Key length is too short".to_string(),
-                });
+                return Err(HubError::new(
+                    HubErrorCode::InvalidParam,
+                    "Key length is too short",
+                ));
             }
         }
 
-        if let Some(root) = self.root.write().unwrap().as_mut() {
+        if let Some(root) = write_or_error(&self.root)?.as_mut() {
             let mut txn = RocksDbTransactionBatch::new();
             let results = root.insert(&self.db, &mut txn, keys, 0)?;
 
-            self.txn_batch.lock().unwrap().merge(txn);
+            lock_or_error(&self.txn_batch)?.merge(txn);
             self.unload_from_memory(root, false)?;
 
             Ok(results)
         } else {
-            Err(HubError {
-                code: "bad_request.internal_error".to_string(),
-                message: format!("Merkle Trie not initialized for insert {:?}", keys),
-            })
+            Err(HubError::new(
+                HubErrorCode::Unavailable,
+                format!("Merkle Trie not initialized for insert {:?}", keys),
+            ))
         }
     }
 
@@ -209,47 +236,47 @@ impl MerkleTrie {
 
         for key in keys.iter() {
             if key.len() < TIMESTAMP_LENGTH {
-                return Err(HubError {
-                    code: "bad_request.invalid_param".to_string(),
-                    message: "Key length is too short".to_string(),
-                });
+                return Err(HubError::new(
+                    HubErrorCode::InvalidParam,
+                    "Key length is too short",
+                ));
             }
         }
 
-        if let Some(root) = self.root.write().unwrap().as_mut() {
+        if let Some(root) = write_or_error(&self.root)?.as_mut() {
             let mut txn = RocksDbTransactionBatch::new();
             let results = root.delete(&self.db, &mut txn, keys, 0)?;
 
-            self.txn_batch.lock().unwrap().merge(txn);
+            lock_or_error(&self.txn_batch)?.merge(txn);
             self.unload_from_memory(root, false)?;
             Ok(results)
         } else {
-            Err(HubError {
-                code: "bad_request.internal_error".to_string(),
-                message: "Merkle Trie not initialized for delete".to_string(),
-            })
+            Err(HubError::new(
+                HubErrorCode::Unavailable,
+                "Merkle Trie not initialized for delete",
+            ))
         }
     }
 
     pub fn exists(&self, key: &Vec<u8>) -> Result<bool, HubError> {
-        if let Some(root) = self.root.write().unwrap().as_mut() {
+        if let Some(root) = write_or_error(&self.root)?.as_mut() {
             root.exists(&self.db, &key, 0)
         } else {
-            Err(HubError {
-                code: "bad_request.internal_error".to_string(),
-                message: "Merkle Trie not initialized for exists".to_string(),
-            })
+            Err(HubError::new(
+                HubErrorCode::Unavailable,
+                "Merkle Trie not initialized for exists",
+            ))
         }
     }
 
     pub fn items(&self) -> Result<usize, HubError> {
-        if let Some(root) = self.root.read().unwrap().as_ref() {
+        if let Some(root) = read_or_error(&self.root)?.as_ref() {
             Ok(root.items())
         } else {
-            Err(HubError {
-                code: "bad_request.internal_error".to_string(),
-                message: "Merkle Trie not initialized for items".to_string(),
-            })
+            Err(HubError::new(
+                HubErrorCode::Unavailable,
+                "Merkle Trie not initialized for items",
+            ))
         }
     }
 
@@ -257,9 +284,11 @@ impl MerkleTrie {
         let node_key = TrieNode::make_primary_key(prefix, None);
 
         // We will first attempt to get it from the DB cache
-        if let Some(Some(node_bytes)) = self.txn_batch.lock().unwrap().batch.get(&node_key) {
-            if let Ok(node) = TrieNode::deserialize(&node_bytes) {
-                return Some(node);
+        if let Ok(txn_batch) = lock_or_error(&self.txn_batch) {
+            if let Some(Some(node_bytes)) = txn_batch.batch.get(&node_key) {
+                if let Ok(node) = TrieNode::deserialize(&node_bytes) {
+                    return Some(node);
+                }
             }
         }
 
@@ -274,80 +303,164 @@ impl MerkleTrie {
     }
 
     pub fn root_hash(&self) -> Result<Vec<u8>, HubError> {
-        if let Some(root) = self.root.read().unwrap().as_ref() {
+        if let Some(root) = read_or_error(&self.root)?.as_ref() {
             Ok(root.hash())
         } else {
-            Err(HubError {
-                code: "bad_request.internal_error".to_string(),
-                message: "Merkle Trie not initialized for root_hash".to_string(),
-            })
+            Err(HubError::new(
+                HubErrorCode::Unavailable,
+                "Merkle Trie not initialized for root_hash",
+            ))
+        }
+    }
+
+    /**
+     * Recompute the root hash from every node in the DB. This walks the whole trie, so it is slow,
+     * but it doesn't trust any of the stored intermediate hashes.
+     */
+    pub fn recompute_root_hash(&self) -> Result<Vec<u8>, HubError> {
+        if let Some(root) = write_or_error(&self.root)?.as_mut() {
+            // The hashes are recomputed from the DB, so flush any pending nodes first
+            self.unload_from_memory(root, true)?;
+
+            // An empty trie has nothing to recompute
+            if root.is_leaf() {
+                return Ok(root.hash());
+            }
+
+            TrieNode::recompute_hash(&self.db, &[])
+        } else {
+            Err(HubError::new(
+                HubErrorCode::Unavailable,
+                "Merkle Trie not initialized for recompute_root_hash",
+            ))
         }
     }
 
     pub fn get_all_values(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>, HubError> {
-        if let Some(root) = self.root.write().unwrap().as_mut() {
+        if let Some(root) = write_or_error(&self.root)?.as_mut() {
             if let Some(node) = root.get_node_from_trie(&self.db, prefix, 0) {
                 node.get_all_values(&self.db, prefix)
             } else {
                 Ok(Vec::new())
             }
         } else {
-            Err(HubError {
-                code: "bad_request.internal_error".to_string(),
-                message: "Merkle Trie not initialized for get_all_values".to_string(),
-            })
+            Err(HubError::new(
+                HubErrorCode::Unavailable,
+                "Merkle Trie not initialized for get_all_values",
+            ))
         }
     }
 
     pub fn get_snapshot(&self, prefix: &[u8]) -> Result<TrieSnapshot, HubError> {
-        if let Some(root) = self.root.write().unwrap().as_mut() {
+        if let Some(root) = write_or_error(&self.root)?.as_mut() {
             let result = root.get_snapshot(&self.db, prefix, 0);
 
             result
         } else {
-            Err(HubError {
-                code: "bad_request.internal_error".to_string(),
-                message: "Merkle Trie not initialized for get_snapshot".to_string(),
-            })
+            Err(HubError::new(
+                HubErrorCode::Unavailable,
+                "Merkle Trie not initialized for get_snapshot",
+            ))
         }
     }
 
     pub fn get_trie_node_metadata(&self, prefix: &[u8]) -> Result<NodeMetadata, HubError> {
-        if let Some(node) = self.get_node(prefix) {
-            let mut children = HashMap::new();
-
-            for char in node.children().keys() {
-                let mut child_prefix = prefix.to_vec();
-                child_prefix.push(*char);
-
-                let child_node = self.get_node(&child_prefix).ok_or(HubError {
-                    code: "bad_request.internal_error".to_string(),
-                    message: "Child Node not found".to_string(),
-                })?;
-
-                children.insert(
-                    *char,
-                    NodeMetadata {
-                        prefix: child_prefix,
-                        num_messages: child_node.items(),
-                        hash: hex::encode(&child_node.hash()),
-                        children: HashMap::new(),
-                    },
-                );
+        self.get_trie_node_metadata_to_depth(prefix, 1, usize::MAX, &HashMap::new())
+    }
+
+    /**
+     * Get the metadata for the node at `prefix` and its descendants, down to `depth` levels below
+     * it. A depth of 1 returns the node and its direct children.
+     *
+     * The walk is breadth first, so if more than `max_nodes` nodes would be returned, the deepest
+     * levels are the ones that get cut off, and the nodes whose children were skipped are marked as
+     * `truncated`.
+     *
+     * If `known_hashes` (prefix -> hex hash) is not empty, nodes whose hash matches the known hash
+     * for their prefix are not expanded, since their subtrees are already in sync.
+     */
+    pub fn get_trie_node_metadata_to_depth(
+        &self,
+        prefix: &[u8],
+        depth: usize,
+        max_nodes: usize,
+        known_hashes: &HashMap<Vec<u8>, String>,
+    ) -> Result<NodeMetadata, HubError> {
+        let node = self
+            .get_node(prefix)
+            .ok_or(HubError::new(HubErrorCode::InvalidParam, "Node not found"))?;
+
+        let mut metadata = HashMap::new();
+        metadata.insert(prefix.to_vec(), NodeMetadata::from_node(prefix, &node));
+        let mut num_nodes = 1;
+
+        // Each level is the list of nodes that were fetched at that depth. The last level's nodes
+        // are not expanded any further
+        let mut levels: Vec<Vec<Vec<u8>>> = vec![vec![prefix.to_vec()]];
+        let mut frontier = vec![(prefix.to_vec(), node)];
+
+        for _ in 0..depth {
+            let mut next_frontier = vec![];
+
+            for (node_prefix, node) in frontier {
+                if node.is_leaf() {
+                    continue;
+                }
+
+                // If the caller already has this exact subtree, there is no need to expand it
+                let node_metadata = metadata.get_mut(&node_prefix).unwrap();
+                if known_hashes.get(&node_prefix) == Some(&node_metadata.hash) {
+                    continue;
+                }
+
+                if num_nodes + node.children().len() > max_nodes {
+                    node_metadata.truncated = true;
+                    continue;
+                }
+
+                let mut chars = node.children().keys().cloned().collect::<Vec<_>>();
+                chars.sort();
+
+                for char in chars {
+                    let mut child_prefix = node_prefix.clone();
+                    child_prefix.push(char);
+
+                    let child_node = self.get_node(&child_prefix).ok_or(HubError::new(
+                        HubErrorCode::Unavailable,
+                        "Child Node not found",
+                    ))?;
+
+                    metadata.insert(
+                        child_prefix.clone(),
+                        NodeMetadata::from_node(&child_prefix, &child_node),
+                    );
+                    next_frontier.push((child_prefix, child_node));
+                }
+                num_nodes += node.children().len();
             }
 
-            Ok(NodeMetadata {
-                prefix: prefix.to_vec(),
-                num_messages: node.items(),
-                hash: hex::encode(&node.hash()),
-                children,
-            })
-        } else {
-            Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: "Node not found".to_string(),
-            })
+            if next_frontier.is_empty() {
+                break;
+            }
+
+            levels.push(next_frontier.iter().map(|(p, _)| p.clone()).collect());
+            frontier = next_frontier;
         }
+
+        // Assemble the tree bottom up, attaching each node to its parent
+        for level in levels.iter().skip(1).rev() {
+            for child_prefix in level {
+                let child = metadata.remove(child_prefix).unwrap();
+                let (char, parent_prefix) = child_prefix.split_last().unwrap();
+                metadata
+                    .get_mut(parent_prefix)
+                    .unwrap()
+                    .children
+                    .insert(*char, child);
+            }
+        }
+
+        Ok(metadata.remove(prefix).unwrap())
     }
 }
 
@@ -356,7 +469,7 @@ impl MerkleTrie {
         let db_path = cx.argument::<JsString>(0)?.value(&mut cx);
         let trie = match MerkleTrie::new(&db_path) {
             Ok(trie) => trie,
-            Err(e) => return cx.throw_error::<String, _>(e.message),
+            Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
         Ok(cx.boxed(Arc::new(trie)))
@@ -368,7 +481,7 @@ impl MerkleTrie {
         let db = cx.argument::<JsBox<Arc<RocksDB>>>(0)?;
         let trie = match MerkleTrie::new_with_db((**db.borrow()).clone()) {
             Ok(trie) => trie,
-            Err(e) => return cx.throw_error::<String, _>(e.message),
+            Err(e) => return hub_error_to_js_throw(&mut cx, e),
         };
 
         Ok(cx.boxed(Arc::new(trie)))
@@ -461,22 +574,22 @@ impl MerkleTrie {
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let insert_results = trie.insert(insert_keys);
-            let delete_results = trie.delete(delete_keys);
+        execute(move || {
+            let insert_results = catch_panic(|| trie.insert(insert_keys));
+            let delete_results = catch_panic(|| trie.delete(delete_keys));
 
             deferred.settle_with(&channel, move |mut cx| {
                 // If either was an error, return the error
                 if insert_results.is_err() || delete_results.is_err() {
                     return hub_error_to_js_throw(
                         &mut cx,
-                        HubError {
-                            code: "bad_request.internal_error".to_string(),
-                            message: format!(
+                        HubError::new(
+                            HubErrorCode::Unavailable,
+                            format!(
                                 "Error in batch update: {:?} {:?}",
                                 insert_results, delete_results
                             ),
-                        },
+                        ),
                     );
                 }
 
@@ -582,11 +695,47 @@ impl MerkleTrie {
         let trie = get_merkle_trie(&mut cx)?;
         let prefix = cx.argument::<JsBuffer>(0)?.as_slice(&cx).to_vec();
 
+        // Optional arguments to fetch more than one level of the trie at a time
+        let depth = match cx.argument_opt(1) {
+            Some(arg) => match arg.downcast::<JsNumber, _>(&mut cx) {
+                Ok(v) => (v.value(&mut cx) as usize).clamp(1, MAX_NODE_METADATA_DEPTH),
+                _ => 1,
+            },
+            None => 1,
+        };
+        let max_nodes = match cx.argument_opt(2) {
+            Some(arg) => match arg.downcast::<JsNumber, _>(&mut cx) {
+                Ok(v) => (v.value(&mut cx) as usize).clamp(1, MAX_NODE_METADATA_NODES),
+                _ => MAX_NODE_METADATA_NODES,
+            },
+            None => MAX_NODE_METADATA_NODES,
+        };
+        let mut known_hashes = HashMap::new();
+        if let Some(arg) = cx.argument_opt(3) {
+            if let Ok(js_known_hashes) = arg.downcast::<JsArray, _>(&mut cx) {
+                // A peer can't know more nodes than we'd return, so ignore the rest
+                let js_known_hashes = js_known_hashes.to_vec(&mut cx)?;
+                for js_known_hash in js_known_hashes.into_iter().take(MAX_NODE_METADATA_NODES) {
+                    let js_known_hash = js_known_hash.downcast_or_throw::<JsObject, _>(&mut cx)?;
+                    let known_prefix = js_known_hash
+                        .get::<JsBuffer, _, _>(&mut cx, "prefix")?
+                        .as_slice(&cx)
+                        .to_vec();
+                    let known_hash = js_known_hash
+                        .get::<JsString, _, _>(&mut cx, "hash")?
+                        .value(&mut cx);
+                    known_hashes.insert(known_prefix, known_hash);
+                }
+            }
+        }
+
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let result = trie.get_trie_node_metadata(&prefix);
+        execute(move || {
+            let result = catch_panic(|| {
+                trie.get_trie_node_metadata_to_depth(&prefix, depth, max_nodes, &known_hashes)
+            });
 
             deferred.settle_with(&channel, move |mut tcx| match result {
                 Ok(node_metadata) => {
@@ -607,8 +756,8 @@ impl MerkleTrie {
         let channel = cx.channel();
         let (deferred, promise) = cx.promise();
 
-        THREAD_POOL.lock().unwrap().execute(move || {
-            let result = trie.get_all_values(&prefix);
+        execute(move || {
+            let result = catch_panic(|| trie.get_all_values(&prefix));
 
             deferred.settle_with(&channel, move |mut tcx| match result {
                 Ok(values) => {
@@ -666,7 +815,11 @@ impl MerkleTrie {
         let (deferred, promise) = cx.promise();
 
         deferred.settle_with(&channel, move |mut cx| {
-            if let Some(root) = trie.root.write().unwrap().as_mut() {
+            let mut root = match write_or_error(&trie.root) {
+                Ok(root) => root,
+                Err(e) => return hub_error_to_js_throw(&mut cx, e),
+            };
+            if let Some(root) = root.as_mut() {
                 if let Err(e) = trie.unload_from_memory(root, true) {
                     return hub_error_to_js_throw(&mut cx, e);
                 }
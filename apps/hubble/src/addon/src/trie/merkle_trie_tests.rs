@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use crate::trie::merkle_trie::MerkleTrie;
+    use std::collections::HashMap;
 
     #[test]
     fn test_merkle_trie_get_node() {
@@ -69,4 +70,125 @@ mod tests {
         // Clean up
         std::fs::remove_dir_all(&tmp_path).unwrap();
     }
+
+    #[test]
+    fn test_merkle_trie_get_node_metadata_to_depth() {
+        let tmp_path = tempfile::tempdir()
+            .unwrap()
+            .path()
+            .as_os_str()
+            .to_string_lossy()
+            .to_string();
+
+        let trie = MerkleTrie::new(&tmp_path).unwrap();
+        trie.initialize().unwrap();
+
+        let key1: Vec<_> = "0000482712".bytes().collect();
+        let key2: Vec<_> = "0000482713".bytes().collect();
+        let key3: Vec<_> = "0000492712".bytes().collect();
+        trie.insert(vec![key1.clone(), key2.clone(), key3.clone()])
+            .unwrap();
+
+        // Depth 1 is the same as the plain metadata call
+        let metadata = trie
+            .get_trie_node_metadata_to_depth(&key1[0..5], 1, usize::MAX, &HashMap::new())
+            .unwrap();
+        assert_eq!(metadata.num_messages, 3);
+        assert_eq!(metadata.children.len(), 2);
+        assert!(metadata.children.values().all(|c| c.children.is_empty()));
+        assert_eq!(metadata.truncated, false);
+
+        // Depth 2 includes the grandchildren
+        let metadata = trie
+            .get_trie_node_metadata_to_depth(&key1[0..5], 2, usize::MAX, &HashMap::new())
+            .unwrap();
+        let child = metadata.children.get(&key1[5]).unwrap();
+        assert_eq!(child.prefix, key1[0..6].to_vec());
+        assert_eq!(child.num_messages, 2);
+        let grandchild = child.children.get(&key1[6]).unwrap();
+        assert_eq!(grandchild.prefix, key1[0..7].to_vec());
+        assert_eq!(grandchild.num_messages, 2);
+        assert_eq!(grandchild.children.len(), 0);
+
+        // Going all the way down reaches the leaves
+        let metadata = trie
+            .get_trie_node_metadata_to_depth(&key1[0..8], 10, usize::MAX, &HashMap::new())
+            .unwrap();
+        let leaf = metadata
+            .children
+            .get(&key1[8])
+            .unwrap()
+            .children
+            .get(&key1[9])
+            .unwrap();
+        assert_eq!(leaf.prefix, key1);
+        assert_eq!(leaf.num_messages, 1);
+
+        // A node cap stops the expansion and marks the node as truncated
+        let metadata = trie
+            .get_trie_node_metadata_to_depth(&key1[0..5], 2, 3, &HashMap::new())
+            .unwrap();
+        assert_eq!(metadata.children.len(), 2);
+        assert!(metadata.children.values().all(|c| c.children.is_empty()));
+        let child = metadata.children.get(&key1[5]).unwrap();
+        assert_eq!(child.truncated, true);
+
+        // Nodes whose hash is already known are not expanded
+        let mut known_hashes = HashMap::new();
+        known_hashes.insert(key3[0..6].to_vec(), child_hash(&trie, &key3[0..6]));
+        let metadata = trie
+            .get_trie_node_metadata_to_depth(&key1[0..5], 2, usize::MAX, &known_hashes)
+            .unwrap();
+        assert_eq!(metadata.children.get(&key1[5]).unwrap().children.len(), 1);
+        assert_eq!(metadata.children.get(&key3[5]).unwrap().children.len(), 0);
+
+        trie.stop().unwrap();
+
+        // Clean up
+        std::fs::remove_dir_all(&tmp_path).unwrap();
+    }
+
+    #[test]
+    fn test_merkle_trie_recompute_root_hash() {
+        let tmp_path = tempfile::tempdir()
+            .unwrap()
+            .path()
+            .as_os_str()
+            .to_string_lossy()
+            .to_string();
+
+        let trie = MerkleTrie::new(&tmp_path).unwrap();
+        trie.initialize().unwrap();
+
+        // An empty trie recomputes to its own root hash
+        assert_eq!(
+            trie.recompute_root_hash().unwrap(),
+            trie.root_hash().unwrap()
+        );
+
+        let key1: Vec<_> = "0000482712".bytes().collect();
+        let key2: Vec<_> = "0000482713".bytes().collect();
+        let key3: Vec<_> = "0000492712".bytes().collect();
+        trie.insert(vec![key1.clone(), key2.clone(), key3.clone()])
+            .unwrap();
+        assert_eq!(
+            trie.recompute_root_hash().unwrap(),
+            trie.root_hash().unwrap()
+        );
+
+        trie.delete(vec![key2.clone()]).unwrap();
+        assert_eq!(
+            trie.recompute_root_hash().unwrap(),
+            trie.root_hash().unwrap()
+        );
+
+        trie.stop().unwrap();
+
+        // Clean up
+        std::fs::remove_dir_all(&tmp_path).unwrap();
+    }
+
+    fn child_hash(trie: &MerkleTrie, prefix: &[u8]) -> String {
+        hex::encode(trie.get_node(prefix).unwrap().hash())
+    }
 }
@@ -1,7 +1,7 @@
 use crate::{
     db::{RocksDB, RocksDbTransactionBatch},
     protos::DbTrieNode,
-    store::{blake3_20, bytes_compare, HubError, RootPrefix},
+    store::{blake3_20, bytes_compare, HubError, HubErrorCode, RootPrefix},
 };
 use prost::Message as _;
 use std::collections::HashMap;
@@ -75,9 +75,11 @@ impl TrieNode {
     }
 
     pub(crate) fn deserialize(serialized: &[u8]) -> Result<TrieNode, HubError> {
-        let db_trie_node = DbTrieNode::decode(serialized).map_err(|e| HubError {
-            code: "bad_request.invalid_param".to_string(),
-            message: format!("Failed to decode trie node: {}", e),
+        let db_trie_node = DbTrieNode::decode(serialized).map_err(|e| {
+            HubError::new(
+                HubErrorCode::InvalidParam,
+                format!("Failed to decode trie node: {}", e),
+            )
         })?;
 
         let mut children = HashMap::new();
@@ -163,10 +165,10 @@ impl TrieNode {
         current_index: usize,
     ) -> Result<Vec<bool>, HubError> {
         if keys.len() == 0 {
-            return Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: "No keys to insert".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::InvalidParam,
+                "No keys to insert",
+            ));
         }
 
         // Note that all the keys will have the same prefix, so we can get the [0]th one
@@ -230,10 +232,10 @@ impl TrieNode {
             .iter()
             .any(|(_, key)| current_index >= key.len())
         {
-            return Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: "Key length exceeded".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::InvalidParam,
+                "Key length exceeded",
+            ));
         }
 
         // For the remaining keys, group them by the key[current_index] and insert them in bulk
@@ -306,10 +308,10 @@ impl TrieNode {
 
         // Check if any of the remaining keys are invalid
         if keys.iter().any(|key| current_index >= key.len()) {
-            return Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: "Key length exceeded".to_string(),
-            });
+            return Err(HubError::new(
+                HubErrorCode::InvalidParam,
+                "Key length exceeded",
+            ));
         }
 
         // For the remaining keys, we group them by the key[current_index] and delete them in bulk
@@ -465,16 +467,16 @@ impl TrieNode {
                 }
                 match entry.into_mut() {
                     TrieNodeType::Node(node) => Ok(node),
-                    _ => Err(HubError {
-                        code: "bad_request.invalid_param".to_string(),
-                        message: format!("Child {} is not a node", char),
-                    }),
+                    _ => Err(HubError::new(
+                        HubErrorCode::InvalidParam,
+                        format!("Child {} is not a node", char),
+                    )),
                 }
             }
-            Entry::Vacant(_) => Err(HubError {
-                code: "bad_request.invalid_param".to_string(),
-                message: format!("Child {} at prefix {:?} not found", char, prefix),
-            }),
+            Entry::Vacant(_) => Err(HubError::new(
+                HubErrorCode::InvalidParam,
+                format!("Child {} at prefix {:?} not found", char, prefix),
+            )),
         }
     }
 
@@ -519,6 +521,40 @@ impl TrieNode {
         Ok(())
     }
 
+    /**
+     * Recompute the hash of the node at `prefix` from scratch, walking all of its descendants in the
+     * DB instead of trusting the stored child hashes. Used to check that a trie on disk is
+     * consistent, e.g. after restoring a snapshot.
+     */
+    pub(crate) fn recompute_hash(db: &RocksDB, prefix: &[u8]) -> Result<Vec<u8>, HubError> {
+        let node_key = Self::make_primary_key(prefix, None);
+        let node = match db.get(&node_key)? {
+            Some(node_bytes) => TrieNode::deserialize(&node_bytes)?,
+            None => {
+                return Err(HubError::internal_db_error(&format!(
+                    "Trie node at prefix {:?} not found",
+                    prefix
+                )))
+            }
+        };
+
+        if node.is_leaf() {
+            return Ok(blake3_20(&node.key.unwrap_or_default()));
+        }
+
+        let mut sorted_children = node.children.keys().map(|c| *c).collect::<Vec<_>>();
+        sorted_children.sort();
+
+        let mut concat_hashes = vec![];
+        for char in sorted_children {
+            let mut child_prefix = prefix.to_vec();
+            child_prefix.push(char);
+            concat_hashes.extend_from_slice(&Self::recompute_hash(db, &child_prefix)?);
+        }
+
+        Ok(blake3_20(&concat_hashes))
+    }
+
     fn excluded_hash(
         &mut self,
         db: &RocksDB,
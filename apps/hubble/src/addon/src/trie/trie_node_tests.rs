@@ -2,6 +2,7 @@
 mod tests {
     use crate::{
         db::{RocksDB, RocksDbTransactionBatch},
+        store::HubErrorCode,
         trie::trie_node::{TrieNode, TrieNodeType, TIMESTAMP_LENGTH},
     };
     use hex::FromHex as _;
@@ -44,7 +45,7 @@ mod tests {
         let key = (0..9).collect::<Vec<_>>();
         let r = node.insert(&db, &mut txn, vec![key], 0);
         assert_eq!(r.is_err(), true);
-        assert_eq!(r.unwrap_err().code, "bad_request.invalid_param".to_string());
+        assert_eq!(r.unwrap_err().code, HubErrorCode::InvalidParam);
         assert_eq!(node.items(), 0);
 
         // Add a new key. [0, 1, 2, .... 20]
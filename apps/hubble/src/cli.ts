@@ -88,6 +88,8 @@ app
     "--log-individual-messages",
     "Log individual submitMessage. If disabled, log one line per second (default: disabled)",
   )
+  .option("--cast-search-index", "Maintain a full-text index of cast text for searchCasts. (default: disabled)")
+  .option("--cast-feed-index", "Maintain an index of every cast in time order for getRecentCasts. (default: disabled)")
 
   // Ethereum Options
   .option("-m, --eth-mainnet-rpc-url <url>", "RPC URL of a Mainnet ETH Node (or comma separated list of URLs)")
@@ -142,6 +144,10 @@ app
   .option("--admin-server-enabled", "Enable the admin server. (default: disabled)")
   .option("--admin-server-host <host>", "The host the admin server should listen on. (default: '127.0.0.1')")
   .option("--http-server-disabled", "Disable the HTTP server. (default: enabled)")
+  .option(
+    "--rust-rpc-server-address <address>",
+    "Serve read-only gRPC requests from Rust on this address, e.g. 0.0.0.0:2285. (default: disabled)",
+  )
 
   // Snapshots
   .option("--enable-snapshot-to-s3", "Enable daily snapshots to be uploaded to S3. (default: disabled)")
@@ -540,6 +546,8 @@ app
       rpcRateLimit,
       rpcSubscribePerIpLimit: cliOptions.rpcSubscribePerIpLimit ?? hubConfig.rpcSubscribePerIpLimit,
       rocksDBName: cliOptions.dbName ?? hubConfig.dbName,
+      castSearchIndex: cliOptions.castSearchIndex ?? hubConfig.castSearchIndex ?? false,
+      castFeedIndex: cliOptions.castFeedIndex ?? hubConfig.castFeedIndex ?? false,
       resetDB: false,
       rebuildSyncTrie,
       profileSync,
@@ -552,6 +560,7 @@ app
       commitLockMaxPending: cliOptions.commitLockMaxPending ?? hubConfig.commitLockMaxPending,
       adminServerEnabled: cliOptions.adminServerEnabled ?? hubConfig.adminServerEnabled,
       httpServerDisabled: cliOptions.httpServerDisabled ?? hubConfig.httpServerDisabled ?? false,
+      rustRpcServerAddress: cliOptions.rustRpcServerAddress ?? hubConfig.rustRpcServerAddress,
       adminServerHost: cliOptions.adminServerHost ?? hubConfig.adminServerHost,
       testUsers: testUsers,
       directPeers,
@@ -40,7 +40,14 @@ import Engine from "./storage/engine/index.js";
 import { PruneEventsJobScheduler } from "./storage/jobs/pruneEventsJob.js";
 import { PruneMessagesJobScheduler } from "./storage/jobs/pruneMessagesJob.js";
 import { sleep } from "./utils/crypto.js";
-import { rsDbDestroy, rsValidationMethods } from "./rustfunctions.js";
+import {
+  RustRpcServer,
+  rsDbDestroy,
+  rsRpcServerAddress,
+  rsStartRpcServer,
+  rsStopRpcServer,
+  rsValidationMethods,
+} from "./rustfunctions.js";
 import { URL } from "node:url";
 import * as tar from "tar";
 import * as zlib from "zlib";
@@ -275,6 +282,12 @@ export interface HubOptions {
   /** Name of the RocksDB instance */
   rocksDBName?: string;
 
+  /** Maintain the full-text index of cast text used by searchCasts */
+  castSearchIndex?: boolean;
+
+  /** Maintain the index of every cast in time order used by getRecentCasts */
+  castFeedIndex?: boolean;
+
   /** Resets the DB on start, if true */
   resetDB?: boolean;
 
@@ -296,6 +309,9 @@ export interface HubOptions {
   /** Http server disabled? */
   httpServerDisabled?: boolean;
 
+  /** host:port for the Rust RPC server, which serves read-only RPCs from the stores. Disabled if not set */
+  rustRpcServerAddress?: string;
+
   /** Enables the Admin Server */
   adminServerEnabled?: boolean;
 
@@ -381,6 +397,7 @@ export class Hub implements HubInterface {
   private rpcServer: Server;
   private adminServer: AdminServer;
   private httpApiServer: HttpAPIServer;
+  private rustRpcServer?: RustRpcServer;
 
   private rocksDB: RocksDB;
   private syncEngine: SyncEngine;
@@ -482,6 +499,7 @@ export class Hub implements HubInterface {
       opClient as PublicClient,
       this.fNameRegistryEventsProvider,
       this.l2RegistryProvider,
+      { searchIndex: options.castSearchIndex ?? false, feedIndex: options.castFeedIndex ?? false },
     );
 
     const profileSync = options.profileSync ?? false;
@@ -796,6 +814,10 @@ export class Hub implements HubInterface {
     } else {
       log.info("HTTP API server disabled");
     }
+    if (this.options.rustRpcServerAddress) {
+      this.rustRpcServer = rsStartRpcServer(this.options.rustRpcServerAddress, this.engine.rustStores);
+      log.info({ address: rsRpcServerAddress(this.rustRpcServer) }, "Rust RPC server started");
+    }
     if (this.options.adminServerEnabled) {
       await this.adminServer.start(this.options.adminServerHost ?? "127.0.0.1");
     }
@@ -1332,6 +1354,10 @@ export class Hub implements HubInterface {
     if (!this.options.httpServerDisabled) {
       await this.httpApiServer.stop();
     }
+    if (this.rustRpcServer) {
+      await rsStopRpcServer(this.rustRpcServer);
+      this.rustRpcServer = undefined;
+    }
     await this.rpcServer.stop(true); // Force shutdown until we have a graceful way of ending active streams
 
     // Stop admin, gossip and sync engine
@@ -1,5 +1,5 @@
 import { blake3 } from "@noble/hashes/blake3";
-import { DbTrieNode, Factories } from "@farcaster/hub-nodejs";
+import { DbTrieNode, Factories, TrieNodeHash } from "@farcaster/hub-nodejs";
 import { EMPTY_HASH, MerkleTrie } from "../sync/merkleTrie.js";
 import { NetworkFactories } from "../utils/factories.js";
 import { jestRocksDB } from "../../storage/db/jestUtils.js";
@@ -533,6 +533,32 @@ describe("MerkleTrie", () => {
       expect(nodeMetadata?.children?.get(new Uint8Array(Buffer.from("3"))[0] as number)).toBeDefined();
       expect(nodeMetadata?.children?.get(new Uint8Array(Buffer.from("4"))[0] as number)).toBeDefined();
     });
+
+    test("returns multiple levels of children if a depth is requested", async () => {
+      const trie = await trieWithIds([1665182332, 1665182343]);
+      const nodeMetadata = await trie.getTrieNodeMetadata(new Uint8Array(Buffer.from("16651823")), 2);
+
+      expect(nodeMetadata?.numMessages).toEqual(2);
+      const child = nodeMetadata?.children?.get(new Uint8Array(Buffer.from("3"))[0] as number);
+      expect(child?.prefix).toEqual(new Uint8Array(Buffer.from("166518233")));
+      expect(child?.children?.size).toEqual(1);
+      const grandchild = child?.children?.get(new Uint8Array(Buffer.from("2"))[0] as number);
+      expect(grandchild?.prefix).toEqual(new Uint8Array(Buffer.from("1665182332")));
+      expect(grandchild?.numMessages).toEqual(1);
+      expect(grandchild?.children?.size ?? 0).toEqual(0);
+    });
+
+    test("doesn't expand children whose hash is already known", async () => {
+      const trie = await trieWithIds([1665182332, 1665182343]);
+      const prefix = new Uint8Array(Buffer.from("16651823"));
+      const known = (await trie.getTrieNodeMetadata(prefix))?.children?.get(Buffer.from("3")[0] as number);
+      const knownHashes = [TrieNodeHash.create({ prefix: known?.prefix ?? new Uint8Array(), hash: known?.hash ?? "" })];
+
+      const nodeMetadata = await trie.getTrieNodeMetadata(prefix, 2, knownHashes);
+      expect(nodeMetadata?.truncated).toBeFalsy();
+      expect(nodeMetadata?.children?.get(Buffer.from("3")[0] as number)?.children?.size ?? 0).toEqual(0);
+      expect(nodeMetadata?.children?.get(Buffer.from("4")[0] as number)?.children?.size).toEqual(1);
+    });
   });
 
   describe("getSnapshot", () => {
@@ -1,5 +1,5 @@
 import { ok, Result, ResultAsync } from "neverthrow";
-import { DbTrieNode, HubAsyncResult, HubError, OnChainEvent, UserNameProof } from "@farcaster/hub-nodejs";
+import { DbTrieNode, HubAsyncResult, HubError, OnChainEvent, TrieNodeHash, UserNameProof } from "@farcaster/hub-nodejs";
 import { SyncId } from "./syncId.js";
 import RocksDB from "../../storage/db/rocksdb.js";
 import {
@@ -55,13 +55,15 @@ export type TrieSnapshot = {
  * @prefix - The prefix of the node, uniquely describes its position in the trie
  * @numMessages - The number of messages under this node
  * @hash - The merkle hash of the node
- * @children - The immediate children of this node
+ * @children - The children of this node that were fetched (immediate children, unless a depth was requested)
+ * @truncated - True if this node's children were left out because the size cap was reached
  */
 export type NodeMetadata = {
   prefix: Uint8Array;
   numMessages: number;
   hash: string;
   children?: Map<number, NodeMetadata>;
+  truncated?: boolean;
 };
 
 const log = logger.child({ component: "SyncMerkleTrie" });
@@ -357,10 +359,15 @@ class MerkleTrie {
   }
 
   /**
-   * Get the metadata for a node in the trie at the given prefix.
+   * Get the metadata for a node in the trie at the given prefix. Nodes whose hash matches the one in
+   * `knownHashes` for their prefix are returned without their children.
    */
-  public async getTrieNodeMetadata(prefix: Uint8Array): Promise<NodeMetadata | undefined> {
-    return await rsMerkleTrieGetTrieNodeMetadata(this._rustTrie, prefix);
+  public async getTrieNodeMetadata(
+    prefix: Uint8Array,
+    depth?: number,
+    knownHashes?: TrieNodeHash[],
+  ): Promise<NodeMetadata | undefined> {
+    return await rsMerkleTrieGetTrieNodeMetadata(this._rustTrie, prefix, depth, undefined, knownHashes);
   }
 
   /**
@@ -14,8 +14,8 @@ import {
   TrieNodePrefix,
 } from "@farcaster/hub-nodejs";
 
+import { toTrieNodeMetadataResponse } from "../../rpc/server.js";
 import Engine from "../../storage/engine/index.js";
-import { NodeMetadata } from "./merkleTrie.js";
 import SyncEngine from "./syncEngine.js";
 import { SyncId } from "./syncId.js";
 
@@ -45,37 +45,7 @@ export class MockRpcClient {
 
   async getSyncMetadataByPrefix(request: TrieNodePrefix): Promise<HubResult<TrieNodeMetadataResponse>> {
     this.getSyncMetadataByPrefixCalls.push(request);
-    const toTrieNodeMetadataResponse = (metadata?: NodeMetadata): TrieNodeMetadataResponse => {
-      const childrenTrie = [];
-
-      if (!metadata) {
-        return TrieNodeMetadataResponse.create({});
-      }
-
-      if (metadata.children) {
-        for (const [, child] of metadata.children) {
-          childrenTrie.push(
-            TrieNodeMetadataResponse.create({
-              prefix: child.prefix,
-              numMessages: child.numMessages,
-              hash: child.hash,
-              children: [],
-            }),
-          );
-        }
-      }
-
-      const metadataResponse = TrieNodeMetadataResponse.create({
-        prefix: metadata.prefix,
-        numMessages: metadata.numMessages,
-        hash: metadata.hash,
-        children: childrenTrie,
-      });
-
-      return metadataResponse;
-    };
-
-    const metadata = await this.syncEngine.getTrieNodeMetadata(request.prefix);
+    const metadata = await this.syncEngine.getTrieNodeMetadata(request.prefix, request.depth, request.knownHashes);
     return ok(toTrieNodeMetadataResponse(metadata));
   }
 
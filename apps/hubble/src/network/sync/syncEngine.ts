@@ -20,6 +20,7 @@ import {
   PruneMessageHubEvent,
   RevokeMessageHubEvent,
   SyncIds,
+  TrieNodeHash,
   TrieNodeMetadataResponse,
   TrieNodePrefix,
   UserNameProof,
@@ -79,6 +80,10 @@ const SYNC_THRESHOLD_IN_SECONDS = 10;
 // The maximum number of nodes to enqueue in the work queue
 const MAX_WORK_QUEUE_SIZE = 100_000;
 
+// How many levels of the trie to fetch from a peer in one call. Children that come back with their own
+// children are worked on without another round trip
+const SYNC_METADATA_DEPTH = 2;
+
 export const FIRST_SYNC_DELAY = 30 * 1000; // How long to wait after startup to start syncing
 const SYNC_MAX_DURATION = 110 * 60 * 1000; // 110 minutes, just slightly less than the periodic sync job frequency
 
@@ -571,9 +576,12 @@ export class SyncEngineWorkItem {
   prefix: Uint8Array;
   score: number;
   finishPromise: Promise<boolean> | undefined;
+  // Their node, if it was fetched with its children as part of its parent's work item
+  prefetchedNode: NodeMetadata | undefined;
 
   constructor(ourNode: NodeMetadata | undefined, theirNode: NodeMetadata) {
     this.prefix = theirNode.prefix;
+    this.prefetchedNode = theirNode.children?.size && !theirNode.truncated ? theirNode : undefined;
 
     // Score the work item
     const depth = TIMESTAMP_LENGTH - Math.min(TIMESTAMP_LENGTH, this.prefix.length);
@@ -1736,14 +1744,26 @@ class SyncEngine extends TypedEmitter<SyncEvents> {
           return;
         }
 
-        const ourNode = await this._trie.getTrieNodeMetadata(workItem.prefix);
+        const ourNode = await this._trie.getTrieNodeMetadata(workItem.prefix, SYNC_METADATA_DEPTH);
 
+        // If the parent's work item already fetched this node's children, skip the round trip. Otherwise fetch
+        // more than one level, and tell the peer which subtrees we have so it doesn't expand them
         const start = Date.now();
-        const theirNodeResult = await this.curSync.rpcClient.getSyncMetadataByPrefix(
-          TrieNodePrefix.create({ prefix: workItem.prefix }),
-          new Metadata(),
-        );
-        statsd().timing("syncengine.peer.get_syncmetadata_by_prefix_ms", Date.now() - start);
+        const theirNodeResult = workItem.prefetchedNode
+          ? ok(workItem.prefetchedNode)
+          : (
+              await this.curSync.rpcClient.getSyncMetadataByPrefix(
+                TrieNodePrefix.create({
+                  prefix: workItem.prefix,
+                  depth: SYNC_METADATA_DEPTH,
+                  knownHashes: knownHashesBelow(ourNode),
+                }),
+                new Metadata(),
+              )
+            ).map(fromNodeMetadataResponse);
+        if (!workItem.prefetchedNode) {
+          statsd().timing("syncengine.peer.get_syncmetadata_by_prefix_ms", Date.now() - start);
+        }
 
         if (theirNodeResult.isErr()) {
           log.warn(theirNodeResult.error, `Error fetching metadata for prefix ${workItem.prefix}`);
@@ -1781,7 +1801,7 @@ class SyncEngine extends TypedEmitter<SyncEvents> {
           return;
         }
 
-        const theirNode = fromNodeMetadataResponse(theirNodeResult.value);
+        const theirNode = theirNodeResult.value;
 
         // First, we'll check if our node is empty. If it is, then we can start importing all the messages from the other node
         // at this prefix
@@ -1968,8 +1988,12 @@ class SyncEngine extends TypedEmitter<SyncEvents> {
     await this._trie.delete(SyncId.fromMessage(message));
   }
 
-  public async getTrieNodeMetadata(prefix: Uint8Array): Promise<NodeMetadata | undefined> {
-    return this._trie.getTrieNodeMetadata(prefix);
+  public async getTrieNodeMetadata(
+    prefix: Uint8Array,
+    depth?: number,
+    knownHashes?: TrieNodeHash[],
+  ): Promise<NodeMetadata | undefined> {
+    return this._trie.getTrieNodeMetadata(prefix, depth, knownHashes);
   }
 
   public async getAllSyncIdsByPrefix(prefix: Uint8Array): Promise<Uint8Array[]> {
@@ -2223,10 +2247,14 @@ export const fromNodeMetadataResponse = (response: TrieNodeMetadataResponse): No
       // Char is the last char of prefix
       const char = prefix[prefix.length - 1] as number;
 
+      // If the peer returned more than one level, keep the grandchildren as well
+      const childMetadata = fromNodeMetadataResponse(child);
       children.set(char, {
         numMessages: Number(child?.numMessages),
         prefix,
         hash: child?.hash ?? "",
+        ...(childMetadata.children?.size ? { children: childMetadata.children } : {}),
+        truncated: child.truncated,
       });
     }
   }
@@ -2236,7 +2264,21 @@ export const fromNodeMetadataResponse = (response: TrieNodeMetadataResponse): No
     numMessages: Number(response.numMessages),
     hash: response.hash ?? "",
     children,
+    truncated: response.truncated,
   };
 };
 
+/**
+ * The prefixes and hashes of the nodes below `node`, so a peer can skip expanding the subtrees we
+ * already have
+ */
+export const knownHashesBelow = (node: NodeMetadata | undefined): TrieNodeHash[] => {
+  const knownHashes: TrieNodeHash[] = [];
+  for (const child of node?.children?.values() ?? []) {
+    knownHashes.push(TrieNodeHash.create({ prefix: child.prefix, hash: child.hash }));
+    knownHashes.push(...knownHashesBelow(child));
+  }
+  return knownHashes;
+};
+
 export default SyncEngine;
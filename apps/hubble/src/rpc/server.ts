@@ -364,14 +364,7 @@ export const toTrieNodeMetadataResponse = (metadata?: NodeMetadata): TrieNodeMet
 
   if (metadata.children) {
     for (const [, child] of metadata.children) {
-      childrenTrie.push(
-        TrieNodeMetadataResponse.create({
-          prefix: child.prefix,
-          numMessages: child.numMessages,
-          hash: child.hash,
-          children: [],
-        }),
-      );
+      childrenTrie.push(toTrieNodeMetadataResponse(child));
     }
   }
 
@@ -380,6 +373,7 @@ export const toTrieNodeMetadataResponse = (metadata?: NodeMetadata): TrieNodeMet
     numMessages: metadata.numMessages,
     hash: metadata.hash,
     children: childrenTrie,
+    truncated: metadata.truncated ?? false,
   });
 
   return metadataResponse;
@@ -790,7 +784,9 @@ export default class Server {
   }
 
   public async getSyncMetadataByPrefix(request: TrieNodePrefix) {
-    const metadata = await this.syncEngine?.getTrieNodeMetadata(request.prefix);
+    // Peers can ask for more than one level of the trie at a time to save round trips. The depth is
+    // clamped in the rust code, which also caps the total number of nodes returned
+    const metadata = await this.syncEngine?.getTrieNodeMetadata(request.prefix, request.depth, request.knownHashes);
     return ok(toTrieNodeMetadataResponse(metadata));
   }
 
@@ -2139,6 +2135,12 @@ export default class Server {
                 startTimestamp,
                 stopTimestamp,
               );
+              if (result?.isOk() && !result.value.nextPageToken) {
+                const additional = await this.engine?.getReactionCompactStateMessageByFid(fid);
+                if (additional?.isOk()) {
+                  result.value.messages.push(...additional.value.messages);
+                }
+              }
             } else if (request.userDataMessagesByFid) {
               result = await this.engine?.getUserDataByFid(
                 fid,
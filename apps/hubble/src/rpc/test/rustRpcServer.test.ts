@@ -0,0 +1,128 @@
+import {
+  Message,
+  FarcasterNetwork,
+  CastAddMessage,
+  CastId,
+  FidRequest,
+  CastsByParentRequest,
+  Factories,
+  HubError,
+  getInsecureHubRpcClient,
+  HubRpcClient,
+  OnChainEvent,
+} from "@farcaster/hub-nodejs";
+import { jestRocksDB } from "../../storage/db/jestUtils.js";
+import Engine from "../../storage/engine/index.js";
+import { RustRpcServer, rsRpcServerAddress, rsStartRpcServer, rsStopRpcServer } from "../../rustfunctions.js";
+
+const db = jestRocksDB("protobufs.rpc.rustRpcServer.test");
+const network = FarcasterNetwork.TESTNET;
+const engine = new Engine(db, network);
+
+let server: RustRpcServer;
+let client: HubRpcClient;
+
+beforeAll(async () => {
+  server = rsStartRpcServer("127.0.0.1:0", engine.rustStores);
+  client = getInsecureHubRpcClient(rsRpcServerAddress(server));
+});
+
+afterAll(async () => {
+  client.close();
+  await rsStopRpcServer(server);
+  await engine.stop();
+});
+
+const fid = Factories.Fid.build();
+const signer = Factories.Ed25519Signer.build();
+const custodySigner = Factories.Eip712Signer.build();
+
+let custodyEvent: OnChainEvent;
+let signerEvent: OnChainEvent;
+let storageEvent: OnChainEvent;
+let castAdd: CastAddMessage;
+let reply: CastAddMessage;
+
+beforeAll(async () => {
+  const signerKey = (await signer.getSignerKey())._unsafeUnwrap();
+  const custodySignerKey = (await custodySigner.getSignerKey())._unsafeUnwrap();
+  custodyEvent = Factories.IdRegistryOnChainEvent.build({ fid }, { transient: { to: custodySignerKey } });
+  signerEvent = Factories.SignerOnChainEvent.build({ fid }, { transient: { signer: signerKey } });
+  storageEvent = Factories.StorageRentOnChainEvent.build({ fid });
+
+  castAdd = await Factories.CastAddMessage.create({ data: { fid, network } }, { transient: { signer } });
+  reply = await Factories.CastAddMessage.create(
+    { data: { fid, network, castAddBody: { parentCastId: { fid, hash: castAdd.hash } } } },
+    { transient: { signer } },
+  );
+});
+
+beforeEach(async () => {
+  await engine.mergeOnChainEvent(custodyEvent);
+  await engine.mergeOnChainEvent(signerEvent);
+  await engine.mergeOnChainEvent(storageEvent);
+});
+
+describe("getCast", () => {
+  test("succeeds", async () => {
+    await engine.mergeMessage(castAdd);
+
+    const result = await client.getCast(CastId.create({ fid, hash: castAdd.hash }));
+    expect(Message.toJSON(result._unsafeUnwrap())).toEqual(Message.toJSON(castAdd));
+  });
+
+  test("fails if cast is missing", async () => {
+    const result = await client.getCast(CastId.create({ fid, hash: castAdd.hash }));
+    expect(result._unsafeUnwrapErr().errCode).toEqual("not_found");
+  });
+
+  test("fails without fid", async () => {
+    const result = await client.getCast(CastId.create({ fid: 0, hash: castAdd.hash }));
+    expect(result._unsafeUnwrapErr()).toEqual(new HubError("bad_request.validation_failure", "fid is missing"));
+  });
+});
+
+describe("getCastsByFid", () => {
+  test("succeeds", async () => {
+    await engine.mergeMessage(castAdd);
+    await engine.mergeMessage(reply);
+
+    const result = await client.getCastsByFid(FidRequest.create({ fid }));
+    expect(result._unsafeUnwrap().messages.map((m) => Message.toJSON(m))).toEqual(
+      [castAdd, reply].map((m) => Message.toJSON(m)),
+    );
+  });
+
+  test("pages results", async () => {
+    await engine.mergeMessage(castAdd);
+    await engine.mergeMessage(reply);
+
+    const page1 = (await client.getCastsByFid(FidRequest.create({ fid, pageSize: 1 })))._unsafeUnwrap();
+    expect(page1.messages.length).toEqual(1);
+
+    const page2 = (
+      await client.getCastsByFid(FidRequest.create({ fid, pageSize: 1, pageToken: page1.nextPageToken }))
+    )._unsafeUnwrap();
+    expect(page2.messages.length).toEqual(1);
+    expect(page2.messages[0]?.hash).not.toEqual(page1.messages[0]?.hash);
+  });
+});
+
+describe("getCastsByParent", () => {
+  test("succeeds", async () => {
+    await engine.mergeMessage(castAdd);
+    await engine.mergeMessage(reply);
+
+    const result = await client.getCastsByParent(
+      CastsByParentRequest.create({ parentCastId: { fid, hash: castAdd.hash } }),
+    );
+    expect(result._unsafeUnwrap().messages.map((m) => Message.toJSON(m))).toEqual([Message.toJSON(reply)]);
+  });
+});
+
+describe("unsupported rpcs", () => {
+  test("fail with unimplemented", async () => {
+    const result = await client.getInfo({ dbStats: false });
+    expect(result.isErr()).toBeTruthy();
+  });
+});
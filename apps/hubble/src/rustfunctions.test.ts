@@ -1,8 +1,14 @@
 import { blake3 } from "@noble/hashes/blake3";
 import { createEd25519PeerId } from "@libp2p/peer-id-factory";
 import { unmarshalPrivateKey } from "@libp2p/crypto/keys";
-import { rsBlake3Hash20, rsEd25519SignMessageHash, rsEd25519Verify } from "./rustfunctions.js";
-import { Factories, ed25519 } from "@farcaster/hub-nodejs";
+import {
+  rsBlake3Hash20,
+  rsEd25519SignMessageHash,
+  rsEd25519Verify,
+  rustErrorToHubError,
+  RustHubError,
+} from "./rustfunctions.js";
+import { Factories, HubError, ed25519 } from "@farcaster/hub-nodejs";
 
 describe("blake3 tests", () => {
   test("hashes match rust", () => {
@@ -99,3 +105,41 @@ describe("ed25519 tests", () => {
     expect(await rsEd25519Verify(signature, hash, empty)).toBeFalsy();
   });
 });
+
+describe("rustErrorToHubError", () => {
+  test("keeps the code and context of structured errors", () => {
+    const rustError = Object.assign(new Error("message has already been merged"), {
+      errCode: "bad_request.duplicate",
+      fid: 1,
+    }) as RustHubError;
+
+    const hubError = rustErrorToHubError(rustError);
+    expect(hubError).toBeInstanceOf(HubError);
+    expect(hubError.errCode).toEqual("bad_request.duplicate");
+    expect(hubError.message).toEqual("message has already been merged");
+    expect(hubError.cause).toBe(rustError);
+  });
+
+  test("parses code/message strings", () => {
+    const hubError = rustErrorToHubError(new Error("not_found/no message at https://example.com/1"));
+    expect(hubError.errCode).toEqual("not_found");
+    expect(hubError.message).toEqual("no message at https://example.com/1");
+  });
+
+  test("maps panics to unknown, even when the panic message has a slash", () => {
+    const panic = new Error("internal error in Neon module: panic at src/store/store.rs: invalid type");
+    const hubError = rustErrorToHubError(panic);
+    expect(hubError.errCode).toEqual("unknown");
+    expect(hubError.message).toEqual(`panic in rust addon: ${panic.message}`);
+    expect(hubError.cause).toBe(panic);
+
+    const unsettled = rustErrorToHubError(new Error("`neon::types::Deferred` was dropped without being settled"));
+    expect(unsettled.errCode).toEqual("unknown");
+  });
+
+  test("falls back to unknown", () => {
+    const hubError = rustErrorToHubError(new TypeError("failed to downcast any to number"));
+    expect(hubError.errCode).toEqual("unknown");
+    expect(hubError.message).toEqual("failed to downcast any to number");
+  });
+});
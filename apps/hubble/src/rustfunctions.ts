@@ -7,7 +7,7 @@ import { createRequire } from "module";
 const require = createRequire(import.meta.url);
 const lib = require("./addon/index.node");
 
-import { HubError, HubErrorCode, HubResult, validations } from "@farcaster/hub-nodejs";
+import { FarcasterNetwork, HubError, HubErrorCode, HubResult, validations } from "@farcaster/hub-nodejs";
 import { PAGE_SIZE_MAX, PageOptions } from "./storage/stores/types.js";
 import { UserMessagePostfix } from "./storage/db/types.js";
 import { DbKeyValue, RocksDbIteratorOptions } from "./storage/db/rocksdb.js";
@@ -44,12 +44,52 @@ export class RustStoreEventHandler {
   private [RustStoreEventHandlerBrand]: never;
 }
 
+const RustSnapshotCancellationTokenBrand = Symbol("RustSnapshotCancellationToken");
+export class RustSnapshotCancellationToken {
+  // @ts-ignore
+  private [RustSnapshotCancellationTokenBrand]: never;
+}
+
+const RustRpcServerBrand = Symbol("RustRpcServer");
+export class RustRpcServer {
+  // @ts-ignore
+  private [RustRpcServerBrand]: never;
+}
+
 // Type returned from Rust which is equivalent to the TypeScript type `MessagesPage`
 export class RustMessagesPage {
   messageBytes?: Buffer[];
   nextPageToken?: Buffer;
 }
 
+// Type returned from Rust for a cast thread. `depths[i]` is the depth of `messageBytes[i]` below the root
+export class RustCastThread {
+  messageBytes: Buffer[] = [];
+  depths: number[] = [];
+  truncated = false;
+  nextPageToken?: Buffer;
+}
+
+export class RustReverseResolution {
+  fid = 0;
+  username?: string;
+  verified = false;
+  proofBytes?: Buffer;
+}
+
+export class RustFnameHistoryPage {
+  entries: { kind: number; previousFid: number; proofBytes: Buffer }[] = [];
+  nextPageToken?: Buffer;
+}
+
+export class RustProfile {
+  fid = 0;
+  userDataBytes: Buffer[] = [];
+  fnameProofBytes?: Buffer;
+  ensProofsBytes: Buffer[] = [];
+  verificationsBytes: Buffer[] = [];
+}
+
 // Use this function in TypeScript to call the rust code.
 export function rsBlake3Hash20(data: Uint8Array): Uint8Array {
   const dataBuf = Buffer.from(data);
@@ -80,10 +120,51 @@ export const rsValidationMethods: validations.ValidationMethods = {
   blake3_20: (message: Uint8Array) => rsBlake3Hash20(message),
 };
 
+/**
+ * The errors Rust throws. They're regular Errors with the HubError code as `errCode`, and the fid,
+ * message hash or DB key the error is about when there is one.
+ */
+export type RustHubError = Error & {
+  errCode: HubErrorCode;
+  fid?: number;
+  hash?: Buffer;
+  key?: Buffer;
+};
+
+export const isRustHubError = (e: unknown): e is RustHubError => {
+  return e instanceof Error && typeof (e as RustHubError).errCode === "string";
+};
+
+/**
+ * Neon turns a panic on the main thread into an Error with this prefix, and rejects the promise of a
+ * threadpool task that panicked before settling it with the second message. The panic itself is
+ * logged by Rust with its backtrace.
+ */
+const NEON_PANIC_PREFIX = "internal error in Neon module:";
+const NEON_UNSETTLED_DEFERRED = "was dropped without being settled";
+
+const isRustPanic = (message: string) => {
+  return message.startsWith(NEON_PANIC_PREFIX) || message.includes(NEON_UNSETTLED_DEFERRED);
+};
+
 export const rustErrorToHubError = (e: unknown) => {
-  // Split the error string by "/", the first part is the error code, the second part is the error message
-  const [errCode, errMsg] = (e as Error).message.split("/");
-  return new HubError(errCode as HubErrorCode, errMsg ?? "");
+  // Keep the Rust error as the cause, so its fid, hash and key are available
+  if (isRustHubError(e)) {
+    return new HubError(e.errCode, { message: e.message, cause: e });
+  }
+
+  if (e instanceof Error && isRustPanic(e.message)) {
+    return new HubError("unknown", { message: `panic in rust addon: ${e.message}`, cause: e });
+  }
+
+  // Errors that didn't come from a HubError, like a bad argument type, are "code/message" strings or
+  // plain messages
+  const message = e instanceof Error ? e.message : String(e);
+  const separator = message.indexOf("/");
+  if (separator === -1) {
+    return new HubError("unknown", message);
+  }
+  return new HubError(message.slice(0, separator) as HubErrorCode, message.slice(separator + 1));
 };
 
 export const rsCreateStatsdClient = (host: string, port: number, prefix: string): void => {
@@ -158,8 +239,124 @@ export const rsDbCommit = async (db: RustDb, keyValues: DbKeyValue[]): Promise<v
   return await lib.dbCommit.call(db, keyValues);
 };
 
-export const rsDbSnapshotBackup = async (mainDb: RustDb, trieDb: RustDb, timestamp: number): Promise<string> => {
-  return await lib.dbSnapshotBackup(mainDb, trieDb, timestamp);
+export type DbSnapshotBackupOptions = {
+  /**
+   * "copy" (default) copies every key into new DBs, which is much slower but never pauses writes.
+   * "checkpoint" hard-links the DB files using RocksDB checkpoints, pausing writes to both DBs briefly. The DBs are
+   * closed and reopened around it, and stay closed if they can't be reopened.
+   */
+  mode?: "checkpoint" | "copy";
+  /** Network recorded in the snapshot's manifest.json */
+  network?: FarcasterNetwork;
+  /**
+   * Path to the manifest.json of an earlier snapshot of the same DB. If set, the snapshot is incremental and only
+   * contains the DB files that changed since then. Requires "checkpoint" mode.
+   */
+  baseManifest?: string;
+  /** Token from `rsCreateSnapshotCancellationToken`. Cancelling it makes the backup fail with "unavailable" */
+  cancellationToken?: RustSnapshotCancellationToken;
+  /** Called periodically with the keys (copy mode only) and bytes copied so far, per DB */
+  onProgress?: (progress: DbSnapshotBackupProgress) => void;
+  /**
+   * Compression for the archive chunks, "gzip" (default) or "zstd". Zstd archives are smaller, but hubs download
+   * snapshots from S3 with gunzip, so only use zstd for archives that are restored with `rsDbRestoreSnapshot`.
+   */
+  compression?: "gzip" | "zstd";
+  /** Compression level, 0-9 for gzip and 1-22 for zstd. Defaults to 3 */
+  compressionLevel?: number;
+  /** Max uncompressed size of each archive chunk in bytes. Defaults to 4GB */
+  chunkSize?: number;
+  /** Threads used to compress each chunk. Defaults to 4 */
+  threads?: number;
+  /** Directory the archive is written to. Defaults to ".rocks" */
+  outputDir?: string;
+};
+
+export type DbSnapshotBackupProgress = {
+  /** "copy" while keys are copied into the backup DBs, "archive" while the backup's files are archived */
+  stage: "copy" | "archive";
+  db: "main" | "trie";
+  keys: number;
+  bytes: number;
+};
+
+/** The manifest.json written next to the chunks of a snapshot */
+export type SnapshotManifest = {
+  manifestVersion: number;
+  dbSchemaVersion: number;
+  network: FarcasterNetwork;
+  trieRootHash: string;
+  numMessages: number;
+  latestHubEventId: number;
+  timestamp: number;
+  numChunks: number;
+  chunks: { name: string; size: number; blake3: string }[];
+  dbIdentity: string;
+  files: { path: string; size: number }[];
+  /** For incremental snapshots, the timestamp of the snapshot they build on. null for full snapshots */
+  baseTimestamp: number | null;
+  compression: "gzip" | "zstd";
+};
+
+export const rsCreateSnapshotCancellationToken = (): RustSnapshotCancellationToken => {
+  return lib.createSnapshotCancellationToken();
+};
+
+/** Cancel the snapshot backups using `token`. Backups check the token often, so they stop soon after */
+export const rsCancelSnapshot = (token: RustSnapshotCancellationToken): void => {
+  lib.cancelSnapshot(token);
+};
+
+export type RustRpcServerStores = {
+  castStore: RustDynStore;
+  linkStore: RustDynStore;
+  reactionStore: RustDynStore;
+  userDataStore: RustDynStore;
+  verificationStore: RustDynStore;
+  usernameProofStore: RustDynStore;
+};
+
+/**
+ * Start a gRPC server in Rust that serves the read-only HubService RPCs straight from the stores.
+ * The address is bound before this returns, so it throws if the address is invalid or in use.
+ */
+export const rsStartRpcServer = (address: string, stores: RustRpcServerStores): RustRpcServer => {
+  return lib.startRpcServer(address, stores);
+};
+
+/** The address the server is listening on. Useful when it was started on port 0 */
+export const rsRpcServerAddress = (server: RustRpcServer): string => {
+  return lib.rpcServerAddress(server);
+};
+
+export const rsStopRpcServer = async (server: RustRpcServer): Promise<void> => {
+  return await lib.stopRpcServer(server);
+};
+
+export const rsDbSnapshotBackup = async (
+  mainDb: RustDb,
+  trieDb: RustDb,
+  timestamp: number,
+  options?: DbSnapshotBackupOptions,
+): Promise<string> => {
+  return await lib.dbSnapshotBackup(mainDb, trieDb, timestamp, options ?? {});
+};
+
+/**
+ * Check the chunks in `archiveDir` against the snapshot's manifest.json, throwing if any are missing or corrupted.
+ */
+export const rsDbVerifySnapshot = async (archiveDir: string): Promise<SnapshotManifest> => {
+  const manifestJson = await lib.dbVerifySnapshot(archiveDir);
+  return JSON.parse(manifestJson) as SnapshotManifest;
+};
+
+/**
+ * Restore a snapshot from the chunked archives in `archiveDirs` to `targetPath`. To restore incremental snapshots,
+ * pass the full snapshot's archive followed by the incremental ones, oldest first. The snapshot is verified before it
+ * replaces anything at `targetPath`, so the DB there must be closed. Returns the restored path.
+ */
+export const rsDbRestoreSnapshot = async (archiveDirs: string | string[], targetPath: string): Promise<string> => {
+  return await lib.dbRestoreSnapshot(archiveDirs, targetPath);
 };
 
 /**
@@ -308,8 +505,9 @@ export const rsCreateCastStore = (
   db: RustDb,
   eventHandler: RustStoreEventHandler,
   pruneSizeLimit: number,
+  indexOptions: { searchIndex?: boolean; feedIndex?: boolean } = {},
 ): RustDynStore => {
-  const store = lib.createCastStore(db, eventHandler, pruneSizeLimit);
+  const store = lib.createCastStore(db, eventHandler, pruneSizeLimit, indexOptions);
 
   return store as RustDynStore;
 };
@@ -326,16 +524,20 @@ export const rsGetCastAddsByFid = async (
   store: RustDynStore,
   fid: number,
   pageOptions: PageOptions,
+  startTime?: number,
+  stopTime?: number,
 ): Promise<RustMessagesPage> => {
-  return await lib.getCastAddsByFid.call(store, fid, pageOptions);
+  return await lib.getCastAddsByFid.call(store, fid, pageOptions, startTime, stopTime);
 };
 
 export const rsGetCastRemovesByFid = async (
   store: RustDynStore,
   fid: number,
   pageOptions: PageOptions,
+  startTime?: number,
+  stopTime?: number,
 ): Promise<RustMessagesPage> => {
-  return await lib.getCastRemovesByFid.call(store, fid, pageOptions);
+  return await lib.getCastRemovesByFid.call(store, fid, pageOptions, startTime, stopTime);
 };
 
 export const rsGetCastsByParent = async (
@@ -347,6 +549,21 @@ export const rsGetCastsByParent = async (
   return await lib.getCastsByParent.call(store, parentCastIdBytes, parentUrl, pageOptions);
 };
 
+export const rsGetCastThread = async (
+  store: RustDynStore,
+  castIdBytes: Buffer,
+  maxDepth: number,
+  maxNodes: number,
+  pageToken?: Uint8Array,
+): Promise<RustCastThread> => {
+  const pageTokenBuffer = pageToken ? Buffer.from(pageToken) : undefined;
+  return await lib.getCastThread.call(store, castIdBytes, maxDepth, maxNodes, pageTokenBuffer);
+};
+
+export const rsGetReplyCount = async (store: RustDynStore, castIdBytes: Buffer): Promise<number> => {
+  return await lib.getReplyCount.call(store, castIdBytes);
+};
+
 export const rsGetCastsByMention = async (
   store: RustDynStore,
   mentionFid: number,
@@ -355,6 +572,49 @@ export const rsGetCastsByMention = async (
   return await lib.getCastsByMention.call(store, mentionFid, pageOptions);
 };
 
+export const rsGetCastsByEmbedUrl = async (
+  store: RustDynStore,
+  url: string,
+  pageOptions: PageOptions,
+): Promise<RustMessagesPage> => {
+  return await lib.getCastsByEmbedUrl.call(store, url, pageOptions);
+};
+
+export const rsGetQuoteCasts = async (
+  store: RustDynStore,
+  castIdBytes: Buffer,
+  pageOptions: PageOptions,
+): Promise<RustMessagesPage> => {
+  return await lib.getQuoteCasts.call(store, castIdBytes, pageOptions);
+};
+
+export const rsGetRecentCasts = async (
+  store: RustDynStore,
+  pageOptions: PageOptions,
+  startTime?: number,
+  stopTime?: number,
+): Promise<RustMessagesPage> => {
+  return await lib.getRecentCasts.call(store, pageOptions, startTime, stopTime);
+};
+
+export const rsGetRecentCastsByParentUrl = async (
+  store: RustDynStore,
+  parentUrl: string,
+  pageOptions: PageOptions,
+  startTime?: number,
+  stopTime?: number,
+): Promise<RustMessagesPage> => {
+  return await lib.getRecentCastsByParentUrl.call(store, parentUrl, pageOptions, startTime, stopTime);
+};
+
+export const rsSearchCasts = async (
+  store: RustDynStore,
+  query: string,
+  pageOptions: PageOptions,
+): Promise<RustMessagesPage> => {
+  return await lib.searchCasts.call(store, query, pageOptions);
+};
+
 export const rsGetMessage = async (
   store: RustDynStore,
   fid: number,
@@ -386,9 +646,9 @@ export const rsMergeMany = async (
   // Parse the results
   for (let i = 0; i < results.length; i++) {
     const result = results[i];
-    if (typeof result === "string") {
+    if (result instanceof Error) {
       // This was an error
-      mergeResults.set(i, err(rustErrorToHubError(new Error(result))));
+      mergeResults.set(i, err(rustErrorToHubError(result)));
     } else if (result instanceof Buffer) {
       // This is a Buffer
       mergeResults.set(i, ok(result));
@@ -416,6 +676,68 @@ export const rsPruneMessages = async (
   return await lib.pruneMessages.call(store, fid, cachedCount, maxCount);
 };
 
+export type CheckIndicesOptions = {
+  /** Delete and rebuild the store's indices from its messages after checking them. Blocks merges while it runs */
+  repair?: boolean;
+  /** Called periodically with the number of keys processed so far in the current stage */
+  onProgress?: (progress: CheckIndicesProgress) => void;
+};
+
+export type CheckIndicesProgress = {
+  /** "messages" while messages are checked, "indices" while index entries are checked, "rebuild" while repairing */
+  stage: "messages" | "indices" | "rebuild";
+  keys: number;
+};
+
+export type CheckIndicesReport = {
+  messagesChecked: number;
+  indexEntriesChecked: number;
+  /** Index entries that a message should have, but that don't exist or have the wrong value */
+  missing: number;
+  /** Index entries for messages that don't exist, or that the message wouldn't create */
+  dangling: number;
+  /** The first 100 missing and dangling keys */
+  missingKeys: Buffer[];
+  danglingKeys: Buffer[];
+  /** Index entries written by the rebuild, if `repair` was set */
+  rebuilt: number;
+};
+
+/** Check a store's secondary indices against its messages, and optionally rebuild them */
+export const rsCheckIndices = async (
+  store: RustDynStore,
+  options: CheckIndicesOptions = {},
+): Promise<CheckIndicesReport> => {
+  return await lib.checkIndices.call(store, options);
+};
+
+/** Get the enabled optional indices of a store that need to be backfilled, and forget the disabled ones */
+export const rsPrepareOptionalIndices = async (store: RustDynStore): Promise<number[]> => {
+  return await lib.prepareOptionalIndices.call(store);
+};
+
+export const rsRebuildCounters = async (store: RustDynStore): Promise<number> => {
+  return await lib.rebuildCounters.call(store);
+};
+
+export type RustMergeValidation = {
+  outcome: "merge" | "duplicate" | "conflict" | "prunable";
+  reason?: string;
+  winnerBytes?: Buffer;
+  deletedMessagesBytes: Buffer[];
+};
+
+export const rsValidateMerge = async (store: RustDynStore, messageBytes: Uint8Array): Promise<RustMergeValidation> => {
+  return await lib.validateMerge.call(store, messageBytes);
+};
+
+export const rsPreviewCompactStateMerge = async (
+  store: RustDynStore,
+  messageBytes: Uint8Array,
+): Promise<RustMessagesPage> => {
+  return await lib.previewCompactStateMerge.call(store, messageBytes);
+};
+
 export const rsGetAllMessagesByFid = async (
   store: RustDynStore,
   fid: number,
@@ -451,8 +773,10 @@ export const rsGetReactionAddsByFid = async (
   fid: number,
   type: number,
   pageOptions: PageOptions,
+  startTime?: number,
+  stopTime?: number,
 ): Promise<RustMessagesPage> => {
-  return await lib.getReactionAddsByFid.call(store, fid, type, pageOptions);
+  return await lib.getReactionAddsByFid.call(store, fid, type, pageOptions, startTime, stopTime);
 };
 
 export const rsGetReactionRemovesByFid = async (
@@ -460,8 +784,10 @@ export const rsGetReactionRemovesByFid = async (
   fid: number,
   type: number,
   pageOptions: PageOptions,
+  startTime?: number,
+  stopTime?: number,
 ): Promise<RustMessagesPage> => {
-  return await lib.getReactionRemovesByFid.call(store, fid, type, pageOptions);
+  return await lib.getReactionRemovesByFid.call(store, fid, type, pageOptions, startTime, stopTime);
 };
 
 export const rsGetReactionsByTarget = async (
@@ -474,6 +800,23 @@ export const rsGetReactionsByTarget = async (
   return await lib.getReactionsByTarget.call(store, targetCastIdBytes, targetUrl, type, pageOptions);
 };
 
+export const rsGetReactionCompactStateMessageByFid = async (
+  store: RustDynStore,
+  fid: number,
+  pageOptions: PageOptions,
+): Promise<RustMessagesPage> => {
+  return await lib.getReactionCompactStateMessageByFid.call(store, fid, pageOptions);
+};
+
+export const rsGetReactionCount = async (
+  store: RustDynStore,
+  targetCastIdBytes: Buffer,
+  targetUrl: string,
+  type: number,
+): Promise<number> => {
+  return await lib.getReactionCount.call(store, targetCastIdBytes, targetUrl, type);
+};
+
 /** UserData Store */
 export const rsCreateUserDataStore = (
   db: RustDb,
@@ -511,6 +854,45 @@ export const rsMergeUserNameProof = async (store: RustDynStore, usernameProof: U
   return await lib.mergeUserNameProof.call(store, usernameProof);
 };
 
+export const rsGetProfile = async (store: RustDynStore, fid: number): Promise<RustProfile> => {
+  return await lib.getProfile.call(store, fid);
+};
+
+export const rsGetProfiles = async (store: RustDynStore, fids: number[]): Promise<RustProfile[]> => {
+  return await lib.getProfiles.call(store, fids);
+};
+
+export const rsResolveName = async (
+  store: RustDynStore,
+  name: string,
+): Promise<{ fid: number; proofBytes: Buffer }> => {
+  return await lib.resolveName.call(store, name);
+};
+
+export const rsReverseResolve = async (store: RustDynStore, fid: number): Promise<RustReverseResolution> => {
+  return await lib.reverseResolve.call(store, fid);
+};
+
+export const rsGetFnameHistory = async (
+  store: RustDynStore,
+  name: Uint8Array,
+  pageOptions: PageOptions,
+): Promise<RustFnameHistoryPage> => {
+  return await lib.getFnameHistory.call(store, name, pageOptions);
+};
+
+export const rsGetFnameHistoryByFid = async (
+  store: RustDynStore,
+  fid: number,
+  pageOptions: PageOptions,
+): Promise<RustFnameHistoryPage> => {
+  return await lib.getFnameHistoryByFid.call(store, fid, pageOptions);
+};
+
+export const rsBackfillFnameHistory = async (store: RustDynStore): Promise<number> => {
+  return await lib.backfillFnameHistory.call(store);
+};
+
 /** VerificationStore */
 export const rsCreateVerificationStore = (
   db: RustDb,
@@ -538,16 +920,20 @@ export const rsGetVerificationAddsByFid = async (
   store: RustDynStore,
   fid: number,
   pageOptions: PageOptions,
+  startTime?: number,
+  stopTime?: number,
 ): Promise<RustMessagesPage> => {
-  return await lib.getVerificationAddsByFid.call(store, fid, pageOptions);
+  return await lib.getVerificationAddsByFid.call(store, fid, pageOptions, startTime, stopTime);
 };
 
 export const rsGetVerificationRemovesByFid = async (
   store: RustDynStore,
   fid: number,
   pageOptions: PageOptions,
+  startTime?: number,
+  stopTime?: number,
 ): Promise<RustMessagesPage> => {
-  return await lib.getVerificationRemovesByFid.call(store, fid, pageOptions);
+  return await lib.getVerificationRemovesByFid.call(store, fid, pageOptions, startTime, stopTime);
 };
 
 export const rsMigrateVerifications = async (store: RustDynStore): Promise<{ total: number; duplicates: number }> => {
@@ -573,8 +959,10 @@ export const rsGetUsernameProofsByFid = async (
   store: RustDynStore,
   fid: number,
   pageOptions: PageOptions,
+  startTime?: number,
+  stopTime?: number,
 ): Promise<RustMessagesPage> => {
-  return await lib.getUsernameProofsByFid.call(store, fid, pageOptions);
+  return await lib.getUsernameProofsByFid.call(store, fid, pageOptions, startTime, stopTime);
 };
 
 export const rsGetUsernameProofByFidAndName = async (
@@ -585,6 +973,12 @@ export const rsGetUsernameProofByFidAndName = async (
   return await lib.getUsernameProofByFidAndName.call(store, fid, name);
 };
 
+export type RustLinkCompactStateCandidate = {
+  type: string;
+  targetFids: number[];
+  timestamp: number;
+};
+
 export namespace rsLinkStore {
   export const CreateLinkStore = (
     db: RustDb,
@@ -605,8 +999,10 @@ export namespace rsLinkStore {
     fid: number,
     type: string,
     pageOptions: PageOptions,
+    startTime?: number,
+    stopTime?: number,
   ): Promise<RustMessagesPage> => {
-    return await lib.getLinkAddsByFid.call(store, fid, type, pageOptions);
+    return await lib.getLinkAddsByFid.call(store, fid, type, pageOptions, startTime, stopTime);
   };
 
   export const GetLinkRemovesByFid = async (
@@ -614,8 +1010,10 @@ export namespace rsLinkStore {
     fid: number,
     type: string,
     pageOptions: PageOptions,
+    startTime?: number,
+    stopTime?: number,
   ): Promise<RustMessagesPage> => {
-    return await lib.getLinkRemovesByFid.call(store, fid, type, pageOptions);
+    return await lib.getLinkRemovesByFid.call(store, fid, type, pageOptions, startTime, stopTime);
   };
 
   export const GetLinksByTarget = async (
@@ -627,6 +1025,14 @@ export namespace rsLinkStore {
     return await lib.getLinksByTarget.call(store, target, type, pageOptions);
   };
 
+  export const GetFollowerCount = async (store: RustDynStore, fid: number, type: string): Promise<number> => {
+    return await lib.getFollowerCount.call(store, fid, type);
+  };
+
+  export const GetFollowingCount = async (store: RustDynStore, fid: number, type: string): Promise<number> => {
+    return await lib.getFollowingCount.call(store, fid, type);
+  };
+
   export const GetLinkRemove = async (
     store: RustDynStore,
     fid: number,
@@ -636,6 +1042,14 @@ export namespace rsLinkStore {
     return await lib.getLinkRemove.call(store, fid, type, target);
   };
 
+  export const BuildLinkCompactState = async (
+    store: RustDynStore,
+    fid: number,
+    type: string,
+  ): Promise<RustLinkCompactStateCandidate> => {
+    return await lib.buildLinkCompactState.call(store, fid, type);
+  };
+
   export const GetLinkCompactStateMessageByFid = async (
     store: RustDynStore,
     fid: number,
@@ -703,25 +1117,54 @@ export const rsMerkleTrieGetSnapshot = async (trie: RustMerkleTrie, prefix: Uint
   };
 };
 
+// Type returned from Rust for a trie node. We can't return a Map from Rust, so the children are
+// returned as two arrays, one with the keys and one with the values
+type RustNodeMetadata = {
+  prefix: Buffer;
+  numMessages: number;
+  hash: string;
+  truncated: boolean;
+  childrenKeys: number[];
+  childrenValues: RustNodeMetadata[];
+};
+
+const fromRustNodeMetadata = (metadata: RustNodeMetadata): NodeMetadata => {
+  const children = new Map<number, NodeMetadata>();
+  metadata.childrenKeys.forEach((key, i) => {
+    const child = metadata.childrenValues[i];
+    if (child) {
+      children.set(key, fromRustNodeMetadata(child));
+    }
+  });
+
+  return {
+    numMessages: metadata.numMessages,
+    hash: metadata.hash,
+    prefix: new Uint8Array(metadata.prefix),
+    children,
+    truncated: metadata.truncated,
+  };
+};
+
+/**
+ * Get the metadata of the trie node at the prefix. By default, only the node and its direct children
+ * are returned. Pass `depth` to fetch more levels at once, `maxNodes` to cap the size of the result and
+ * `knownHashes` to skip expanding subtrees whose hashes are already known to match.
+ */
 export const rsMerkleTrieGetTrieNodeMetadata = async (
   trie: RustMerkleTrie,
   prefix: Uint8Array,
+  depth?: number,
+  maxNodes?: number,
+  knownHashes?: { prefix: Uint8Array; hash: string }[],
 ): Promise<NodeMetadata | undefined> => {
   try {
-    const metadata = await lib.merkleTrieGetTrieNodeMetadata.call(trie, prefix);
+    // The prefixes have to be Buffers for Rust, but decoded protobufs can hand us plain Uint8Arrays
+    const rustKnownHashes = knownHashes?.map(({ prefix, hash }) => ({ prefix: Buffer.from(prefix), hash }));
+    const metadata = await lib.merkleTrieGetTrieNodeMetadata.call(trie, prefix, depth, maxNodes, rustKnownHashes);
 
     // The returned metadata has a childrenKeys and childrenValues, which need to be turned into a Map
-    const children = new Map<number, NodeMetadata>();
-    for (let i = 0; i < metadata.childrenKeys.length; i++) {
-      children.set(metadata.childrenKeys[i], metadata.childrenValues[i]);
-    }
-
-    return {
-      numMessages: metadata.numMessages,
-      hash: metadata.hash,
-      prefix: new Uint8Array(metadata.prefix),
-      children,
-    };
+    return fromRustNodeMetadata(metadata);
   } catch (err) {
     const e = err as HubError;
     if (e.message.includes("Node not found")) {
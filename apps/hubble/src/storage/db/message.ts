@@ -104,6 +104,10 @@ export const typeToSetPostfix = (type: MessageType): UserMessagePostfix => {
     return UserPostfix.LinkCompactStateMessage;
   }
 
+  if (type === MessageType.REACTION_COMPACT_STATE) {
+    return UserPostfix.ReactionCompactStateMessage;
+  }
+
   throw new Error(`invalid type: ${type}`);
 };
 
@@ -0,0 +1,35 @@
+import { performDbMigrations } from "./migrations.js";
+import { jestRocksDB } from "../jestUtils.js";
+import { Factories, Message } from "@farcaster/hub-nodejs";
+import { makeMessagePrimaryKeyFromMessage } from "../message.js";
+import CastStore from "../../stores/castStore.js";
+import ReactionStore from "../../stores/reactionStore.js";
+import StoreEventHandler from "../../stores/storeEventHandler.js";
+
+const db = jestRocksDB("aggregateCounts.migration.test");
+
+describe("aggregateCounts migration", () => {
+  test("should count existing replies and reactions", async () => {
+    const parentCastId = Factories.CastId.build();
+    const reply = await Factories.CastAddMessage.create({ data: { castAddBody: { parentCastId } } });
+    const reaction = await Factories.ReactionAddMessage.create({
+      data: { reactionBody: { targetCastId: parentCastId } },
+    });
+
+    // Write the messages the way a hub without the counters would have: the messages, but no AggregateCounts entries
+    for (const message of [reply, reaction]) {
+      await db.put(makeMessagePrimaryKeyFromMessage(message), Buffer.from(Message.encode(message).finish()));
+    }
+
+    const eventHandler = new StoreEventHandler(db);
+    const castStore = new CastStore(db, eventHandler);
+    const reactionStore = new ReactionStore(db, eventHandler);
+    await expect(castStore.getReplyCount(parentCastId)).resolves.toEqual(0);
+    await expect(reactionStore.getReactionCount(parentCastId, reaction.data.reactionBody.type)).resolves.toEqual(0);
+
+    await performDbMigrations(db, 11, 12);
+
+    await expect(castStore.getReplyCount(parentCastId)).resolves.toEqual(1);
+    await expect(reactionStore.getReactionCount(parentCastId, reaction.data.reactionBody.type)).resolves.toEqual(1);
+  });
+});
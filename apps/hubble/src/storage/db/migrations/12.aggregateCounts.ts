@@ -0,0 +1,37 @@
+import { logger } from "../../../utils/logger.js";
+import RocksDB from "../rocksdb.js";
+import CastStore from "../../stores/castStore.js";
+import LinkStore from "../../stores/linkStore.js";
+import ReactionStore from "../../stores/reactionStore.js";
+import StoreEventHandler from "../../stores/storeEventHandler.js";
+
+const log = logger.child({ component: "aggregateCounts" });
+
+/**
+ * Messages merged before the AggregateCounts counters existed aren't counted, so the reply, reaction and link counts
+ * would start from 0. Recount every store that keeps counters from its messages.
+ */
+export const aggregateCountsMigration = async (db: RocksDB): Promise<boolean> => {
+  log.info({}, "Starting aggregateCounts migration");
+  const start = Date.now();
+
+  const eventHandler = new StoreEventHandler(db);
+  const stores = {
+    cast: new CastStore(db, eventHandler),
+    reaction: new ReactionStore(db, eventHandler),
+    link: new LinkStore(db, eventHandler),
+  };
+
+  for (const [name, store] of Object.entries(stores)) {
+    const result = await store.rebuildCounters();
+    if (result.isErr()) {
+      log.error({ err: result.error, store: name }, "aggregateCounts migration failed");
+      return false;
+    }
+
+    log.info({ store: name, messages: result.value }, "aggregateCounts migration progress...");
+  }
+
+  log.info({ duration: Date.now() - start }, "aggregateCounts migration done");
+  return true;
+};
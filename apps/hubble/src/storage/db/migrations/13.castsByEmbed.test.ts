@@ -0,0 +1,39 @@
+import { performDbMigrations } from "./migrations.js";
+import { jestRocksDB } from "../jestUtils.js";
+import { Factories, Message } from "@farcaster/hub-nodejs";
+import { RootPrefix } from "../types.js";
+import { makeMessagePrimaryKeyFromMessage } from "../message.js";
+import CastStore from "../../stores/castStore.js";
+import StoreEventHandler from "../../stores/storeEventHandler.js";
+
+const db = jestRocksDB("castsByEmbed.migration.test");
+
+describe("castsByEmbed migration", () => {
+  test("should index the embeds of existing casts", async () => {
+    const url = "https://farcaster.xyz/";
+    const quotedCastId = Factories.CastId.build();
+    const cast = await Factories.CastAddMessage.create({
+      data: { castAddBody: { embeds: [{ url }, { castId: quotedCastId }] } },
+    });
+
+    // Write the cast the way a hub without the index would have: the message, but no CastsByEmbed entries
+    await db.put(makeMessagePrimaryKeyFromMessage(cast), Buffer.from(Message.encode(cast).finish()));
+
+    const castStore = new CastStore(db, new StoreEventHandler(db));
+    await expect(castStore.getCastsByEmbedUrl(url)).resolves.toEqual({ messages: [], nextPageToken: undefined });
+
+    await performDbMigrations(db, 12, 13);
+
+    await expect(castStore.getCastsByEmbedUrl(url)).resolves.toEqual({ messages: [cast], nextPageToken: undefined });
+    await expect(castStore.getQuoteCasts(quotedCastId)).resolves.toEqual({
+      messages: [cast],
+      nextPageToken: undefined,
+    });
+
+    const embedKeys: Buffer[] = [];
+    await db.forEachIteratorByPrefix(Buffer.from([RootPrefix.CastsByEmbed]), (key) => {
+      embedKeys.push(key as Buffer);
+    });
+    expect(embedKeys.length).toEqual(2);
+  });
+});
@@ -0,0 +1,31 @@
+import { logger } from "../../../utils/logger.js";
+import RocksDB from "../rocksdb.js";
+import CastStore from "../../stores/castStore.js";
+import StoreEventHandler from "../../stores/storeEventHandler.js";
+
+const log = logger.child({ component: "castsByEmbed" });
+
+/**
+ * Casts merged before the CastsByEmbed index existed aren't in it. Rebuilding the cast store's secondary indices
+ * writes their CastsByEmbed entries, and rewrites the CastsByParent and CastsByMention entries they already had.
+ */
+export const castsByEmbedIndexMigration = async (db: RocksDB): Promise<boolean> => {
+  log.info({}, "Starting castsByEmbed migration");
+  const start = Date.now();
+
+  const castStore = new CastStore(db, new StoreEventHandler(db));
+  const result = await castStore.checkIndices({
+    repair: true,
+    onProgress: ({ stage, keys }) => log.info({ stage, keys }, "castsByEmbed migration progress..."),
+  });
+  if (result.isErr()) {
+    log.error({ err: result.error }, "castsByEmbed migration failed");
+    return false;
+  }
+
+  log.info(
+    { messages: result.value.messagesChecked, rebuilt: result.value.rebuilt, duration: Date.now() - start },
+    "castsByEmbed migration done",
+  );
+  return true;
+};
@@ -0,0 +1,28 @@
+import { performDbMigrations } from "./migrations.js";
+import { jestRocksDB } from "../jestUtils.js";
+import { Factories, UserNameProof } from "@farcaster/hub-nodejs";
+import { makeFNameUserNameProofKey } from "../nameRegistryEvent.js";
+import UserDataStore from "../../stores/userDataStore.js";
+import StoreEventHandler from "../../stores/storeEventHandler.js";
+import { FnameHistoryKind } from "../../stores/types.js";
+
+const db = jestRocksDB("fnameHistory.migration.test");
+
+describe("fnameHistory migration", () => {
+  test("should record the current owner of existing fnames", async () => {
+    const proof = Factories.UserNameProof.build();
+
+    // Write the proof the way a hub without the history would have: the proof, but no FNameHistory entries
+    await db.put(makeFNameUserNameProofKey(proof.name), Buffer.from(UserNameProof.encode(proof).finish()));
+
+    const userDataStore = new UserDataStore(db, new StoreEventHandler(db));
+    await expect(userDataStore.getFnameHistory(proof.name)).resolves.toEqual({ entries: [], nextPageToken: undefined });
+
+    await performDbMigrations(db, 13, 14);
+
+    await expect(userDataStore.getFnameHistory(proof.name)).resolves.toEqual({
+      entries: [{ kind: FnameHistoryKind.REGISTER, previousFid: 0, proof }],
+      nextPageToken: undefined,
+    });
+  });
+});
@@ -0,0 +1,26 @@
+import { logger } from "../../../utils/logger.js";
+import RocksDB from "../rocksdb.js";
+import UserDataStore from "../../stores/userDataStore.js";
+import StoreEventHandler from "../../stores/storeEventHandler.js";
+
+const log = logger.child({ component: "fnameHistory" });
+
+/**
+ * Fname proofs merged before the FNameHistory index existed have no history. Seed it with a REGISTER entry for each
+ * name that currently has an owner, so that later transfers and deletions have something to follow.
+ */
+export const fnameHistoryMigration = async (db: RocksDB): Promise<boolean> => {
+  log.info({}, "Starting fnameHistory migration");
+  const start = Date.now();
+
+  const userDataStore = new UserDataStore(db, new StoreEventHandler(db));
+  try {
+    const written = await userDataStore.backfillFnameHistory();
+    log.info({ entries: written, duration: Date.now() - start }, "fnameHistory migration done");
+  } catch (e) {
+    log.error({ err: e }, "fnameHistory migration failed");
+    return false;
+  }
+
+  return true;
+};
@@ -13,6 +13,9 @@ import { oldContractEvents } from "./6.oldContractEvents.js";
 import { clearAdminResets } from "./7.clearAdminResets.js";
 import { fnameUserNameProofByFidPrefix } from "./9.fnameUserNameProofByFidPrefix.js";
 import { fixFnameIndexLittleEndianToBigEndian } from "./11.fnameIndex.js";
+import { aggregateCountsMigration } from "./12.aggregateCounts.js";
+import { castsByEmbedIndexMigration } from "./13.castsByEmbed.js";
+import { fnameHistoryMigration } from "./14.fnameHistory.js";
 
 type MigrationFunctionType = (db: RocksDB) => Promise<boolean>;
 const migrations = new Map<number, MigrationFunctionType>();
@@ -73,6 +76,18 @@ migrations.set(11, async (db: RocksDB) => {
   return await fixFnameIndexLittleEndianToBigEndian(db);
 });
 
+migrations.set(12, async (db: RocksDB) => {
+  return await aggregateCountsMigration(db);
+});
+
+migrations.set(13, async (db: RocksDB) => {
+  return await castsByEmbedIndexMigration(db);
+});
+
+migrations.set(14, async (db: RocksDB) => {
+  return await fnameHistoryMigration(db);
+});
+
 // To Add a new migration
 // migrations.set(<next number>, async (db: RocksDB) => {
 //   <call migration script>
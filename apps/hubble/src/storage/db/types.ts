@@ -78,6 +78,30 @@ export enum RootPrefix {
 
   /* Used to index fname username proofs by fid */
   FNameUserNameProofByFid = 27,
+
+  /* Aggregate counters, e.g. reactions per cast. Followed by a CounterType */
+  AggregateCounts = 28,
+
+  /* Full-text search postings for cast text. <termLength>:<term>:<tsHash>:<fid> */
+  CastSearchTerms = 29,
+
+  /* Set once an optional secondary index has been built from every message. <indexPrefix> */
+  OptionalIndexComplete = 30,
+
+  /* Used to index casts by the urls and casts they embed. <embedKey>:<tsHash>:<fid> */
+  CastsByEmbed = 31,
+
+  /* Every CastAdd in time order. <tsHash>:<fid> */
+  CastsByTime = 32,
+
+  /* The proof that deleted an fname, so older proofs for it can't be merged again. <name> */
+  FNameDeletion = 33,
+
+  /* Every fname proof, transfer and deletion. <nameLength>:<name>:<timestamp>:<fid> */
+  FNameHistory = 34,
+
+  /* FNameHistory entries a fid was the owner or previous owner in. <fid>:<timestamp>:<name> */
+  FNameHistoryByFid = 35,
 }
 
 /**
@@ -136,6 +160,9 @@ export enum UserPostfix {
 
   /* Link Compact State set */
   LinkCompactStateMessage = 100,
+
+  /* Reaction Compact State set */
+  ReactionCompactStateMessage = 101,
 }
 
 export enum OnChainEventPostfix {
@@ -160,4 +187,5 @@ export type UserMessagePostfix =
   | UserPostfix.ReactionMessage
   | UserPostfix.UserDataMessage
   | UserPostfix.UsernameProofMessage
-  | UserPostfix.LinkCompactStateMessage;
+  | UserPostfix.LinkCompactStateMessage
+  | UserPostfix.ReactionCompactStateMessage;
@@ -205,6 +205,12 @@ describe("mergeMessage", () => {
         await expect(engine.getCast(fid, castAdd.hash)).resolves.toEqual(ok(castAdd));
         expect(mergedMessages).toEqual([castAdd]);
       });
+
+      test("is returned as a thread", async () => {
+        await expect(engine.mergeMessage(castAdd)).resolves.toBeInstanceOf(Ok);
+        const thread = await engine.getCastThread({ fid, hash: castAdd.hash });
+        expect(thread._unsafeUnwrap().messages).toEqual([{ message: castAdd, depth: 0 }]);
+      });
     });
 
     describe("ReactionAdd", () => {
@@ -16,6 +16,7 @@ import {
   HubEvent,
   HubResult,
   isLinkCompactStateMessage,
+  isReactionCompactStateMessage,
   isSignerOnChainEvent,
   isUserDataAddMessage,
   isUsernameProofMessage,
@@ -32,6 +33,7 @@ import {
   OnChainEventType,
   Protocol,
   ReactionAddMessage,
+  ReactionCompactStateMessage,
   ReactionRemoveMessage,
   ReactionType,
   RevokeMessagesBySignerJobPayload,
@@ -51,7 +53,7 @@ import {
   VerificationAddAddressMessage,
   VerificationRemoveMessage,
 } from "@farcaster/hub-nodejs";
-import { err, ok, ResultAsync } from "neverthrow";
+import { err, ok, Result, ResultAsync } from "neverthrow";
 import fs from "fs";
 import { Worker } from "worker_threads";
 import { forEachMessageBySigner, typeToSetPostfix } from "../db/message.js";
@@ -61,7 +63,18 @@ import CastStore from "../stores/castStore.js";
 import LinkStore from "../stores/linkStore.js";
 import ReactionStore from "../stores/reactionStore.js";
 import StoreEventHandler from "../stores/storeEventHandler.js";
-import { DEFAULT_PAGE_SIZE, MessagesPage, PageOptions } from "../stores/types.js";
+import {
+  CastStoreOptions,
+  CastThread,
+  DEFAULT_PAGE_SIZE,
+  FnameHistoryPage,
+  LinkCompactStateCandidate,
+  MergeValidation,
+  MessagesPage,
+  PageOptions,
+  Profile,
+  ReverseResolution,
+} from "../stores/types.js";
 import UserDataStore from "../stores/userDataStore.js";
 import VerificationStore from "../stores/verificationStore.js";
 import { logger } from "../../utils/logger.js";
@@ -72,7 +85,7 @@ import { normalize } from "viem/ens";
 import UsernameProofStore from "../stores/usernameProofStore.js";
 import OnChainEventStore from "../stores/onChainEventStore.js";
 import { consumeRateLimitByKey, getRateLimiterForTotalMessages, isRateLimitedByKey } from "../../utils/rateLimits.js";
-import { rsValidationMethods } from "../../rustfunctions.js";
+import { RustRpcServerStores, rsValidationMethods } from "../../rustfunctions.js";
 import { RateLimiterAbstract, RateLimiterMemory } from "rate-limiter-flexible";
 import { TypedEmitter } from "tiny-typed-emitter";
 import { FNameRegistryEventsProvider } from "../../eth/fnameRegistryEventsProvider.js";
@@ -160,6 +173,7 @@ class Engine extends TypedEmitter<EngineEvents> {
     l2PublicClient?: PublicClient,
     fNameRegistryEventsProvider?: FNameRegistryEventsProvider,
     l2EventsProvider?: L2EventsProvider,
+    castStoreOptions: CastStoreOptions = {},
   ) {
     super();
     this._db = db;
@@ -173,7 +187,7 @@ class Engine extends TypedEmitter<EngineEvents> {
 
     this._linkStore = new LinkStore(db, this.eventHandler);
     this._reactionStore = new ReactionStore(db, this.eventHandler);
-    this._castStore = new CastStore(db, this.eventHandler);
+    this._castStore = new CastStore(db, this.eventHandler, castStoreOptions);
     this._userDataStore = new UserDataStore(db, this.eventHandler);
     this._verificationStore = new VerificationStore(db, this.eventHandler);
     this._onchainEventsStore = new OnChainEventStore(db, this.eventHandler);
@@ -243,6 +257,18 @@ class Engine extends TypedEmitter<EngineEvents> {
     this.eventHandler.on("mergeOnChainEvent", this.handleMergeOnChainEvent);
 
     await this.eventHandler.syncCache();
+
+    // Optional cast indices that were just enabled only have the casts merged since, so they're backfilled before
+    // the hub merges anything else
+    const backfilled = await this._castStore.backfillOptionalIndices({
+      onProgress: ({ stage, keys }) => log.info({ stage, keys }, "backfilling cast indices..."),
+    });
+    if (backfilled.isErr()) {
+      log.error({ err: backfilled.error }, "failed to backfill cast indices");
+    } else if (backfilled.value.length > 0) {
+      log.info({ prefixes: backfilled.value }, "backfilled cast indices");
+    }
+
     log.info("engine started");
   }
 
@@ -272,6 +298,18 @@ class Engine extends TypedEmitter<EngineEvents> {
     this._onchainEventsStore.clearCaches();
   }
 
+  /** The Rust stores backing this engine, for serving reads directly from Rust */
+  get rustStores(): RustRpcServerStores {
+    return {
+      castStore: this._castStore.rustStore,
+      linkStore: this._linkStore.rustStore,
+      reactionStore: this._reactionStore.rustStore,
+      userDataStore: this._userDataStore.rustStore,
+      verificationStore: this._verificationStore.rustStore,
+      usernameProofStore: this._usernameProofStore.rustStore,
+    };
+  }
+
   get solanaVerificationsEnabled(): boolean {
     return this._solanaVerificationsEnabled;
   }
@@ -386,6 +424,7 @@ class Engine extends TypedEmitter<EngineEvents> {
           linkMessages.push({ i, message });
           break;
         }
+        case UserPostfix.ReactionCompactStateMessage:
         case UserPostfix.ReactionMessage: {
           reactionMessages.push({ i, message });
           break;
@@ -476,6 +515,55 @@ class Engine extends TypedEmitter<EngineEvents> {
     return mergeResult;
   }
 
+  /**
+   * Dry run of mergeMessage. Explains what merging the message would do, or which existing message it
+   * loses to. The message itself isn't validated.
+   */
+  async validateMerge(message: Message): HubAsyncResult<MergeValidation> {
+    if (!message.data) {
+      return err(new HubError("bad_request.invalid_param", "missing message data"));
+    }
+
+    const validatedFid = validations.validateFid(message.data.fid);
+    if (validatedFid.isErr()) {
+      return err(validatedFid.error);
+    }
+
+    const postfix = Result.fromThrowable(
+      typeToSetPostfix,
+      () => new HubError("bad_request.invalid_param", "invalid message type"),
+    )(message.data.type);
+    if (postfix.isErr()) {
+      return err(postfix.error);
+    }
+
+    switch (postfix.value) {
+      case UserPostfix.LinkCompactStateMessage:
+      case UserPostfix.LinkMessage: {
+        return this._linkStore.validateMerge(message);
+      }
+      case UserPostfix.ReactionCompactStateMessage:
+      case UserPostfix.ReactionMessage: {
+        return this._reactionStore.validateMerge(message);
+      }
+      case UserPostfix.CastMessage: {
+        return this._castStore.validateMerge(message);
+      }
+      case UserPostfix.UserDataMessage: {
+        return this._userDataStore.validateMerge(message);
+      }
+      case UserPostfix.VerificationMessage: {
+        return this._verificationStore.validateMerge(message);
+      }
+      case UserPostfix.UsernameProofMessage: {
+        return this._usernameProofStore.validateMerge(message);
+      }
+      default: {
+        return err(new HubError("bad_request.invalid_param", "invalid message type"));
+      }
+    }
+  }
+
   async revokeMessagesBySigner(fid: number, signer: Uint8Array): HubAsyncResult<void> {
     const signerHex = bytesToHexString(signer);
     if (signerHex.isErr()) {
@@ -496,6 +584,7 @@ class Engine extends TypedEmitter<EngineEvents> {
         case UserPostfix.LinkMessage: {
           return this._linkStore.revoke(message);
         }
+        case UserPostfix.ReactionCompactStateMessage:
         case UserPostfix.ReactionMessage: {
           return this._reactionStore.revoke(message);
         }
@@ -590,6 +679,7 @@ class Engine extends TypedEmitter<EngineEvents> {
         case UserPostfix.LinkMessage: {
           return this._linkStore.revoke(message);
         }
+        case UserPostfix.ReactionCompactStateMessage:
         case UserPostfix.ReactionMessage: {
           return this._reactionStore.revoke(message);
         }
@@ -684,6 +774,23 @@ class Engine extends TypedEmitter<EngineEvents> {
     return ResultAsync.fromPromise(this._castStore.getCastsByParent(parent, pageOptions), (e) => e as HubError);
   }
 
+  async getCastThread(
+    castId: CastId,
+    maxDepth?: number,
+    maxNodes?: number,
+    pageToken?: Uint8Array,
+  ): HubAsyncResult<CastThread> {
+    const validatedCastId = validations.validateCastId(castId);
+    if (validatedCastId.isErr()) {
+      return err(validatedCastId.error);
+    }
+
+    return ResultAsync.fromPromise(
+      this._castStore.getCastThread(castId, maxDepth, maxNodes, pageToken),
+      (e) => e as HubError,
+    );
+  }
+
   async getCastsByMention(
     mentionFid: number,
     pageOptions: PageOptions = {},
@@ -696,6 +803,56 @@ class Engine extends TypedEmitter<EngineEvents> {
     return ResultAsync.fromPromise(this._castStore.getCastsByMention(mentionFid, pageOptions), (e) => e as HubError);
   }
 
+  async getCastsByEmbedUrl(url: string, pageOptions: PageOptions = {}): HubAsyncResult<MessagesPage<CastAddMessage>> {
+    const validatedUrl = validations.validateUrl(url);
+    if (validatedUrl.isErr()) {
+      return err(validatedUrl.error);
+    }
+
+    return ResultAsync.fromPromise(this._castStore.getCastsByEmbedUrl(url, pageOptions), (e) => e as HubError);
+  }
+
+  async getQuoteCasts(castId: CastId, pageOptions: PageOptions = {}): HubAsyncResult<MessagesPage<CastAddMessage>> {
+    const validatedCastId = validations.validateCastId(castId);
+    if (validatedCastId.isErr()) {
+      return err(validatedCastId.error);
+    }
+
+    return ResultAsync.fromPromise(this._castStore.getQuoteCasts(castId, pageOptions), (e) => e as HubError);
+  }
+
+  async getRecentCasts(
+    pageOptions: PageOptions = {},
+    startTime?: number,
+    stopTime?: number,
+  ): HubAsyncResult<MessagesPage<CastAddMessage>> {
+    return ResultAsync.fromPromise(
+      this._castStore.getRecentCasts(pageOptions, startTime, stopTime),
+      (e) => e as HubError,
+    );
+  }
+
+  async getRecentCastsByParentUrl(
+    parentUrl: string,
+    pageOptions: PageOptions = {},
+    startTime?: number,
+    stopTime?: number,
+  ): HubAsyncResult<MessagesPage<CastAddMessage>> {
+    const validatedUrl = validations.validateUrl(parentUrl);
+    if (validatedUrl.isErr()) {
+      return err(validatedUrl.error);
+    }
+
+    return ResultAsync.fromPromise(
+      this._castStore.getRecentCastsByParentUrl(parentUrl, pageOptions, startTime, stopTime),
+      (e) => e as HubError,
+    );
+  }
+
+  async searchCasts(query: string, pageOptions: PageOptions = {}): HubAsyncResult<MessagesPage<CastAddMessage>> {
+    return ResultAsync.fromPromise(this._castStore.searchCasts(query, pageOptions), (e) => e as HubError);
+  }
+
   async getAllCastMessagesByFid(
     fid: number,
     pageOptions: PageOptions = {},
@@ -775,6 +932,21 @@ class Engine extends TypedEmitter<EngineEvents> {
     );
   }
 
+  async getReactionCompactStateMessageByFid(
+    fid: number,
+    pageOptions: PageOptions = {},
+  ): HubAsyncResult<MessagesPage<ReactionCompactStateMessage>> {
+    const validatedFid = validations.validateFid(fid);
+    if (validatedFid.isErr()) {
+      return err(validatedFid.error);
+    }
+
+    return ResultAsync.fromPromise(
+      this._reactionStore.getReactionCompactStateMessageByFid(fid, pageOptions),
+      (e) => e as HubError,
+    );
+  }
+
   async getAllReactionMessagesByFid(
     fid: number,
     pageOptions: PageOptions = {},
@@ -1004,6 +1176,52 @@ class Engine extends TypedEmitter<EngineEvents> {
     });
   }
 
+  async getProfile(fid: number): HubAsyncResult<Profile> {
+    const validatedFid = validations.validateFid(fid);
+    if (validatedFid.isErr()) {
+      return err(validatedFid.error);
+    }
+
+    return ResultAsync.fromPromise(this._userDataStore.getProfile(fid), (e) => e as HubError);
+  }
+
+  async getProfiles(fids: number[]): HubAsyncResult<Profile[]> {
+    for (const fid of fids) {
+      const validatedFid = validations.validateFid(fid);
+      if (validatedFid.isErr()) {
+        return err(validatedFid.error);
+      }
+    }
+
+    return ResultAsync.fromPromise(this._userDataStore.getProfiles(fids), (e) => e as HubError);
+  }
+
+  async resolveName(name: string): HubAsyncResult<UserNameProof> {
+    return ResultAsync.fromPromise(this._userDataStore.resolveName(name), (e) => e as HubError);
+  }
+
+  async reverseResolve(fid: number): HubAsyncResult<ReverseResolution> {
+    const validatedFid = validations.validateFid(fid);
+    if (validatedFid.isErr()) {
+      return err(validatedFid.error);
+    }
+
+    return ResultAsync.fromPromise(this._userDataStore.reverseResolve(fid), (e) => e as HubError);
+  }
+
+  async getFnameHistory(name: Uint8Array, pageOptions: PageOptions = {}): HubAsyncResult<FnameHistoryPage> {
+    return ResultAsync.fromPromise(this._userDataStore.getFnameHistory(name, pageOptions), (e) => e as HubError);
+  }
+
+  async getFnameHistoryByFid(fid: number, pageOptions: PageOptions = {}): HubAsyncResult<FnameHistoryPage> {
+    const validatedFid = validations.validateFid(fid);
+    if (validatedFid.isErr()) {
+      return err(validatedFid.error);
+    }
+
+    return ResultAsync.fromPromise(this._userDataStore.getFnameHistoryByFid(fid, pageOptions), (e) => e as HubError);
+  }
+
   async getUserNameProof(name: Uint8Array, retries = 1): HubAsyncResult<UserNameProof> {
     const nameString = bytesToUtf8String(name);
     if (nameString.isErr()) {
@@ -1180,6 +1398,39 @@ class Engine extends TypedEmitter<EngineEvents> {
     );
   }
 
+  async buildLinkCompactState(fid: number, type: string): HubAsyncResult<LinkCompactStateCandidate> {
+    const validatedFid = validations.validateFid(fid);
+    if (validatedFid.isErr()) {
+      return err(validatedFid.error);
+    }
+
+    const validatedType = validations.validateLinkType(type);
+    if (validatedType.isErr()) {
+      return err(validatedType.error);
+    }
+
+    return ResultAsync.fromPromise(this._linkStore.buildCompactState(fid, type), (e) => e as HubError);
+  }
+
+  /** Dry run of merging a LinkCompactState or ReactionCompactState message, see RustStoreBase */
+  async previewCompactStateMerge(message: Message): HubAsyncResult<Message[]> {
+    if (!message.data) {
+      return err(new HubError("bad_request.invalid_param", "missing message data"));
+    }
+
+    switch (message.data.type) {
+      case MessageType.LINK_COMPACT_STATE: {
+        return this._linkStore.previewCompactStateMerge(message);
+      }
+      case MessageType.REACTION_COMPACT_STATE: {
+        return this._reactionStore.previewCompactStateMerge(message);
+      }
+      default: {
+        return err(new HubError("bad_request.invalid_param", "not a compact state message"));
+      }
+    }
+  }
+
   /* -------------------------------------------------------------------------- */
   /*                               Private Methods                              */
   /* -------------------------------------------------------------------------- */
@@ -1333,6 +1584,21 @@ class Engine extends TypedEmitter<EngineEvents> {
       );
     }
 
+    // ReactionCompactStateMessages can't be more than 100 storage units either
+    if (
+      isReactionCompactStateMessage(message) &&
+      message.data.reactionCompactStateBody.targetCastIds.length +
+        message.data.reactionCompactStateBody.targetUrls.length >
+        getDefaultStoreLimit(StoreType.REACTIONS, StorageUnitType.UNIT_TYPE_LEGACY) * 100
+    ) {
+      return err(
+        new HubError(
+          "bad_request.validation_failure",
+          "ReactionCompactStateMessage is too big. Limit = 100 storage units",
+        ),
+      );
+    }
+
     // 6. Check message body and envelope
     if (this._validationWorkers) {
       this._nextValidationWorker += 1;
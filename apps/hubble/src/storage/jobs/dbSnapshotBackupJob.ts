@@ -2,7 +2,12 @@ import { FarcasterNetwork, HubAsyncResult, HubError } from "@farcaster/hub-nodej
 import { Result, ResultAsync, err, ok } from "neverthrow";
 import cron from "node-cron";
 import { logger } from "../../utils/logger.js";
-import { rsDbSnapshotBackup } from "../../rustfunctions.js";
+import {
+  RustSnapshotCancellationToken,
+  rsCancelSnapshot,
+  rsCreateSnapshotCancellationToken,
+  rsDbSnapshotBackup,
+} from "../../rustfunctions.js";
 import RocksDB from "../../storage/db/rocksdb.js";
 import { uploadToS3 } from "../../utils/snapshot.js";
 import SyncEngine from "../../network/sync/syncEngine.js";
@@ -21,6 +26,7 @@ type SchedulerStatus = "started" | "stopped";
 export class DbSnapshotBackupJobScheduler {
   private _cronTask?: cron.ScheduledTask;
   private _running = false;
+  private _cancellationToken?: RustSnapshotCancellationToken;
 
   private _mainDb: RocksDB;
   private _trieDb: RocksDB;
@@ -48,6 +54,11 @@ export class DbSnapshotBackupJobScheduler {
     if (this._cronTask) {
       this._cronTask.stop();
     }
+
+    // Stop any backup that is still running, so it doesn't hold the DBs while the hub shuts down
+    if (this._cancellationToken) {
+      rsCancelSnapshot(this._cancellationToken);
+    }
   }
 
   status(): SchedulerStatus {
@@ -68,12 +79,18 @@ export class DbSnapshotBackupJobScheduler {
 
     log.info({}, "starting Db Snapshot Backup job");
     const startTimestampMs = Date.now();
+    this._cancellationToken = rsCreateSnapshotCancellationToken();
 
     // Back up the DB before opening it
     const tarGzResult = await ResultAsync.fromPromise(
-      rsDbSnapshotBackup(this._mainDb.rustDb, this._trieDb.rustDb, startTimestampMs),
+      rsDbSnapshotBackup(this._mainDb.rustDb, this._trieDb.rustDb, startTimestampMs, {
+        network: this._options.network,
+        cancellationToken: this._cancellationToken,
+        onProgress: (progress) => log.debug({ progress }, "Db Snapshot Backup progress"),
+      }),
       (e) => e as Error,
     );
+    this._cancellationToken = undefined;
 
     if (tarGzResult.isOk()) {
       const messageCount = await this._syncEngine.trie.items();
@@ -15,7 +15,7 @@ import {
 } from "@farcaster/hub-nodejs";
 import { jestRocksDB } from "../db/jestUtils.js";
 import { getMessage, makeTsHash } from "../db/message.js";
-import { UserPostfix } from "../db/types.js";
+import { RootPrefix, UserPostfix } from "../db/types.js";
 import CastStore from "./castStore.js";
 import StoreEventHandler from "./storeEventHandler.js";
 import { sleep } from "../../utils/crypto.js";
@@ -123,6 +123,19 @@ describe("getCastAddsByFid", () => {
     const results2 = await store.getCastAddsByFid(fid, { pageToken: results1.nextPageToken });
     expect(results2).toEqual({ messages: [castAdd2], nextPageToken: undefined });
   });
+
+  test("returns only cast adds in the time range", async () => {
+    const timestamp = castAdd.data.timestamp;
+    const castAdd2 = await Factories.CastAddMessage.create({ data: { fid, timestamp: timestamp + 10 } });
+    await store.merge(castAdd);
+    await store.merge(castAdd2);
+
+    const after = await store.getCastAddsByFid(fid, {}, timestamp + 1);
+    expect(after.messages).toEqual([castAdd2]);
+
+    const before = await store.getCastAddsByFid(fid, {}, undefined, timestamp + 9);
+    expect(before.messages).toEqual([castAdd]);
+  });
 });
 
 describe("getCastRemovesByFid", () => {
@@ -208,6 +221,132 @@ describe("getCastsByParent", () => {
   });
 });
 
+describe("getCastThread", () => {
+  let root: CastAddMessage;
+  let reply1: CastAddMessage;
+  let reply1a: CastAddMessage;
+  let reply2: CastAddMessage;
+
+  const makeReply = async (parent: CastAddMessage, timestamp: number) => {
+    return await Factories.CastAddMessage.create({
+      data: { fid, timestamp, castAddBody: { parentCastId: { fid, hash: parent.hash } } },
+    });
+  };
+
+  beforeAll(async () => {
+    const timestamp = castAdd.data.timestamp;
+    root = castAdd;
+    reply1 = await makeReply(root, timestamp + 1);
+    reply1a = await makeReply(reply1, timestamp + 2);
+    reply2 = await makeReply(root, timestamp + 3);
+  });
+
+  beforeEach(async () => {
+    await store.mergeMessages([root, reply2, reply1a, reply1]);
+  });
+
+  test("returns the thread in tree order", async () => {
+    const thread = await store.getCastThread({ fid, hash: root.hash });
+    expect(thread.messages).toEqual([
+      { message: root, depth: 0 },
+      { message: reply1, depth: 1 },
+      { message: reply1a, depth: 2 },
+      { message: reply2, depth: 1 },
+    ]);
+    expect(thread.truncated).toBeFalsy();
+  });
+
+  test("finds the root from a reply", async () => {
+    const thread = await store.getCastThread({ fid, hash: reply1a.hash });
+    expect(thread.messages.map(({ message }) => message)).toEqual([root, reply1, reply1a, reply2]);
+  });
+
+  test("stops at maxDepth", async () => {
+    const thread = await store.getCastThread({ fid, hash: root.hash }, 1);
+    expect(thread.messages.map(({ message }) => message)).toEqual([root, reply1, reply2]);
+    expect(thread.truncated).toBeFalsy();
+  });
+
+  test("truncates at maxNodes", async () => {
+    const thread = await store.getCastThread({ fid, hash: root.hash }, 10, 3);
+    expect(thread.messages.map(({ message }) => message)).toEqual([root, reply1, reply1a]);
+    expect(thread.truncated).toBeTruthy();
+  });
+
+  test("continues a truncated thread from its nextPageToken", async () => {
+    const first = await store.getCastThread({ fid, hash: root.hash }, 10, 2);
+    expect(first.messages.map(({ message }) => message)).toEqual([root, reply1]);
+    expect(first.nextPageToken).toBeDefined();
+
+    const second = await store.getCastThread({ fid, hash: root.hash }, 10, 2, first.nextPageToken);
+    expect(second.messages).toEqual([
+      { message: reply1a, depth: 2 },
+      { message: reply2, depth: 1 },
+    ]);
+    expect(second.truncated).toBeFalsy();
+    expect(second.nextPageToken).toBeUndefined();
+  });
+
+  test("fails with an invalid nextPageToken", async () => {
+    const pageToken = new Uint8Array([1, 2, 3]);
+    await expect(store.getCastThread({ fid, hash: root.hash }, 10, 2, pageToken)).rejects.toThrow(HubError);
+  });
+
+  test("fails if the cast is missing", async () => {
+    await expect(store.getCastThread(Factories.CastId.build())).rejects.toThrow(HubError);
+  });
+});
+
+describe("getReplyCount", () => {
+  test("returns 0 if there are no replies", async () => {
+    await expect(store.getReplyCount(parentCastId)).resolves.toEqual(0);
+  });
+
+  test("counts replies as they are merged and removed", async () => {
+    const reply = await Factories.CastAddMessage.create({
+      data: { castAddBody: { parentCastId }, timestamp: castAdd.data.timestamp + 1 },
+    });
+    await store.merge(castAdd);
+    await store.merge(reply);
+    await expect(store.getReplyCount(parentCastId)).resolves.toEqual(2);
+
+    await store.merge(castRemove);
+    await expect(store.getReplyCount(parentCastId)).resolves.toEqual(1);
+
+    await store.revoke(reply);
+    await expect(store.getReplyCount(parentCastId)).resolves.toEqual(0);
+  });
+
+  test("does not count casts with a parent url", async () => {
+    const cast = await Factories.CastAddMessage.create({
+      data: { castAddBody: { parentCastId: undefined, parentUrl: faker.internet.url() } },
+    });
+    await store.merge(cast);
+
+    const counterKeys: Buffer[] = [];
+    await db.forEachIteratorByPrefix(Buffer.from([RootPrefix.AggregateCounts]), (key) => {
+      counterKeys.push(key as Buffer);
+    });
+    expect(counterKeys).toEqual([]);
+  });
+
+  test("rebuildCounters restores deleted counters", async () => {
+    await store.merge(castAdd);
+
+    const counterKeys: Buffer[] = [];
+    await db.forEachIteratorByPrefix(Buffer.from([RootPrefix.AggregateCounts]), (key) => {
+      counterKeys.push(key as Buffer);
+    });
+    for (const key of counterKeys) {
+      await db.del(key);
+    }
+    await expect(store.getReplyCount(parentCastId)).resolves.toEqual(0);
+
+    await expect(store.rebuildCounters()).resolves.toEqual(ok(1));
+    await expect(store.getReplyCount(parentCastId)).resolves.toEqual(1);
+  });
+});
+
 describe("getCastsByMention", () => {
   test("returns empty array if no casts exist", async () => {
     const byTargetUser = await store.getCastsByMention(Factories.Fid.build());
@@ -247,6 +386,184 @@ describe("getCastsByMention", () => {
   });
 });
 
+describe("getCastsByEmbedUrl", () => {
+  const url = "https://farcaster.xyz/blog";
+
+  test("returns empty array if no casts embed the url", async () => {
+    await store.merge(castAdd);
+    await expect(store.getCastsByEmbedUrl(url)).resolves.toEqual({ messages: [], nextPageToken: undefined });
+  });
+
+  test("returns casts that embed the url, normalized, according to pageOptions", async () => {
+    const cast1 = await Factories.CastAddMessage.create({ data: { castAddBody: { embeds: [{ url }] } } });
+    const cast2 = await Factories.CastAddMessage.create({
+      data: {
+        timestamp: cast1.data.timestamp + 1,
+        castAddBody: { embeds: [{ url: "HTTPS://Farcaster.xyz:443/blog/#latest" }, { url }] },
+      },
+    });
+    await store.merge(cast1);
+    await store.merge(cast2);
+
+    await expect(store.getCastsByEmbedUrl(url)).resolves.toEqual({
+      messages: [cast1, cast2],
+      nextPageToken: undefined,
+    });
+    await expect(store.getCastsByEmbedUrl("https://farcaster.xyz/blog/")).resolves.toEqual({
+      messages: [cast1, cast2],
+      nextPageToken: undefined,
+    });
+
+    const results1 = await store.getCastsByEmbedUrl(url, { pageSize: 1 });
+    expect(results1.messages).toEqual([cast1]);
+
+    const results2 = await store.getCastsByEmbedUrl(url, { pageToken: results1.nextPageToken });
+    expect(results2).toEqual({ messages: [cast2], nextPageToken: undefined });
+
+    const results3 = await store.getCastsByEmbedUrl(url, { reverse: true });
+    expect(results3).toEqual({ messages: [cast2, cast1], nextPageToken: undefined });
+  });
+
+  test("does not return removed casts", async () => {
+    const cast = await Factories.CastAddMessage.create({ data: { fid, castAddBody: { embeds: [{ url }] } } });
+    const remove = await Factories.CastRemoveMessage.create({
+      data: { fid, timestamp: cast.data.timestamp + 1, castRemoveBody: { targetHash: cast.hash } },
+    });
+    await store.merge(cast);
+    await store.merge(remove);
+
+    await expect(store.getCastsByEmbedUrl(url)).resolves.toEqual({ messages: [], nextPageToken: undefined });
+  });
+});
+
+describe("getQuoteCasts", () => {
+  test("returns casts that embed the cast id", async () => {
+    const quotedCastId = Factories.CastId.build();
+    const quote = await Factories.CastAddMessage.create({
+      data: { castAddBody: { embeds: [{ castId: quotedCastId }] } },
+    });
+    await store.merge(castAdd);
+    await store.merge(quote);
+
+    await expect(store.getQuoteCasts(quotedCastId)).resolves.toEqual({ messages: [quote], nextPageToken: undefined });
+    await expect(store.getQuoteCasts(Factories.CastId.build())).resolves.toEqual({
+      messages: [],
+      nextPageToken: undefined,
+    });
+  });
+});
+
+describe("searchCasts", () => {
+  const searchStore = new CastStore(db, eventHandler, { searchIndex: true });
+
+  const castWithText = async (text: string, timestamp: number, mentions: number[] = []) => {
+    return Factories.CastAddMessage.create({
+      data: { fid, timestamp, castAddBody: { text, mentions, mentionsPositions: mentions.map(() => 0) } },
+    });
+  };
+
+  beforeEach(async () => {
+    await searchStore.backfillOptionalIndices();
+  });
+
+  test("fails if the store doesn't have the search index", async () => {
+    await expect(store.searchCasts("hello")).rejects.toEqual(
+      new HubError("bad_request.invalid_param", "cast search index is not enabled on this hub"),
+    );
+  });
+
+  test("fails until the index is backfilled after it's enabled", async () => {
+    // Disabling the index marks it incomplete, and casts merged meanwhile aren't in it
+    await store.backfillOptionalIndices();
+    const cast = await castWithText("merged while disabled", getFarcasterTime()._unsafeUnwrap());
+    await store.merge(cast);
+
+    await expect(searchStore.searchCasts("disabled")).rejects.toEqual(
+      new HubError("unavailable", "cast search index is still being built"),
+    );
+
+    await expect(searchStore.backfillOptionalIndices()).resolves.toEqual(ok([RootPrefix.CastSearchTerms]));
+    await expect(searchStore.searchCasts("disabled")).resolves.toEqual({ messages: [cast], nextPageToken: undefined });
+    await expect(searchStore.backfillOptionalIndices()).resolves.toEqual(ok([]));
+  });
+
+  test("fails with a query without terms", async () => {
+    await expect(searchStore.searchCasts(" ?! ")).rejects.toEqual(
+      new HubError("bad_request.invalid_param", "search query has no terms"),
+    );
+  });
+
+  test("returns casts with all the terms, newest first", async () => {
+    const timestamp = getFarcasterTime()._unsafeUnwrap();
+    const cast1 = await castWithText("Good morning, Farcaster!", timestamp);
+    const cast2 = await castWithText("good night farcaster", timestamp + 1);
+    const cast3 = await castWithText("GM everyone", timestamp + 2);
+    await searchStore.mergeMessages([cast1, cast2, cast3]);
+
+    await expect(searchStore.searchCasts("farcaster")).resolves.toEqual({
+      messages: [cast2, cast1],
+      nextPageToken: undefined,
+    });
+    await expect(searchStore.searchCasts("FARCASTER good")).resolves.toEqual({
+      messages: [cast2, cast1],
+      nextPageToken: undefined,
+    });
+    await expect(searchStore.searchCasts("morning farcaster")).resolves.toEqual({
+      messages: [cast1],
+      nextPageToken: undefined,
+    });
+    await expect(searchStore.searchCasts("farcast")).resolves.toEqual({ messages: [], nextPageToken: undefined });
+
+    const results1 = await searchStore.searchCasts("farcaster", { pageSize: 1 });
+    expect(results1.messages).toEqual([cast2]);
+    const results2 = await searchStore.searchCasts("farcaster", { pageToken: results1.nextPageToken });
+    expect(results2).toEqual({ messages: [cast1], nextPageToken: undefined });
+
+    await expect(searchStore.searchCasts("farcaster", { reverse: true })).resolves.toEqual({
+      messages: [cast1, cast2],
+      nextPageToken: undefined,
+    });
+  });
+
+  test("matches phrases and mentions", async () => {
+    const timestamp = getFarcasterTime()._unsafeUnwrap();
+    const mentionFid = Factories.Fid.build();
+    const cast1 = await castWithText("the quick brown fox", timestamp, [mentionFid]);
+    const cast2 = await castWithText("the brown, quick fox", timestamp + 1);
+    await searchStore.mergeMessages([cast1, cast2]);
+
+    await expect(searchStore.searchCasts('"quick brown"')).resolves.toEqual({
+      messages: [cast1],
+      nextPageToken: undefined,
+    });
+    await expect(searchStore.searchCasts("quick brown")).resolves.toEqual({
+      messages: [cast2, cast1],
+      nextPageToken: undefined,
+    });
+    await expect(searchStore.searchCasts(`fox @${mentionFid}`)).resolves.toEqual({
+      messages: [cast1],
+      nextPageToken: undefined,
+    });
+  });
+
+  test("removes casts from the index when they are removed", async () => {
+    const cast = await castWithText("hello world", getFarcasterTime()._unsafeUnwrap());
+    const remove = await Factories.CastRemoveMessage.create({
+      data: { fid, timestamp: cast.data.timestamp + 1, castRemoveBody: { targetHash: cast.hash } },
+    });
+    await searchStore.merge(cast);
+    await searchStore.merge(remove);
+
+    await expect(searchStore.searchCasts("hello")).resolves.toEqual({ messages: [], nextPageToken: undefined });
+
+    const searchKeys: Buffer[] = [];
+    await db.forEachIteratorByPrefix(Buffer.from([RootPrefix.CastSearchTerms]), (key) => {
+      searchKeys.push(key as Buffer);
+    });
+    expect(searchKeys).toEqual([]);
+  });
+});
+
 describe("merge", () => {
   let mergeEvents: [Message | undefined, Message[]][] = [];
 
@@ -597,6 +914,203 @@ describe("merge", () => {
   });
 });
 
+describe("checkIndices", () => {
+  // Only indexed by its parent, so that it has exactly one index entry
+  let reply: CastAddMessage;
+
+  beforeAll(async () => {
+    reply = await Factories.CastAddMessage.create({
+      data: { fid, castAddBody: { parentCastId, mentions: [], mentionsPositions: [], embeds: [] } },
+    });
+  });
+
+  const getByParentKeys = async () => {
+    const keys: Buffer[] = [];
+    await db.forEachIteratorByPrefix(Buffer.from([RootPrefix.CastsByParent]), (key) => {
+      keys.push(Buffer.from(key));
+    });
+    return keys;
+  };
+
+  test("succeeds with no missing or dangling entries", async () => {
+    await store.merge(reply);
+
+    const report = (await store.checkIndices())._unsafeUnwrap();
+    expect(report.messagesChecked).toEqual(1);
+    expect(report.indexEntriesChecked).toEqual(1);
+    expect(report.missing).toEqual(0);
+    expect(report.dangling).toEqual(0);
+    expect(report.rebuilt).toEqual(0);
+  });
+
+  test("reports missing and dangling entries", async () => {
+    await store.merge(reply);
+
+    const [byParentKey] = await getByParentKeys();
+    expect(byParentKey).toBeDefined();
+    await db.del(byParentKey as Buffer);
+
+    const danglingKey = Buffer.from(byParentKey as Buffer);
+    danglingKey[danglingKey.length - 1] = (danglingKey[danglingKey.length - 1] as number) ^ 0xff;
+    await db.put(danglingKey, Buffer.from([]));
+
+    const progress: string[] = [];
+    const report = (await store.checkIndices({ onProgress: ({ stage }) => progress.push(stage) }))._unsafeUnwrap();
+    expect(report.missing).toEqual(1);
+    expect(report.dangling).toEqual(1);
+    expect(report.missingKeys).toEqual([byParentKey]);
+    expect(report.danglingKeys).toEqual([danglingKey]);
+    expect(progress).toContain("messages");
+    expect(progress).toContain("indices");
+  });
+
+  test("rebuilds indices with repair", async () => {
+    await store.merge(reply);
+
+    const byParentKeys = await getByParentKeys();
+    for (const key of byParentKeys) {
+      await db.del(key);
+    }
+    await db.put(Buffer.from([RootPrefix.CastsByParent, 1, 2, 3]), Buffer.from([]));
+
+    const report = (await store.checkIndices({ repair: true }))._unsafeUnwrap();
+    expect(report.missing).toEqual(1);
+    expect(report.dangling).toEqual(1);
+    expect(report.rebuilt).toEqual(1);
+
+    expect(await getByParentKeys()).toEqual(byParentKeys);
+    const recheck = (await store.checkIndices())._unsafeUnwrap();
+    expect(recheck.missing).toEqual(0);
+    expect(recheck.dangling).toEqual(0);
+    await expect(store.getCastsByParent(parentCastId)).resolves.toEqual({
+      messages: [reply],
+      nextPageToken: undefined,
+    });
+  });
+});
+
+describe("getRecentCasts", () => {
+  const feedStore = new CastStore(db, eventHandler, { feedIndex: true });
+
+  const castAt = async (timestamp: number, castFid = fid) => {
+    return Factories.CastAddMessage.create({ data: { fid: castFid, timestamp } });
+  };
+
+  beforeEach(async () => {
+    await feedStore.backfillOptionalIndices();
+  });
+
+  test("fails if the store doesn't have the feed index", async () => {
+    await expect(store.getRecentCasts()).rejects.toEqual(
+      new HubError("bad_request.invalid_param", "cast feed index is not enabled on this hub"),
+    );
+  });
+
+  test("fails until the index is backfilled after it's enabled", async () => {
+    await store.backfillOptionalIndices();
+    const cast = await castAt(getFarcasterTime()._unsafeUnwrap());
+    await store.merge(cast);
+
+    await expect(feedStore.getRecentCasts()).rejects.toEqual(
+      new HubError("unavailable", "cast feed index is still being built"),
+    );
+
+    await expect(feedStore.backfillOptionalIndices()).resolves.toEqual(ok([RootPrefix.CastsByTime]));
+    await expect(feedStore.getRecentCasts()).resolves.toEqual({ messages: [cast], nextPageToken: undefined });
+  });
+
+  test("returns casts from every fid, newest first", async () => {
+    const timestamp = getFarcasterTime()._unsafeUnwrap();
+    const cast1 = await castAt(timestamp);
+    const cast2 = await castAt(timestamp + 1, Factories.Fid.build());
+    const cast3 = await castAt(timestamp + 2);
+    await feedStore.mergeMessages([cast1, cast2, cast3]);
+
+    await expect(feedStore.getRecentCasts()).resolves.toEqual({
+      messages: [cast3, cast2, cast1],
+      nextPageToken: undefined,
+    });
+    await expect(feedStore.getRecentCasts({ reverse: true })).resolves.toEqual({
+      messages: [cast1, cast2, cast3],
+      nextPageToken: undefined,
+    });
+
+    const results1 = await feedStore.getRecentCasts({ pageSize: 2 });
+    expect(results1.messages).toEqual([cast3, cast2]);
+    const results2 = await feedStore.getRecentCasts({ pageToken: results1.nextPageToken });
+    expect(results2).toEqual({ messages: [cast1], nextPageToken: undefined });
+  });
+
+  test("only returns casts in the time window", async () => {
+    const timestamp = getFarcasterTime()._unsafeUnwrap();
+    const cast1 = await castAt(timestamp);
+    const cast2 = await castAt(timestamp + 10);
+    const cast3 = await castAt(timestamp + 20);
+    await feedStore.mergeMessages([cast1, cast2, cast3]);
+
+    await expect(feedStore.getRecentCasts({}, timestamp + 10)).resolves.toEqual({
+      messages: [cast3, cast2],
+      nextPageToken: undefined,
+    });
+    await expect(feedStore.getRecentCasts({}, timestamp + 5, timestamp + 15)).resolves.toEqual({
+      messages: [cast2],
+      nextPageToken: undefined,
+    });
+    await expect(feedStore.getRecentCasts({}, undefined, timestamp + 10)).resolves.toEqual({
+      messages: [cast2, cast1],
+      nextPageToken: undefined,
+    });
+  });
+
+  test("drops casts that are removed or revoked", async () => {
+    const timestamp = getFarcasterTime()._unsafeUnwrap();
+    const cast1 = await castAt(timestamp);
+    const cast2 = await castAt(timestamp + 1);
+    const remove = await Factories.CastRemoveMessage.create({
+      data: { fid, timestamp: timestamp + 2, castRemoveBody: { targetHash: cast1.hash } },
+    });
+    await feedStore.mergeMessages([cast1, cast2]);
+    await feedStore.merge(remove);
+    await feedStore.revoke(cast2);
+
+    await expect(feedStore.getRecentCasts()).resolves.toEqual({ messages: [], nextPageToken: undefined });
+
+    const feedKeys: Buffer[] = [];
+    await db.forEachIteratorByPrefix(Buffer.from([RootPrefix.CastsByTime]), (key) => {
+      feedKeys.push(key as Buffer);
+    });
+    expect(feedKeys).toEqual([]);
+  });
+
+  test("returns casts in a channel in the time window, without the feed index", async () => {
+    const timestamp = getFarcasterTime()._unsafeUnwrap();
+    const parentUrl = faker.internet.url();
+    const inChannel = async (url: string, castTimestamp: number) => {
+      return Factories.CastAddMessage.create({
+        data: { fid, timestamp: castTimestamp, castAddBody: { parentCastId: undefined, parentUrl: url } },
+      });
+    };
+    const cast1 = await inChannel(parentUrl, timestamp);
+    const cast2 = await inChannel(parentUrl, timestamp + 10);
+    // Casts in a channel whose url starts with this one's aren't in it
+    const other = await inChannel(`${parentUrl}/other`, timestamp + 5);
+    await store.mergeMessages([cast1, cast2, other]);
+
+    await expect(store.getRecentCastsByParentUrl(parentUrl)).resolves.toEqual({
+      messages: [cast2, cast1],
+      nextPageToken: undefined,
+    });
+    await expect(store.getRecentCastsByParentUrl(parentUrl, {}, timestamp + 1)).resolves.toEqual({
+      messages: [cast2],
+      nextPageToken: undefined,
+    });
+    await expect(store.getRecentCastsByParentUrl(parentUrl, { reverse: true }, timestamp, timestamp)).resolves.toEqual({
+      messages: [cast1],
+      nextPageToken: undefined,
+    });
+  });
+});
+
 describe("revoke", () => {
   let revokedMessages: Message[] = [];
 
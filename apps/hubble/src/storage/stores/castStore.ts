@@ -2,7 +2,7 @@ import { CastAddMessage, CastId, CastRemoveMessage } from "@farcaster/hub-nodejs
 import { ResultAsync } from "neverthrow";
 import RocksDB from "../db/rocksdb.js";
 import { UserPostfix } from "../db/types.js";
-import { MessagesPage, PageOptions, StorePruneOptions } from "../stores/types.js";
+import { CastStoreOptions, CastThread, MessagesPage, PageOptions } from "../stores/types.js";
 import { RustStoreBase } from "./rustStoreBase.js";
 import StoreEventHandler from "./storeEventHandler.js";
 import {
@@ -11,16 +11,28 @@ import {
   rsGetCastAddsByFid,
   rsGetCastRemove,
   rsGetCastRemovesByFid,
+  rsGetCastsByEmbedUrl,
   rsGetCastsByMention,
   rsGetCastsByParent,
+  rsGetCastThread,
+  rsGetQuoteCasts,
+  rsGetRecentCasts,
+  rsGetRecentCastsByParentUrl,
+  rsGetReplyCount,
+  rsSearchCasts,
   rustErrorToHubError,
 } from "../../rustfunctions.js";
 import { messageDecode } from "../../storage/db/message.js";
 
 class CastStore extends RustStoreBase<CastAddMessage, CastRemoveMessage> {
-  constructor(db: RocksDB, eventHandler: StoreEventHandler, options: StorePruneOptions = {}) {
+  constructor(db: RocksDB, eventHandler: StoreEventHandler, options: CastStoreOptions = {}) {
     const pruneSizeLimit = options.pruneSizeLimit ?? 0;
-    const rustCastStore = rsCreateCastStore(db.rustDb, eventHandler.getRustStoreEventHandler(), pruneSizeLimit);
+    const rustCastStore = rsCreateCastStore(
+      db.rustDb,
+      eventHandler.getRustStoreEventHandler(),
+      pruneSizeLimit,
+      { searchIndex: options.searchIndex ?? false, feedIndex: options.feedIndex ?? false },
+    );
 
     super(db, rustCastStore, UserPostfix.CastMessage, eventHandler, pruneSizeLimit);
   }
@@ -46,8 +58,13 @@ class CastStore extends RustStoreBase<CastAddMessage, CastRemoveMessage> {
   }
 
   /** Gets all CastAdd messages for an fid */
-  async getCastAddsByFid(fid: number, pageOptions: PageOptions = {}): Promise<MessagesPage<CastAddMessage>> {
-    const messages_page = await rsGetCastAddsByFid(this._rustStore, fid, pageOptions ?? {});
+  async getCastAddsByFid(
+    fid: number,
+    pageOptions: PageOptions = {},
+    startTime?: number,
+    stopTime?: number,
+  ): Promise<MessagesPage<CastAddMessage>> {
+    const messages_page = await rsGetCastAddsByFid(this._rustStore, fid, pageOptions ?? {}, startTime, stopTime);
 
     const messages =
       messages_page.messageBytes?.map((message_bytes) => {
@@ -58,8 +75,13 @@ class CastStore extends RustStoreBase<CastAddMessage, CastRemoveMessage> {
   }
 
   /** Gets all CastRemove messages for an fid */
-  async getCastRemovesByFid(fid: number, pageOptions: PageOptions = {}): Promise<MessagesPage<CastRemoveMessage>> {
-    const message_page = await rsGetCastRemovesByFid(this._rustStore, fid, pageOptions ?? {});
+  async getCastRemovesByFid(
+    fid: number,
+    pageOptions: PageOptions = {},
+    startTime?: number,
+    stopTime?: number,
+  ): Promise<MessagesPage<CastRemoveMessage>> {
+    const message_page = await rsGetCastRemovesByFid(this._rustStore, fid, pageOptions ?? {}, startTime, stopTime);
 
     const messages =
       message_page.messageBytes?.map((message_bytes) => {
@@ -102,6 +124,42 @@ class CastStore extends RustStoreBase<CastAddMessage, CastRemoveMessage> {
     return { messages, nextPageToken: message_page.nextPageToken };
   }
 
+  /**
+   * Gets the thread a cast is in, starting from its root cast, in tree order. The root is found by following
+   * parentCastId up from the cast, so any cast in the thread can be passed in. A truncated thread has a
+   * nextPageToken, which continues the thread after the last returned cast.
+   */
+  async getCastThread(castId: CastId, maxDepth = 10, maxNodes = 100, pageToken?: Uint8Array): Promise<CastThread> {
+    const castIdBytes = Buffer.from(CastId.encode(castId).finish());
+    const thread = await ResultAsync.fromPromise(
+      rsGetCastThread(this._rustStore, castIdBytes, maxDepth, maxNodes, pageToken),
+      rustErrorToHubError,
+    );
+    if (thread.isErr()) {
+      throw thread.error;
+    }
+
+    const messages = thread.value.messageBytes.map((message_bytes, i) => {
+      return {
+        message: messageDecode(new Uint8Array(message_bytes)) as CastAddMessage,
+        depth: thread.value.depths[i] as number,
+      };
+    });
+
+    const nextPageToken = thread.value.nextPageToken ? new Uint8Array(thread.value.nextPageToken) : undefined;
+    return { messages, truncated: thread.value.truncated, nextPageToken };
+  }
+
+  /** Gets the number of CastAdd messages that reply to a cast */
+  async getReplyCount(castId: CastId): Promise<number> {
+    const castIdBytes = Buffer.from(CastId.encode(castId).finish());
+    const result = await ResultAsync.fromPromise(rsGetReplyCount(this._rustStore, castIdBytes), rustErrorToHubError);
+    if (result.isErr()) {
+      throw result.error;
+    }
+    return result.value;
+  }
+
   /** Gets all CastAdd messages for a mention (fid) */
   async getCastsByMention(mentionFid: number, pageOptions: PageOptions = {}): Promise<MessagesPage<CastAddMessage>> {
     const message_page = await rsGetCastsByMention(this._rustStore, mentionFid, pageOptions ?? {});
@@ -113,6 +171,108 @@ class CastStore extends RustStoreBase<CastAddMessage, CastRemoveMessage> {
 
     return { messages, nextPageToken: message_page.nextPageToken };
   }
+
+  /**
+   * Gets all CastAdd messages that embed a url. Urls are normalized before they're indexed and looked up, so the
+   * scheme and host are case insensitive and default ports, trailing slashes and fragments are ignored.
+   */
+  async getCastsByEmbedUrl(url: string, pageOptions: PageOptions = {}): Promise<MessagesPage<CastAddMessage>> {
+    const message_page = await rsGetCastsByEmbedUrl(this._rustStore, url, pageOptions);
+
+    const messages =
+      message_page.messageBytes?.map((message_bytes) => {
+        return messageDecode(new Uint8Array(message_bytes)) as CastAddMessage;
+      }) ?? [];
+
+    return { messages, nextPageToken: message_page.nextPageToken };
+  }
+
+  /** Gets all CastAdd messages that quote a cast, i.e. embed its CastId */
+  async getQuoteCasts(castId: CastId, pageOptions: PageOptions = {}): Promise<MessagesPage<CastAddMessage>> {
+    const castIdBytes = Buffer.from(CastId.encode(castId).finish());
+    const message_page = await rsGetQuoteCasts(this._rustStore, castIdBytes, pageOptions);
+
+    const messages =
+      message_page.messageBytes?.map((message_bytes) => {
+        return messageDecode(new Uint8Array(message_bytes)) as CastAddMessage;
+      }) ?? [];
+
+    return { messages, nextPageToken: message_page.nextPageToken };
+  }
+
+  /**
+   * Gets CastAdd messages from every fid, newest first, optionally only those with farcaster timestamps in
+   * [startTime, stopTime]. Requires the store to be created with feedIndex, and fails with unavailable until
+   * backfillOptionalIndices has run.
+   */
+  async getRecentCasts(
+    pageOptions: PageOptions = {},
+    startTime?: number,
+    stopTime?: number,
+  ): Promise<MessagesPage<CastAddMessage>> {
+    const result = await ResultAsync.fromPromise(
+      rsGetRecentCasts(this._rustStore, pageOptions, startTime, stopTime),
+      rustErrorToHubError,
+    );
+    if (result.isErr()) {
+      throw result.error;
+    }
+
+    const messages =
+      result.value.messageBytes?.map((message_bytes) => {
+        return messageDecode(new Uint8Array(message_bytes)) as CastAddMessage;
+      }) ?? [];
+
+    return { messages, nextPageToken: result.value.nextPageToken };
+  }
+
+  /**
+   * Gets CastAdd messages in a channel, i.e. replies to a parent url, newest first, optionally only those with
+   * farcaster timestamps in [startTime, stopTime]. These don't need the feed index.
+   */
+  async getRecentCastsByParentUrl(
+    parentUrl: string,
+    pageOptions: PageOptions = {},
+    startTime?: number,
+    stopTime?: number,
+  ): Promise<MessagesPage<CastAddMessage>> {
+    const message_page = await rsGetRecentCastsByParentUrl(
+      this._rustStore,
+      parentUrl,
+      pageOptions,
+      startTime,
+      stopTime,
+    );
+
+    const messages =
+      message_page.messageBytes?.map((message_bytes) => {
+        return messageDecode(new Uint8Array(message_bytes)) as CastAddMessage;
+      }) ?? [];
+
+    return { messages, nextPageToken: message_page.nextPageToken };
+  }
+
+  /**
+   * Gets CastAdd messages whose text matches a query, newest first. Words are ANDed together, "quoted words" must
+   * appear as a phrase and @fid matches casts that mention fid. Requires the store to be created with searchIndex,
+   * and fails with unavailable until backfillOptionalIndices has run.
+   */
+  async searchCasts(query: string, pageOptions: PageOptions = {}): Promise<MessagesPage<CastAddMessage>> {
+    const result = await ResultAsync.fromPromise(
+      rsSearchCasts(this._rustStore, query, pageOptions),
+      rustErrorToHubError,
+    );
+    if (result.isErr()) {
+      throw result.error;
+    }
+
+    const messages =
+      result.value.messageBytes?.map((message_bytes) => {
+        return messageDecode(new Uint8Array(message_bytes)) as CastAddMessage;
+      }) ?? [];
+
+    return { messages, nextPageToken: result.value.nextPageToken };
+  }
 }
 
 export default CastStore;
@@ -13,7 +13,7 @@ import {
   PruneMessageHubEvent,
   RevokeMessageHubEvent,
 } from "@farcaster/hub-nodejs";
-import { err, ok } from "neverthrow";
+import { err, ok, ResultAsync } from "neverthrow";
 import { jestRocksDB } from "../db/jestUtils.js";
 import {
   getMessage,
@@ -23,8 +23,9 @@ import {
   makeUserKey,
   putMessageTransaction,
 } from "../db/message.js";
-import { UserPostfix } from "../db/types.js";
+import { RootPrefix, UserPostfix } from "../db/types.js";
 import LinkStore from "./linkStore.js";
+import { MergeOutcome } from "./types.js";
 import StoreEventHandler from "./storeEventHandler.js";
 import { putOnChainEventTransaction } from "../db/onChainEvent.js";
 
@@ -309,6 +310,42 @@ describe("getLinksByTarget", () => {
   });
 });
 
+describe("getFollowerCount", () => {
+  test("returns 0 if there are no links", async () => {
+    await expect(set.getFollowerCount(targetFid)).resolves.toEqual(0);
+    await expect(set.getFollowingCount(fid)).resolves.toEqual(0);
+  });
+
+  test("counts links by type as they are merged and removed", async () => {
+    await set.merge(linkAdd);
+    await set.merge(linkAddEndorse);
+    await expect(set.getFollowerCount(targetFid)).resolves.toEqual(1);
+    await expect(set.getFollowingCount(fid)).resolves.toEqual(1);
+    await expect(set.getFollowerCount(targetFid, "endorse")).resolves.toEqual(1);
+    await expect(set.getFollowerCount(fid)).resolves.toEqual(0);
+
+    await set.merge(linkRemove);
+    await expect(set.getFollowerCount(targetFid)).resolves.toEqual(0);
+    await expect(set.getFollowingCount(fid)).resolves.toEqual(0);
+    await expect(set.getFollowingCount(fid, "endorse")).resolves.toEqual(1);
+  });
+
+  test("rebuildCounters recounts links", async () => {
+    await set.merge(linkAdd);
+    const counterKeys: Buffer[] = [];
+    await db.forEachIteratorByPrefix(Buffer.from([RootPrefix.AggregateCounts]), (key) => {
+      counterKeys.push(key as Buffer);
+    });
+    for (const key of counterKeys) {
+      await db.put(key, Buffer.alloc(8, 0xff));
+    }
+
+    await expect(set.rebuildCounters()).resolves.toEqual(ok(1));
+    await expect(set.getFollowerCount(targetFid)).resolves.toEqual(1);
+    await expect(set.getFollowingCount(fid)).resolves.toEqual(1);
+  });
+});
+
 describe("merge", () => {
   let mergeEvents: [Message | undefined, Message[]][] = [];
 
@@ -392,6 +429,15 @@ describe("merge", () => {
       expect(mergeEvents).toEqual([[linkAdd, []]]);
     });
 
+    test("fails with the fid and hash of the message if merged twice", async () => {
+      await expect(set.merge(linkAdd)).resolves.toBeGreaterThan(0);
+
+      const result = await ResultAsync.fromPromise(set.merge(linkAdd), (e) => e as HubError);
+      const error = result._unsafeUnwrapErr();
+      expect(error.errCode).toEqual("bad_request.duplicate");
+      expect(error.cause).toMatchObject({ fid, hash: Buffer.from(linkAdd.hash) });
+    });
+
     describe("with a conflicting LinkAdd with different timestamps", () => {
       let linkAddLater: LinkAddMessage;
 
@@ -817,6 +863,56 @@ describe("merge", () => {
   });
 });
 
+describe("validateMerge", () => {
+  test("would merge a new message without merging it", async () => {
+    const result = await set.validateMerge(linkAdd);
+    expect(result._unsafeUnwrap()).toEqual({ outcome: MergeOutcome.MERGE, deletedMessages: [] });
+    await expect(set.getLinkAdd(fid, linkAdd.data.linkBody.type, targetFid)).rejects.toThrow(HubError);
+  });
+
+  test("lists the messages a merge would delete", async () => {
+    await set.merge(linkAdd);
+
+    const result = await set.validateMerge(linkRemove);
+    expect(result._unsafeUnwrap()).toEqual({ outcome: MergeOutcome.MERGE, deletedMessages: [linkAdd] });
+    await expect(set.getLinkAdd(fid, linkAdd.data.linkBody.type, targetFid)).resolves.toEqual(linkAdd);
+  });
+
+  test("reports duplicates", async () => {
+    await set.merge(linkAdd);
+
+    const result = await set.validateMerge(linkAdd);
+    expect(result._unsafeUnwrap()).toEqual({ outcome: MergeOutcome.DUPLICATE, deletedMessages: [] });
+  });
+
+  test("reports the message a conflicting message loses to", async () => {
+    await set.merge(linkRemove);
+
+    const result = await set.validateMerge(linkAdd);
+    expect(result._unsafeUnwrap()).toEqual({
+      outcome: MergeOutcome.CONFLICT,
+      reason: "message conflicts with a more recent remove",
+      winner: linkRemove,
+      deletedMessages: [],
+    });
+  });
+
+  test("reports the compact state message a link loses to", async () => {
+    const linkCompactState = await Factories.LinkCompactStateMessage.create({
+      data: { fid, linkCompactStateBody: { targetFids: [] }, timestamp: linkAdd.data.timestamp + 1 },
+    });
+    await set.merge(linkCompactState);
+
+    const result = await set.validateMerge(linkAdd);
+    expect(result._unsafeUnwrap()).toEqual({
+      outcome: MergeOutcome.CONFLICT,
+      reason: "Target not in the compact state targets",
+      winner: linkCompactState,
+      deletedMessages: [],
+    });
+  });
+});
+
 describe("revoke", () => {
   let revokedMessages: Message[] = [];
 
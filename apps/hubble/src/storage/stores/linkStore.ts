@@ -1,7 +1,12 @@
-import { LinkAddMessage, LinkCompactStateMessage, LinkRemoveMessage } from "@farcaster/hub-nodejs";
+import {
+  LinkAddMessage,
+  LinkCompactStateBody,
+  LinkCompactStateMessage,
+  LinkRemoveMessage,
+} from "@farcaster/hub-nodejs";
 import { makeFidKey, messageDecode } from "../../storage/db/message.js";
 import { UserPostfix } from "../db/types.js";
-import { MessagesPage, PageOptions, StorePruneOptions } from "./types.js";
+import { LinkCompactStateCandidate, MessagesPage, PageOptions, StorePruneOptions } from "./types.js";
 import { ResultAsync } from "neverthrow";
 import RocksDB from "../db/rocksdb.js";
 import { rsGetAllMessagesByFid, rsLinkStore, rustErrorToHubError } from "../../rustfunctions.js";
@@ -73,8 +78,17 @@ class LinkStore extends RustStoreBase<LinkAddMessage, LinkRemoveMessage> {
     fid: number,
     type?: string,
     pageOptions: PageOptions = {},
+    startTime?: number,
+    stopTime?: number,
   ): Promise<MessagesPage<LinkAddMessage>> {
-    const messages_page = await rsLinkStore.GetLinkAddsByFid(this._rustStore, fid, type ?? "", pageOptions);
+    const messages_page = await rsLinkStore.GetLinkAddsByFid(
+      this._rustStore,
+      fid,
+      type ?? "",
+      pageOptions,
+      startTime,
+      stopTime,
+    );
 
     const messages =
       messages_page.messageBytes?.map((message_bytes) => {
@@ -89,8 +103,17 @@ class LinkStore extends RustStoreBase<LinkAddMessage, LinkRemoveMessage> {
     fid: number,
     type?: string,
     pageOptions: PageOptions = {},
+    startTime?: number,
+    stopTime?: number,
   ): Promise<MessagesPage<LinkRemoveMessage>> {
-    const messages_page = await rsLinkStore.GetLinkRemovesByFid(this._rustStore, fid, type ?? "", pageOptions);
+    const messages_page = await rsLinkStore.GetLinkRemovesByFid(
+      this._rustStore,
+      fid,
+      type ?? "",
+      pageOptions,
+      startTime,
+      stopTime,
+    );
 
     const messages =
       messages_page.messageBytes?.map((message_bytes) => {
@@ -116,6 +139,30 @@ class LinkStore extends RustStoreBase<LinkAddMessage, LinkRemoveMessage> {
     return { messages, nextPageToken: messages_page.nextPageToken };
   }
 
+  /** Gets the number of links of a type that target a fid, e.g. its followers */
+  async getFollowerCount(fid: number, type = "follow"): Promise<number> {
+    const result = await ResultAsync.fromPromise(
+      rsLinkStore.GetFollowerCount(this._rustStore, fid, type),
+      rustErrorToHubError,
+    );
+    if (result.isErr()) {
+      throw result.error;
+    }
+    return result.value;
+  }
+
+  /** Gets the number of links of a type that a fid has made, e.g. the fids it follows */
+  async getFollowingCount(fid: number, type = "follow"): Promise<number> {
+    const result = await ResultAsync.fromPromise(
+      rsLinkStore.GetFollowingCount(this._rustStore, fid, type),
+      rustErrorToHubError,
+    );
+    if (result.isErr()) {
+      throw result.error;
+    }
+    return result.value;
+  }
+
   async getAllLinkMessagesByFid(
     fid: number,
     pageOptions: PageOptions = {},
@@ -132,6 +179,27 @@ class LinkStore extends RustStoreBase<LinkAddMessage, LinkRemoveMessage> {
     return { messages, nextPageToken: messages_page.nextPageToken };
   }
 
+  /**
+   * Build the LinkCompactStateBody that keeps exactly the links of a type the fid has now, and the earliest
+   * timestamp it's safe at. Links of other types to fids outside targetFids that are older than the compact
+   * state are still deleted by it, previewCompactStateMerge shows them.
+   */
+  async buildCompactState(fid: number, type: string): Promise<LinkCompactStateCandidate> {
+    const result = await ResultAsync.fromPromise(
+      rsLinkStore.BuildLinkCompactState(this._rustStore, fid, type),
+      rustErrorToHubError,
+    );
+
+    if (result.isErr()) {
+      throw result.error;
+    }
+
+    return {
+      body: LinkCompactStateBody.create({ type: result.value.type, targetFids: result.value.targetFids }),
+      timestamp: result.value.timestamp,
+    };
+  }
+
   async getLinkCompactStateMessageByFid(
     fid: number,
     pageOptions: PageOptions = {},
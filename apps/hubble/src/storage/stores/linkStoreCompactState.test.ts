@@ -613,4 +613,97 @@ describe("Merge LinkCompactState messages", () => {
       expect(allMessages).toContainEqual(linkAdd2);
     });
   });
+
+  describe("buildCompactState", () => {
+    test("builds the current targets of the link type", async () => {
+      const timestamp = getFarcasterTime()._unsafeUnwrap();
+      const follow1 = await Factories.LinkAddMessage.create({
+        data: { fid, linkBody: Factories.LinkBody.build({ type: "follow", targetFid }), timestamp },
+      });
+      const follow2 = await Factories.LinkAddMessage.create({
+        data: { fid, linkBody: Factories.LinkBody.build({ type: "follow", targetFid: targetFid + 1 }), timestamp },
+      });
+      const unfollow2 = await Factories.LinkRemoveMessage.create({
+        data: { fid, linkBody: follow2.data.linkBody, timestamp: timestamp + 3 },
+      });
+      const block = await Factories.LinkAddMessage.create({
+        data: {
+          fid,
+          linkBody: Factories.LinkBody.build({ type: "block", targetFid: targetFid + 2 }),
+          timestamp: timestamp + 5,
+        },
+      });
+      await set.mergeMessages([follow1, follow2, unfollow2, block]);
+
+      const candidate = await set.buildCompactState(fid, "follow");
+      expect(candidate.body).toEqual({ type: "follow", targetFids: [targetFid] });
+      // The unfollow is the newest follow message, the block doesn't count
+      expect(candidate.timestamp).toEqual(timestamp + 3);
+
+      // Merging it doesn't delete any follows
+      const linkCompactState = await Factories.LinkCompactStateMessage.create({
+        data: { fid, linkCompactStateBody: candidate.body, timestamp: candidate.timestamp },
+      });
+      await set.merge(linkCompactState);
+      expect(await set.getLinkAdd(fid, "follow", targetFid)).toEqual(follow1);
+
+      // The next compact state has to be newer than the merged one
+      const next = await set.buildCompactState(fid, "follow");
+      expect(next.timestamp).toEqual(candidate.timestamp + 1);
+    });
+
+    test("builds an empty compact state for a fid without links", async () => {
+      const candidate = await set.buildCompactState(fid, "follow");
+      expect(candidate).toEqual({ body: { type: "follow", targetFids: [] }, timestamp: 0 });
+    });
+
+    test("fails with an invalid link type", async () => {
+      const result = await ResultAsync.fromPromise(set.buildCompactState(fid, ""), (e) => e as HubError);
+      expect(result._unsafeUnwrapErr().errCode).toEqual("bad_request.invalid_param");
+    });
+  });
+
+  describe("previewCompactStateMerge", () => {
+    test("lists the messages merging would delete, without deleting them", async () => {
+      const timestamp = getFarcasterTime()._unsafeUnwrap();
+      const linkAdd1 = await Factories.LinkAddMessage.create({
+        data: { fid, linkBody: Factories.LinkBody.build({ type: "follow", targetFid }), timestamp },
+      });
+      const linkAdd2 = await Factories.LinkAddMessage.create({
+        data: { fid, linkBody: Factories.LinkBody.build({ type: "follow", targetFid: targetFid + 1 }), timestamp },
+      });
+      const linkRemove3 = await Factories.LinkRemoveMessage.create({
+        data: { fid, linkBody: Factories.LinkBody.build({ type: "follow", targetFid: targetFid + 2 }), timestamp },
+      });
+      await set.mergeMessages([linkAdd1, linkAdd2, linkRemove3]);
+
+      const previous = await Factories.LinkCompactStateMessage.create({
+        data: { fid, linkCompactStateBody: { targetFids: [targetFid, targetFid + 1] }, timestamp: timestamp + 1 },
+      });
+      await set.merge(previous);
+
+      const linkCompactState = await Factories.LinkCompactStateMessage.create({
+        data: { fid, linkCompactStateBody: { targetFids: [targetFid] }, timestamp: timestamp + 2 },
+      });
+      const preview = (await set.previewCompactStateMerge(linkCompactState))._unsafeUnwrap();
+      expect(preview.length).toEqual(3);
+      expect(preview).toContainEqual(previous);
+      expect(preview).toContainEqual(linkAdd2);
+      expect(preview).toContainEqual(linkRemove3);
+
+      // Nothing was deleted
+      expect((await set.getAllLinkMessagesByFid(fid)).messages.length).toEqual(3);
+
+      // Older compact states can't be merged, so they can't be previewed either
+      const older = await Factories.LinkCompactStateMessage.create({
+        data: { fid, linkCompactStateBody: { targetFids: [] }, timestamp },
+      });
+      const olderPreview = await set.previewCompactStateMerge(older);
+      expect(olderPreview._unsafeUnwrapErr().errCode).toEqual("bad_request.conflict");
+
+      // Only compact state messages can be previewed
+      const notCompactState = await set.previewCompactStateMerge(linkAdd1);
+      expect(notCompactState._unsafeUnwrapErr().errCode).toEqual("bad_request.validation_failure");
+    });
+  });
 });
@@ -0,0 +1,162 @@
+import { Factories, HubError, Message, MessageType } from "@farcaster/hub-nodejs";
+import { ResultAsync } from "neverthrow";
+import { jestRocksDB } from "../db/jestUtils.js";
+import { putOnChainEventTransaction } from "../db/onChainEvent.js";
+import {
+  revoke,
+  rsGetCastThread,
+  rsGetMessage,
+  rsGetReplyCount,
+  rsMerge,
+  rsMergeMany,
+  rsPreviewCompactStateMerge,
+  rsValidateMerge,
+  rustErrorToHubError,
+} from "../../rustfunctions.js";
+import { UserPostfix } from "../db/types.js";
+import CastStore from "./castStore.js";
+import LinkStore from "./linkStore.js";
+import ReactionStore from "./reactionStore.js";
+import StoreEventHandler from "./storeEventHandler.js";
+import UserDataStore from "./userDataStore.js";
+import UsernameProofStore from "./usernameProofStore.js";
+import VerificationStore from "./verificationStore.js";
+
+const db = jestRocksDB("protobufs.malformedMessages.test");
+const eventHandler = new StoreEventHandler(db);
+const fid = Factories.Fid.build();
+
+const stores = {
+  cast: { store: new CastStore(db, eventHandler), build: () => Factories.CastAddMessage.create({ data: { fid } }) },
+  link: { store: new LinkStore(db, eventHandler), build: () => Factories.LinkAddMessage.create({ data: { fid } }) },
+  reaction: {
+    store: new ReactionStore(db, eventHandler),
+    build: () => Factories.ReactionAddMessage.create({ data: { fid } }),
+  },
+  verification: {
+    store: new VerificationStore(db, eventHandler),
+    build: () => Factories.VerificationAddEthAddressMessage.create({ data: { fid } }),
+  },
+  userData: {
+    store: new UserDataStore(db, eventHandler),
+    build: () => Factories.UserDataAddMessage.create({ data: { fid } }),
+  },
+  usernameProof: {
+    store: new UsernameProofStore(db, eventHandler),
+    build: () => Factories.UsernameProofMessage.create({ data: { fid } }),
+  },
+};
+
+/** Messages that fail to decode, or decode into something no store can merge */
+const malformedMessages = async (valid: Message): Promise<[string, Uint8Array][]> => {
+  const data = valid.data as NonNullable<Message["data"]>;
+  // A body from another store, so the type and the body don't match
+  const otherBody =
+    data.type === MessageType.CAST_ADD
+      ? { castAddBody: undefined, linkBody: Factories.LinkBody.build() }
+      : { castAddBody: Factories.CastAddBody.build() };
+
+  return [
+    ["garbage bytes", new Uint8Array([0xff, 0xff, 0xff, 0xff])],
+    ["empty bytes", new Uint8Array()],
+    ["no data", Message.encode({ ...valid, data: undefined }).finish()],
+    ["unknown type", Message.encode({ ...valid, data: { ...data, type: 99 as MessageType } }).finish()],
+    ["mismatched body", Message.encode({ ...valid, data: { ...data, ...otherBody } }).finish()],
+    ["short hash", Message.encode({ ...valid, hash: new Uint8Array([1, 2, 3]) }).finish()],
+  ];
+};
+
+const expectHubError = async (promise: Promise<unknown>): Promise<HubError> => {
+  const result = await ResultAsync.fromPromise(promise, rustErrorToHubError);
+  expect(result.isErr()).toBe(true);
+
+  const error = result._unsafeUnwrapErr();
+  expect(error).toBeInstanceOf(HubError);
+  return error;
+};
+
+beforeAll(async () => {
+  const rent = Factories.StorageRentOnChainEvent.build({ fid }, { transient: { units: 1 } });
+  await db.commit(putOnChainEventTransaction(db.transaction(), rent));
+});
+
+beforeEach(async () => {
+  await eventHandler.syncCache();
+});
+
+describe.each(Object.entries(stores))("malformed messages in the %s store", (_name, { store, build }) => {
+  test("are rejected by every export that takes a message", async () => {
+    for (const [, bytes] of await malformedMessages(await build())) {
+      const rustStore = store.rustStore;
+
+      await expectHubError(rsMerge(rustStore, bytes));
+      await expectHubError(revoke(rustStore, bytes));
+      await expectHubError(rsValidateMerge(rustStore, bytes));
+      await expectHubError(rsPreviewCompactStateMerge(rustStore, bytes));
+
+      // mergeMany fails each malformed message on its own
+      const results = await rsMergeMany(rustStore, [bytes, bytes]);
+      expect(results.size).toEqual(2);
+      for (const result of results.values()) {
+        expect(result.isErr()).toBe(true);
+        expect(result._unsafeUnwrapErr()).toBeInstanceOf(HubError);
+      }
+    }
+  });
+
+  test("fail with a validation failure when they can't be decoded", async () => {
+    const bytes = new Uint8Array([0xff, 0xff, 0xff, 0xff]);
+    const rustStore = store.rustStore;
+
+    expect((await expectHubError(rsMerge(rustStore, bytes))).errCode).toEqual("bad_request.validation_failure");
+    expect((await expectHubError(revoke(rustStore, bytes))).errCode).toEqual("bad_request.validation_failure");
+    expect((await expectHubError(rsValidateMerge(rustStore, bytes))).errCode).toEqual(
+      "bad_request.validation_failure",
+    );
+  });
+
+  test("fail without data instead of panicking", async () => {
+    const valid = await build();
+    const bytes = Message.encode({ ...valid, data: undefined }).finish();
+
+    expect((await expectHubError(rsMerge(store.rustStore, bytes))).errCode).toEqual("bad_request.validation_failure");
+    expect((await expectHubError(revoke(store.rustStore, bytes))).errCode).toEqual("bad_request.invalid_param");
+  });
+
+  test("don't break the store for valid messages", async () => {
+    const valid = await build();
+    const malformed = await malformedMessages(valid);
+
+    // Interleave the valid message with the malformed ones, so they're merged in the same batch
+    const results = await rsMergeMany(store.rustStore, [
+      ...malformed.map(([, bytes]) => bytes),
+      Message.encode(valid).finish(),
+    ]);
+    expect(results.get(malformed.length)?.isOk()).toBe(true);
+
+    const duplicate = await ResultAsync.fromPromise(store.merge(valid), (e) => e as HubError);
+    expect(duplicate._unsafeUnwrapErr().errCode).toEqual("bad_request.duplicate");
+  });
+});
+
+describe("malformed arguments", () => {
+  const castStore = stores.cast.store;
+
+  test("are rejected by getters that take message bytes", async () => {
+    const garbage = Buffer.from([0xff, 0xff, 0xff, 0xff]);
+
+    await expectHubError(rsGetCastThread(castStore.rustStore, garbage, 1, 1));
+    await expectHubError(rsGetReplyCount(castStore.rustStore, garbage));
+    await expectHubError(rsGetMessage(castStore.rustStore, fid, UserPostfix.CastMessage, new Uint8Array([1, 2, 3])));
+  });
+
+  test("are rejected by merge instead of throwing synchronously", async () => {
+    const merge = rsMerge(castStore.rustStore, "not a buffer" as unknown as Uint8Array);
+    await expectHubError(merge);
+
+    const results = rsMergeMany(castStore.rustStore, ["not a buffer", "nor this"] as unknown as Uint8Array[]);
+    for (const result of (await results).values()) {
+      expect(result.isErr()).toBe(true);
+    }
+  });
+});
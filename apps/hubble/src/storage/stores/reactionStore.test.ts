@@ -352,6 +352,38 @@ describe("getReactionsByTarget", () => {
   });
 });
 
+describe("getReactionCount", () => {
+  test("returns 0 if there are no reactions", async () => {
+    await expect(set.getReactionCount(castId)).resolves.toEqual(0);
+  });
+
+  test("counts reactions by type as they are merged and removed", async () => {
+    await set.merge(reactionAdd);
+    await set.merge(reactionAddRecast);
+    await expect(set.getReactionCount(castId, ReactionType.LIKE)).resolves.toEqual(1);
+    await expect(set.getReactionCount(castId, ReactionType.RECAST)).resolves.toEqual(1);
+    await expect(set.getReactionCount(castId)).resolves.toEqual(2);
+
+    await set.merge(reactionRemove);
+    await expect(set.getReactionCount(castId, ReactionType.LIKE)).resolves.toEqual(0);
+    await expect(set.getReactionCount(castId)).resolves.toEqual(1);
+
+    await set.revoke(reactionAddRecast);
+    await expect(set.getReactionCount(castId)).resolves.toEqual(0);
+  });
+
+  test("counts reactions to a target url", async () => {
+    const targetUrl = faker.internet.url();
+    const reaction = await Factories.ReactionAddMessage.create({
+      data: { reactionBody: { type: ReactionType.LIKE, targetCastId: undefined, targetUrl } },
+    });
+    await set.merge(reaction);
+
+    await expect(set.getReactionCount(targetUrl, ReactionType.LIKE)).resolves.toEqual(1);
+    await expect(set.getReactionCount(castId, ReactionType.LIKE)).resolves.toEqual(0);
+  });
+});
+
 describe("merge", () => {
   let mergeEvents: [Message | undefined, Message[]][] = [];
 
@@ -1,7 +1,15 @@
-import { CastId, ReactionAddMessage, ReactionRemoveMessage, ReactionType } from "@farcaster/hub-nodejs";
+import {
+  CastId,
+  ReactionAddMessage,
+  ReactionCompactStateMessage,
+  ReactionRemoveMessage,
+  ReactionType,
+} from "@farcaster/hub-nodejs";
 import {
   rsCreateReactionStore,
   rsGetReactionAdd,
+  rsGetReactionCompactStateMessageByFid,
+  rsGetReactionCount,
   rsGetReactionAddsByFid,
   rsGetReactionRemove,
   rsGetReactionRemovesByFid,
@@ -68,8 +76,17 @@ class ReactionStore extends RustStoreBase<ReactionAddMessage, ReactionRemoveMess
     fid: number,
     type?: ReactionType,
     pageOptions?: PageOptions,
+    startTime?: number,
+    stopTime?: number,
   ): Promise<MessagesPage<ReactionAddMessage>> {
-    const messages_page = await rsGetReactionAddsByFid(this._rustStore, fid, type ?? 0, pageOptions ?? {});
+    const messages_page = await rsGetReactionAddsByFid(
+      this._rustStore,
+      fid,
+      type ?? 0,
+      pageOptions ?? {},
+      startTime,
+      stopTime,
+    );
 
     const messages =
       messages_page.messageBytes?.map((message_bytes) => {
@@ -83,8 +100,17 @@ class ReactionStore extends RustStoreBase<ReactionAddMessage, ReactionRemoveMess
     fid: number,
     type?: ReactionType,
     pageOptions?: PageOptions,
+    startTime?: number,
+    stopTime?: number,
   ): Promise<MessagesPage<ReactionRemoveMessage>> {
-    const message_page = await rsGetReactionRemovesByFid(this._rustStore, fid, type ?? 0, pageOptions ?? {});
+    const message_page = await rsGetReactionRemovesByFid(
+      this._rustStore,
+      fid,
+      type ?? 0,
+      pageOptions ?? {},
+      startTime,
+      stopTime,
+    );
 
     const messages =
       message_page.messageBytes?.map((message_bytes) => {
@@ -94,6 +120,20 @@ class ReactionStore extends RustStoreBase<ReactionAddMessage, ReactionRemoveMess
     return { messages, nextPageToken: message_page.nextPageToken };
   }
 
+  async getReactionCompactStateMessageByFid(
+    fid: number,
+    pageOptions: PageOptions = {},
+  ): Promise<MessagesPage<ReactionCompactStateMessage>> {
+    const message_page = await rsGetReactionCompactStateMessageByFid(this._rustStore, fid, pageOptions);
+
+    const messages =
+      message_page.messageBytes?.map((message_bytes) => {
+        return messageDecode(new Uint8Array(message_bytes)) as ReactionCompactStateMessage;
+      }) ?? [];
+
+    return { messages, nextPageToken: message_page.nextPageToken };
+  }
+
   async getAllReactionMessagesByFid(
     fid: number,
     pageOptions: PageOptions = {},
@@ -132,6 +172,27 @@ class ReactionStore extends RustStoreBase<ReactionAddMessage, ReactionRemoveMess
 
     return { messages, nextPageToken: message_page.nextPageToken };
   }
+
+  /** Gets the number of reactions to a cast or url. Without a type, likes and recasts are both counted */
+  async getReactionCount(target: CastId | string, type?: ReactionType): Promise<number> {
+    let targetCastId = Buffer.from([]);
+    let targetUrl = "";
+
+    if (typeof target === "string") {
+      targetUrl = target;
+    } else {
+      targetCastId = Buffer.from(CastId.encode(target).finish());
+    }
+
+    const result = await ResultAsync.fromPromise(
+      rsGetReactionCount(this._rustStore, targetCastId, targetUrl, type ?? ReactionType.NONE),
+      rustErrorToHubError,
+    );
+    if (result.isErr()) {
+      throw result.error;
+    }
+    return result.value;
+  }
 }
 
 export default ReactionStore;
@@ -0,0 +1,153 @@
+import { Factories, getFarcasterTime, HubError, HubEvent, ReactionType } from "@farcaster/hub-nodejs";
+import { ResultAsync } from "neverthrow";
+import { jestRocksDB } from "../db/jestUtils.js";
+import ReactionStore from "./reactionStore.js";
+import StoreEventHandler from "./storeEventHandler.js";
+import { putOnChainEventTransaction } from "../db/onChainEvent.js";
+
+const db = jestRocksDB("protobufs.reactionStoreCompactState.test");
+const eventHandler = new StoreEventHandler(db);
+const set = new ReactionStore(db, eventHandler);
+const fid = Factories.Fid.build();
+const castId1 = Factories.CastId.build();
+const castId2 = Factories.CastId.build();
+
+beforeAll(async () => {
+  const rent = Factories.StorageRentOnChainEvent.build({ fid }, { transient: { units: 1 } });
+  await db.commit(putOnChainEventTransaction(db.transaction(), rent));
+});
+
+beforeEach(async () => {
+  await eventHandler.syncCache();
+});
+
+describe("Merge ReactionCompactState messages", () => {
+  test("merge reaction compaction messages in an empty set", async () => {
+    const reactionCompactState = await Factories.ReactionCompactStateMessage.create({
+      data: {
+        fid,
+        reactionCompactStateBody: { type: ReactionType.LIKE, targetCastIds: [castId1], targetUrls: [] },
+      },
+    });
+
+    const result = await set.merge(reactionCompactState);
+    expect(result).toBeGreaterThan(0);
+
+    // Merging the same message again is an error, because only newer compact state messages
+    // can be merged
+    const expectError = await ResultAsync.fromPromise(set.merge(reactionCompactState), (e) => e as HubError);
+    expect(expectError.isErr()).toBe(true);
+    expect(expectError._unsafeUnwrapErr().errCode).toBe("bad_request.conflict");
+
+    const reactionCompactState2 = await Factories.ReactionCompactStateMessage.create({
+      data: {
+        fid,
+        reactionCompactStateBody: { type: ReactionType.LIKE, targetCastIds: [castId1], targetUrls: [] },
+        timestamp: reactionCompactState.data.timestamp + 1,
+      },
+    });
+
+    const result2 = await set.merge(reactionCompactState2);
+    expect(result2).toBeGreaterThan(result);
+  });
+
+  test("merge reaction compaction messages in a non-empty set", async () => {
+    const timestamp = getFarcasterTime()._unsafeUnwrap();
+    const like1 = await Factories.ReactionAddMessage.create({
+      data: { fid, reactionBody: { type: ReactionType.LIKE, targetCastId: castId1 }, timestamp },
+    });
+    const like2 = await Factories.ReactionAddMessage.create({
+      data: { fid, reactionBody: { type: ReactionType.LIKE, targetCastId: castId2 }, timestamp },
+    });
+    const recast2 = await Factories.ReactionAddMessage.create({
+      data: { fid, reactionBody: { type: ReactionType.RECAST, targetCastId: castId2 }, timestamp },
+    });
+    const urlLike = await Factories.ReactionAddMessage.create({
+      data: {
+        fid,
+        reactionBody: { type: ReactionType.LIKE, targetUrl: "https://farcaster.xyz" },
+        timestamp,
+      },
+    });
+
+    await set.mergeMessages([like1, like2, recast2, urlLike]);
+
+    let hubEvent: HubEvent | undefined;
+    eventHandler.addListener("mergeMessage", (event) => {
+      hubEvent = event;
+    });
+
+    // A LIKE compact state that only has like1 and the url like
+    const reactionCompactState = await Factories.ReactionCompactStateMessage.create({
+      data: {
+        fid,
+        reactionCompactStateBody: {
+          type: ReactionType.LIKE,
+          targetCastIds: [castId1],
+          targetUrls: ["https://farcaster.xyz"],
+        },
+        timestamp: timestamp + 2,
+      },
+    });
+    const result = await set.merge(reactionCompactState);
+    expect(result).toBeGreaterThan(0);
+
+    expect(await set.getReactionAdd(fid, ReactionType.LIKE, castId1)).toEqual(like1);
+    expect(await set.getReactionAdd(fid, ReactionType.LIKE, "https://farcaster.xyz")).toEqual(urlLike);
+    const expectError = await ResultAsync.fromPromise(
+      set.getReactionAdd(fid, ReactionType.LIKE, castId2),
+      (e) => e as HubError,
+    );
+    expect(expectError._unsafeUnwrapErr().errCode).toBe("not_found");
+
+    // Recasts have their own compact state, so the LIKE compact state doesn't touch them
+    expect(await set.getReactionAdd(fid, ReactionType.RECAST, castId2)).toEqual(recast2);
+
+    expect(hubEvent?.mergeMessageBody?.message?.data?.fid).toEqual(fid);
+    expect(hubEvent?.mergeMessageBody?.deletedMessages).toEqual([]);
+
+    // like2 can't be merged again, since its target isn't in the compact state
+    const expectError2 = await ResultAsync.fromPromise(set.merge(like2), (e) => e as HubError);
+    expect(expectError2._unsafeUnwrapErr().errCode).toBe("bad_request.conflict");
+
+    // Older removes can't be merged either
+    const remove1 = await Factories.ReactionRemoveMessage.create({
+      data: { fid, reactionBody: like1.data.reactionBody, timestamp: timestamp + 1 },
+    });
+    const expectError3 = await ResultAsync.fromPromise(set.merge(remove1), (e) => e as HubError);
+    expect(expectError3._unsafeUnwrapErr().errCode).toBe("bad_request.prunable");
+
+    // But an older add for a target in the compact state can
+    const like1Again = await Factories.ReactionAddMessage.create({
+      data: { fid, reactionBody: like1.data.reactionBody, timestamp: timestamp + 1 },
+    });
+    await set.merge(like1Again);
+    expect(await set.getReactionAdd(fid, ReactionType.LIKE, castId1)).toEqual(like1Again);
+
+    const compactStates = await set.getReactionCompactStateMessageByFid(fid);
+    expect(compactStates.messages.length).toBe(1);
+    expect(compactStates.messages[0]?.data.reactionCompactStateBody.targetCastIds).toEqual([castId1]);
+  });
+
+  test("doesn't remove reactions timestamped after the reaction compact state", async () => {
+    const timestamp = getFarcasterTime()._unsafeUnwrap();
+    const like1 = await Factories.ReactionAddMessage.create({
+      data: { fid, reactionBody: { type: ReactionType.LIKE, targetCastId: castId1 }, timestamp },
+    });
+    const like2 = await Factories.ReactionAddMessage.create({
+      data: { fid, reactionBody: { type: ReactionType.LIKE, targetCastId: castId2 }, timestamp: timestamp + 2 },
+    });
+    await set.mergeMessages([like1, like2]);
+
+    const reactionCompactState = await Factories.ReactionCompactStateMessage.create({
+      data: {
+        fid,
+        reactionCompactStateBody: { type: ReactionType.LIKE, targetCastIds: [], targetUrls: [] },
+        timestamp: timestamp + 1,
+      },
+    });
+    await set.merge(reactionCompactState);
+
+    expect((await set.getReactionAddsByFid(fid, ReactionType.LIKE)).messages).toEqual([like2]);
+  });
+});
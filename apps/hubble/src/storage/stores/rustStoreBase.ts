@@ -1,6 +1,11 @@
 import { HubAsyncResult, HubError, HubEvent, HubResult, Message } from "@farcaster/hub-nodejs";
 import {
+  CheckIndicesOptions,
+  CheckIndicesReport,
   RustDynStore,
+  rsCheckIndices,
+  rsPrepareOptionalIndices,
+  rsRebuildCounters,
   rsGetAllMessagesByFid,
   rsGetMessage,
   rsMerge,
@@ -8,9 +13,11 @@ import {
   revoke,
   rustErrorToHubError,
   rsMergeMany,
+  rsPreviewCompactStateMerge,
+  rsValidateMerge,
 } from "../../rustfunctions.js";
 import StoreEventHandler from "./storeEventHandler.js";
-import { MessagesPage, PageOptions } from "./types.js";
+import { MergeOutcome, MergeValidation, MessagesPage, PageOptions } from "./types.js";
 import { UserMessagePostfix } from "../db/types.js";
 import RocksDB from "../db/rocksdb.js";
 import { ResultAsync, err, ok } from "neverthrow";
@@ -71,6 +78,10 @@ export abstract class RustStoreBase<TAdd extends Message, TRemove extends Messag
     return this._postfix;
   }
 
+  get rustStore(): RustDynStore {
+    return this._rustStore;
+  }
+
   async mergeMessages(messages: Message[]): Promise<Map<number, HubResult<number>>> {
     const mergeResults: Map<number, HubResult<number>> = new Map();
 
@@ -124,13 +135,17 @@ export abstract class RustStoreBase<TAdd extends Message, TRemove extends Messag
     return mergeResults;
   }
 
-  async merge(message: Message): Promise<number> {
-    const prunableResult = await this._eventHandler.isPrunable(
+  private async isPrunable(message: Message): HubAsyncResult<boolean> {
+    return this._eventHandler.isPrunable(
       // biome-ignore lint/suspicious/noExplicitAny: legacy code, avoid using ignore for new code
       message as any,
       this._postfix,
       this._pruneSizeLimit,
     );
+  }
+
+  async merge(message: Message): Promise<number> {
+    const prunableResult = await this.isPrunable(message);
     if (prunableResult.isErr()) {
       throw prunableResult.error;
     } else if (prunableResult.value) {
@@ -219,6 +234,73 @@ export abstract class RustStoreBase<TAdd extends Message, TRemove extends Messag
     return messageDecode(new Uint8Array(message_bytes.value));
   }
 
+  /** Check this store's secondary indices against its messages. With `repair`, the indices are rebuilt */
+  async checkIndices(options: CheckIndicesOptions = {}): HubAsyncResult<CheckIndicesReport> {
+    return ResultAsync.fromPromise(rsCheckIndices(this._rustStore, options), rustErrorToHubError);
+  }
+
+  /**
+   * Backfill the enabled optional indices that don't have every message yet, e.g. because they were just turned on.
+   * Queries that need them fail until then. Returns the RootPrefixes of the indices that were backfilled
+   */
+  async backfillOptionalIndices(options: Omit<CheckIndicesOptions, "repair"> = {}): HubAsyncResult<number[]> {
+    const incomplete = await ResultAsync.fromPromise(rsPrepareOptionalIndices(this._rustStore), rustErrorToHubError);
+    if (incomplete.isErr() || incomplete.value.length === 0) {
+      return incomplete;
+    }
+
+    const result = await this.checkIndices({ ...options, repair: true });
+    return result.map(() => incomplete.value);
+  }
+
+  /** Recount this store's aggregate counters from its messages. Returns the number of messages counted */
+  async rebuildCounters(): HubAsyncResult<number> {
+    return ResultAsync.fromPromise(rsRebuildCounters(this._rustStore), rustErrorToHubError);
+  }
+
+  /**
+   * Dry run of merge. Explains what merging the message would do: whether it would be merged and which
+   * messages that deletes, or which existing message it loses to. Signatures and other validations aren't
+   * checked.
+   */
+  async validateMerge(message: Message): HubAsyncResult<MergeValidation> {
+    const prunableResult = await this.isPrunable(message);
+    if (prunableResult.isErr()) {
+      return err(prunableResult.error);
+    } else if (prunableResult.value) {
+      return ok({ outcome: MergeOutcome.PRUNABLE, reason: "message would be pruned", deletedMessages: [] });
+    }
+
+    const messageBytes = Message.encode(message).finish();
+    const result = await ResultAsync.fromPromise(rsValidateMerge(this._rustStore, messageBytes), rustErrorToHubError);
+
+    return result.map((validation) => ({
+      outcome: validation.outcome as MergeOutcome,
+      reason: validation.reason,
+      winner: validation.winnerBytes ? messageDecode(new Uint8Array(validation.winnerBytes)) : undefined,
+      deletedMessages: validation.deletedMessagesBytes.map((message_bytes) =>
+        messageDecode(new Uint8Array(message_bytes)),
+      ),
+    }));
+  }
+
+  /**
+   * Dry run of merging a compact state message. Returns the messages merging it would delete, including
+   * the older compact state message it replaces, without changing anything.
+   */
+  async previewCompactStateMerge(message: Message): HubAsyncResult<Message[]> {
+    const messageBytes = Message.encode(message).finish();
+    const result = await ResultAsync.fromPromise(
+      rsPreviewCompactStateMerge(this._rustStore, messageBytes),
+      rustErrorToHubError,
+    );
+
+    return result.map(
+      (messagesPage) =>
+        messagesPage.messageBytes?.map((message_bytes) => messageDecode(new Uint8Array(message_bytes))) ?? [],
+    );
+  }
+
   async getAllMessagesByFid(
     fid: number,
     pageOptions: PageOptions = {},
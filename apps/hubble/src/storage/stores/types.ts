@@ -1,10 +1,22 @@
-import { Message } from "@farcaster/hub-nodejs";
+import {
+  CastAddMessage,
+  LinkCompactStateBody,
+  Message,
+  UserNameProof,
+  UsernameProofMessage,
+  VerificationAddAddressMessage,
+} from "@farcaster/hub-nodejs";
 export const MERGE_TIMEOUT_DEFAULT = 10_000; // 10 seconds
 
 export type StorePruneOptions = {
   pruneSizeLimit?: number; // Max number of messages per fid
 };
 
+export type CastStoreOptions = StorePruneOptions & {
+  searchIndex?: boolean; // Maintain the full-text index used by searchCasts
+  feedIndex?: boolean; // Maintain the index of every cast in time order used by getRecentCasts
+};
+
 export const PAGE_SIZE_MAX = 10_000;
 export const DEFAULT_PAGE_SIZE = 100;
 
@@ -18,3 +30,93 @@ export type MessagesPage<T extends Message> = {
   messages: T[];
   nextPageToken?: Uint8Array | undefined;
 };
+
+export type CastThread = {
+  /** CastAdds in tree order, each followed by its replies. depth is 0 for the root cast */
+  messages: { message: CastAddMessage; depth: number }[];
+  /** Set if the thread had more casts than maxNodes */
+  truncated: boolean;
+  /** Pass back in to get the rest of a truncated thread */
+  nextPageToken?: Uint8Array | undefined;
+};
+
+/** What merging a message would do, see validateMerge */
+export enum MergeOutcome {
+  /** The message would be merged */
+  MERGE = "merge",
+  /** The message is already merged */
+  DUPLICATE = "duplicate",
+  /** The message loses to an existing message */
+  CONFLICT = "conflict",
+  /** The message would be pruned right away, because of a compact state message or the fid's storage */
+  PRUNABLE = "prunable",
+}
+
+export type MergeValidation = {
+  outcome: MergeOutcome;
+  /** Why the message wouldn't be merged, the message merge would fail with */
+  reason?: string;
+  /** The existing message the message loses to, if there is one */
+  winner?: Message;
+  /** The messages merging would delete */
+  deletedMessages: Message[];
+};
+
+/** A LinkCompactStateBody that matches a fid's current links of its type */
+export type LinkCompactStateCandidate = {
+  body: LinkCompactStateBody;
+  /** The earliest timestamp a LinkCompactStateMessage with body can have without deleting any of them */
+  timestamp: number;
+};
+
+/** What happened to an fname in an FnameHistoryEntry */
+export enum FnameHistoryKind {
+  /** The name got an owner, after not having one */
+  REGISTER = 1,
+  /** The name moved from one fid to another */
+  TRANSFER = 2,
+  /** The name got a new proof for the fid that already owned it */
+  UPDATE = 3,
+  /** The name's proof was deleted */
+  DELETE = 4,
+}
+
+export type FnameHistoryEntry = {
+  kind: FnameHistoryKind;
+  /** The fid that owned the name before this entry, or 0 if it had no owner */
+  previousFid: number;
+  /** The proof that did it. Deletions have fid 0 */
+  proof: UserNameProof;
+};
+
+export type FnameHistoryPage = {
+  entries: FnameHistoryEntry[];
+  nextPageToken?: Uint8Array | undefined;
+};
+
+export type Profile = {
+  fid: number;
+  pfp?: string;
+  display?: string;
+  bio?: string;
+  url?: string;
+  username?: string;
+  location?: string;
+  twitter?: string;
+  github?: string;
+  /** The fname the fid owns */
+  fnameProof?: UserNameProof;
+  ensProofs: UsernameProofMessage[];
+  /** The oldest verification for each protocol, i.e. the fid's primary address on it */
+  primaryVerifications: VerificationAddAddressMessage[];
+};
+
+export type ReverseResolution = {
+  fid: number;
+  /** The username the fid set with USER_DATA_TYPE_USERNAME */
+  username?: string;
+  /** Whether username is backed by a proof the fid currently holds */
+  verified: boolean;
+  /** The proof for the name the fid resolves to: its username if verified, else its fname, else its oldest ENS name */
+  proof?: UserNameProof;
+};
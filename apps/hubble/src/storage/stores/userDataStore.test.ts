@@ -11,14 +11,18 @@ import {
   UserDataAddMessage,
   UserDataType,
   UserNameProof,
+  UserNameType,
 } from "@farcaster/hub-nodejs";
 import { jestRocksDB } from "../db/jestUtils.js";
 import StoreEventHandler from "./storeEventHandler.js";
 import UserDataStore from "./userDataStore.js";
+import UsernameProofStore from "./usernameProofStore.js";
+import VerificationStore from "./verificationStore.js";
 import { getMessage, makeTsHash } from "../db/message.js";
-import { UserPostfix } from "../db/types.js";
+import { RootPrefix, UserPostfix } from "../db/types.js";
 import { err } from "neverthrow";
 import { putOnChainEventTransaction } from "../db/onChainEvent.js";
+import { FnameHistoryKind } from "./types.js";
 
 const db = jestRocksDB("protobufs.userDataSet.test");
 const eventHandler = new StoreEventHandler(db);
@@ -157,11 +161,35 @@ describe("mergeUserNameProof", () => {
     await expect(set.getUserNameProof(existingProof.name)).rejects.toThrowError("NotFound");
     await expect(set.getUserNameProofByFid(existingProof.fid)).rejects.toThrowError("NotFound");
     expect(proofEvents).toEqual([[newProof, existingProof]]);
+
+    // The proof itself is deleted, and only the deletion is kept
+    const proofKey = Buffer.concat([Buffer.from([RootPrefix.FNameUserNameProof]), Buffer.from(existingProof.name)]);
+    await expect(db.get(proofKey)).rejects.toThrow();
     await expect(set.mergeUserNameProof(existingProof)).rejects.toThrowError(
       "event conflicts with a more recent UserNameProof",
     );
   });
 
+  test("merges proofs newer than a deletion", async () => {
+    const existingProof = await Factories.UserNameProof.build();
+    await set.mergeUserNameProof(existingProof);
+    const deletion = await Factories.UserNameProof.build({
+      timestamp: existingProof.timestamp + 10,
+      name: existingProof.name,
+      fid: 0,
+    });
+    await set.mergeUserNameProof(deletion);
+
+    const newProof = await Factories.UserNameProof.build({
+      timestamp: deletion.timestamp + 10,
+      name: existingProof.name,
+    });
+    proofEvents = [];
+    await set.mergeUserNameProof(newProof);
+    await expect(set.getUserNameProof(existingProof.name)).resolves.toEqual(newProof);
+    expect(proofEvents).toEqual([[newProof, undefined]]);
+  });
+
   test("does not emit an event if there is no existing proof and new proof is to fid 0", async () => {
     const proof = await Factories.UserNameProof.build({
       fid: 0,
@@ -199,6 +227,281 @@ describe("mergeUserNameProof", () => {
   });
 });
 
+describe("getProfile", () => {
+  const usernameProofStore = new UsernameProofStore(db, eventHandler);
+  const verificationStore = new VerificationStore(db, eventHandler);
+
+  test("returns an empty profile without data", async () => {
+    await expect(set.getProfile(fid)).resolves.toEqual({ fid, ensProofs: [], primaryVerifications: [] });
+  });
+
+  test("returns user data, proofs and primary verifications", async () => {
+    const fnameProof = Factories.UserNameProof.build({ fid });
+    await set.mergeUserNameProof(fnameProof);
+
+    const ensProof = await Factories.UsernameProofMessage.create({
+      data: {
+        fid,
+        usernameProofBody: Factories.UserNameProof.build({
+          fid,
+          name: Factories.EnsName.build(),
+          type: UserNameType.USERNAME_TYPE_ENS_L1,
+        }),
+      },
+    });
+    await usernameProofStore.merge(ensProof);
+
+    const ethVerification = await Factories.VerificationAddEthAddressMessage.create({ data: { fid } });
+    const laterEthVerification = await Factories.VerificationAddEthAddressMessage.create({
+      data: { fid, timestamp: ethVerification.data.timestamp + 1 },
+    });
+    const solVerification = await Factories.VerificationAddSolAddressMessage.create({ data: { fid } });
+    await verificationStore.mergeMessages([ethVerification, laterEthVerification, solVerification]);
+
+    await set.mergeMessages([addPfp, addBio]);
+
+    const profile = await set.getProfile(fid);
+    expect(profile).toEqual({
+      fid,
+      pfp: addPfp.data.userDataBody.value,
+      bio: addBio.data.userDataBody.value,
+      fnameProof,
+      ensProofs: [ensProof],
+      primaryVerifications: [ethVerification, solVerification],
+    });
+  });
+
+  test("returns profiles in the order of the fids", async () => {
+    const otherFid = Factories.Fid.build();
+    await set.merge(addPfp);
+
+    const profiles = await set.getProfiles([otherFid, fid]);
+    expect(profiles.map((profile) => profile.fid)).toEqual([otherFid, fid]);
+    expect(profiles[0]?.pfp).toBeUndefined();
+    expect(profiles[1]?.pfp).toEqual(addPfp.data.userDataBody.value);
+  });
+
+  test("fails with too many fids", async () => {
+    const fids = Array.from({ length: 101 }, (_, i) => i + 1);
+    await expect(set.getProfiles(fids)).rejects.toEqual(
+      new HubError("bad_request.invalid_param", "at most 100 fids can be requested at once"),
+    );
+  });
+});
+
+describe("resolveName", () => {
+  const usernameProofStore = new UsernameProofStore(db, eventHandler);
+
+  test("resolves fnames", async () => {
+    const proof = Factories.UserNameProof.build({ fid });
+    await set.mergeUserNameProof(proof);
+
+    await expect(set.resolveName(Buffer.from(proof.name).toString())).resolves.toEqual(proof);
+  });
+
+  test("resolves ENS names", async () => {
+    const ensProof = await Factories.UsernameProofMessage.create({
+      data: {
+        fid,
+        usernameProofBody: Factories.UserNameProof.build({
+          fid,
+          name: Factories.EnsName.build(),
+          type: UserNameType.USERNAME_TYPE_ENS_L1,
+        }),
+      },
+    });
+    await usernameProofStore.merge(ensProof);
+
+    const name = Buffer.from(ensProof.data.usernameProofBody.name).toString();
+    await expect(set.resolveName(name)).resolves.toEqual(ensProof.data.usernameProofBody);
+  });
+
+  test("does not resolve deleted fnames", async () => {
+    const proof = Factories.UserNameProof.build({ fid });
+    await set.mergeUserNameProof(proof);
+    await set.mergeUserNameProof({ ...proof, timestamp: proof.timestamp + 10, fid: 0 });
+
+    await expect(set.resolveName(Buffer.from(proof.name).toString())).rejects.toThrowError("NotFound");
+  });
+});
+
+describe("reverseResolve", () => {
+  const usernameProofStore = new UsernameProofStore(db, eventHandler);
+
+  const setUsername = async (username: string) => {
+    const message = await Factories.UserDataAddMessage.create({
+      data: { fid, userDataBody: { type: UserDataType.USERNAME, value: username } },
+    });
+    await set.merge(message);
+  };
+
+  test("returns a verified username", async () => {
+    const proof = Factories.UserNameProof.build({ fid });
+    await set.mergeUserNameProof(proof);
+    await setUsername(Buffer.from(proof.name).toString());
+
+    await expect(set.reverseResolve(fid)).resolves.toEqual({
+      fid,
+      username: Buffer.from(proof.name).toString(),
+      verified: true,
+      proof,
+    });
+  });
+
+  test("falls back to the fname if the username isn't backed by a proof", async () => {
+    const proof = Factories.UserNameProof.build({ fid });
+    await set.mergeUserNameProof(proof);
+    // Someone else's fname
+    const otherProof = Factories.UserNameProof.build();
+    await set.mergeUserNameProof(otherProof);
+    await setUsername(Buffer.from(otherProof.name).toString());
+
+    await expect(set.reverseResolve(fid)).resolves.toEqual({
+      fid,
+      username: Buffer.from(otherProof.name).toString(),
+      verified: false,
+      proof,
+    });
+  });
+
+  test("falls back to the ENS name without an fname", async () => {
+    const ensProof = await Factories.UsernameProofMessage.create({
+      data: {
+        fid,
+        usernameProofBody: Factories.UserNameProof.build({
+          fid,
+          name: Factories.EnsName.build(),
+          type: UserNameType.USERNAME_TYPE_ENS_L1,
+        }),
+      },
+    });
+    await usernameProofStore.merge(ensProof);
+
+    await expect(set.reverseResolve(fid)).resolves.toEqual({
+      fid,
+      username: undefined,
+      verified: false,
+      proof: ensProof.data.usernameProofBody,
+    });
+  });
+
+  test("returns nothing without names", async () => {
+    await expect(set.reverseResolve(fid)).resolves.toEqual({ fid, verified: false });
+  });
+});
+
+describe("getFnameHistory", () => {
+  const newFid = Factories.Fid.build();
+
+  test("records registrations, transfers and deletions", async () => {
+    const proof = Factories.UserNameProof.build({ fid });
+    await set.mergeUserNameProof(proof);
+    const transfer = Factories.UserNameProof.build({ name: proof.name, fid: newFid, timestamp: proof.timestamp + 10 });
+    await set.mergeUserNameProof(transfer);
+    const deletion = Factories.UserNameProof.build({ name: proof.name, fid: 0, timestamp: proof.timestamp + 20 });
+    await set.mergeUserNameProof(deletion);
+
+    const history = await set.getFnameHistory(proof.name);
+    expect(history).toEqual({
+      entries: [
+        { kind: FnameHistoryKind.REGISTER, previousFid: 0, proof },
+        { kind: FnameHistoryKind.TRANSFER, previousFid: fid, proof: transfer },
+        { kind: FnameHistoryKind.DELETE, previousFid: newFid, proof: deletion },
+      ],
+      nextPageToken: undefined,
+    });
+    // Names can be looked up as strings too
+    await expect(set.getFnameHistory(Buffer.from(proof.name).toString())).resolves.toEqual(history);
+
+    // Each fid sees the entries it got or lost the name in
+    await expect(set.getFnameHistoryByFid(fid)).resolves.toEqual({
+      entries: history.entries.slice(0, 2),
+      nextPageToken: undefined,
+    });
+    await expect(set.getFnameHistoryByFid(newFid)).resolves.toEqual({
+      entries: history.entries.slice(1),
+      nextPageToken: undefined,
+    });
+  });
+
+  test("records proofs that keep the owner as updates", async () => {
+    const proof = Factories.UserNameProof.build({ fid });
+    await set.mergeUserNameProof(proof);
+    const update = Factories.UserNameProof.build({ name: proof.name, fid, timestamp: proof.timestamp + 10 });
+    await set.mergeUserNameProof(update);
+
+    const history = await set.getFnameHistory(proof.name);
+    expect(history.entries.map((entry) => entry.kind)).toEqual([FnameHistoryKind.REGISTER, FnameHistoryKind.UPDATE]);
+  });
+
+  test("pages newest first with reverse", async () => {
+    const proof = Factories.UserNameProof.build({ fid });
+    await set.mergeUserNameProof(proof);
+    const transfer = Factories.UserNameProof.build({ name: proof.name, fid: newFid, timestamp: proof.timestamp + 10 });
+    await set.mergeUserNameProof(transfer);
+
+    const page1 = await set.getFnameHistory(proof.name, { pageSize: 1, reverse: true });
+    expect(page1.entries.map((entry) => entry.proof)).toEqual([transfer]);
+    expect(page1.nextPageToken).toBeDefined();
+
+    const page2 = await set.getFnameHistory(proof.name, {
+      pageSize: 1,
+      reverse: true,
+      pageToken: page1.nextPageToken,
+    });
+    expect(page2.entries.map((entry) => entry.proof)).toEqual([proof]);
+  });
+
+  test("does not include the history of longer names", async () => {
+    const proof = Factories.UserNameProof.build({ fid, name: Buffer.from("alice") });
+    await set.mergeUserNameProof(proof);
+    await set.mergeUserNameProof(Factories.UserNameProof.build({ name: Buffer.from("alicia") }));
+
+    const history = await set.getFnameHistory("alice");
+    expect(history.entries.map((entry) => entry.proof)).toEqual([proof]);
+  });
+
+  test("returns nothing for names without history", async () => {
+    await expect(set.getFnameHistory("nobody")).resolves.toEqual({ entries: [], nextPageToken: undefined });
+  });
+
+  test("backfills the history of existing proofs", async () => {
+    const proof = Factories.UserNameProof.build({ fid });
+    await set.mergeUserNameProof(proof);
+    const otherProof = Factories.UserNameProof.build();
+    await set.mergeUserNameProof(otherProof);
+
+    // Remove the history of one name, as if it had been merged before the history was kept
+    const historyKeys: Buffer[] = [];
+    await db.forEachIteratorByPrefix(Buffer.from([RootPrefix.FNameHistory]), (key) => {
+      historyKeys.push(key as Buffer);
+    });
+    await db.forEachIteratorByPrefix(Buffer.from([RootPrefix.FNameHistoryByFid]), (key) => {
+      historyKeys.push(key as Buffer);
+    });
+    const txn = db.transaction();
+    for (const key of historyKeys) {
+      txn.del(key);
+    }
+    await db.commit(txn);
+    const deletion = Factories.UserNameProof.build({
+      name: otherProof.name,
+      fid: 0,
+      timestamp: otherProof.timestamp + 1,
+    });
+    await set.mergeUserNameProof(deletion);
+
+    await expect(set.backfillFnameHistory()).resolves.toEqual(1);
+    await expect(set.getFnameHistoryByFid(fid)).resolves.toEqual({
+      entries: [{ kind: FnameHistoryKind.REGISTER, previousFid: 0, proof }],
+      nextPageToken: undefined,
+    });
+
+    // Names that have history are left alone
+    await expect(set.backfillFnameHistory()).resolves.toEqual(0);
+  });
+});
+
 describe("getUserDataAddsByFid", () => {
   test("returns user data adds for an fid in chronological order", async () => {
     await set.merge(addPfp);
@@ -1,11 +1,34 @@
-import { UserNameProof, UserDataAddMessage, UserDataType, HubEvent } from "@farcaster/hub-nodejs";
+import {
+  UserNameProof,
+  UserDataAddMessage,
+  UserDataType,
+  HubEvent,
+  UsernameProofMessage,
+  VerificationAddAddressMessage,
+} from "@farcaster/hub-nodejs";
 import { ResultAsync } from "neverthrow";
 import { UserPostfix } from "../db/types.js";
-import { MessagesPage, PageOptions, StorePruneOptions } from "../stores/types.js";
+import {
+  FnameHistoryPage,
+  MessagesPage,
+  PageOptions,
+  Profile,
+  ReverseResolution,
+  StorePruneOptions,
+} from "../stores/types.js";
 import RocksDB from "../db/rocksdb.js";
 import StoreEventHandler from "./storeEventHandler.js";
 import {
+  RustFnameHistoryPage,
+  RustProfile,
+  rsBackfillFnameHistory,
   rsCreateUserDataStore,
+  rsGetFnameHistory,
+  rsGetFnameHistoryByFid,
+  rsGetProfile,
+  rsGetProfiles,
+  rsResolveName,
+  rsReverseResolve,
   rsGetUserDataAdd,
   rsGetUserDataAddsByFid,
   rsGetUserNameProof,
@@ -16,6 +39,53 @@ import {
 import { RustStoreBase } from "./rustStoreBase.js";
 import { messageDecode } from "../../storage/db/message.js";
 
+type ProfileUserDataField = Exclude<keyof Profile, "fid" | "fnameProof" | "ensProofs" | "primaryVerifications">;
+
+const decodeFnameHistoryPage = (page: RustFnameHistoryPage): FnameHistoryPage => {
+  return {
+    entries: page.entries.map(({ kind, previousFid, proofBytes }) => ({
+      kind,
+      previousFid,
+      proof: UserNameProof.decode(new Uint8Array(proofBytes)),
+    })),
+    nextPageToken: page.nextPageToken ? new Uint8Array(page.nextPageToken) : undefined,
+  };
+};
+
+const PROFILE_FIELDS: { [type: number]: ProfileUserDataField } = {
+  [UserDataType.PFP]: "pfp",
+  [UserDataType.DISPLAY]: "display",
+  [UserDataType.BIO]: "bio",
+  [UserDataType.URL]: "url",
+  [UserDataType.USERNAME]: "username",
+  [UserDataType.LOCATION]: "location",
+  [UserDataType.TWITTER]: "twitter",
+  [UserDataType.GITHUB]: "github",
+};
+
+const decodeProfile = (rustProfile: RustProfile): Profile => {
+  const profile: Profile = {
+    fid: rustProfile.fid,
+    fnameProof: rustProfile.fnameProofBytes
+      ? UserNameProof.decode(new Uint8Array(rustProfile.fnameProofBytes))
+      : undefined,
+    ensProofs: rustProfile.ensProofsBytes.map((bytes) => messageDecode(new Uint8Array(bytes)) as UsernameProofMessage),
+    primaryVerifications: rustProfile.verificationsBytes.map(
+      (bytes) => messageDecode(new Uint8Array(bytes)) as VerificationAddAddressMessage,
+    ),
+  };
+
+  for (const bytes of rustProfile.userDataBytes) {
+    const body = (messageDecode(new Uint8Array(bytes)) as UserDataAddMessage).data.userDataBody;
+    const field = PROFILE_FIELDS[body.type];
+    if (field) {
+      profile[field] = body.value;
+    }
+  }
+
+  return profile;
+};
+
 /**
  * UserDataStore persists UserData messages in RocksDB using a grow-only CRDT set to guarantee
  * eventual consistency.
@@ -94,6 +164,104 @@ class UserDataStore extends RustStoreBase<UserDataAddMessage, never> {
     return UserNameProof.decode(new Uint8Array(result.value));
   }
 
+  /**
+   * Gets a fid's user data, fname and ENS proofs and primary verified addresses, all read from one snapshot. Fids
+   * without any of these get a profile with only the fid set.
+   */
+  async getProfile(fid: number): Promise<Profile> {
+    const result = await ResultAsync.fromPromise(rsGetProfile(this._rustStore, fid), rustErrorToHubError);
+    if (result.isErr()) {
+      throw result.error;
+    }
+
+    return decodeProfile(result.value);
+  }
+
+  /** Same as getProfile, for up to 100 fids at once. The profiles are in the same order as the fids */
+  async getProfiles(fids: number[]): Promise<Profile[]> {
+    const result = await ResultAsync.fromPromise(rsGetProfiles(this._rustStore, fids), rustErrorToHubError);
+    if (result.isErr()) {
+      throw result.error;
+    }
+
+    return result.value.map(decodeProfile);
+  }
+
+  /**
+   * Finds the proof that a fid owns a name, either an fname or an ENS name. Names whose proofs were deleted or revoked
+   * don't resolve.
+   */
+  async resolveName(name: string): Promise<UserNameProof> {
+    const result = await ResultAsync.fromPromise(rsResolveName(this._rustStore, name), rustErrorToHubError);
+    if (result.isErr()) {
+      throw result.error;
+    }
+
+    return UserNameProof.decode(new Uint8Array(result.value.proofBytes));
+  }
+
+  /**
+   * Finds the name a fid goes by, which is its username if a proof it holds backs it, and otherwise its fname or its
+   * oldest ENS name
+   */
+  async reverseResolve(fid: number): Promise<ReverseResolution> {
+    const result = await ResultAsync.fromPromise(rsReverseResolve(this._rustStore, fid), rustErrorToHubError);
+    if (result.isErr()) {
+      throw result.error;
+    }
+
+    const { username, verified, proofBytes } = result.value;
+    return {
+      fid: result.value.fid,
+      username,
+      verified,
+      proof: proofBytes ? UserNameProof.decode(new Uint8Array(proofBytes)) : undefined,
+    };
+  }
+
+  /**
+   * Gets every proof, transfer and deletion of an fname, oldest first. Entries are kept after the name's proof is
+   * replaced or deleted.
+   */
+  async getFnameHistory(name: Uint8Array | string, pageOptions: PageOptions = {}): Promise<FnameHistoryPage> {
+    const nameBytes = typeof name === "string" ? Buffer.from(name) : name;
+    const result = await ResultAsync.fromPromise(
+      rsGetFnameHistory(this._rustStore, nameBytes, pageOptions),
+      rustErrorToHubError,
+    );
+    if (result.isErr()) {
+      throw result.error;
+    }
+
+    return decodeFnameHistoryPage(result.value);
+  }
+
+  /** Gets the fname history entries in which a fid got or lost a name, oldest first */
+  async getFnameHistoryByFid(fid: number, pageOptions: PageOptions = {}): Promise<FnameHistoryPage> {
+    const result = await ResultAsync.fromPromise(
+      rsGetFnameHistoryByFid(this._rustStore, fid, pageOptions),
+      rustErrorToHubError,
+    );
+    if (result.isErr()) {
+      throw result.error;
+    }
+
+    return decodeFnameHistoryPage(result.value);
+  }
+
+  /**
+   * Records a REGISTER entry for each current fname proof whose name has no history, e.g. because it was merged
+   * before the history was kept. Returns the number of entries written.
+   */
+  async backfillFnameHistory(): Promise<number> {
+    const result = await ResultAsync.fromPromise(rsBackfillFnameHistory(this._rustStore), rustErrorToHubError);
+    if (result.isErr()) {
+      throw result.error;
+    }
+
+    return result.value;
+  }
+
   async mergeUserNameProof(usernameProof: UserNameProof): Promise<number> {
     const usernameProofBytes = UserNameProof.encode(usernameProof).finish();
 
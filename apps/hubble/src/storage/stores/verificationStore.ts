@@ -53,8 +53,16 @@ class VerificationStore extends RustStoreBase<VerificationAddAddressMessage, Ver
   async getVerificationAddsByFid(
     fid: number,
     pageOptions?: PageOptions,
+    startTime?: number,
+    stopTime?: number,
   ): Promise<MessagesPage<VerificationAddAddressMessage>> {
-    const messages_page = await rsGetVerificationAddsByFid(this._rustStore, fid, pageOptions ?? {});
+    const messages_page = await rsGetVerificationAddsByFid(
+      this._rustStore,
+      fid,
+      pageOptions ?? {},
+      startTime,
+      stopTime,
+    );
 
     const messages =
       messages_page.messageBytes?.map((message_bytes) => {
@@ -67,8 +75,16 @@ class VerificationStore extends RustStoreBase<VerificationAddAddressMessage, Ver
   async getVerificationRemovesByFid(
     fid: number,
     pageOptions?: PageOptions,
+    startTime?: number,
+    stopTime?: number,
   ): Promise<MessagesPage<VerificationRemoveMessage>> {
-    const message_page = await rsGetVerificationRemovesByFid(this._rustStore, fid, pageOptions ?? {});
+    const message_page = await rsGetVerificationRemovesByFid(
+      this._rustStore,
+      fid,
+      pageOptions ?? {},
+      startTime,
+      stopTime,
+    );
 
     const messages =
       message_page.messageBytes?.map((message_bytes) => {
@@ -134,7 +134,8 @@ export const uploadToS3 = async (
 
   const metadata: SnapshotMetadata = {
     keyBase,
-    chunks: files,
+    // The manifest.json is uploaded alongside the chunks, but is not a chunk itself
+    chunks: files.filter((file) => file !== "manifest.json"),
     timestamp: startTimestamp,
     serverDate: new Date(startTimestamp).toISOString(),
     ...(messageCount && { numMessages: messageCount }),
@@ -25,6 +25,7 @@ type MessageBodyOptions = Pick<
   | "userDataBody"
   | "linkBody"
   | "linkCompactStateBody"
+  | "reactionCompactStateBody"
   | "usernameProofBody"
   | "frameActionBody"
 >;
@@ -246,6 +247,25 @@ export const makeReactionRemoveData = (
   return makeMessageData({ reactionBody: body }, protobufs.MessageType.REACTION_REMOVE, dataOptions);
 };
 
+export const makeReactionCompactState = async (
+  body: protobufs.ReactionCompactStateBody,
+  dataOptions: MessageDataOptions,
+  signer: Signer,
+): HubAsyncResult<protobufs.ReactionCompactStateMessage> => {
+  const data = await makeReactionCompactStateData(body, dataOptions);
+  if (data.isErr()) {
+    return err(data.error);
+  }
+  return makeMessage(data.value, signer);
+};
+
+export const makeReactionCompactStateData = (
+  body: protobufs.ReactionCompactStateBody,
+  dataOptions: MessageDataOptions,
+): HubAsyncResult<protobufs.ReactionCompactStateData> => {
+  return makeMessageData({ reactionCompactStateBody: body }, protobufs.MessageType.REACTION_COMPACT_STATE, dataOptions);
+};
+
 /* -------------------------------------------------------------------------- */
 /*                            VERIFICATION METHODS                            */
 /* -------------------------------------------------------------------------- */
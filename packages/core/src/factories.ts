@@ -411,6 +411,37 @@ const ReactionRemoveMessageFactory = Factory.define<protobufs.ReactionRemoveMess
   },
 );
 
+const ReactionCompactStateBodyFactory = Factory.define<protobufs.ReactionCompactStateBody>(() => {
+  return protobufs.ReactionCompactStateBody.create({
+    targetCastIds: [CastIdFactory.build()],
+    targetUrls: [],
+    type: ReactionTypeFactory.build(),
+  });
+});
+
+const ReactionCompactStateDataFactory = Factory.define<protobufs.ReactionCompactStateData>(() => {
+  return MessageDataFactory.build({
+    reactionCompactStateBody: ReactionCompactStateBodyFactory.build(),
+    type: protobufs.MessageType.REACTION_COMPACT_STATE,
+  }) as protobufs.ReactionCompactStateData;
+});
+
+const ReactionCompactStateMessageFactory = Factory.define<
+  protobufs.ReactionCompactStateMessage,
+  { signer?: Ed25519Signer }
+>(({ onCreate, transientParams }) => {
+  onCreate((message) => {
+    return MessageFactory.create(message, {
+      transient: transientParams,
+    }) as Promise<protobufs.ReactionCompactStateMessage>;
+  });
+
+  return MessageFactory.build(
+    { data: ReactionCompactStateDataFactory.build(), signatureScheme: protobufs.SignatureScheme.ED25519 },
+    { transient: transientParams },
+  ) as protobufs.ReactionCompactStateMessage;
+});
+
 const VerificationSolAddressClaimFactory = Factory.define<VerificationAddressClaimSolana>(() => {
   const address = bytesToBase58(SolAddressFactory.build())._unsafeUnwrap();
   const blockHash = bytesToBase58(BlockHashFactory.build())._unsafeUnwrap();
@@ -864,6 +895,8 @@ export const Factories = {
   ReactionAddMessage: ReactionAddMessageFactory,
   ReactionRemoveData: ReactionRemoveDataFactory,
   ReactionRemoveMessage: ReactionRemoveMessageFactory,
+  ReactionCompactStateBody: ReactionCompactStateBodyFactory,
+  ReactionCompactStateMessage: ReactionCompactStateMessageFactory,
   VerificationEthAddressClaim: VerificationEthAddressClaimFactory,
   VerificationAddAddressBody: VerificationAddAddressBodyFactory,
   VerificationAddEthAddressData: VerificationAddEthAddressDataFactory,
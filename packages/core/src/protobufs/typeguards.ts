@@ -87,6 +87,17 @@ export const isReactionRemoveMessage = (message: protobufs.Message): message is
   );
 };
 
+export const isReactionCompactStateMessage = (
+  message: protobufs.Message,
+): message is types.ReactionCompactStateMessage => {
+  return (
+    message.signatureScheme === protobufs.SignatureScheme.ED25519 &&
+    typeof message.data !== "undefined" &&
+    message.data.type === protobufs.MessageType.REACTION_COMPACT_STATE &&
+    message.data.reactionCompactStateBody !== undefined
+  );
+};
+
 export const isVerificationAddAddressData = (data: protobufs.MessageData): data is types.VerificationAddAddressData => {
   return (
     data.type === protobufs.MessageType.VERIFICATION_ADD_ETH_ADDRESS &&
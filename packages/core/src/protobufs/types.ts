@@ -76,6 +76,16 @@ export type ReactionRemoveMessage = protobufs.Message & {
   signatureScheme: protobufs.SignatureScheme.ED25519;
 };
 
+export type ReactionCompactStateData = protobufs.MessageData & {
+  type: protobufs.MessageType.REACTION_COMPACT_STATE;
+  reactionCompactStateBody: protobufs.ReactionCompactStateBody;
+};
+
+export type ReactionCompactStateMessage = protobufs.Message & {
+  data: ReactionCompactStateData;
+  signatureScheme: protobufs.SignatureScheme.ED25519;
+};
+
 export type VerificationAddAddressData = protobufs.MessageData & {
   type: protobufs.MessageType.VERIFICATION_ADD_ETH_ADDRESS;
   verificationAddAddressBody: protobufs.VerificationAddAddressBody;
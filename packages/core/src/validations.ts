@@ -420,6 +420,8 @@ export const validateMessageData = async <T extends protobufs.MessageData>(
     bodyResult = validateReactionBody(data.reactionBody);
   } else if (validType.value === protobufs.MessageType.LINK_COMPACT_STATE && !!data.linkCompactStateBody) {
     bodyResult = validateLinkCompactStateBody(data.linkCompactStateBody);
+  } else if (validType.value === protobufs.MessageType.REACTION_COMPACT_STATE && !!data.reactionCompactStateBody) {
+    bodyResult = validateReactionCompactStateBody(data.reactionCompactStateBody);
   } else if (
     (validType.value === protobufs.MessageType.LINK_ADD || validType.value === protobufs.MessageType.LINK_REMOVE) &&
     !!data.linkBody
@@ -807,6 +809,31 @@ export const validateReactionBody = (body: protobufs.ReactionBody): HubResult<pr
   return validateTarget(target).map(() => body);
 };
 
+export const validateReactionCompactStateBody = (
+  body: protobufs.ReactionCompactStateBody,
+): HubResult<protobufs.ReactionCompactStateBody> => {
+  const validatedType = validateReactionType(body.type);
+  if (validatedType.isErr()) {
+    return err(validatedType.error);
+  }
+
+  for (const targetCastId of body.targetCastIds) {
+    const validCastId = validateCastId(targetCastId);
+    if (validCastId.isErr()) {
+      return err(validCastId.error);
+    }
+  }
+
+  for (const targetUrl of body.targetUrls) {
+    const validUrl = validateUrl(targetUrl);
+    if (validUrl.isErr()) {
+      return err(validUrl.error);
+    }
+  }
+
+  return ok(body);
+};
+
 export const validateVerificationAddAddressBody = async (
   body: protobufs.VerificationAddAddressBody,
   fid: number,
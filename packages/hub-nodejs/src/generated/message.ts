@@ -103,6 +103,8 @@ export enum MessageType {
   FRAME_ACTION = 13,
   /** LINK_COMPACT_STATE - Link Compaction State Message */
   LINK_COMPACT_STATE = 14,
+  /** REACTION_COMPACT_STATE - Reaction Compaction State Message */
+  REACTION_COMPACT_STATE = 15,
 }
 
 export function messageTypeFromJSON(object: any): MessageType {
@@ -146,6 +148,9 @@ export function messageTypeFromJSON(object: any): MessageType {
     case 14:
     case "MESSAGE_TYPE_LINK_COMPACT_STATE":
       return MessageType.LINK_COMPACT_STATE;
+    case 15:
+    case "MESSAGE_TYPE_REACTION_COMPACT_STATE":
+      return MessageType.REACTION_COMPACT_STATE;
     default:
       throw new tsProtoGlobalThis.Error("Unrecognized enum value " + object + " for enum MessageType");
   }
@@ -179,6 +184,8 @@ export function messageTypeToJSON(object: MessageType): string {
       return "MESSAGE_TYPE_FRAME_ACTION";
     case MessageType.LINK_COMPACT_STATE:
       return "MESSAGE_TYPE_LINK_COMPACT_STATE";
+    case MessageType.REACTION_COMPACT_STATE:
+      return "MESSAGE_TYPE_REACTION_COMPACT_STATE";
     default:
       throw new tsProtoGlobalThis.Error("Unrecognized enum value " + object + " for enum MessageType");
   }
@@ -339,6 +346,14 @@ export function castTypeToJSON(object: CastType): string {
   }
 }
 
+/** A Compaction message for the Reaction Store */
+export interface ReactionCompactStateBody {
+  /** Type of reaction */
+  type: ReactionType;
+  targetCastIds: CastId[];
+  targetUrls: string[];
+}
+
 /** Type of Reaction */
 export enum ReactionType {
   NONE = 0,
@@ -462,6 +477,7 @@ export interface MessageData {
     | undefined;
   /** Compaction messages */
   linkCompactStateBody?: LinkCompactStateBody | undefined;
+  reactionCompactStateBody?: ReactionCompactStateBody | undefined;
 }
 
 /** Adds metadata about a user */
@@ -756,6 +772,7 @@ function createBaseMessageData(): MessageData {
     usernameProofBody: undefined,
     frameActionBody: undefined,
     linkCompactStateBody: undefined,
+    reactionCompactStateBody: undefined,
   };
 }
 
@@ -803,6 +820,9 @@ export const MessageData = {
     if (message.linkCompactStateBody !== undefined) {
       LinkCompactStateBody.encode(message.linkCompactStateBody, writer.uint32(138).fork()).ldelim();
     }
+    if (message.reactionCompactStateBody !== undefined) {
+      ReactionCompactStateBody.encode(message.reactionCompactStateBody, writer.uint32(146).fork()).ldelim();
+    }
     return writer;
   },
 
@@ -911,6 +931,13 @@ export const MessageData = {
 
           message.linkCompactStateBody = LinkCompactStateBody.decode(reader, reader.uint32());
           continue;
+        case 18:
+          if (tag != 146) {
+            break;
+          }
+
+          message.reactionCompactStateBody = ReactionCompactStateBody.decode(reader, reader.uint32());
+          continue;
       }
       if ((tag & 7) == 4 || tag == 0) {
         break;
@@ -942,6 +969,9 @@ export const MessageData = {
       linkCompactStateBody: isSet(object.linkCompactStateBody)
         ? LinkCompactStateBody.fromJSON(object.linkCompactStateBody)
         : undefined,
+      reactionCompactStateBody: isSet(object.reactionCompactStateBody)
+        ? ReactionCompactStateBody.fromJSON(object.reactionCompactStateBody)
+        : undefined,
     };
   },
 
@@ -974,6 +1004,9 @@ export const MessageData = {
     message.linkCompactStateBody !== undefined && (obj.linkCompactStateBody = message.linkCompactStateBody
       ? LinkCompactStateBody.toJSON(message.linkCompactStateBody)
       : undefined);
+    message.reactionCompactStateBody !== undefined && (obj.reactionCompactStateBody = message.reactionCompactStateBody
+      ? ReactionCompactStateBody.toJSON(message.reactionCompactStateBody)
+      : undefined);
     return obj;
   },
 
@@ -1019,6 +1052,10 @@ export const MessageData = {
     message.linkCompactStateBody = (object.linkCompactStateBody !== undefined && object.linkCompactStateBody !== null)
       ? LinkCompactStateBody.fromPartial(object.linkCompactStateBody)
       : undefined;
+    message.reactionCompactStateBody =
+      (object.reactionCompactStateBody !== undefined && object.reactionCompactStateBody !== null)
+        ? ReactionCompactStateBody.fromPartial(object.reactionCompactStateBody)
+        : undefined;
     return message;
   },
 };
@@ -1586,6 +1623,100 @@ export const ReactionBody = {
   },
 };
 
+function createBaseReactionCompactStateBody(): ReactionCompactStateBody {
+  return { type: 0, targetCastIds: [], targetUrls: [] };
+}
+
+export const ReactionCompactStateBody = {
+  encode(message: ReactionCompactStateBody, writer: _m0.Writer = _m0.Writer.create()): _m0.Writer {
+    if (message.type !== 0) {
+      writer.uint32(8).int32(message.type);
+    }
+    for (const v of message.targetCastIds) {
+      CastId.encode(v!, writer.uint32(18).fork()).ldelim();
+    }
+    for (const v of message.targetUrls) {
+      writer.uint32(26).string(v!);
+    }
+    return writer;
+  },
+
+  decode(input: _m0.Reader | Uint8Array, length?: number): ReactionCompactStateBody {
+    const reader = input instanceof _m0.Reader ? input : _m0.Reader.create(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseReactionCompactStateBody();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1:
+          if (tag != 8) {
+            break;
+          }
+
+          message.type = reader.int32() as any;
+          continue;
+        case 2:
+          if (tag != 18) {
+            break;
+          }
+
+          message.targetCastIds.push(CastId.decode(reader, reader.uint32()));
+          continue;
+        case 3:
+          if (tag != 26) {
+            break;
+          }
+
+          message.targetUrls.push(reader.string());
+          continue;
+      }
+      if ((tag & 7) == 4 || tag == 0) {
+        break;
+      }
+      reader.skipType(tag & 7);
+    }
+    return message;
+  },
+
+  fromJSON(object: any): ReactionCompactStateBody {
+    return {
+      type: isSet(object.type) ? reactionTypeFromJSON(object.type) : 0,
+      targetCastIds: Array.isArray(object?.targetCastIds)
+        ? object.targetCastIds.map((e: any) => CastId.fromJSON(e))
+        : [],
+      targetUrls: Array.isArray(object?.targetUrls) ? object.targetUrls.map((e: any) => String(e)) : [],
+    };
+  },
+
+  toJSON(message: ReactionCompactStateBody): unknown {
+    const obj: any = {};
+    message.type !== undefined && (obj.type = reactionTypeToJSON(message.type));
+    if (message.targetCastIds) {
+      obj.targetCastIds = message.targetCastIds.map((e) => e ? CastId.toJSON(e) : undefined);
+    } else {
+      obj.targetCastIds = [];
+    }
+    if (message.targetUrls) {
+      obj.targetUrls = message.targetUrls.map((e) => e);
+    } else {
+      obj.targetUrls = [];
+    }
+    return obj;
+  },
+
+  create<I extends Exact<DeepPartial<ReactionCompactStateBody>, I>>(base?: I): ReactionCompactStateBody {
+    return ReactionCompactStateBody.fromPartial(base ?? {});
+  },
+
+  fromPartial<I extends Exact<DeepPartial<ReactionCompactStateBody>, I>>(object: I): ReactionCompactStateBody {
+    const message = createBaseReactionCompactStateBody();
+    message.type = object.type ?? 0;
+    message.targetCastIds = object.targetCastIds?.map((e) => CastId.fromPartial(e)) || [];
+    message.targetUrls = object.targetUrls?.map((e) => e) || [];
+    return message;
+  },
+};
+
 function createBaseVerificationAddAddressBody(): VerificationAddAddressBody {
   return {
     address: new Uint8Array(),
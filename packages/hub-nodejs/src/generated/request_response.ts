@@ -167,6 +167,8 @@ export interface TrieNodeMetadataResponse {
   numMessages: number;
   hash: string;
   children: TrieNodeMetadataResponse[];
+  /** Set if the children were left out because the response size cap was reached */
+  truncated: boolean;
 }
 
 export interface TrieNodeSnapshotResponse {
@@ -178,6 +180,17 @@ export interface TrieNodeSnapshotResponse {
 
 export interface TrieNodePrefix {
   prefix: Uint8Array;
+  /** Number of levels of children to return, only used by GetSyncMetadataByPrefix */
+  depth?:
+    | number
+    | undefined;
+  /** Nodes the caller already has, which aren't expanded. Only used by GetSyncMetadataByPrefix */
+  knownHashes: TrieNodeHash[];
+}
+
+export interface TrieNodeHash {
+  prefix: Uint8Array;
+  hash: string;
 }
 
 export interface SyncIds {
@@ -1244,7 +1257,7 @@ export const SyncStatus = {
 };
 
 function createBaseTrieNodeMetadataResponse(): TrieNodeMetadataResponse {
-  return { prefix: new Uint8Array(), numMessages: 0, hash: "", children: [] };
+  return { prefix: new Uint8Array(), numMessages: 0, hash: "", children: [], truncated: false };
 }
 
 export const TrieNodeMetadataResponse = {
@@ -1261,6 +1274,9 @@ export const TrieNodeMetadataResponse = {
     for (const v of message.children) {
       TrieNodeMetadataResponse.encode(v!, writer.uint32(34).fork()).ldelim();
     }
+    if (message.truncated === true) {
+      writer.uint32(40).bool(message.truncated);
+    }
     return writer;
   },
 
@@ -1299,6 +1315,13 @@ export const TrieNodeMetadataResponse = {
 
           message.children.push(TrieNodeMetadataResponse.decode(reader, reader.uint32()));
           continue;
+        case 5:
+          if (tag != 40) {
+            break;
+          }
+
+          message.truncated = reader.bool();
+          continue;
       }
       if ((tag & 7) == 4 || tag == 0) {
         break;
@@ -1316,6 +1339,7 @@ export const TrieNodeMetadataResponse = {
       children: Array.isArray(object?.children)
         ? object.children.map((e: any) => TrieNodeMetadataResponse.fromJSON(e))
         : [],
+      truncated: isSet(object.truncated) ? Boolean(object.truncated) : false,
     };
   },
 
@@ -1330,6 +1354,7 @@ export const TrieNodeMetadataResponse = {
     } else {
       obj.children = [];
     }
+    message.truncated !== undefined && (obj.truncated = message.truncated);
     return obj;
   },
 
@@ -1343,6 +1368,7 @@ export const TrieNodeMetadataResponse = {
     message.numMessages = object.numMessages ?? 0;
     message.hash = object.hash ?? "";
     message.children = object.children?.map((e) => TrieNodeMetadataResponse.fromPartial(e)) || [];
+    message.truncated = object.truncated ?? false;
     return message;
   },
 };
@@ -1450,7 +1476,7 @@ export const TrieNodeSnapshotResponse = {
 };
 
 function createBaseTrieNodePrefix(): TrieNodePrefix {
-  return { prefix: new Uint8Array() };
+  return { prefix: new Uint8Array(), depth: undefined, knownHashes: [] };
 }
 
 export const TrieNodePrefix = {
@@ -1458,6 +1484,12 @@ export const TrieNodePrefix = {
     if (message.prefix.length !== 0) {
       writer.uint32(10).bytes(message.prefix);
     }
+    if (message.depth !== undefined) {
+      writer.uint32(16).uint32(message.depth);
+    }
+    for (const v of message.knownHashes) {
+      TrieNodeHash.encode(v!, writer.uint32(26).fork()).ldelim();
+    }
     return writer;
   },
 
@@ -1475,6 +1507,20 @@ export const TrieNodePrefix = {
 
           message.prefix = reader.bytes();
           continue;
+        case 2:
+          if (tag != 16) {
+            break;
+          }
+
+          message.depth = reader.uint32();
+          continue;
+        case 3:
+          if (tag != 26) {
+            break;
+          }
+
+          message.knownHashes.push(TrieNodeHash.decode(reader, reader.uint32()));
+          continue;
       }
       if ((tag & 7) == 4 || tag == 0) {
         break;
@@ -1485,13 +1531,25 @@ export const TrieNodePrefix = {
   },
 
   fromJSON(object: any): TrieNodePrefix {
-    return { prefix: isSet(object.prefix) ? bytesFromBase64(object.prefix) : new Uint8Array() };
+    return {
+      prefix: isSet(object.prefix) ? bytesFromBase64(object.prefix) : new Uint8Array(),
+      depth: isSet(object.depth) ? Number(object.depth) : undefined,
+      knownHashes: Array.isArray(object?.knownHashes)
+        ? object.knownHashes.map((e: any) => TrieNodeHash.fromJSON(e))
+        : [],
+    };
   },
 
   toJSON(message: TrieNodePrefix): unknown {
     const obj: any = {};
     message.prefix !== undefined &&
       (obj.prefix = base64FromBytes(message.prefix !== undefined ? message.prefix : new Uint8Array()));
+    message.depth !== undefined && (obj.depth = Math.round(message.depth));
+    if (message.knownHashes) {
+      obj.knownHashes = message.knownHashes.map((e) => e ? TrieNodeHash.toJSON(e) : undefined);
+    } else {
+      obj.knownHashes = [];
+    }
     return obj;
   },
 
@@ -1502,6 +1560,80 @@ export const TrieNodePrefix = {
   fromPartial<I extends Exact<DeepPartial<TrieNodePrefix>, I>>(object: I): TrieNodePrefix {
     const message = createBaseTrieNodePrefix();
     message.prefix = object.prefix ?? new Uint8Array();
+    message.depth = object.depth ?? undefined;
+    message.knownHashes = object.knownHashes?.map((e) => TrieNodeHash.fromPartial(e)) || [];
+    return message;
+  },
+};
+
+function createBaseTrieNodeHash(): TrieNodeHash {
+  return { prefix: new Uint8Array(), hash: "" };
+}
+
+export const TrieNodeHash = {
+  encode(message: TrieNodeHash, writer: _m0.Writer = _m0.Writer.create()): _m0.Writer {
+    if (message.prefix.length !== 0) {
+      writer.uint32(10).bytes(message.prefix);
+    }
+    if (message.hash !== "") {
+      writer.uint32(18).string(message.hash);
+    }
+    return writer;
+  },
+
+  decode(input: _m0.Reader | Uint8Array, length?: number): TrieNodeHash {
+    const reader = input instanceof _m0.Reader ? input : _m0.Reader.create(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseTrieNodeHash();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1:
+          if (tag != 10) {
+            break;
+          }
+
+          message.prefix = reader.bytes();
+          continue;
+        case 2:
+          if (tag != 18) {
+            break;
+          }
+
+          message.hash = reader.string();
+          continue;
+      }
+      if ((tag & 7) == 4 || tag == 0) {
+        break;
+      }
+      reader.skipType(tag & 7);
+    }
+    return message;
+  },
+
+  fromJSON(object: any): TrieNodeHash {
+    return {
+      prefix: isSet(object.prefix) ? bytesFromBase64(object.prefix) : new Uint8Array(),
+      hash: isSet(object.hash) ? String(object.hash) : "",
+    };
+  },
+
+  toJSON(message: TrieNodeHash): unknown {
+    const obj: any = {};
+    message.prefix !== undefined &&
+      (obj.prefix = base64FromBytes(message.prefix !== undefined ? message.prefix : new Uint8Array()));
+    message.hash !== undefined && (obj.hash = message.hash);
+    return obj;
+  },
+
+  create<I extends Exact<DeepPartial<TrieNodeHash>, I>>(base?: I): TrieNodeHash {
+    return TrieNodeHash.fromPartial(base ?? {});
+  },
+
+  fromPartial<I extends Exact<DeepPartial<TrieNodeHash>, I>>(object: I): TrieNodeHash {
+    const message = createBaseTrieNodeHash();
+    message.prefix = object.prefix ?? new Uint8Array();
+    message.hash = object.hash ?? "";
     return message;
   },
 };
@@ -0,0 +1,566 @@
+use crate::error::HubError;
+use crate::protos::{
+    message_data::Body, CastAddBody, CastRemoveBody, CastType, FarcasterNetwork, FrameActionBody,
+    HashScheme, LinkBody, LinkCompactStateBody, Message, MessageData, MessageType, ReactionBody,
+    ReactionCompactStateBody, ReactionType, SignatureScheme, UserDataBody, UserDataType,
+    UserNameProof, VerificationAddAddressBody, VerificationRemoveBody,
+};
+use crate::time::get_farcaster_time;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use prost::Message as _;
+
+const HASH_LENGTH: usize = 20;
+const MAX_DATA_BYTES: usize = 2048;
+
+/**
+ * Builds signed messages for a single fid. Every message gets the MessageData encoded into
+ * `data_bytes`, a blake3 hash of those bytes (truncated to 20 bytes) and an Ed25519 signature of
+ * the hash, which is what Hubs validate against.
+ *
+ * The builders run a few cheap checks to catch common mistakes before a round trip to the Hub, but
+ * the Hub still does the full validation when the message is submitted.
+ */
+pub struct MessageBuilder {
+    fid: u64,
+    network: FarcasterNetwork,
+    signer: SigningKey,
+    timestamp: Option<u32>,
+}
+
+impl MessageBuilder {
+    /** `signer` must be an Ed25519 key that was registered onchain for `fid` */
+    pub fn new(fid: u64, network: FarcasterNetwork, signer: SigningKey) -> MessageBuilder {
+        MessageBuilder {
+            fid,
+            network,
+            signer,
+            timestamp: None,
+        }
+    }
+
+    /** Use a fixed Farcaster timestamp for all messages instead of the current time */
+    pub fn with_timestamp(mut self, timestamp: u32) -> MessageBuilder {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn fid(&self) -> u64 {
+        self.fid
+    }
+
+    pub fn signer(&self) -> VerifyingKey {
+        self.signer.verifying_key()
+    }
+
+    pub fn cast_add(&self, body: CastAddBody) -> Result<Message, HubError> {
+        let text_length = body.text.len();
+        if body.r#type == CastType::Cast as i32 && text_length > 320 {
+            return Err(HubError::validation_failure("text > 320 bytes"));
+        }
+        if body.r#type == CastType::LongCast as i32 && text_length > 1024 {
+            return Err(HubError::validation_failure(
+                "text > 1024 bytes for long cast",
+            ));
+        }
+        if body.embeds.len() > 2 {
+            return Err(HubError::validation_failure("embeds > 2"));
+        }
+        if body.mentions.len() > 10 {
+            return Err(HubError::validation_failure("mentions > 10"));
+        }
+        if body.mentions.len() != body.mentions_positions.len() {
+            return Err(HubError::validation_failure(
+                "mentions and mentionsPositions must match",
+            ));
+        }
+
+        self.build(MessageType::CastAdd, Body::CastAddBody(body))
+    }
+
+    pub fn cast_remove(&self, target_hash: &[u8]) -> Result<Message, HubError> {
+        if target_hash.len() != HASH_LENGTH {
+            return Err(HubError::validation_failure("targetHash must be 20 bytes"));
+        }
+
+        self.build(
+            MessageType::CastRemove,
+            Body::CastRemoveBody(CastRemoveBody {
+                target_hash: target_hash.to_vec(),
+            }),
+        )
+    }
+
+    pub fn reaction_add(&self, body: ReactionBody) -> Result<Message, HubError> {
+        Self::validate_reaction_body(&body)?;
+        self.build(MessageType::ReactionAdd, Body::ReactionBody(body))
+    }
+
+    pub fn reaction_remove(&self, body: ReactionBody) -> Result<Message, HubError> {
+        Self::validate_reaction_body(&body)?;
+        self.build(MessageType::ReactionRemove, Body::ReactionBody(body))
+    }
+
+    pub fn link_add(&self, body: LinkBody) -> Result<Message, HubError> {
+        Self::validate_link_type(&body.r#type)?;
+        if body.target.is_none() {
+            return Err(HubError::validation_failure("target is missing"));
+        }
+
+        self.build(MessageType::LinkAdd, Body::LinkBody(body))
+    }
+
+    pub fn link_remove(&self, body: LinkBody) -> Result<Message, HubError> {
+        Self::validate_link_type(&body.r#type)?;
+        if body.target.is_none() {
+            return Err(HubError::validation_failure("target is missing"));
+        }
+
+        self.build(MessageType::LinkRemove, Body::LinkBody(body))
+    }
+
+    pub fn link_compact_state(&self, body: LinkCompactStateBody) -> Result<Message, HubError> {
+        Self::validate_link_type(&body.r#type)?;
+        self.build(
+            MessageType::LinkCompactState,
+            Body::LinkCompactStateBody(body),
+        )
+    }
+
+    pub fn reaction_compact_state(
+        &self,
+        body: ReactionCompactStateBody,
+    ) -> Result<Message, HubError> {
+        if body.r#type == ReactionType::None as i32 {
+            return Err(HubError::validation_failure("invalid reaction type"));
+        }
+        self.build(
+            MessageType::ReactionCompactState,
+            Body::ReactionCompactStateBody(body),
+        )
+    }
+
+    /**
+     * The body must already carry the `claim_signature` produced by the verified address, since
+     * that signature can't be made with the Ed25519 signer
+     */
+    pub fn verification_add_address(
+        &self,
+        body: VerificationAddAddressBody,
+    ) -> Result<Message, HubError> {
+        if body.address.is_empty() {
+            return Err(HubError::validation_failure("address is missing"));
+        }
+        if body.claim_signature.is_empty() {
+            return Err(HubError::validation_failure("claimSignature is missing"));
+        }
+
+        self.build(
+            MessageType::VerificationAddEthAddress,
+            Body::VerificationAddAddressBody(body),
+        )
+    }
+
+    pub fn verification_remove(&self, body: VerificationRemoveBody) -> Result<Message, HubError> {
+        if body.address.is_empty() {
+            return Err(HubError::validation_failure("address is missing"));
+        }
+
+        self.build(
+            MessageType::VerificationRemove,
+            Body::VerificationRemoveBody(body),
+        )
+    }
+
+    pub fn user_data_add(&self, body: UserDataBody) -> Result<Message, HubError> {
+        if body.r#type == UserDataType::None as i32 {
+            return Err(HubError::validation_failure("invalid user data type"));
+        }
+
+        self.build(MessageType::UserDataAdd, Body::UserDataBody(body))
+    }
+
+    /** The proof must already be signed by the owner of the name */
+    pub fn username_proof(&self, body: UserNameProof) -> Result<Message, HubError> {
+        if body.fid != self.fid {
+            return Err(HubError::validation_failure(
+                "fid in username proof does not match message fid",
+            ));
+        }
+
+        self.build(MessageType::UsernameProof, Body::UsernameProofBody(body))
+    }
+
+    pub fn frame_action(&self, body: FrameActionBody) -> Result<Message, HubError> {
+        if body.url.is_empty() {
+            return Err(HubError::validation_failure("url is missing"));
+        }
+
+        self.build(MessageType::FrameAction, Body::FrameActionBody(body))
+    }
+
+    /** Build, hash and sign a message for any type and body */
+    pub fn build(&self, message_type: MessageType, body: Body) -> Result<Message, HubError> {
+        if self.fid == 0 {
+            return Err(HubError::validation_failure("fid is missing"));
+        }
+        if body_message_types(&body)
+            .iter()
+            .all(|body_type| *body_type != message_type)
+        {
+            return Err(HubError::validation_failure(&format!(
+                "body does not match message type {}",
+                message_type.as_str_name()
+            )));
+        }
+
+        let timestamp = match self.timestamp {
+            Some(timestamp) => timestamp,
+            None => get_farcaster_time()?,
+        };
+
+        let data = MessageData {
+            r#type: message_type as i32,
+            fid: self.fid,
+            timestamp,
+            network: self.network as i32,
+            body: Some(body),
+        };
+        let data_bytes = data.encode_to_vec();
+        if data_bytes.len() > MAX_DATA_BYTES {
+            return Err(HubError::validation_failure("dataBytes > 2048 bytes"));
+        }
+
+        let hash = blake3_20(&data_bytes);
+        let signature = self.signer.sign(&hash);
+
+        Ok(Message {
+            data: Some(data),
+            hash,
+            hash_scheme: HashScheme::Blake3 as i32,
+            signature: signature.to_bytes().to_vec(),
+            signature_scheme: SignatureScheme::Ed25519 as i32,
+            signer: self.signer.verifying_key().to_bytes().to_vec(),
+            data_bytes: Some(data_bytes),
+        })
+    }
+
+    fn validate_reaction_body(body: &ReactionBody) -> Result<(), HubError> {
+        if body.r#type == ReactionType::None as i32 {
+            return Err(HubError::validation_failure("invalid reaction type"));
+        }
+        if body.target.is_none() {
+            return Err(HubError::validation_failure("target is missing"));
+        }
+
+        Ok(())
+    }
+
+    fn validate_link_type(link_type: &str) -> Result<(), HubError> {
+        if link_type.is_empty() || link_type.len() > 8 {
+            return Err(HubError::validation_failure(
+                "type must be between 1-8 bytes",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/** The message types each body can be used with */
+fn body_message_types(body: &Body) -> &'static [MessageType] {
+    match body {
+        Body::CastAddBody(_) => &[MessageType::CastAdd],
+        Body::CastRemoveBody(_) => &[MessageType::CastRemove],
+        Body::ReactionBody(_) => &[MessageType::ReactionAdd, MessageType::ReactionRemove],
+        Body::VerificationAddAddressBody(_) => &[MessageType::VerificationAddEthAddress],
+        Body::VerificationRemoveBody(_) => &[MessageType::VerificationRemove],
+        Body::UserDataBody(_) => &[MessageType::UserDataAdd],
+        Body::LinkBody(_) => &[MessageType::LinkAdd, MessageType::LinkRemove],
+        Body::UsernameProofBody(_) => &[MessageType::UsernameProof],
+        Body::FrameActionBody(_) => &[MessageType::FrameAction],
+        Body::LinkCompactStateBody(_) => &[MessageType::LinkCompactState],
+        Body::ReactionCompactStateBody(_) => &[MessageType::ReactionCompactState],
+    }
+}
+
+/** blake3 hash, truncated to 20 bytes */
+pub fn blake3_20(input: &[u8]) -> Vec<u8> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(input);
+    hasher.finalize().as_bytes()[0..HASH_LENGTH].to_vec()
+}
+
+/**
+ * Check that a message's hash matches its data and that it was signed by its signer, the same way
+ * Hubs do. Only Ed25519 signatures are supported.
+ */
+pub fn verify_message(message: &Message) -> Result<(), HubError> {
+    let data_bytes = match (&message.data_bytes, &message.data) {
+        (Some(data_bytes), _) if !data_bytes.is_empty() => data_bytes.clone(),
+        (_, Some(data)) => data.encode_to_vec(),
+        _ => return Err(HubError::validation_failure("data is missing")),
+    };
+
+    if message.hash_scheme != HashScheme::Blake3 as i32 {
+        return Err(HubError::validation_failure("invalid hashScheme"));
+    }
+    if blake3_20(&data_bytes) != message.hash {
+        return Err(HubError::validation_failure("invalid hash"));
+    }
+
+    if message.signature_scheme != SignatureScheme::Ed25519 as i32 {
+        return Err(HubError::validation_failure("invalid signatureScheme"));
+    }
+    let signer: [u8; 32] = message
+        .signer
+        .as_slice()
+        .try_into()
+        .map_err(|_| HubError::validation_failure("signer must be 32 bytes"))?;
+    let signer = VerifyingKey::from_bytes(&signer)
+        .map_err(|_| HubError::validation_failure("invalid signer"))?;
+    let signature = Signature::from_slice(&message.signature)
+        .map_err(|_| HubError::validation_failure("invalid signature"))?;
+
+    signer
+        .verify_strict(&message.hash, &signature)
+        .map_err(|_| HubError::validation_failure("invalid signature"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::{cast_add_body, link_body, reaction_body, CastId, UserNameType};
+
+    fn builder() -> MessageBuilder {
+        MessageBuilder::new(
+            6833,
+            FarcasterNetwork::Mainnet,
+            SigningKey::from_bytes(&[7u8; 32]),
+        )
+        .with_timestamp(100_000)
+    }
+
+    #[test]
+    fn test_cast_add() {
+        let builder = builder();
+        let message = builder
+            .cast_add(CastAddBody {
+                text: "Welcome to Rust!".to_string(),
+                parent: Some(cast_add_body::Parent::ParentUrl(
+                    "https://farcaster.xyz".to_string(),
+                )),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let data = message.data.as_ref().unwrap();
+        assert_eq!(data.r#type, MessageType::CastAdd as i32);
+        assert_eq!(data.fid, 6833);
+        assert_eq!(data.timestamp, 100_000);
+        assert_eq!(data.network, FarcasterNetwork::Mainnet as i32);
+
+        // The hash is of the data bytes, and the data bytes are the encoded data
+        let data_bytes = message.data_bytes.as_ref().unwrap();
+        assert_eq!(data_bytes, &data.encode_to_vec());
+        assert_eq!(message.hash, blake3_20(data_bytes));
+        assert_eq!(message.hash.len(), 20);
+        assert_eq!(message.signer, builder.signer().to_bytes().to_vec());
+        assert!(verify_message(&message).is_ok());
+
+        // Messages round trip through protobuf encoding
+        let decoded = Message::decode(message.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded, message);
+        assert!(verify_message(&decoded).is_ok());
+    }
+
+    #[test]
+    fn test_all_body_types() {
+        let builder = builder();
+        let cast_id = CastId {
+            fid: 1,
+            hash: vec![1; 20],
+        };
+
+        let messages = vec![
+            builder.cast_remove(&[1; 20]).unwrap(),
+            builder
+                .reaction_add(ReactionBody {
+                    r#type: ReactionType::Like as i32,
+                    target: Some(reaction_body::Target::TargetCastId(cast_id.clone())),
+                })
+                .unwrap(),
+            builder
+                .reaction_remove(ReactionBody {
+                    r#type: ReactionType::Recast as i32,
+                    target: Some(reaction_body::Target::TargetUrl(
+                        "https://farcaster.xyz".to_string(),
+                    )),
+                })
+                .unwrap(),
+            builder
+                .link_add(LinkBody {
+                    r#type: "follow".to_string(),
+                    display_timestamp: None,
+                    target: Some(link_body::Target::TargetFid(2)),
+                })
+                .unwrap(),
+            builder
+                .link_remove(LinkBody {
+                    r#type: "follow".to_string(),
+                    display_timestamp: None,
+                    target: Some(link_body::Target::TargetFid(2)),
+                })
+                .unwrap(),
+            builder
+                .link_compact_state(LinkCompactStateBody {
+                    r#type: "follow".to_string(),
+                    target_fids: vec![2, 3],
+                })
+                .unwrap(),
+            builder
+                .reaction_compact_state(ReactionCompactStateBody {
+                    r#type: ReactionType::Like as i32,
+                    target_cast_ids: vec![cast_id.clone()],
+                    target_urls: vec!["https://farcaster.xyz".to_string()],
+                })
+                .unwrap(),
+            builder
+                .verification_add_address(VerificationAddAddressBody {
+                    address: vec![2; 20],
+                    claim_signature: vec![3; 65],
+                    block_hash: vec![4; 32],
+                    ..Default::default()
+                })
+                .unwrap(),
+            builder
+                .verification_remove(VerificationRemoveBody {
+                    address: vec![2; 20],
+                    ..Default::default()
+                })
+                .unwrap(),
+            builder
+                .user_data_add(UserDataBody {
+                    r#type: UserDataType::Bio as i32,
+                    value: "rustacean".to_string(),
+                })
+                .unwrap(),
+            builder
+                .username_proof(UserNameProof {
+                    timestamp: 1,
+                    name: b"rust.eth".to_vec(),
+                    owner: vec![2; 20],
+                    signature: vec![3; 65],
+                    fid: 6833,
+                    r#type: UserNameType::UsernameTypeEnsL1 as i32,
+                })
+                .unwrap(),
+            builder
+                .frame_action(FrameActionBody {
+                    url: b"https://frame.xyz".to_vec(),
+                    button_index: 1,
+                    cast_id: Some(cast_id),
+                    ..Default::default()
+                })
+                .unwrap(),
+        ];
+
+        let expected_types = [
+            MessageType::CastRemove,
+            MessageType::ReactionAdd,
+            MessageType::ReactionRemove,
+            MessageType::LinkAdd,
+            MessageType::LinkRemove,
+            MessageType::LinkCompactState,
+            MessageType::ReactionCompactState,
+            MessageType::VerificationAddEthAddress,
+            MessageType::VerificationRemove,
+            MessageType::UserDataAdd,
+            MessageType::UsernameProof,
+            MessageType::FrameAction,
+        ];
+        for (message, expected_type) in messages.iter().zip(expected_types) {
+            assert_eq!(message.data.as_ref().unwrap().r#type, expected_type as i32);
+            assert!(verify_message(message).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        let builder = builder();
+
+        let long_text = "a".repeat(321);
+        let error = builder
+            .cast_add(CastAddBody {
+                text: long_text,
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert_eq!(error.code, "bad_request.validation_failure");
+
+        assert!(builder.cast_remove(&[1; 19]).is_err());
+        assert!(builder
+            .link_add(LinkBody {
+                r#type: "toolongtype".to_string(),
+                display_timestamp: None,
+                target: Some(link_body::Target::TargetFid(2)),
+            })
+            .is_err());
+        assert!(builder
+            .reaction_add(ReactionBody {
+                r#type: ReactionType::Like as i32,
+                target: None,
+            })
+            .is_err());
+        assert!(builder
+            .reaction_compact_state(ReactionCompactStateBody {
+                r#type: ReactionType::None as i32,
+                ..Default::default()
+            })
+            .is_err());
+
+        // The body has to match the message type
+        assert!(builder
+            .build(
+                MessageType::CastAdd,
+                Body::CastRemoveBody(CastRemoveBody {
+                    target_hash: vec![1; 20]
+                })
+            )
+            .is_err());
+
+        let no_fid = MessageBuilder::new(
+            0,
+            FarcasterNetwork::Mainnet,
+            SigningKey::from_bytes(&[7u8; 32]),
+        );
+        assert_eq!(
+            no_fid.cast_remove(&[1; 20]).unwrap_err(),
+            HubError::validation_failure("fid is missing")
+        );
+    }
+
+    #[test]
+    fn test_verify_message() {
+        let message = builder().cast_remove(&[1; 20]).unwrap();
+
+        let mut bad_hash = message.clone();
+        bad_hash.hash[0] ^= 1;
+        assert!(verify_message(&bad_hash).is_err());
+
+        let mut bad_signature = message.clone();
+        bad_signature.signature[0] ^= 1;
+        assert!(verify_message(&bad_signature).is_err());
+
+        let mut other_signer = message.clone();
+        other_signer.signer = SigningKey::from_bytes(&[8u8; 32])
+            .verifying_key()
+            .to_bytes()
+            .to_vec();
+        assert!(verify_message(&other_signer).is_err());
+
+        // Without data_bytes, the hash is checked against the encoded data
+        let mut no_data_bytes = message.clone();
+        no_data_bytes.data_bytes = None;
+        assert!(verify_message(&no_data_bytes).is_ok());
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+
+/**
+ * Errors returned by the builders and clients. `code` uses the same error codes as the Hub
+ * (e.g. "bad_request.validation_failure"), so errors returned by a Hub are passed through as-is.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HubError {
+    pub code: String,
+    pub message: String,
+}
+
+impl HubError {
+    pub fn new(code: &str, message: &str) -> HubError {
+        HubError {
+            code: code.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn validation_failure(error_message: &str) -> HubError {
+        HubError::new("bad_request.validation_failure", error_message)
+    }
+
+    pub fn invalid_parameter(error_message: &str) -> HubError {
+        HubError::new("bad_request.invalid_param", error_message)
+    }
+
+    pub fn network_failure(error_message: &str) -> HubError {
+        HubError::new("unavailable.network_failure", error_message)
+    }
+
+    pub fn parse_failure(error_message: &str) -> HubError {
+        HubError::new("bad_request.parse_failure", error_message)
+    }
+}
+
+impl fmt::Display for HubError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for HubError {}
+
+impl From<tonic::Status> for HubError {
+    /** Hubs send the HubError code in the "errcode" metadata. Fall back to the gRPC code if it's missing */
+    fn from(status: tonic::Status) -> HubError {
+        let code = match status.metadata().get("errcode").map(|v| v.to_str()) {
+            Some(Ok(code)) => code.to_string(),
+            _ => match status.code() {
+                tonic::Code::Unauthenticated => "unauthenticated".to_string(),
+                tonic::Code::PermissionDenied => "unauthorized".to_string(),
+                tonic::Code::InvalidArgument => "bad_request".to_string(),
+                tonic::Code::NotFound => "not_found".to_string(),
+                tonic::Code::Unavailable => "unavailable".to_string(),
+                _ => "unknown".to_string(),
+            },
+        };
+
+        HubError {
+            code,
+            message: status.message().to_string(),
+        }
+    }
+}
+
+impl From<tonic::transport::Error> for HubError {
+    fn from(e: tonic::transport::Error) -> HubError {
+        HubError::network_failure(&e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for HubError {
+    fn from(e: reqwest::Error) -> HubError {
+        HubError::network_failure(&e.to_string())
+    }
+}
+
+impl From<prost::DecodeError> for HubError {
+    fn from(e: prost::DecodeError) -> HubError {
+        HubError::parse_failure(&e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::metadata::MetadataValue;
+
+    #[test]
+    fn test_from_status() {
+        let mut status = tonic::Status::invalid_argument("invalid signature");
+        status.metadata_mut().insert(
+            "errcode",
+            MetadataValue::from_static("bad_request.validation_failure"),
+        );
+
+        let error = HubError::from(status);
+        assert_eq!(error.code, "bad_request.validation_failure");
+        assert_eq!(error.message, "invalid signature");
+
+        // Without the metadata, the code is derived from the gRPC status
+        let error = HubError::from(tonic::Status::not_found("no cast"));
+        assert_eq!(error.code, "not_found");
+        assert_eq!(error.to_string(), "not_found/no cast");
+    }
+}
@@ -0,0 +1,277 @@
+use crate::basic_auth_header;
+use crate::error::HubError;
+use crate::protos::{
+    hub_service_client::HubServiceClient, CastId, HubInfoRequest, HubInfoResponse, Message,
+    ValidationResponse,
+};
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tonic::Request;
+
+/**
+ * gRPC client for a Hub. `submit_message` and a few common reads are wrapped to return
+ * `HubError`s; everything else is available on the generated client through `inner()`.
+ */
+#[derive(Clone)]
+pub struct HubGrpcClient {
+    client: HubServiceClient<Channel>,
+    authorization: Option<MetadataValue<Ascii>>,
+}
+
+impl HubGrpcClient {
+    /** Connect to a Hub's gRPC endpoint, e.g. "http://127.0.0.1:2283". https endpoints use TLS */
+    pub async fn connect(endpoint: &str) -> Result<HubGrpcClient, HubError> {
+        let mut endpoint = Endpoint::from_shared(endpoint.to_string())
+            .map_err(|e| HubError::invalid_parameter(&e.to_string()))?;
+        if endpoint.uri().scheme_str() == Some("https") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new())?;
+        }
+
+        Ok(HubGrpcClient::new(endpoint.connect().await?))
+    }
+
+    pub fn new(channel: Channel) -> HubGrpcClient {
+        HubGrpcClient {
+            client: HubServiceClient::new(channel),
+            authorization: None,
+        }
+    }
+
+    /** Send basic auth credentials with every request, for Hubs that are started with `--rpc-auth` */
+    pub fn with_basic_auth(
+        mut self,
+        username: &str,
+        password: &str,
+    ) -> Result<HubGrpcClient, HubError> {
+        let header = basic_auth_header(username, password);
+        self.authorization = Some(
+            header
+                .parse()
+                .map_err(|_| HubError::invalid_parameter("invalid credentials"))?,
+        );
+        Ok(self)
+    }
+
+    /** The generated client, for the RPCs that aren't wrapped here */
+    pub fn inner(&mut self) -> &mut HubServiceClient<Channel> {
+        &mut self.client
+    }
+
+    /** Submit a message. The Hub returns the merged message */
+    pub async fn submit_message(&mut self, message: Message) -> Result<Message, HubError> {
+        let request = self.request(message);
+        Ok(self.client.submit_message(request).await?.into_inner())
+    }
+
+    /** Ask the Hub to validate a message without merging it */
+    pub async fn validate_message(
+        &mut self,
+        message: Message,
+    ) -> Result<ValidationResponse, HubError> {
+        let request = self.request(message);
+        Ok(self.client.validate_message(request).await?.into_inner())
+    }
+
+    pub async fn get_cast(&mut self, fid: u64, hash: Vec<u8>) -> Result<Message, HubError> {
+        let request = self.request(CastId { fid, hash });
+        Ok(self.client.get_cast(request).await?.into_inner())
+    }
+
+    pub async fn get_info(&mut self) -> Result<HubInfoResponse, HubError> {
+        let request = self.request(HubInfoRequest { db_stats: false });
+        Ok(self.client.get_info(request).await?.into_inner())
+    }
+
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(authorization) = &self.authorization {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization.clone());
+        }
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{verify_message, MessageBuilder};
+    use crate::protos::{CastAddBody, FarcasterNetwork};
+    use ed25519_dalek::SigningKey;
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::body::BoxBody;
+    use tonic::codec::ProstCodec;
+    use tonic::codegen::{http, BoxFuture, Service};
+    use tonic::server::{Grpc, NamedService, UnaryService};
+    use tonic::transport::{Body, Server};
+    use tonic::{Response, Status};
+
+    type Submitted = Arc<Mutex<Vec<(Message, Option<String>)>>>;
+
+    /** A Hub that only implements SubmitMessage, and validates messages like a real Hub */
+    #[derive(Clone, Default)]
+    struct MockHub {
+        submitted: Submitted,
+    }
+
+    struct SubmitMessage(MockHub);
+
+    impl UnaryService<Message> for SubmitMessage {
+        type Response = Message;
+        type Future = BoxFuture<Response<Message>, Status>;
+
+        fn call(&mut self, request: Request<Message>) -> Self::Future {
+            let hub = self.0.clone();
+
+            Box::pin(async move {
+                let authorization = request
+                    .metadata()
+                    .get("authorization")
+                    .map(|v| v.to_str().unwrap().to_string());
+                let message = request.into_inner();
+
+                if let Err(e) = verify_message(&message) {
+                    let mut status = Status::invalid_argument(e.message);
+                    status
+                        .metadata_mut()
+                        .insert("errcode", e.code.parse().unwrap());
+                    return Err(status);
+                }
+
+                hub.submitted
+                    .lock()
+                    .unwrap()
+                    .push((message.clone(), authorization));
+                Ok(Response::new(message))
+            })
+        }
+    }
+
+    impl Service<http::Request<Body>> for MockHub {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+            let hub = self.clone();
+
+            Box::pin(async move {
+                match request.uri().path() {
+                    "/HubService/SubmitMessage" => {
+                        let mut grpc = Grpc::new(ProstCodec::<Message, Message>::default());
+                        Ok(grpc.unary(SubmitMessage(hub), request).await)
+                    }
+                    _ => Ok(Status::unimplemented("not implemented by the mock hub").to_http()),
+                }
+            })
+        }
+    }
+
+    impl NamedService for MockHub {
+        const NAME: &'static str = "HubService";
+    }
+
+    async fn start_mock_hub(hub: MockHub) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(
+            Server::builder()
+                .add_service(hub)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        format!("http://{}", address)
+    }
+
+    fn builder() -> MessageBuilder {
+        MessageBuilder::new(
+            6833,
+            FarcasterNetwork::Mainnet,
+            SigningKey::from_bytes(&[7u8; 32]),
+        )
+    }
+
+    fn cast(text: &str) -> CastAddBody {
+        CastAddBody {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit_message() {
+        let hub = MockHub::default();
+        let endpoint = start_mock_hub(hub.clone()).await;
+
+        let mut client = HubGrpcClient::connect(&endpoint).await.unwrap();
+        let message = builder().cast_add(cast("Welcome to Rust!")).unwrap();
+
+        let merged = client.submit_message(message.clone()).await.unwrap();
+        assert_eq!(merged, message);
+
+        let submitted = hub.submitted.lock().unwrap();
+        assert_eq!(submitted.len(), 1);
+        assert_eq!(submitted[0], (message, None));
+    }
+
+    #[tokio::test]
+    async fn test_submit_invalid_message() {
+        let hub = MockHub::default();
+        let endpoint = start_mock_hub(hub.clone()).await;
+
+        let mut client = HubGrpcClient::connect(&endpoint).await.unwrap();
+        let mut message = builder().cast_add(cast("tampered")).unwrap();
+        message.signature[0] ^= 1;
+
+        let error = client.submit_message(message).await.unwrap_err();
+        assert_eq!(error, HubError::validation_failure("invalid signature"));
+        assert!(hub.submitted.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth() {
+        let hub = MockHub::default();
+        let endpoint = start_mock_hub(hub.clone()).await;
+
+        let mut client = HubGrpcClient::connect(&endpoint)
+            .await
+            .unwrap()
+            .with_basic_auth("user", "pass")
+            .unwrap();
+        let message = builder().cast_add(cast("authenticated")).unwrap();
+        client.submit_message(message).await.unwrap();
+
+        let submitted = hub.submitted.lock().unwrap();
+        assert_eq!(submitted[0].1.as_deref(), Some("Basic dXNlcjpwYXNz"));
+    }
+
+    #[tokio::test]
+    async fn test_unimplemented_rpc() {
+        let endpoint = start_mock_hub(MockHub::default()).await;
+
+        let mut client = HubGrpcClient::connect(&endpoint).await.unwrap();
+        let error = client.get_cast(6833, vec![1; 20]).await.unwrap_err();
+        assert_eq!(error.code, "unknown");
+    }
+
+    #[tokio::test]
+    async fn test_connect_failure() {
+        assert!(HubGrpcClient::connect("not a uri").await.is_err());
+
+        // Nothing is listening on port 1
+        let error = HubGrpcClient::connect("http://127.0.0.1:1")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.code, "unavailable.network_failure");
+    }
+}
@@ -0,0 +1,271 @@
+use crate::basic_auth_header;
+use crate::error::HubError;
+use crate::protos::Message;
+use prost::Message as _;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+
+/** HTTP client for a Hub's HTTP API, e.g. "http://127.0.0.1:2281" */
+#[derive(Clone)]
+pub struct HubHttpClient {
+    base_url: String,
+    client: reqwest::Client,
+    authorization: Option<String>,
+}
+
+impl HubHttpClient {
+    pub fn new(base_url: &str) -> HubHttpClient {
+        HubHttpClient::with_client(base_url, reqwest::Client::new())
+    }
+
+    /** Use an existing reqwest client, e.g. to share a connection pool or set timeouts */
+    pub fn with_client(base_url: &str, client: reqwest::Client) -> HubHttpClient {
+        HubHttpClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+            authorization: None,
+        }
+    }
+
+    /** Send basic auth credentials, for Hubs that are started with `--rpc-auth` */
+    pub fn with_basic_auth(mut self, username: &str, password: &str) -> HubHttpClient {
+        self.authorization = Some(basic_auth_header(username, password));
+        self
+    }
+
+    /**
+     * POST the message to `/v1/submitMessage` as an encoded protobuf. Returns the hash of the
+     * merged message.
+     */
+    pub async fn submit_message(&self, message: &Message) -> Result<Vec<u8>, HubError> {
+        let mut request = self
+            .client
+            .post(format!("{}/v1/submitMessage", self.base_url))
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(message.encode_to_vec());
+        if let Some(authorization) = &self.authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.bytes().await?;
+
+        let json: serde_json::Value = serde_json::from_slice(&body).map_err(|_| {
+            HubError::parse_failure(&format!(
+                "HTTP {} with a non-JSON body: {}",
+                status,
+                String::from_utf8_lossy(&body)
+            ))
+        })?;
+
+        if !status.is_success() {
+            return Err(Self::error_from_json(&json, status));
+        }
+
+        let hash = json["hash"]
+            .as_str()
+            .ok_or_else(|| HubError::parse_failure("response has no hash"))?;
+        hex::decode(hash.trim_start_matches("0x"))
+            .map_err(|_| HubError::parse_failure("response hash is not hex"))
+    }
+
+    /**
+     * Hub errors have an `errCode` and `details`. Requests the HTTP server rejects before they
+     * reach the Hub (e.g. bodies that don't decode) have an `error` and `errorDetail` instead.
+     */
+    fn error_from_json(json: &serde_json::Value, status: reqwest::StatusCode) -> HubError {
+        if let Some(code) = json["errCode"].as_str() {
+            let message = json["details"]
+                .as_str()
+                .or_else(|| json["message"].as_str())
+                .unwrap_or_default();
+            return HubError::new(code, message);
+        }
+
+        let message = match (json["error"].as_str(), json["errorDetail"].as_str()) {
+            (Some(error), Some(detail)) => format!("{}: {}", error, detail),
+            (Some(error), None) => error.to_string(),
+            _ => format!("HTTP {}", status),
+        };
+        HubError::new("bad_request", &message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{verify_message, MessageBuilder};
+    use crate::protos::{CastAddBody, FarcasterNetwork};
+    use ed25519_dalek::SigningKey;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    type Submitted = Arc<Mutex<Vec<(Message, Option<String>)>>>;
+
+    /** Responds like the Hub's HTTP API does for /v1/submitMessage */
+    async fn handle(request: Request<Body>, submitted: Submitted) -> Response<Body> {
+        let json_response = |status: StatusCode, json: serde_json::Value| {
+            Response::builder()
+                .status(status)
+                .header("content-type", "application/json")
+                .body(Body::from(json.to_string()))
+                .unwrap()
+        };
+
+        if request.uri().path() != "/v1/submitMessage" {
+            return json_response(
+                StatusCode::NOT_FOUND,
+                serde_json::json!({ "error": "Not Found" }),
+            );
+        }
+
+        let content_type = request.headers().get("content-type").cloned();
+        if content_type.as_ref().map(|v| v.as_bytes()) != Some(b"application/octet-stream") {
+            return json_response(
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({
+                    "error": "Unsupported Media Type",
+                    "errorDetail": format!("Content-Type {:?} is not supported", content_type),
+                }),
+            );
+        }
+
+        let authorization = request
+            .headers()
+            .get("authorization")
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+        let message = match Message::decode(body) {
+            Ok(message) => message,
+            Err(e) => {
+                return json_response(
+                    StatusCode::BAD_REQUEST,
+                    serde_json::json!({
+                        "error": "Could not parse Message.",
+                        "errorDetail": e.to_string(),
+                    }),
+                )
+            }
+        };
+
+        if let Err(e) = verify_message(&message) {
+            return json_response(
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({
+                    "errCode": e.code,
+                    "presentable": false,
+                    "name": "HubError",
+                    "code": 3,
+                    "details": e.message,
+                    "metadata": { "errcode": [e.code] },
+                }),
+            );
+        }
+
+        let hash = format!("0x{}", hex::encode(&message.hash));
+        submitted.lock().unwrap().push((message, authorization));
+        json_response(StatusCode::OK, serde_json::json!({ "hash": hash }))
+    }
+
+    fn start_mock_hub(submitted: Submitted) -> String {
+        let make_service = make_service_fn(move |_| {
+            let submitted = submitted.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let submitted = submitted.clone();
+                    async move { Ok::<_, Infallible>(handle(request, submitted).await) }
+                }))
+            }
+        });
+
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let address = server.local_addr();
+        tokio::spawn(server);
+
+        format!("http://{}/", address)
+    }
+
+    fn builder() -> MessageBuilder {
+        MessageBuilder::new(
+            6833,
+            FarcasterNetwork::Mainnet,
+            SigningKey::from_bytes(&[7u8; 32]),
+        )
+    }
+
+    fn cast(text: &str) -> CastAddBody {
+        CastAddBody {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit_message() {
+        let submitted = Submitted::default();
+        let client = HubHttpClient::new(&start_mock_hub(submitted.clone()));
+
+        let message = builder().cast_add(cast("Welcome to Rust!")).unwrap();
+        let hash = client.submit_message(&message).await.unwrap();
+        assert_eq!(hash, message.hash);
+
+        let submitted = submitted.lock().unwrap();
+        assert_eq!(submitted.len(), 1);
+        assert_eq!(submitted[0], (message, None));
+    }
+
+    #[tokio::test]
+    async fn test_submit_invalid_message() {
+        let submitted = Submitted::default();
+        let client = HubHttpClient::new(&start_mock_hub(submitted.clone()));
+
+        let mut message = builder().cast_add(cast("tampered")).unwrap();
+        message.hash[0] ^= 1;
+
+        let error = client.submit_message(&message).await.unwrap_err();
+        assert_eq!(error, HubError::validation_failure("invalid hash"));
+        assert!(submitted.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth() {
+        let submitted = Submitted::default();
+        let client =
+            HubHttpClient::new(&start_mock_hub(submitted.clone())).with_basic_auth("user", "pass");
+
+        let message = builder().cast_add(cast("authenticated")).unwrap();
+        client.submit_message(&message).await.unwrap();
+
+        let submitted = submitted.lock().unwrap();
+        assert_eq!(submitted[0].1.as_deref(), Some("Basic dXNlcjpwYXNz"));
+    }
+
+    #[test]
+    fn test_error_from_json() {
+        let error = HubHttpClient::error_from_json(
+            &serde_json::json!({ "error": "Unsupported Media Type", "errorDetail": "text/plain" }),
+            reqwest::StatusCode::BAD_REQUEST,
+        );
+        assert_eq!(
+            error,
+            HubError::new("bad_request", "Unsupported Media Type: text/plain")
+        );
+
+        let error = HubHttpClient::error_from_json(
+            &serde_json::json!({}),
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+        );
+        assert_eq!(error.message, "HTTP 500 Internal Server Error");
+    }
+
+    #[tokio::test]
+    async fn test_connect_failure() {
+        let client = HubHttpClient::new("http://127.0.0.1:1");
+        let message = builder().cast_add(cast("nobody home")).unwrap();
+
+        let error = client.submit_message(&message).await.unwrap_err();
+        assert_eq!(error.code, "unavailable.network_failure");
+    }
+}
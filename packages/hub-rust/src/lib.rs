@@ -0,0 +1,48 @@
+//! Build, sign and submit Farcaster messages to a Hub.
+//!
+//! ```no_run
+//! use ed25519_dalek::SigningKey;
+//! use hub_rust::protos::{CastAddBody, FarcasterNetwork};
+//! use hub_rust::{HubHttpClient, MessageBuilder};
+//!
+//! # async fn submit(private_key: [u8; 32]) -> Result<(), hub_rust::HubError> {
+//! let builder = MessageBuilder::new(6833, FarcasterNetwork::Mainnet, SigningKey::from_bytes(&private_key));
+//! let message = builder.cast_add(CastAddBody {
+//!     text: "Welcome to Rust!".to_string(),
+//!     ..Default::default()
+//! })?;
+//!
+//! let client = HubHttpClient::new("http://127.0.0.1:2281");
+//! client.submit_message(&message).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::Engine as _;
+
+pub use self::builder::{blake3_20, verify_message, MessageBuilder};
+pub use self::error::HubError;
+pub use self::grpc::HubGrpcClient;
+pub use self::http::HubHttpClient;
+pub use self::time::{from_farcaster_time, get_farcaster_time, to_farcaster_time, FARCASTER_EPOCH};
+
+mod builder;
+mod error;
+mod grpc;
+mod http;
+mod time;
+
+/** Generated protobuf types and the gRPC client */
+#[allow(clippy::all)]
+pub mod protos {
+    tonic::include_proto!("_");
+}
+
+/** Hubs started with `--rpc-auth username:password` expect basic auth on submits */
+pub(crate) fn basic_auth_header(username: &str, password: &str) -> String {
+    let credentials = format!("{}:{}", username, password);
+    format!(
+        "Basic {}",
+        base64::engine::general_purpose::STANDARD.encode(credentials)
+    )
+}
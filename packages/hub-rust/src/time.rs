@@ -0,0 +1,49 @@
+use crate::error::HubError;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/** January 1, 2021 UTC, in unix milliseconds */
+pub const FARCASTER_EPOCH: u64 = 1609459200000;
+
+/** Converts unix milliseconds to a Farcaster timestamp (seconds since the Farcaster epoch) */
+pub fn to_farcaster_time(time: u64) -> Result<u32, HubError> {
+    if time < FARCASTER_EPOCH {
+        return Err(HubError::invalid_parameter(
+            "time must be after Farcaster epoch (01/01/2021)",
+        ));
+    }
+
+    let seconds_since_epoch = (time - FARCASTER_EPOCH + 500) / 1000;
+    u32::try_from(seconds_since_epoch)
+        .map_err(|_| HubError::invalid_parameter("time too far in future"))
+}
+
+/** Converts a Farcaster timestamp back to unix milliseconds */
+pub fn from_farcaster_time(time: u32) -> u64 {
+    time as u64 * 1000 + FARCASTER_EPOCH
+}
+
+/** The current Farcaster time */
+pub fn get_farcaster_time() -> Result<u32, HubError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| HubError::invalid_parameter(&e.to_string()))?;
+
+    to_farcaster_time(now.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_farcaster_time() {
+        assert_eq!(to_farcaster_time(FARCASTER_EPOCH).unwrap(), 0);
+        assert_eq!(to_farcaster_time(FARCASTER_EPOCH + 1_499).unwrap(), 1);
+        assert_eq!(to_farcaster_time(FARCASTER_EPOCH + 1_500).unwrap(), 2);
+        assert_eq!(from_farcaster_time(2), FARCASTER_EPOCH + 2_000);
+
+        assert!(to_farcaster_time(FARCASTER_EPOCH - 1).is_err());
+        assert!(to_farcaster_time(u64::MAX).is_err());
+        assert!(get_farcaster_time().unwrap() > 0);
+    }
+}